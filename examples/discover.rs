@@ -80,18 +80,7 @@ fn main() {
                 }
                 for descriptor in descriptors {
                     log::info!("\t\t{:?}", descriptor);
-                    let data = descriptor.read().unwrap();
-                    let mut ascii = Vec::with_capacity(data.len());
-                    for byte in &data[..data.len()] {
-                        ascii.extend(std::ascii::escape_default(*byte));
-                    }
-                    let s = String::from_utf8(ascii).unwrap();
-                    log::info!("\t\t- Contents (ASCII) [{}]: {}", data.len(), s.trim());
-                    log::info!(
-                        "\t\t- Contents (bytes) [{}]: {:?}",
-                        data.len(),
-                        &data[..data.len()]
-                    );
+                    log::info!("\t\t- Contents: {:?}", descriptor.read_decoded().unwrap());
                 }
             }
         }
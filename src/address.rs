@@ -0,0 +1,343 @@
+//! Bluetooth device addresses.
+
+use std::convert::TryFrom;
+
+use crate::AddressType;
+
+mod oui;
+
+/// Largest value that fits in a 48-bit address.
+const MAX_ADDRESS: u64 = 0xFFFF_FFFF_FFFF;
+
+/// A MAC address for Bluetooth devices.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BluetoothAddress(pub u64);
+
+impl BluetoothAddress {
+    /// Get the bytes contained within the address, most significant first.
+    pub fn bytes(&self) -> [u8; 6] {
+        self.to_be_bytes()
+    }
+
+    /// Create an address from bytes with the most significant byte first, the
+    /// order used when writing an address.
+    pub fn from_be_bytes(bytes: [u8; 6]) -> Self {
+        let mut buf = [0u8; 8];
+        buf[2..].copy_from_slice(&bytes);
+
+        BluetoothAddress(u64::from_be_bytes(buf))
+    }
+
+    /// Create an address from bytes with the least significant byte first, the
+    /// order used over the air.
+    pub fn from_le_bytes(bytes: [u8; 6]) -> Self {
+        let mut buf = [0u8; 8];
+        buf[..6].copy_from_slice(&bytes);
+
+        BluetoothAddress(u64::from_le_bytes(buf))
+    }
+
+    /// Get the bytes of the address with the most significant byte first.
+    pub fn to_be_bytes(&self) -> [u8; 6] {
+        let mut buf = [0u8; 6];
+        buf.copy_from_slice(&self.0.to_be_bytes()[2..]);
+
+        buf
+    }
+
+    /// Get the bytes of the address with the least significant byte first.
+    pub fn to_le_bytes(&self) -> [u8; 6] {
+        let mut buf = [0u8; 6];
+        buf.copy_from_slice(&self.0.to_le_bytes()[..6]);
+
+        buf
+    }
+
+    /// Get an uppercase and colon separated String representing the address.
+    pub fn hex_string(&self) -> String {
+        self.bytes()
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// Get the Organizationally Unique Identifier, the upper 24 bits of the
+    /// address.
+    ///
+    /// This only identifies the manufacturer for public addresses.
+    pub fn oui(&self) -> u32 {
+        (self.0 >> 24) as u32 & 0xFF_FFFF
+    }
+
+    /// Look up the manufacturer assigned this address.
+    ///
+    /// Only a small set of manufacturers commonly seen with Bluetooth devices
+    /// are known. Random addresses do not have a manufacturer, so check the
+    /// type of address first or use [TypedAddress::vendor].
+    pub fn vendor(&self) -> Option<&'static str> {
+        oui::vendor(self.oui())
+    }
+}
+
+impl TryFrom<u64> for BluetoothAddress {
+    type Error = BluetoothAddressParseError;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        if value > MAX_ADDRESS {
+            return Err(BluetoothAddressParseError::TooLarge);
+        }
+
+        Ok(BluetoothAddress(value))
+    }
+}
+
+/// Error generated when parsing a MAC address from a string.
+#[derive(Debug, PartialEq)]
+pub enum BluetoothAddressParseError {
+    /// MAC address has a number of segments not equal to 6.
+    IncorrectSegments,
+    /// MAC segment at the given index, starting from 0, is not a valid
+    /// two-digit base-16 number.
+    InvalidNumber(usize),
+    /// Value does not fit in 48 bits.
+    TooLarge,
+}
+
+impl std::fmt::Display for BluetoothAddressParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BluetoothAddressParseError::IncorrectSegments => {
+                write!(f, "MAC address has incorrect number of segments")
+            }
+            BluetoothAddressParseError::InvalidNumber(index) => {
+                write!(f, "MAC segment {} was not valid base-16 number", index)
+            }
+            BluetoothAddressParseError::TooLarge => {
+                write!(f, "MAC address was larger than 48 bits")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BluetoothAddressParseError {}
+
+impl std::str::FromStr for BluetoothAddress {
+    type Err = BluetoothAddressParseError;
+
+    /// Parse an address separated by colons or dashes, or 12 digits without
+    /// any separators. Digits may be upper or lowercase.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = if s.contains(':') {
+            s.split(':').collect()
+        } else if s.contains('-') {
+            s.split('-').collect()
+        } else if s.len() > 12 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(BluetoothAddressParseError::TooLarge);
+        } else if s.len() == 12 && s.is_ascii() {
+            (0..6).map(|index| &s[index * 2..index * 2 + 2]).collect()
+        } else {
+            return Err(BluetoothAddressParseError::IncorrectSegments);
+        };
+
+        if parts.len() != 6 {
+            return Err(BluetoothAddressParseError::IncorrectSegments);
+        }
+
+        let mut bytes = [0u8; 6];
+        for (index, part) in parts.iter().enumerate() {
+            if part.len() != 2 || !part.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(BluetoothAddressParseError::InvalidNumber(index));
+            }
+
+            bytes[index] = u8::from_str_radix(part, 16)
+                .map_err(|_err| BluetoothAddressParseError::InvalidNumber(index))?;
+        }
+
+        Ok(BluetoothAddress::from_be_bytes(bytes))
+    }
+}
+
+impl std::fmt::Display for BluetoothAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.hex_string())
+    }
+}
+
+impl std::fmt::Debug for BluetoothAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = self.hex_string();
+
+        f.debug_tuple("BluetoothAddress").field(&s).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BluetoothAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.hex_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BluetoothAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AddressVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for AddressVisitor {
+            type Value = BluetoothAddress;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "a Bluetooth address")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(AddressVisitor)
+    }
+}
+
+/// A Bluetooth address along with its type.
+///
+/// A public and a random address with the same value belong to different
+/// devices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TypedAddress {
+    /// The address.
+    pub address: BluetoothAddress,
+    /// Type of the address.
+    pub address_type: AddressType,
+}
+
+impl TypedAddress {
+    /// Create an address, classifying it as given if it was sent as a random
+    /// address.
+    pub fn new(address: BluetoothAddress, random: bool) -> Self {
+        Self {
+            address,
+            address_type: address.address_type(random),
+        }
+    }
+
+    /// Create a public address.
+    pub fn public(address: BluetoothAddress) -> Self {
+        Self::new(address, false)
+    }
+
+    /// Create a random address.
+    pub fn random(address: BluetoothAddress) -> Self {
+        Self::new(address, true)
+    }
+
+    /// Look up the manufacturer assigned this address, always `None` for
+    /// random addresses.
+    pub fn vendor(&self) -> Option<&'static str> {
+        if self.address_type.is_random() {
+            None
+        } else {
+            self.address.vendor()
+        }
+    }
+}
+
+impl std::fmt::Display for TypedAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.address_type.is_random() {
+            "random"
+        } else {
+            "public"
+        };
+
+        write!(f, "{} ({})", self.address, kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::{BluetoothAddress, BluetoothAddressParseError, TypedAddress};
+    use crate::AddressType;
+
+    #[test]
+    fn test_parse_mac() {
+        let input = "C8:FD:19:12:7F:CD";
+        let expected = Ok(BluetoothAddress(220989372923853));
+        let parsed = input.parse();
+        assert_eq!(parsed, expected, "unable to parse MAC address");
+
+        for input in &["c8:fd:19:12:7f:cd", "C8-FD-19-12-7F-CD", "c8fd19127fcd"] {
+            assert_eq!(input.parse(), expected, "unable to parse {}", input);
+        }
+
+        let input = "test";
+        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::IncorrectSegments);
+        let parsed = input.parse();
+        assert_eq!(parsed, expected, "invalid mac was accepted");
+
+        let input = "C8:FD:ZZ:12:7F:CD";
+        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::InvalidNumber(2));
+        let parsed = input.parse();
+        assert_eq!(parsed, expected, "invalid mac was accepted");
+
+        let input = "C8:FD:19:12:7F:1CD";
+        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::InvalidNumber(5));
+        let parsed = input.parse();
+        assert_eq!(parsed, expected, "invalid mac was accepted");
+
+        let input = "1C8FD19127FCD";
+        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::TooLarge);
+        let parsed = input.parse();
+        assert_eq!(parsed, expected, "invalid mac was accepted");
+
+        assert_eq!(
+            BluetoothAddress::try_from(1 << 48),
+            Err(BluetoothAddressParseError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let address = BluetoothAddress(0xC8FD_1912_7FCD);
+        let be = [0xC8, 0xFD, 0x19, 0x12, 0x7F, 0xCD];
+        let le = [0xCD, 0x7F, 0x12, 0x19, 0xFD, 0xC8];
+
+        assert_eq!(address.to_be_bytes(), be);
+        assert_eq!(address.to_le_bytes(), le);
+        assert_eq!(BluetoothAddress::from_be_bytes(be), address);
+        assert_eq!(BluetoothAddress::from_le_bytes(le), address);
+    }
+
+    #[test]
+    fn test_vendor() {
+        let address: BluetoothAddress = "B8:27:EB:12:34:56".parse().unwrap();
+        assert_eq!(address.oui(), 0xB8_27EB);
+        assert_eq!(address.vendor(), Some("Raspberry Pi Foundation"));
+
+        let typed = TypedAddress::public(address);
+        assert_eq!(typed.vendor(), Some("Raspberry Pi Foundation"));
+        assert_eq!(typed.to_string(), "B8:27:EB:12:34:56 (public)");
+
+        let random = TypedAddress::random(address);
+        assert_eq!(random.address_type, AddressType::NonResolvablePrivate);
+        assert_eq!(random.vendor(), None);
+        assert_eq!(random.to_string(), "B8:27:EB:12:34:56 (random)");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde::de::{value::StrDeserializer, IntoDeserializer};
+        use serde::Deserialize;
+
+        let deserializer: StrDeserializer<serde::de::value::Error> =
+            "c8-fd-19-12-7f-cd".into_deserializer();
+        assert_eq!(
+            BluetoothAddress::deserialize(deserializer).unwrap(),
+            BluetoothAddress(0xC8FD_1912_7FCD)
+        );
+    }
+}
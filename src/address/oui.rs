@@ -0,0 +1,48 @@
+//! Manufacturers for a small set of Organizationally Unique Identifiers.
+
+/// Known OUIs, sorted so they can be binary searched.
+const VENDORS: &[(u32, &str)] = &[
+    (0x00_025B, "Cambridge Silicon Radio"),
+    (0x00_0393, "Apple"),
+    (0x00_0A95, "Apple"),
+    (0x00_0B57, "Silicon Laboratories"),
+    (0x00_1018, "Broadcom"),
+    (0x00_124B, "Texas Instruments"),
+    (0x00_1B63, "Apple"),
+    (0x00_22D0, "Polar Electro"),
+    (0x00_2500, "Apple"),
+    (0x00_50F2, "Microsoft"),
+    (0x00_E04C, "Realtek"),
+    (0x24_0AC4, "Espressif"),
+    (0x24_6F28, "Espressif"),
+    (0x30_AEA4, "Espressif"),
+    (0x34_B1F7, "Texas Instruments"),
+    (0x3C_71BF, "Espressif"),
+    (0x78_A504, "Texas Instruments"),
+    (0xA0_9E1A, "Polar Electro"),
+    (0xA4_CF12, "Espressif"),
+    (0xAC_BC32, "Apple"),
+    (0xB0_B448, "Texas Instruments"),
+    (0xB8_27EB, "Raspberry Pi Foundation"),
+    (0xD0_3972, "Texas Instruments"),
+    (0xDC_A632, "Raspberry Pi Trading"),
+    (0xE4_5F01, "Raspberry Pi Trading"),
+];
+
+/// Find the manufacturer assigned the given OUI.
+pub(super) fn vendor(oui: u32) -> Option<&'static str> {
+    VENDORS
+        .binary_search_by_key(&oui, |(oui, _name)| *oui)
+        .ok()
+        .map(|index| VENDORS[index].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VENDORS;
+
+    #[test]
+    fn test_vendors_sorted() {
+        assert!(VENDORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+}
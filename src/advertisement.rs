@@ -0,0 +1,747 @@
+//! Building, parsing, and publishing advertising payloads.
+//!
+//! Advertising payloads are a sequence of AD structures, each made of a
+//! length, an AD type, and type specific data. [AdvertisementData] holds these
+//! structures and can encode them to or parse them from their on-air form
+//! without touching any platform APIs.
+
+use crate::windows::devices::bluetooth::advertisement::{
+    BluetoothLEAdvertisement, BluetoothLEAdvertisementDataSection,
+    BluetoothLEAdvertisementPublisher,
+};
+use crate::windows::storage::streams::DataWriter;
+use crate::{Error, Result, Uuid};
+
+/// Maximum number of bytes in a legacy advertising payload.
+pub const MAX_PAYLOAD_LEN: usize = 31;
+
+/// Maximum data in a single AD structure, whose length byte also counts the
+/// AD type.
+const MAX_STRUCTURE_DATA_LEN: usize = 254;
+
+/// AD type values from the Bluetooth Assigned Numbers.
+pub mod ad_type {
+    pub const FLAGS: u8 = 0x01;
+    pub const INCOMPLETE_SERVICE_UUIDS_16: u8 = 0x02;
+    pub const COMPLETE_SERVICE_UUIDS_16: u8 = 0x03;
+    pub const INCOMPLETE_SERVICE_UUIDS_32: u8 = 0x04;
+    pub const COMPLETE_SERVICE_UUIDS_32: u8 = 0x05;
+    pub const INCOMPLETE_SERVICE_UUIDS_128: u8 = 0x06;
+    pub const COMPLETE_SERVICE_UUIDS_128: u8 = 0x07;
+    pub const SHORTENED_LOCAL_NAME: u8 = 0x08;
+    pub const COMPLETE_LOCAL_NAME: u8 = 0x09;
+    pub const TX_POWER_LEVEL: u8 = 0x0A;
+    pub const SERVICE_DATA_16: u8 = 0x16;
+    pub const SERVICE_DATA_32: u8 = 0x20;
+    pub const SERVICE_DATA_128: u8 = 0x21;
+    pub const MANUFACTURER_DATA: u8 = 0xFF;
+}
+
+bitflags::bitflags! {
+    /// Contents of the Flags AD structure, describing how the device may be
+    /// discovered.
+    pub struct AdvertisingFlags: u8 {
+        const LE_LIMITED_DISCOVERABLE = 0x01;
+        const LE_GENERAL_DISCOVERABLE = 0x02;
+        const BR_EDR_NOT_SUPPORTED = 0x04;
+        const SIMULTANEOUS_LE_BR_EDR_CONTROLLER = 0x08;
+        const SIMULTANEOUS_LE_BR_EDR_HOST = 0x10;
+    }
+}
+
+/// A single AD structure from an advertising payload.
+///
+/// Structures with an unknown type, or with data that does not match what
+/// their type requires, are kept as [AdStructure::Other] so that nothing is
+/// lost when parsing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdStructure {
+    /// Discoverability flags.
+    Flags(AdvertisingFlags),
+    /// List of 16-bit service UUIDs.
+    ServiceUuids16 { complete: bool, uuids: Vec<u16> },
+    /// List of 32-bit service UUIDs.
+    ServiceUuids32 { complete: bool, uuids: Vec<u32> },
+    /// List of 128-bit service UUIDs.
+    ServiceUuids128 { complete: bool, uuids: Vec<Uuid> },
+    /// Shortened or complete device name.
+    LocalName { complete: bool, name: String },
+    /// Transmitted power level in dBm.
+    TxPowerLevel(i8),
+    /// Data associated with a 16-bit service UUID.
+    ServiceData16 { uuid: u16, data: Vec<u8> },
+    /// Data associated with a 32-bit service UUID.
+    ServiceData32 { uuid: u32, data: Vec<u8> },
+    /// Data associated with a 128-bit service UUID.
+    ServiceData128 { uuid: Uuid, data: Vec<u8> },
+    /// Data defined by the company with the given identifier.
+    ManufacturerData { company_id: u16, data: Vec<u8> },
+    /// Any other structure, stored as its raw type and data.
+    Other { ad_type: u8, data: Vec<u8> },
+}
+
+impl AdStructure {
+    /// Create a structure from its AD type and data.
+    pub fn from_raw(ad_type: u8, data: &[u8]) -> Self {
+        Self::decode(ad_type, data).unwrap_or_else(|| AdStructure::Other {
+            ad_type,
+            data: data.to_vec(),
+        })
+    }
+
+    fn decode(ad_type: u8, data: &[u8]) -> Option<Self> {
+        let structure = match ad_type {
+            ad_type::FLAGS if data.len() == 1 => {
+                AdStructure::Flags(AdvertisingFlags::from_bits_truncate(data[0]))
+            }
+            ad_type::INCOMPLETE_SERVICE_UUIDS_16 | ad_type::COMPLETE_SERVICE_UUIDS_16
+                if data.len() % 2 == 0 =>
+            {
+                AdStructure::ServiceUuids16 {
+                    complete: ad_type == ad_type::COMPLETE_SERVICE_UUIDS_16,
+                    uuids: data
+                        .chunks(2)
+                        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+                        .collect(),
+                }
+            }
+            ad_type::INCOMPLETE_SERVICE_UUIDS_32 | ad_type::COMPLETE_SERVICE_UUIDS_32
+                if data.len() % 4 == 0 =>
+            {
+                AdStructure::ServiceUuids32 {
+                    complete: ad_type == ad_type::COMPLETE_SERVICE_UUIDS_32,
+                    uuids: data.chunks(4).map(read_u32).collect(),
+                }
+            }
+            ad_type::INCOMPLETE_SERVICE_UUIDS_128 | ad_type::COMPLETE_SERVICE_UUIDS_128
+                if data.len() % 16 == 0 =>
+            {
+                AdStructure::ServiceUuids128 {
+                    complete: ad_type == ad_type::COMPLETE_SERVICE_UUIDS_128,
+                    uuids: data.chunks(16).map(read_uuid).collect(),
+                }
+            }
+            ad_type::SHORTENED_LOCAL_NAME | ad_type::COMPLETE_LOCAL_NAME => {
+                AdStructure::LocalName {
+                    complete: ad_type == ad_type::COMPLETE_LOCAL_NAME,
+                    name: String::from_utf8(data.to_vec()).ok()?,
+                }
+            }
+            ad_type::TX_POWER_LEVEL if data.len() == 1 => AdStructure::TxPowerLevel(data[0] as i8),
+            ad_type::SERVICE_DATA_16 if data.len() >= 2 => AdStructure::ServiceData16 {
+                uuid: u16::from_le_bytes([data[0], data[1]]),
+                data: data[2..].to_vec(),
+            },
+            ad_type::SERVICE_DATA_32 if data.len() >= 4 => AdStructure::ServiceData32 {
+                uuid: read_u32(&data[..4]),
+                data: data[4..].to_vec(),
+            },
+            ad_type::SERVICE_DATA_128 if data.len() >= 16 => AdStructure::ServiceData128 {
+                uuid: read_uuid(&data[..16]),
+                data: data[16..].to_vec(),
+            },
+            ad_type::MANUFACTURER_DATA if data.len() >= 2 => AdStructure::ManufacturerData {
+                company_id: u16::from_le_bytes([data[0], data[1]]),
+                data: data[2..].to_vec(),
+            },
+            _ => return None,
+        };
+
+        Some(structure)
+    }
+
+    /// The AD type identifying this structure.
+    pub fn ad_type(&self) -> u8 {
+        match self {
+            AdStructure::Flags(_) => ad_type::FLAGS,
+            AdStructure::ServiceUuids16 { complete: true, .. } => {
+                ad_type::COMPLETE_SERVICE_UUIDS_16
+            }
+            AdStructure::ServiceUuids16 { .. } => ad_type::INCOMPLETE_SERVICE_UUIDS_16,
+            AdStructure::ServiceUuids32 { complete: true, .. } => {
+                ad_type::COMPLETE_SERVICE_UUIDS_32
+            }
+            AdStructure::ServiceUuids32 { .. } => ad_type::INCOMPLETE_SERVICE_UUIDS_32,
+            AdStructure::ServiceUuids128 { complete: true, .. } => {
+                ad_type::COMPLETE_SERVICE_UUIDS_128
+            }
+            AdStructure::ServiceUuids128 { .. } => ad_type::INCOMPLETE_SERVICE_UUIDS_128,
+            AdStructure::LocalName { complete: true, .. } => ad_type::COMPLETE_LOCAL_NAME,
+            AdStructure::LocalName { .. } => ad_type::SHORTENED_LOCAL_NAME,
+            AdStructure::TxPowerLevel(_) => ad_type::TX_POWER_LEVEL,
+            AdStructure::ServiceData16 { .. } => ad_type::SERVICE_DATA_16,
+            AdStructure::ServiceData32 { .. } => ad_type::SERVICE_DATA_32,
+            AdStructure::ServiceData128 { .. } => ad_type::SERVICE_DATA_128,
+            AdStructure::ManufacturerData { .. } => ad_type::MANUFACTURER_DATA,
+            AdStructure::Other { ad_type, .. } => *ad_type,
+        }
+    }
+
+    /// The data of this structure, not including the length or AD type.
+    pub fn data(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        match self {
+            AdStructure::Flags(flags) => buf.push(flags.bits()),
+            AdStructure::ServiceUuids16 { uuids, .. } => {
+                for uuid in uuids {
+                    buf.extend_from_slice(&uuid.to_le_bytes());
+                }
+            }
+            AdStructure::ServiceUuids32 { uuids, .. } => {
+                for uuid in uuids {
+                    buf.extend_from_slice(&uuid.to_le_bytes());
+                }
+            }
+            AdStructure::ServiceUuids128 { uuids, .. } => {
+                for uuid in uuids {
+                    buf.extend_from_slice(&uuid.to_le_bytes());
+                }
+            }
+            AdStructure::LocalName { name, .. } => buf.extend_from_slice(name.as_bytes()),
+            AdStructure::TxPowerLevel(level) => buf.push(*level as u8),
+            AdStructure::ServiceData16 { uuid, data } => {
+                buf.extend_from_slice(&uuid.to_le_bytes());
+                buf.extend_from_slice(data);
+            }
+            AdStructure::ServiceData32 { uuid, data } => {
+                buf.extend_from_slice(&uuid.to_le_bytes());
+                buf.extend_from_slice(data);
+            }
+            AdStructure::ServiceData128 { uuid, data } => {
+                buf.extend_from_slice(&uuid.to_le_bytes());
+                buf.extend_from_slice(data);
+            }
+            AdStructure::ManufacturerData { company_id, data } => {
+                buf.extend_from_slice(&company_id.to_le_bytes());
+                buf.extend_from_slice(data);
+            }
+            AdStructure::Other { data, .. } => buf.extend_from_slice(data),
+        }
+
+        buf
+    }
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn read_uuid(data: &[u8]) -> Uuid {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(data);
+
+    Uuid::from_le_bytes(bytes)
+}
+
+/// Error generated when parsing an advertising payload.
+#[derive(Debug, PartialEq)]
+pub enum AdvertisementParseError {
+    /// AD structure at the given offset claims more data than is available.
+    Truncated(usize),
+}
+
+impl std::fmt::Display for AdvertisementParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdvertisementParseError::Truncated(offset) => {
+                write!(f, "AD structure at offset {} was truncated", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AdvertisementParseError {}
+
+/// Contents of an advertising payload.
+///
+/// # Example
+///
+/// ```
+/// use wible::{AdvertisementData, AdvertisingFlags, Uuid};
+///
+/// let data = AdvertisementData::new()
+///     .with_flags(AdvertisingFlags::LE_GENERAL_DISCOVERABLE)
+///     .with_local_name("wible")
+///     .with_service_uuid(Uuid::from_u16(0x180D));
+///
+/// let parsed = AdvertisementData::parse(&data.encode().unwrap()).unwrap();
+/// assert_eq!(parsed.local_name(), Some("wible"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdvertisementData {
+    structures: Vec<AdStructure>,
+}
+
+impl AdvertisementData {
+    /// Create an empty payload.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the discoverability flags, replacing any existing flags.
+    pub fn with_flags(mut self, flags: AdvertisingFlags) -> Self {
+        self.structures
+            .retain(|structure| !matches!(structure, AdStructure::Flags(_)));
+        self.structures.insert(0, AdStructure::Flags(flags));
+        self
+    }
+
+    /// Set the complete local name, replacing any existing name.
+    pub fn with_local_name(mut self, name: &str) -> Self {
+        self.structures
+            .retain(|structure| !matches!(structure, AdStructure::LocalName { .. }));
+        self.with_structure(AdStructure::LocalName {
+            complete: true,
+            name: name.to_string(),
+        })
+    }
+
+    /// Add a service UUID to the complete list of services, using the
+    /// shortest form the UUID allows.
+    pub fn with_service_uuid(mut self, uuid: Uuid) -> Self {
+        let added = self.structures.iter_mut().any(|structure| match structure {
+            AdStructure::ServiceUuids16 { uuids, .. } => match uuid.as_u16() {
+                Some(short) => {
+                    uuids.push(short);
+                    true
+                }
+                None => false,
+            },
+            AdStructure::ServiceUuids32 { uuids, .. } => match uuid.as_u32() {
+                Some(short) if uuid.as_u16().is_none() => {
+                    uuids.push(short);
+                    true
+                }
+                _ => false,
+            },
+            AdStructure::ServiceUuids128 { uuids, .. } if uuid.as_u32().is_none() => {
+                uuids.push(uuid);
+                true
+            }
+            _ => false,
+        });
+
+        if added {
+            return self;
+        }
+
+        let structure = match (uuid.as_u16(), uuid.as_u32()) {
+            (Some(short), _) => AdStructure::ServiceUuids16 {
+                complete: true,
+                uuids: vec![short],
+            },
+            (None, Some(short)) => AdStructure::ServiceUuids32 {
+                complete: true,
+                uuids: vec![short],
+            },
+            (None, None) => AdStructure::ServiceUuids128 {
+                complete: true,
+                uuids: vec![uuid],
+            },
+        };
+
+        self.with_structure(structure)
+    }
+
+    /// Add data associated with a service UUID.
+    pub fn with_service_data(self, uuid: Uuid, data: &[u8]) -> Self {
+        let data = data.to_vec();
+
+        let structure = match (uuid.as_u16(), uuid.as_u32()) {
+            (Some(uuid), _) => AdStructure::ServiceData16 { uuid, data },
+            (None, Some(uuid)) => AdStructure::ServiceData32 { uuid, data },
+            (None, None) => AdStructure::ServiceData128 { uuid, data },
+        };
+
+        self.with_structure(structure)
+    }
+
+    /// Add data defined by the company with the given identifier.
+    pub fn with_manufacturer_data(self, company_id: u16, data: &[u8]) -> Self {
+        self.with_structure(AdStructure::ManufacturerData {
+            company_id,
+            data: data.to_vec(),
+        })
+    }
+
+    /// Set the transmitted power level in dBm.
+    pub fn with_tx_power_level(mut self, level: i8) -> Self {
+        self.structures
+            .retain(|structure| !matches!(structure, AdStructure::TxPowerLevel(_)));
+        self.with_structure(AdStructure::TxPowerLevel(level))
+    }
+
+    /// Add an arbitrary AD structure.
+    pub fn with_structure(mut self, structure: AdStructure) -> Self {
+        self.structures.push(structure);
+        self
+    }
+
+    /// All structures contained within this payload.
+    pub fn structures(&self) -> &[AdStructure] {
+        &self.structures
+    }
+
+    /// Discoverability flags, if present.
+    pub fn flags(&self) -> Option<AdvertisingFlags> {
+        self.structures
+            .iter()
+            .find_map(|structure| match structure {
+                AdStructure::Flags(flags) => Some(*flags),
+                _ => None,
+            })
+    }
+
+    /// Shortened or complete local name, if present.
+    pub fn local_name(&self) -> Option<&str> {
+        self.structures
+            .iter()
+            .find_map(|structure| match structure {
+                AdStructure::LocalName { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Every advertised service UUID, from both complete and incomplete lists.
+    pub fn service_uuids(&self) -> Vec<Uuid> {
+        let mut service_uuids = Vec::new();
+
+        for structure in &self.structures {
+            match structure {
+                AdStructure::ServiceUuids16 { uuids, .. } => {
+                    service_uuids.extend(uuids.iter().copied().map(Uuid::from_u16))
+                }
+                AdStructure::ServiceUuids32 { uuids, .. } => {
+                    service_uuids.extend(uuids.iter().copied().map(Uuid::from_u32))
+                }
+                AdStructure::ServiceUuids128 { uuids, .. } => service_uuids.extend(uuids),
+                _ => (),
+            }
+        }
+
+        service_uuids
+    }
+
+    /// Data associated with the given service UUID, if present.
+    pub fn service_data(&self, service: Uuid) -> Option<&[u8]> {
+        self.structures
+            .iter()
+            .find_map(|structure| match structure {
+                AdStructure::ServiceData16 { uuid, data } if Uuid::from_u16(*uuid) == service => {
+                    Some(data.as_slice())
+                }
+                AdStructure::ServiceData32 { uuid, data } if Uuid::from_u32(*uuid) == service => {
+                    Some(data.as_slice())
+                }
+                AdStructure::ServiceData128 { uuid, data } if *uuid == service => {
+                    Some(data.as_slice())
+                }
+                _ => None,
+            })
+    }
+
+    /// Data defined by the company with the given identifier, if present.
+    pub fn manufacturer_data(&self, company: u16) -> Option<&[u8]> {
+        self.structures
+            .iter()
+            .find_map(|structure| match structure {
+                AdStructure::ManufacturerData { company_id, data } if *company_id == company => {
+                    Some(data.as_slice())
+                }
+                _ => None,
+            })
+    }
+
+    /// Transmitted power level in dBm, if present.
+    pub fn tx_power_level(&self) -> Option<i8> {
+        self.structures
+            .iter()
+            .find_map(|structure| match structure {
+                AdStructure::TxPowerLevel(level) => Some(*level),
+                _ => None,
+            })
+    }
+
+    /// Encode all structures into their on-air form.
+    ///
+    /// Fails with [Error::PayloadTooLarge] if a structure has more data than
+    /// its length byte can describe.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+
+        for structure in &self.structures {
+            let data = structure.data();
+            if data.len() > MAX_STRUCTURE_DATA_LEN {
+                return Err(Error::PayloadTooLarge(buf.len() + data.len() + 2));
+            }
+
+            buf.push(data.len() as u8 + 1);
+            buf.push(structure.ad_type());
+            buf.extend(data);
+        }
+
+        Ok(buf)
+    }
+
+    /// Parse a payload from its on-air form.
+    ///
+    /// A zero length byte marks the end of the significant part of a payload,
+    /// anything after it is ignored.
+    pub fn parse(payload: &[u8]) -> std::result::Result<Self, AdvertisementParseError> {
+        let mut structures = Vec::new();
+        let mut offset = 0;
+
+        while offset < payload.len() {
+            let len = payload[offset] as usize;
+            if len == 0 {
+                break;
+            }
+
+            if offset + len >= payload.len() {
+                return Err(AdvertisementParseError::Truncated(offset));
+            }
+
+            let ad_type = payload[offset + 1];
+            let data = &payload[offset + 2..offset + 1 + len];
+            structures.push(AdStructure::from_raw(ad_type, data));
+
+            offset += len + 1;
+        }
+
+        Ok(Self { structures })
+    }
+}
+
+/// A method of broadcasting advertisements for an [AdvertisementPublisher].
+pub trait PublisherBackend {
+    /// Begin broadcasting the given payload, replacing anything previously
+    /// being broadcast.
+    fn start(&mut self, data: &AdvertisementData) -> Result<()>;
+
+    /// Stop broadcasting.
+    fn stop(&mut self) -> Result<()>;
+}
+
+/// Broadcasts advertisements from this device.
+///
+/// Advertisements are not sent until [start](AdvertisementPublisher::start)
+/// is called, and stop when the publisher is dropped.
+///
+/// # Example
+///
+/// ```no_run
+/// use wible::{AdvertisementData, AdvertisementPublisher};
+///
+/// let data = AdvertisementData::new().with_manufacturer_data(0xFFFF, b"hello");
+/// let mut publisher = AdvertisementPublisher::new(data);
+/// publisher.start().expect("Unable to start publishing");
+/// ```
+pub struct AdvertisementPublisher {
+    data: AdvertisementData,
+    backend: Box<dyn PublisherBackend>,
+    started: bool,
+}
+
+impl AdvertisementPublisher {
+    /// Create a publisher which broadcasts using Windows.
+    ///
+    /// Windows manages the flags structure itself, so any flags in the payload
+    /// are not broadcast.
+    pub fn new(data: AdvertisementData) -> Self {
+        Self::with_backend(data, WindowsPublisher::default())
+    }
+
+    /// Create a publisher which broadcasts using the given backend.
+    pub fn with_backend<B: PublisherBackend + 'static>(
+        data: AdvertisementData,
+        backend: B,
+    ) -> Self {
+        Self {
+            data,
+            backend: Box::new(backend),
+            started: false,
+        }
+    }
+
+    /// The payload being published.
+    pub fn data(&self) -> &AdvertisementData {
+        &self.data
+    }
+
+    /// Replace the payload, updating the broadcast if already started.
+    pub fn set_data(&mut self, data: AdvertisementData) -> Result<()> {
+        self.data = data;
+
+        if self.started {
+            self.start()?;
+        }
+
+        Ok(())
+    }
+
+    /// If advertisements are currently being broadcast.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Start broadcasting advertisements.
+    ///
+    /// Returns an error without broadcasting if the encoded payload does not
+    /// fit in a single advertisement.
+    pub fn start(&mut self) -> Result<()> {
+        let len = self.data.encode()?.len();
+        if len > MAX_PAYLOAD_LEN {
+            return Err(Error::PayloadTooLarge(len));
+        }
+
+        log::debug!("Starting advertisement publisher");
+        self.backend.start(&self.data)?;
+        self.started = true;
+
+        Ok(())
+    }
+
+    /// Stop broadcasting advertisements.
+    pub fn stop(&mut self) -> Result<()> {
+        log::debug!("Stopping advertisement publisher");
+        self.backend.stop()?;
+        self.started = false;
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for AdvertisementPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdvertisementPublisher")
+            .field("data", &self.data)
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+impl Drop for AdvertisementPublisher {
+    fn drop(&mut self) {
+        if !self.started {
+            return;
+        }
+
+        if let Err(err) = self.stop() {
+            log::error!("Error stopping advertisement publisher: {:?}", err);
+        }
+    }
+}
+
+/// Publishes advertisements through BluetoothLEAdvertisementPublisher.
+#[derive(Default)]
+struct WindowsPublisher {
+    publisher: Option<BluetoothLEAdvertisementPublisher>,
+}
+
+impl PublisherBackend for WindowsPublisher {
+    fn start(&mut self, data: &AdvertisementData) -> Result<()> {
+        self.stop()?;
+
+        let advertisement = BluetoothLEAdvertisement::new()?;
+        let sections = advertisement.data_sections()?;
+
+        for structure in data.structures() {
+            if structure.ad_type() == ad_type::FLAGS {
+                log::debug!("Skipping flags, Windows sets them itself");
+                continue;
+            }
+
+            let writer = DataWriter::new()?;
+            writer.write_bytes(&structure.data())?;
+            let buf = writer.detach_buffer()?;
+
+            let section = BluetoothLEAdvertisementDataSection::create(structure.ad_type(), &buf)?;
+            sections.append(&section)?;
+        }
+
+        let publisher = BluetoothLEAdvertisementPublisher::create(&advertisement)?;
+        publisher.start()?;
+        self.publisher = Some(publisher);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if let Some(publisher) = self.publisher.take() {
+            publisher.stop()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_round_trip() {
+        let data = AdvertisementData::new()
+            .with_flags(
+                AdvertisingFlags::LE_GENERAL_DISCOVERABLE | AdvertisingFlags::BR_EDR_NOT_SUPPORTED,
+            )
+            .with_local_name("wible")
+            .with_service_uuid(Uuid::from_u16(0x180D))
+            .with_service_uuid(Uuid::from_u16(0x180F))
+            .with_service_data(Uuid::from_u16(0x180F), &[87])
+            .with_manufacturer_data(0xFFFF, &[1, 2, 3]);
+
+        let encoded = data.encode().unwrap();
+        assert_eq!(
+            encoded,
+            vec![
+                0x02, 0x01, 0x06, 0x06, 0x09, b'w', b'i', b'b', b'l', b'e', 0x05, 0x03, 0x0D, 0x18,
+                0x0F, 0x18, 0x04, 0x16, 0x0F, 0x18, 87, 0x06, 0xFF, 0xFF, 0xFF, 1, 2, 3
+            ]
+        );
+
+        let parsed = AdvertisementData::parse(&encoded).unwrap();
+        assert_eq!(parsed, data);
+        assert_eq!(
+            parsed.service_uuids(),
+            vec![Uuid::from_u16(0x180D), Uuid::from_u16(0x180F)]
+        );
+        assert_eq!(parsed.service_data(Uuid::from_u16(0x180F)), Some(&[87][..]));
+        assert_eq!(parsed.manufacturer_data(0xFFFF), Some(&[1, 2, 3][..]));
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let parsed = AdvertisementData::parse(&[0x02, 0x0A, 0xF4, 0x03, 0x01, 0x06, 0x00, 0x00]);
+        assert_eq!(
+            parsed.unwrap().structures(),
+            &[
+                AdStructure::TxPowerLevel(-12),
+                AdStructure::Other {
+                    ad_type: ad_type::FLAGS,
+                    data: vec![0x06, 0x00]
+                }
+            ]
+        );
+
+        let parsed = AdvertisementData::parse(&[0x02, 0x01, 0x06, 0x05, 0xFF, 0x4C]);
+        assert_eq!(parsed, Err(AdvertisementParseError::Truncated(3)));
+    }
+
+    #[test]
+    fn test_encode_oversized_structure() {
+        let data = AdvertisementData::new().with_manufacturer_data(0xFFFF, &[0; 252]);
+        assert_eq!(data.encode().unwrap()[0], 0xFF);
+
+        for len in &[253, 300] {
+            let data = AdvertisementData::new()
+                .with_flags(AdvertisingFlags::LE_GENERAL_DISCOVERABLE)
+                .with_manufacturer_data(0xFFFF, &vec![0; *len]);
+            assert!(matches!(
+                data.encode(),
+                Err(Error::PayloadTooLarge(size)) if size == len + 7
+            ));
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! Encoding and decoding of iBeacon and Eddystone frames.
+
+use crate::{AdvertisementData, AdvertisingFlags, Uuid};
+
+/// Company identifier assigned to Apple, used for iBeacon frames.
+const APPLE_COMPANY_ID: u16 = 0x004C;
+
+/// 16-bit service UUID assigned to Eddystone.
+const EDDYSTONE_SERVICE: u16 = 0xFEAA;
+
+/// An Apple iBeacon frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IBeacon {
+    /// Identifier shared by all beacons belonging to a deployment.
+    pub proximity_uuid: Uuid,
+    /// Identifier for a group of beacons.
+    pub major: u16,
+    /// Identifier for an individual beacon.
+    pub minor: u16,
+    /// Calibrated signal strength at 1 meter in dBm.
+    pub measured_power: i8,
+}
+
+impl IBeacon {
+    /// Build an advertising payload containing this frame.
+    pub fn to_advertisement_data(&self) -> AdvertisementData {
+        let mut data = vec![0x02, 0x15];
+        data.extend_from_slice(&self.proximity_uuid.0.to_be_bytes());
+        data.extend_from_slice(&self.major.to_be_bytes());
+        data.extend_from_slice(&self.minor.to_be_bytes());
+        data.push(self.measured_power as u8);
+
+        AdvertisementData::new()
+            .with_flags(
+                AdvertisingFlags::LE_GENERAL_DISCOVERABLE | AdvertisingFlags::BR_EDR_NOT_SUPPORTED,
+            )
+            .with_manufacturer_data(APPLE_COMPANY_ID, &data)
+    }
+
+    /// Find an iBeacon frame within an advertising payload.
+    pub fn from_advertisement_data(data: &AdvertisementData) -> Option<Self> {
+        let data = data.manufacturer_data(APPLE_COMPANY_ID)?;
+        if data.len() != 23 || data[..2] != [0x02, 0x15] {
+            return None;
+        }
+
+        let mut uuid = [0u8; 16];
+        uuid.copy_from_slice(&data[2..18]);
+
+        Some(Self {
+            proximity_uuid: Uuid(u128::from_be_bytes(uuid)),
+            major: u16::from_be_bytes([data[18], data[19]]),
+            minor: u16::from_be_bytes([data[20], data[21]]),
+            measured_power: data[22] as i8,
+        })
+    }
+}
+
+/// URL scheme prefixes which may be encoded as a single byte.
+const URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+
+/// Common URL substrings which may be encoded as a single byte.
+const URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+/// Error generated when a URL cannot be encoded in an Eddystone-URL frame.
+#[derive(Debug, PartialEq)]
+pub enum EddystoneUrlError {
+    /// URL did not start with `http://` or `https://`.
+    UnsupportedScheme,
+    /// URL contained a character outside of printable ASCII.
+    InvalidCharacter,
+    /// URL took more than 17 bytes once encoded.
+    TooLong,
+}
+
+impl std::fmt::Display for EddystoneUrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EddystoneUrlError::UnsupportedScheme => write!(f, "URL scheme must be http or https"),
+            EddystoneUrlError::InvalidCharacter => {
+                write!(f, "URL contained a non-printable or non-ASCII character")
+            }
+            EddystoneUrlError::TooLong => write!(f, "URL was longer than 17 bytes once encoded"),
+        }
+    }
+}
+
+impl std::error::Error for EddystoneUrlError {}
+
+/// An Eddystone frame.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Eddystone {
+    /// Eddystone-UID, identifying a beacon by namespace and instance.
+    Uid {
+        /// Calibrated transmission power at 0 meters in dBm.
+        tx_power: i8,
+        namespace: [u8; 10],
+        instance: [u8; 6],
+    },
+    /// Eddystone-URL, broadcasting a URL.
+    Url {
+        /// Calibrated transmission power at 0 meters in dBm.
+        tx_power: i8,
+        url: String,
+    },
+    /// Unencrypted Eddystone-TLM, broadcasting beacon telemetry.
+    Tlm {
+        /// Battery voltage in millivolts, 0 if not supported.
+        battery_voltage: u16,
+        /// Beacon temperature in degrees Celsius, if supported.
+        temperature: Option<f32>,
+        /// Number of advertisements sent since boot.
+        advertisement_count: u32,
+        /// Time since boot, with a resolution of 0.1 seconds.
+        uptime: std::time::Duration,
+    },
+}
+
+impl Eddystone {
+    /// Encode the frame as it appears in the Eddystone service data.
+    pub fn encode(&self) -> Result<Vec<u8>, EddystoneUrlError> {
+        let mut buf = Vec::new();
+
+        match self {
+            Eddystone::Uid {
+                tx_power,
+                namespace,
+                instance,
+            } => {
+                buf.extend_from_slice(&[0x00, *tx_power as u8]);
+                buf.extend_from_slice(namespace);
+                buf.extend_from_slice(instance);
+                buf.extend_from_slice(&[0x00, 0x00]);
+            }
+            Eddystone::Url { tx_power, url } => {
+                buf.extend_from_slice(&[0x10, *tx_power as u8]);
+                buf.extend(encode_url(url)?);
+            }
+            Eddystone::Tlm {
+                battery_voltage,
+                temperature,
+                advertisement_count,
+                uptime,
+            } => {
+                let temperature = match temperature {
+                    Some(temperature) => (temperature * 256.0).round() as i16,
+                    None => i16::MIN,
+                };
+                let uptime = (uptime.as_millis() / 100) as u32;
+
+                buf.extend_from_slice(&[0x20, 0x00]);
+                buf.extend_from_slice(&battery_voltage.to_be_bytes());
+                buf.extend_from_slice(&temperature.to_be_bytes());
+                buf.extend_from_slice(&advertisement_count.to_be_bytes());
+                buf.extend_from_slice(&uptime.to_be_bytes());
+            }
+        }
+
+        Ok(buf)
+    }
+
+    /// Decode a frame from the Eddystone service data.
+    pub fn decode(frame: &[u8]) -> Option<Self> {
+        match frame {
+            // The two reserved bytes at the end are left out by some beacons.
+            [0x00, tx_power, rest @ ..] if rest.len() == 16 || rest.len() == 18 => {
+                let mut namespace = [0u8; 10];
+                namespace.copy_from_slice(&rest[..10]);
+                let mut instance = [0u8; 6];
+                instance.copy_from_slice(&rest[10..16]);
+
+                Some(Eddystone::Uid {
+                    tx_power: *tx_power as i8,
+                    namespace,
+                    instance,
+                })
+            }
+            [0x10, tx_power, rest @ ..] => Some(Eddystone::Url {
+                tx_power: *tx_power as i8,
+                url: decode_url(rest)?,
+            }),
+            [0x20, 0x00, rest @ ..] if rest.len() == 12 => {
+                let temperature = i16::from_be_bytes([rest[2], rest[3]]);
+                let uptime = u32::from_be_bytes([rest[8], rest[9], rest[10], rest[11]]);
+
+                Some(Eddystone::Tlm {
+                    battery_voltage: u16::from_be_bytes([rest[0], rest[1]]),
+                    temperature: if temperature == i16::MIN {
+                        None
+                    } else {
+                        Some(f32::from(temperature) / 256.0)
+                    },
+                    advertisement_count: u32::from_be_bytes([rest[4], rest[5], rest[6], rest[7]]),
+                    uptime: std::time::Duration::from_millis(u64::from(uptime) * 100),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Build an advertising payload containing this frame.
+    pub fn to_advertisement_data(&self) -> Result<AdvertisementData, EddystoneUrlError> {
+        let service = Uuid::from_u16(EDDYSTONE_SERVICE);
+
+        Ok(AdvertisementData::new()
+            .with_flags(
+                AdvertisingFlags::LE_GENERAL_DISCOVERABLE | AdvertisingFlags::BR_EDR_NOT_SUPPORTED,
+            )
+            .with_service_uuid(service)
+            .with_service_data(service, &self.encode()?))
+    }
+
+    /// Find an Eddystone frame within an advertising payload.
+    pub fn from_advertisement_data(data: &AdvertisementData) -> Option<Self> {
+        Self::decode(data.service_data(Uuid::from_u16(EDDYSTONE_SERVICE))?)
+    }
+}
+
+fn encode_url(url: &str) -> Result<Vec<u8>, EddystoneUrlError> {
+    let (scheme, mut rest) = URL_SCHEMES
+        .iter()
+        .enumerate()
+        .find_map(|(code, scheme)| url.strip_prefix(scheme).map(|rest| (code as u8, rest)))
+        .ok_or(EddystoneUrlError::UnsupportedScheme)?;
+
+    let mut buf = vec![scheme];
+
+    'outer: while !rest.is_empty() {
+        for (code, expansion) in URL_EXPANSIONS.iter().enumerate() {
+            if let Some(remaining) = rest.strip_prefix(expansion) {
+                buf.push(code as u8);
+                rest = remaining;
+                continue 'outer;
+            }
+        }
+
+        let c = rest.as_bytes()[0];
+        if !(0x21..0x7F).contains(&c) {
+            return Err(EddystoneUrlError::InvalidCharacter);
+        }
+        buf.push(c);
+        rest = &rest[1..];
+    }
+
+    if buf.len() > 18 {
+        return Err(EddystoneUrlError::TooLong);
+    }
+
+    Ok(buf)
+}
+
+fn decode_url(data: &[u8]) -> Option<String> {
+    let (scheme, rest) = data.split_first()?;
+    let mut url = URL_SCHEMES.get(*scheme as usize)?.to_string();
+
+    for byte in rest {
+        match URL_EXPANSIONS.get(*byte as usize) {
+            Some(expansion) => url.push_str(expansion),
+            None if (0x21..0x7F).contains(byte) => url.push(*byte as char),
+            None => return None,
+        }
+    }
+
+    Some(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ibeacon() {
+        let beacon = IBeacon {
+            proximity_uuid: "e2c56db5-dffb-48d2-b060-d0f5a71096e0".parse().unwrap(),
+            major: 1,
+            minor: 2,
+            measured_power: -59,
+        };
+
+        let data = beacon.to_advertisement_data();
+        assert_eq!(
+            data.encode().unwrap(),
+            vec![
+                0x02, 0x01, 0x06, 0x1A, 0xFF, 0x4C, 0x00, 0x02, 0x15, 0xE2, 0xC5, 0x6D, 0xB5, 0xDF,
+                0xFB, 0x48, 0xD2, 0xB0, 0x60, 0xD0, 0xF5, 0xA7, 0x10, 0x96, 0xE0, 0x00, 0x01, 0x00,
+                0x02, 0xC5
+            ]
+        );
+
+        let parsed = AdvertisementData::parse(&data.encode().unwrap()).unwrap();
+        assert_eq!(IBeacon::from_advertisement_data(&parsed), Some(beacon));
+    }
+
+    #[test]
+    fn test_eddystone() {
+        let url = Eddystone::Url {
+            tx_power: -20,
+            url: "https://www.example.com/".to_string(),
+        };
+        assert_eq!(
+            url.encode().unwrap(),
+            vec![0x10, 0xEC, 0x01, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0x00]
+        );
+
+        let tlm = Eddystone::Tlm {
+            battery_voltage: 3000,
+            temperature: Some(21.5),
+            advertisement_count: 1000,
+            uptime: std::time::Duration::from_secs(60),
+        };
+
+        let uid = Eddystone::Uid {
+            tx_power: -10,
+            namespace: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+            instance: [11, 12, 13, 14, 15, 16],
+        };
+        let encoded = uid.encode().unwrap();
+        assert_eq!(encoded.len(), 20);
+        assert_eq!(Eddystone::decode(&encoded[..18]).as_ref(), Some(&uid));
+        assert_eq!(Eddystone::decode(&encoded[..17]), None);
+        assert_eq!(Eddystone::decode(&[&encoded[..], &[0]].concat()), None);
+
+        for frame in &[url, tlm, uid] {
+            let data = frame.to_advertisement_data().unwrap();
+            let parsed = AdvertisementData::parse(&data.encode().unwrap()).unwrap();
+            assert_eq!(
+                Eddystone::from_advertisement_data(&parsed).as_ref(),
+                Some(frame)
+            );
+        }
+
+        let long = Eddystone::Url {
+            tx_power: 0,
+            url: "https://example.com/a/very/long/path".to_string(),
+        };
+        assert_eq!(long.encode(), Err(EddystoneUrlError::TooLong));
+    }
+}
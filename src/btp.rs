@@ -0,0 +1,841 @@
+//! Matter's Bluetooth Transport Protocol (BTP), which carries commissioning
+//! messages to Matter devices over BLE.
+//!
+//! The client writes packets to the C1 characteristic and the device
+//! indicates packets on C2. After a handshake agreeing on the protocol
+//! version, segment size, and window size, messages are split into segments,
+//! each packet carrying a sequence number and optionally acknowledging the
+//! packets received so far. A [Connection] hides this and sends and receives
+//! whole messages.
+//!
+//! ```no_run
+//! use wible::btp::Btp;
+//! # fn commission(device: &wible::Device) -> Result<(), Box<dyn std::error::Error>> {
+//! let service = device.service(wible::btp::SERVICE)?.expect("not commissionable");
+//!
+//! let btp = Btp::new(&service)?.with_mtu(247);
+//! let mut connection = btp.connect()?;
+//! connection.send(b"PBKDFParamRequest")?;
+//! let response = connection.receive(std::time::Duration::from_secs(10))?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::services::Reader;
+use crate::{CharacteristicIO, Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Matter service.
+pub const SERVICE: Uuid = Uuid::from_u16(0xFFF6);
+/// UUID of the C1 characteristic, which the client writes packets to.
+pub const C1: Uuid = Uuid(0x18EE_2EF5_263D_4559_959F_4F9C_429F_9D11);
+/// UUID of the C2 characteristic, which the device indicates packets on.
+pub const C2: Uuid = Uuid(0x18EE_2EF5_263D_4559_959F_4F9C_429F_9D12);
+
+/// Version of the protocol implemented.
+pub const VERSION: u8 = 4;
+
+/// How long the device has to answer the handshake.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a peer has to acknowledge a packet before the connection is
+/// considered lost.
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long received packets may go unacknowledged before an acknowledgement
+/// is sent on its own.
+pub const SEND_ACK_TIMEOUT: Duration = Duration::from_millis(2500);
+/// How long the client may send nothing before sending a keep-alive.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+const MANAGEMENT_OPCODE: u8 = 0x6C;
+
+bitflags::bitflags! {
+    /// Flags in the first byte of a packet.
+    struct Flags: u8 {
+        const BEGINNING = 1 << 0;
+        const CONTINUING = 1 << 1;
+        const ENDING = 1 << 2;
+        const ACK = 1 << 3;
+        const MANAGEMENT = 1 << 5;
+        const HANDSHAKE = 1 << 6;
+    }
+}
+
+/// Errors from a peer not following the protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BtpError {
+    /// Device chose a version other than the one offered.
+    UnsupportedVersion(u8),
+    /// Packet did not have the next sequence number.
+    UnexpectedSequence { expected: u8, actual: u8 },
+    /// Packet acknowledged a sequence number which was not sent or was
+    /// already acknowledged.
+    InvalidAck(u8),
+    /// Packet was malformed or did not fit the message being received.
+    InvalidPacket,
+}
+
+impl std::fmt::Display for BtpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BtpError::UnsupportedVersion(version) => {
+                write!(f, "device chose unsupported BTP version {}", version)
+            }
+            BtpError::UnexpectedSequence { expected, actual } => write!(
+                f,
+                "received sequence number {} instead of {}",
+                actual, expected
+            ),
+            BtpError::InvalidAck(ack) => write!(f, "received invalid ack of {}", ack),
+            BtpError::InvalidPacket => write!(f, "received invalid BTP packet"),
+        }
+    }
+}
+
+impl std::error::Error for BtpError {}
+
+/// Parameters chosen by the device in the handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Handshake {
+    version: u8,
+    segment_size: u16,
+    window_size: u8,
+}
+
+impl Handshake {
+    fn request(mtu: u16, window_size: u8) -> [u8; 9] {
+        let flags = Flags::HANDSHAKE | Flags::MANAGEMENT | Flags::ENDING | Flags::BEGINNING;
+        let mtu = mtu.to_le_bytes();
+
+        [
+            flags.bits(),
+            MANAGEMENT_OPCODE,
+            VERSION,
+            0,
+            0,
+            0,
+            mtu[0],
+            mtu[1],
+            window_size,
+        ]
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(data);
+        let flags = Flags::from_bits_truncate(reader.u8()?);
+        if !flags.contains(Flags::HANDSHAKE) || reader.u8()? != MANAGEMENT_OPCODE {
+            return None;
+        }
+
+        Some(Self {
+            version: reader.u8()? & 0x0F,
+            segment_size: reader.u16()?,
+            window_size: reader.u8()?,
+        })
+    }
+}
+
+/// State of an established connection, driven by the time given to it so
+/// timers can be tested without waiting.
+struct Session {
+    segment_size: usize,
+    window_size: u8,
+    /// Sequence number of the next packet sent.
+    tx_next: u8,
+    /// Oldest sequence number sent which the peer has not acknowledged.
+    tx_unacked: u8,
+    /// Packets the peer must acknowledge in time, with when they were sent.
+    awaiting_ack: VecDeque<(u8, Instant)>,
+    last_sent: Instant,
+    /// Sequence number expected from the peer next.
+    rx_next: u8,
+    /// Oldest sequence number received which has not been acknowledged.
+    rx_unacked: u8,
+    /// When a standalone acknowledgement must be sent, if one is pending.
+    ack_due: Option<Instant>,
+    outgoing: VecDeque<Vec<u8>>,
+    /// Bytes of the first outgoing message already sent.
+    offset: usize,
+    /// Length and received bytes of the message being reassembled.
+    incoming: Option<(usize, Vec<u8>)>,
+    received: VecDeque<Vec<u8>>,
+}
+
+impl Session {
+    /// Start a client's session, which treats the handshake response as the
+    /// device's packet 0.
+    fn new(segment_size: usize, window_size: u8, now: Instant) -> Self {
+        Self {
+            segment_size,
+            window_size,
+            tx_next: 0,
+            tx_unacked: 0,
+            awaiting_ack: VecDeque::new(),
+            last_sent: now,
+            rx_next: 1,
+            rx_unacked: 0,
+            ack_due: Some(now + SEND_ACK_TIMEOUT),
+            outgoing: VecDeque::new(),
+            offset: 0,
+            incoming: None,
+            received: VecDeque::new(),
+        }
+    }
+
+    fn queue(&mut self, message: &[u8]) -> Result<()> {
+        if message.len() > usize::from(u16::MAX) {
+            return Err(Error::InvalidValue);
+        }
+
+        self.outgoing.push_back(message.to_vec());
+        Ok(())
+    }
+
+    fn take_message(&mut self) -> Option<Vec<u8>> {
+        self.received.pop_front()
+    }
+
+    /// Packets sent which the peer has not acknowledged.
+    fn in_flight(&self) -> u8 {
+        self.tx_next.wrapping_sub(self.tx_unacked)
+    }
+
+    /// Packets received which have not been acknowledged.
+    fn unacked(&self) -> u8 {
+        self.rx_next.wrapping_sub(self.rx_unacked)
+    }
+
+    /// Handle a packet indicated by the device.
+    fn receive(&mut self, packet: &[u8], now: Instant) -> Result<()> {
+        let mut reader = Reader::new(packet);
+        let flags = reader
+            .u8()
+            .map(Flags::from_bits_truncate)
+            .ok_or(BtpError::InvalidPacket)?;
+        if flags.intersects(Flags::HANDSHAKE | Flags::MANAGEMENT) {
+            return Err(BtpError::InvalidPacket.into());
+        }
+
+        if flags.contains(Flags::ACK) {
+            let ack = reader.u8().ok_or(BtpError::InvalidPacket)?;
+            let acked = ack.wrapping_sub(self.tx_unacked).wrapping_add(1);
+            if acked > self.in_flight() {
+                return Err(BtpError::InvalidAck(ack).into());
+            }
+
+            while let Some(&(sequence, _)) = self.awaiting_ack.front() {
+                if sequence.wrapping_sub(self.tx_unacked) >= acked {
+                    break;
+                }
+                self.awaiting_ack.pop_front();
+            }
+            self.tx_unacked = ack.wrapping_add(1);
+        }
+
+        let sequence = reader.u8().ok_or(BtpError::InvalidPacket)?;
+        if sequence != self.rx_next {
+            return Err(BtpError::UnexpectedSequence {
+                expected: self.rx_next,
+                actual: sequence,
+            }
+            .into());
+        }
+        self.rx_next = sequence.wrapping_add(1);
+
+        // Acknowledgements sent on their own only need acknowledging before
+        // the peer's window fills up, or they would be answered forever.
+        let standalone_ack = flags == Flags::ACK && reader.is_empty();
+        if self.unacked().saturating_add(1) >= self.window_size {
+            self.ack_due = Some(now);
+        } else if !standalone_ack && self.ack_due.is_none() {
+            self.ack_due = Some(now + SEND_ACK_TIMEOUT);
+        }
+
+        if flags.contains(Flags::BEGINNING) {
+            let len = reader.u16().ok_or(BtpError::InvalidPacket)?;
+            if self.incoming.is_some() {
+                return Err(BtpError::InvalidPacket.into());
+            }
+            self.incoming = Some((usize::from(len), Vec::new()));
+        }
+
+        let payload = reader.bytes(reader.remaining()).unwrap_or_default();
+        if !flags.intersects(Flags::BEGINNING | Flags::CONTINUING | Flags::ENDING) {
+            return match payload {
+                [] => Ok(()),
+                _ => Err(BtpError::InvalidPacket.into()),
+            };
+        }
+
+        let (len, message) = self.incoming.as_mut().ok_or(BtpError::InvalidPacket)?;
+        message.extend_from_slice(payload);
+        if message.len() > *len || (flags.contains(Flags::ENDING) && message.len() != *len) {
+            return Err(BtpError::InvalidPacket.into());
+        }
+
+        if flags.contains(Flags::ENDING) {
+            let (_, message) = self.incoming.take().unwrap_or_default();
+            self.received.push_back(message);
+        }
+
+        Ok(())
+    }
+
+    /// The next packet to write at the given time, if any.
+    fn next_packet(&mut self, now: Instant) -> Result<Option<Vec<u8>>> {
+        if let Some(&(_, sent)) = self.awaiting_ack.front() {
+            if now >= sent + ACK_TIMEOUT {
+                return Err(Error::Timeout);
+            }
+        }
+
+        // Keep the last slot of the peer's window for a packet which
+        // acknowledges, so neither side can be left unable to send.
+        let ack = self.unacked() > 0;
+        let window = self.window_size.saturating_sub(self.in_flight());
+        let packet = if !self.outgoing.is_empty() && (window > 1 || (window == 1 && ack)) {
+            self.segment(ack)
+        } else if ack && window > 0 && matches!(self.ack_due, Some(due) if now >= due) {
+            self.header(Flags::ACK, true)
+        } else if window > 0
+            && self.awaiting_ack.is_empty()
+            && now >= self.last_sent + KEEP_ALIVE_INTERVAL
+        {
+            // A keep-alive which also acknowledges is a standalone ack.
+            self.header(Flags::empty(), ack)
+        } else {
+            return Ok(None);
+        };
+
+        let sequence = self.tx_next;
+        self.tx_next = sequence.wrapping_add(1);
+        self.last_sent = now;
+        if packet[0] != Flags::ACK.bits() || packet.len() > 3 {
+            self.awaiting_ack.push_back((sequence, now));
+        }
+
+        Ok(Some(packet))
+    }
+
+    /// Start a packet, acknowledging every packet received if `ack` is set.
+    fn header(&mut self, flags: Flags, ack: bool) -> Vec<u8> {
+        let mut packet = vec![flags.bits()];
+        if ack {
+            packet[0] |= Flags::ACK.bits();
+            packet.push(self.rx_next.wrapping_sub(1));
+            self.rx_unacked = self.rx_next;
+            self.ack_due = None;
+        }
+        packet.push(self.tx_next);
+
+        packet
+    }
+
+    /// Build the next segment of the first outgoing message.
+    fn segment(&mut self, ack: bool) -> Vec<u8> {
+        let message = &self.outgoing[0];
+        let mut flags = Flags::empty();
+        let mut header_size = 2 + usize::from(ack);
+        if self.offset == 0 {
+            flags |= Flags::BEGINNING;
+            header_size += 2;
+        } else {
+            flags |= Flags::CONTINUING;
+        }
+
+        let end = message
+            .len()
+            .min(self.offset + self.segment_size.saturating_sub(header_size).max(1));
+        if end == message.len() {
+            flags |= Flags::ENDING;
+        }
+
+        let len = message.len() as u16;
+        let payload = message[self.offset..end].to_vec();
+        let mut packet = self.header(flags, ack);
+        if flags.contains(Flags::BEGINNING) {
+            packet.extend_from_slice(&len.to_le_bytes());
+        }
+        packet.extend_from_slice(&payload);
+
+        if flags.contains(Flags::ENDING) {
+            self.outgoing.pop_front();
+            self.offset = 0;
+        } else {
+            self.offset = end;
+        }
+
+        packet
+    }
+
+    /// When a timer next needs [next_packet](Self::next_packet) to be called.
+    fn next_deadline(&self) -> Instant {
+        let mut deadline = self.last_sent + KEEP_ALIVE_INTERVAL;
+        if let Some(&(_, sent)) = self.awaiting_ack.front() {
+            deadline = sent + ACK_TIMEOUT;
+        }
+        // Acknowledgements wait for the peer to open its window, which it
+        // does by sending a packet.
+        if let Some(due) = self.ack_due.filter(|_| self.in_flight() < self.window_size) {
+            deadline = deadline.min(due);
+        }
+
+        deadline
+    }
+}
+
+/// Client for the Matter service, which connects to the device.
+pub struct Btp<C: RemoteCharacteristic> {
+    c1: C,
+    c2: C,
+    mtu: u16,
+    window_size: u8,
+}
+
+impl<C: RemoteCharacteristic> Btp<C> {
+    /// Default number of packets which may be sent without being
+    /// acknowledged.
+    pub const DEFAULT_WINDOW_SIZE: u8 = 6;
+
+    /// Find the characteristics of the Matter service.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let c1 = service
+            .characteristic(C1)?
+            .ok_or(Error::MissingAttribute(C1))?;
+        let c2 = service
+            .characteristic(C2)?
+            .ok_or(Error::MissingAttribute(C2))?;
+
+        Ok(Self {
+            c1,
+            c2,
+            mtu: 23,
+            window_size: Self::DEFAULT_WINDOW_SIZE,
+        })
+    }
+
+    /// Set the ATT MTU of the connection, which limits the segment size the
+    /// device can choose.
+    ///
+    /// Defaults to 23, the smallest MTU.
+    pub fn with_mtu(mut self, mtu: u16) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Set how many packets the device may send before they are
+    /// acknowledged.
+    pub fn with_window_size(mut self, window_size: u8) -> Self {
+        self.window_size = window_size.max(2);
+        self
+    }
+
+    /// Perform the handshake, which starts a new connection.
+    pub fn connect(&self) -> Result<Connection<'_, C>> {
+        let request = Handshake::request(self.mtu, self.window_size);
+        log::debug!("Sending BTP handshake {:?}", request);
+        self.c1.write_value(&request)?;
+
+        // The device answers once indications are enabled.
+        let mut io = CharacteristicIO::new(&self.c2)?;
+        let response = io
+            .next_notification_timeout(HANDSHAKE_TIMEOUT)
+            .ok_or(Error::Timeout)?;
+        let handshake = Handshake::decode(&response).ok_or(BtpError::InvalidPacket)?;
+
+        if handshake.version != VERSION {
+            return Err(BtpError::UnsupportedVersion(handshake.version).into());
+        }
+        // Segments need room for the largest header and some payload.
+        if handshake.segment_size < 6 || handshake.window_size < 2 {
+            return Err(BtpError::InvalidPacket.into());
+        }
+
+        log::debug!("Established BTP connection {:?}", handshake);
+        Ok(Connection {
+            c1: &self.c1,
+            io,
+            session: Session::new(
+                usize::from(handshake.segment_size),
+                handshake.window_size.min(self.window_size),
+                Instant::now(),
+            ),
+        })
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Btp<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Btp")
+            .field("c1", &self.c1)
+            .field("c2", &self.c2)
+            .field("mtu", &self.mtu)
+            .field("window_size", &self.window_size)
+            .finish()
+    }
+}
+
+/// Reliable pipe for messages to and from a device.
+///
+/// Acknowledgements and keep-alives are only sent while sending or receiving,
+/// so an idle connection should be kept waiting in
+/// [receive](Self::receive).
+pub struct Connection<'a, C: RemoteCharacteristic> {
+    c1: &'a C,
+    io: CharacteristicIO<'a, C>,
+    session: Session,
+}
+
+impl<'a, C: RemoteCharacteristic> Connection<'a, C> {
+    /// Largest packet either side sends.
+    pub fn segment_size(&self) -> usize {
+        self.session.segment_size
+    }
+
+    /// Number of packets either side may send before they are acknowledged.
+    pub fn window_size(&self) -> u8 {
+        self.session.window_size
+    }
+
+    /// Send a message, returning once every segment has been written.
+    pub fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.session.queue(message)?;
+
+        loop {
+            self.flush()?;
+            if self.session.outgoing.is_empty() {
+                return Ok(());
+            }
+
+            // Wait for the device to acknowledge enough to open the window.
+            self.wait(self.session.next_deadline())?;
+        }
+    }
+
+    /// Wait up to the given duration for the next message.
+    pub fn receive(&mut self, timeout: Duration) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.flush()?;
+            if let Some(message) = self.session.take_message() {
+                // Acknowledge promptly if the device is waiting to send more.
+                self.flush()?;
+                return Ok(message);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            self.wait(deadline.min(self.session.next_deadline()))?;
+        }
+    }
+
+    /// Write every packet which is ready to be sent.
+    fn flush(&mut self) -> Result<()> {
+        while let Some(packet) = self.session.next_packet(Instant::now())? {
+            log::trace!("Sending BTP packet {:?}", packet);
+            self.c1.write_value(&packet)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle packets from the device until one arrives or the deadline
+    /// passes.
+    fn wait(&mut self, deadline: Instant) -> Result<()> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if let Some(packet) = self.io.next_notification_timeout(timeout) {
+            log::trace!("Received BTP packet {:?}", packet);
+            self.session.receive(&packet, Instant::now())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Connection<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("c1", &self.c1)
+            .field("segment_size", &self.session.segment_size)
+            .field("window_size", &self.session.window_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use super::{
+        Btp, BtpError, Flags, Handshake, Session, ACK_TIMEOUT, C1, C2, KEEP_ALIVE_INTERVAL,
+        SEND_ACK_TIMEOUT, SERVICE,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error, NotifyMode};
+
+    #[test]
+    fn test_handshake() {
+        assert_eq!(
+            Handshake::request(247, 6),
+            [0x65, 0x6C, 0x04, 0x00, 0x00, 0x00, 0xF7, 0x00, 0x06]
+        );
+        assert_eq!(
+            Handshake::decode(&[0x65, 0x6C, 0x04, 0xF4, 0x00, 0x05]),
+            Some(Handshake {
+                version: 4,
+                segment_size: 244,
+                window_size: 5,
+            })
+        );
+        assert_eq!(Handshake::decode(&[0x05, 0x00, 0x02, 0x41]), None);
+        assert_eq!(Handshake::decode(&[0x65, 0x6C, 0x04, 0xF4]), None);
+    }
+
+    #[test]
+    fn test_segmentation() {
+        let now = Instant::now();
+        let mut session = Session::new(20, 4, now);
+        let message: Vec<u8> = (0..40).collect();
+        session.queue(&message).unwrap();
+
+        // The first packet acknowledges the handshake response.
+        let mut expected = vec![0x09, 0x00, 0x00, 0x28, 0x00];
+        expected.extend(0..15);
+        assert_eq!(session.next_packet(now).unwrap(), Some(expected));
+        let mut expected = vec![0x02, 0x01];
+        expected.extend(15..33);
+        assert_eq!(session.next_packet(now).unwrap(), Some(expected));
+        let mut expected = vec![0x06, 0x02];
+        expected.extend(33..40);
+        assert_eq!(session.next_packet(now).unwrap(), Some(expected));
+
+        // The last slot of the window is kept for acknowledgements.
+        session.queue(b"more").unwrap();
+        assert_eq!(session.next_packet(now).unwrap(), None);
+
+        session.receive(&[0x08, 0x01, 0x01], now).unwrap();
+        assert_eq!(session.in_flight(), 1);
+        assert_eq!(
+            session.next_packet(now).unwrap(),
+            Some(vec![0x0D, 0x01, 0x03, 0x04, 0x00, b'm', b'o', b'r', b'e'])
+        );
+
+        session
+            .receive(&[0x09, 0x03, 0x02, 0x05, 0x00, 1, 2], now)
+            .unwrap();
+        session.receive(&[0x02, 0x03, 3, 4], now).unwrap();
+        assert_eq!(session.take_message(), None);
+        session.receive(&[0x06, 0x04, 5], now).unwrap();
+        assert_eq!(session.take_message(), Some(vec![1, 2, 3, 4, 5]));
+
+        assert!(matches!(
+            session.receive(&[0x00, 0x07], now),
+            Err(Error::Btp(BtpError::UnexpectedSequence {
+                expected: 5,
+                actual: 7
+            }))
+        ));
+        assert!(matches!(
+            session.receive(&[0x08, 0x09, 0x05], now),
+            Err(Error::Btp(BtpError::InvalidAck(9)))
+        ));
+        assert!(matches!(
+            session.receive(&[0x04, 0x05, 1], now),
+            Err(Error::Btp(BtpError::InvalidPacket))
+        ));
+    }
+
+    #[test]
+    fn test_timers() {
+        let start = Instant::now();
+        let mut session = Session::new(20, 4, start);
+
+        // The handshake response is acknowledged once the timer expires.
+        assert_eq!(session.next_packet(start).unwrap(), None);
+        assert_eq!(session.next_deadline(), start + SEND_ACK_TIMEOUT);
+        let now = start + SEND_ACK_TIMEOUT;
+        assert_eq!(
+            session.next_packet(now).unwrap(),
+            Some(vec![0x08, 0x00, 0x00])
+        );
+        assert_eq!(session.next_packet(now).unwrap(), None);
+
+        // Keep-alives are sent when idle, and must be acknowledged in time.
+        assert_eq!(session.next_deadline(), now + KEEP_ALIVE_INTERVAL);
+        let now = now + KEEP_ALIVE_INTERVAL;
+        assert_eq!(session.next_packet(now).unwrap(), Some(vec![0x00, 0x01]));
+        assert_eq!(session.next_deadline(), now + ACK_TIMEOUT);
+
+        // An acknowledgement on its own is not acknowledged back.
+        let now = now + Duration::from_secs(1);
+        session.receive(&[0x08, 0x01, 0x01], now).unwrap();
+        assert_eq!(session.in_flight(), 0);
+        assert_eq!(session.next_packet(now).unwrap(), None);
+
+        let now = now + KEEP_ALIVE_INTERVAL - Duration::from_secs(1);
+        assert_eq!(
+            session.next_packet(now).unwrap(),
+            Some(vec![0x08, 0x01, 0x02])
+        );
+        let now = now + KEEP_ALIVE_INTERVAL;
+        assert_eq!(session.next_packet(now).unwrap(), Some(vec![0x00, 0x03]));
+        assert!(matches!(
+            session.next_packet(now + ACK_TIMEOUT),
+            Err(Error::Timeout)
+        ));
+
+        // Received data is acknowledged after a delay, or at once if the
+        // device's window is nearly full.
+        let mut session = Session::new(20, 3, start);
+        session
+            .receive(&[0x05, 0x01, 0x01, 0x00, 0xAA], start)
+            .unwrap();
+        assert_eq!(
+            session.next_packet(start).unwrap(),
+            Some(vec![0x08, 0x01, 0x00])
+        );
+        session
+            .receive(&[0x05, 0x02, 0x01, 0x00, 0xBB], start)
+            .unwrap();
+        assert_eq!(session.next_packet(start).unwrap(), None);
+        assert_eq!(
+            session.next_packet(start + SEND_ACK_TIMEOUT).unwrap(),
+            Some(vec![0x08, 0x02, 0x01])
+        );
+
+        // A peer which keeps sending while the widest window is closed.
+        let mut session = Session {
+            tx_next: 255,
+            ..Session::new(20, 255, start)
+        };
+        for sequence in 1..=254 {
+            session.receive(&[0x00, sequence], start).unwrap();
+        }
+        assert_eq!(session.unacked(), 255);
+        assert_eq!(session.next_packet(start).unwrap(), None);
+        assert_eq!(session.next_deadline(), start + KEEP_ALIVE_INTERVAL);
+
+        session.tx_unacked = 1;
+        assert_eq!(session.next_deadline(), start);
+    }
+
+    /// Device side of a simulated connection.
+    #[derive(Default)]
+    struct Peripheral {
+        request: Option<Vec<u8>>,
+        session: Option<Session>,
+    }
+
+    impl Peripheral {
+        const MAX_WINDOW_SIZE: u8 = 4;
+
+        /// Start the session with the client, returning the handshake
+        /// response.
+        fn accept(&mut self) -> Vec<u8> {
+            let request = self.request.take().unwrap();
+            let mtu = u16::from_le_bytes([request[6], request[7]]);
+            let segment_size = (mtu - 3).min(64);
+            let window_size = request[8].min(Self::MAX_WINDOW_SIZE);
+
+            // The response is the device's packet 0.
+            let now = Instant::now();
+            let mut awaiting_ack = VecDeque::new();
+            awaiting_ack.push_back((0, now));
+            self.session = Some(Session {
+                tx_next: 1,
+                awaiting_ack,
+                rx_next: 0,
+                ack_due: None,
+                ..Session::new(usize::from(segment_size), window_size, now)
+            });
+
+            let mut response = vec![0x65, 0x6C, 0x04];
+            response.extend_from_slice(&segment_size.to_le_bytes());
+            response.push(window_size);
+            response
+        }
+    }
+
+    fn btp_server(peripheral: Arc<Mutex<Peripheral>>) -> GattServer {
+        let writer = peripheral.clone();
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(C1, CharacteristicProperties::WRITE).on_write(
+                        move |server, packet| {
+                            let mut peripheral = writer.lock().unwrap();
+                            if Flags::from_bits_truncate(packet[0]).contains(Flags::HANDSHAKE) {
+                                peripheral.request = Some(packet.to_vec());
+                                return Ok(());
+                            }
+
+                            // Echo every message back.
+                            let now = Instant::now();
+                            let session = peripheral.session.as_mut().unwrap();
+                            session.receive(packet, now).unwrap();
+                            while let Some(message) = session.take_message() {
+                                session.queue(&message).unwrap();
+                            }
+                            while let Some(packet) = session.next_packet(now).unwrap() {
+                                server.indicate(SERVICE, C2, &packet);
+                            }
+                            Ok(())
+                        },
+                    ),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(C2, CharacteristicProperties::INDICATE).on_subscribe(
+                        move |server, mode| {
+                            if mode == NotifyMode::Indicate {
+                                let response = peripheral.lock().unwrap().accept();
+                                server.indicate(SERVICE, C2, &response);
+                            }
+                        },
+                    ),
+                ),
+        );
+
+        server
+    }
+
+    #[test]
+    fn test_simulated_device() {
+        let peripheral = Arc::new(Mutex::new(Peripheral::default()));
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, btp_server(peripheral.clone()));
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let btp = Btp::new(&service).unwrap();
+        let mut connection = btp.connect().unwrap();
+        assert_eq!(connection.segment_size(), 20);
+        assert_eq!(connection.window_size(), 4);
+
+        // Long enough to need the device to acknowledge several times.
+        let message: Vec<u8> = (0..=255).collect();
+        connection.send(&message).unwrap();
+        assert_eq!(connection.receive(Duration::from_secs(1)).unwrap(), message);
+
+        connection.send(b"hello").unwrap();
+        connection.send(b"world").unwrap();
+        assert_eq!(
+            connection.receive(Duration::from_secs(1)).unwrap(),
+            b"hello"
+        );
+        assert_eq!(
+            connection.receive(Duration::from_secs(1)).unwrap(),
+            b"world"
+        );
+        assert!(matches!(
+            connection.receive(Duration::from_millis(10)),
+            Err(Error::Timeout)
+        ));
+    }
+}
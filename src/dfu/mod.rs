@@ -0,0 +1,699 @@
+//! Firmware updates for Nordic devices using Secure DFU.
+//!
+//! Firmware is sent as objects: first the signed init packet as a command
+//! object, then the firmware itself split into data objects. Each object is
+//! created, written to the data point, checked against its CRC-32, and
+//! executed.
+//!
+//! ```no_run
+//! use wible::dfu::{Dfu, Package};
+//! # fn update(device: &wible::Device) -> Result<(), Box<dyn std::error::Error>> {
+//! let package = Package::open("app_dfu_package.zip")?;
+//! let service = device.service(wible::dfu::SERVICE)?.expect("not in DFU mode");
+//!
+//! let dfu = Dfu::new(&service)?;
+//! dfu.update(&package.images()[0], |progress| {
+//!     println!("{}%", progress.percent());
+//! })?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use crate::services::{ControlPointClient, Reader};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+mod package;
+
+pub use package::{Image, ImageKind, Package};
+
+/// UUID of the Secure DFU service.
+pub const SERVICE: Uuid = Uuid::from_u16(0xFE59);
+/// UUID of the DFU Control Point characteristic.
+pub const CONTROL_POINT: Uuid = Uuid(0x8EC9_0001_F315_4F60_9FB8_8388_30DA_EA50);
+/// UUID of the DFU Packet characteristic, which object data is written to.
+pub const DATA_POINT: Uuid = Uuid(0x8EC9_0002_F315_4F60_9FB8_8388_30DA_EA50);
+
+const CREATE: u8 = 0x01;
+const SET_RECEIPT_NOTIFICATION: u8 = 0x02;
+const CALCULATE_CHECKSUM: u8 = 0x03;
+const EXECUTE: u8 = 0x04;
+const SELECT: u8 = 0x06;
+const RESPONSE_CODE: u8 = 0x60;
+
+/// Errors from reading a package or sending it to a device.
+#[derive(Debug)]
+pub enum DfuError {
+    /// Package was not a valid `.zip` file or could not be read.
+    Zip(zip::result::ZipError),
+    /// Package manifest was not valid JSON.
+    Json(serde_json::Error),
+    /// Package manifest did not describe any images.
+    InvalidManifest,
+    /// Package did not contain a file named by its manifest.
+    MissingFile(String),
+    /// Device reported a different checksum for the data it received.
+    ChecksumMismatch {
+        offset: usize,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl std::fmt::Display for DfuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DfuError::Zip(err) => write!(f, "unable to read package: {}", err),
+            DfuError::Json(err) => write!(f, "invalid package manifest: {}", err),
+            DfuError::InvalidManifest => write!(f, "package manifest contained no images"),
+            DfuError::MissingFile(name) => write!(f, "package is missing {}", name),
+            DfuError::ChecksumMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum at offset {} was {:08X} instead of {:08X}",
+                offset, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DfuError {}
+
+impl From<zip::result::ZipError> for DfuError {
+    fn from(err: zip::result::ZipError) -> Self {
+        DfuError::Zip(err)
+    }
+}
+
+impl From<serde_json::Error> for DfuError {
+    fn from(err: serde_json::Error) -> Self {
+        DfuError::Json(err)
+    }
+}
+
+/// Type of object being transferred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ObjectType {
+    Command = 0x01,
+    Data = 0x02,
+}
+
+/// Response to selecting an object type.
+#[derive(Debug)]
+struct ObjectInfo {
+    max_size: usize,
+    offset: usize,
+    crc: u32,
+}
+
+/// Progress of sending firmware.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// Bytes of firmware the device has received.
+    pub sent: usize,
+    /// Size of the firmware.
+    pub total: usize,
+}
+
+impl Progress {
+    /// Percentage of the firmware the device has received.
+    pub fn percent(&self) -> u8 {
+        if self.total == 0 {
+            return 100;
+        }
+
+        (self.sent * 100 / self.total) as u8
+    }
+}
+
+/// Client for a device in Secure DFU mode.
+pub struct Dfu<C: RemoteCharacteristic> {
+    control_point: C,
+    data_point: C,
+    packet_size: usize,
+    receipt_interval: u16,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> Dfu<C> {
+    /// Default number of packets sent between checksum notifications.
+    pub const DEFAULT_RECEIPT_INTERVAL: u16 = 12;
+
+    /// Find the characteristics of the Secure DFU service.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let control_point = service
+            .characteristic(CONTROL_POINT)?
+            .ok_or(Error::MissingAttribute(CONTROL_POINT))?;
+        let data_point = service
+            .characteristic(DATA_POINT)?
+            .ok_or(Error::MissingAttribute(DATA_POINT))?;
+
+        Ok(Self {
+            control_point,
+            data_point,
+            packet_size: 20,
+            receipt_interval: Self::DEFAULT_RECEIPT_INTERVAL,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    /// Set how many bytes are written to the data point at once.
+    ///
+    /// Defaults to 20, which fits the smallest MTU. Larger packets are much
+    /// faster when the connection's MTU allows them.
+    pub fn with_packet_size(mut self, packet_size: usize) -> Self {
+        self.packet_size = packet_size.max(1);
+        self
+    }
+
+    /// Set how many packets are written before waiting for the device to
+    /// report its checksum, or 0 to never wait.
+    ///
+    /// Waiting keeps devices from being sent data faster than they can
+    /// store it.
+    pub fn with_receipt_interval(mut self, receipt_interval: u16) -> Self {
+        self.receipt_interval = receipt_interval;
+        self
+    }
+
+    /// Set how long to wait for the device to respond to each request.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send an image to the device, resuming a previous attempt where
+    /// possible.
+    ///
+    /// The device restarts into the new firmware once it has been received.
+    pub fn update(&self, image: &Image, mut progress: impl FnMut(Progress)) -> Result<()> {
+        let mut control =
+            ControlPointClient::new(&self.control_point, RESPONSE_CODE, self.timeout)?;
+        control.execute(
+            SET_RECEIPT_NOTIFICATION,
+            &self.receipt_interval.to_le_bytes(),
+        )?;
+
+        self.send_init_packet(&mut control, &image.init_packet)?;
+        self.send_firmware(&mut control, &image.firmware, &mut progress)
+    }
+
+    fn send_init_packet(&self, control: &mut ControlPointClient<C>, data: &[u8]) -> Result<()> {
+        let info = select(control, ObjectType::Command)?;
+        if info.offset == data.len() && info.crc == crc32fast::hash(data) {
+            log::debug!("Device already has init packet");
+            return execute(control);
+        }
+
+        create(control, ObjectType::Command, data.len())?;
+        let mut hasher = crc32fast::Hasher::new();
+        self.write(control, data, 0, &mut hasher, false, &mut |_| ())?;
+        execute(control)
+    }
+
+    fn send_firmware(
+        &self,
+        control: &mut ControlPointClient<C>,
+        data: &[u8],
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<()> {
+        let info = select(control, ObjectType::Data)?;
+        let max_size = info.max_size.max(1);
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut offset = info.offset;
+        if offset <= data.len() && info.crc == crc32fast::hash(&data[..offset]) {
+            log::debug!("Resuming firmware at offset {}", offset);
+            hasher.update(&data[..offset]);
+            progress(Progress {
+                sent: offset,
+                total: data.len(),
+            });
+
+            let partial = offset % max_size;
+            if partial != 0 {
+                // The device's receipt count continues from before the
+                // interruption, so don't wait for receipts until the next
+                // object. Any it sends are skipped before the final checksum.
+                let end = (offset - partial + max_size).min(data.len());
+                self.write(control, &data[..end], offset, &mut hasher, false, progress)?;
+                execute(control)?;
+                offset = end;
+            } else if offset > 0 {
+                execute(control)?;
+            }
+        } else {
+            // New objects are created after the last executed one, so only
+            // the object that wasn't executed is sent again.
+            let start = offset.min(data.len());
+            offset = start - start % max_size;
+            log::debug!(
+                "Ignoring firmware at offset {} on device, restarting at {}",
+                info.offset,
+                offset
+            );
+            hasher.update(&data[..offset]);
+            progress(Progress {
+                sent: offset,
+                total: data.len(),
+            });
+        }
+
+        while offset < data.len() {
+            let end = (offset + max_size).min(data.len());
+            create(control, ObjectType::Data, end - offset)?;
+            self.write(control, &data[..end], offset, &mut hasher, true, progress)?;
+            execute(control)?;
+            offset = end;
+        }
+
+        Ok(())
+    }
+
+    /// Write `data[offset..]` to the data point, then check the device has
+    /// all of `data`.
+    fn write(
+        &self,
+        control: &mut ControlPointClient<C>,
+        data: &[u8],
+        offset: usize,
+        hasher: &mut crc32fast::Hasher,
+        receipts: bool,
+        progress: &mut dyn FnMut(Progress),
+    ) -> Result<()> {
+        let total = data.len();
+        let mut sent = offset;
+
+        for (index, packet) in data[offset..].chunks(self.packet_size).enumerate() {
+            self.data_point.write_value(packet)?;
+            hasher.update(packet);
+            sent += packet.len();
+            progress(Progress { sent, total });
+
+            if receipts
+                && self.receipt_interval > 0
+                && (index + 1) % self.receipt_interval as usize == 0
+            {
+                let response = control.wait(CALCULATE_CHECKSUM)?;
+                verify(&response, sent, hasher)?;
+            }
+        }
+
+        let mut response = control.execute(CALCULATE_CHECKSUM, &[])?;
+        if !receipts {
+            // Receipts that weren't waited for are still queued ahead of the
+            // response, but report less data than has been sent.
+            while checksum_offset(&response)? < sent {
+                log::trace!("Skipping stale checksum receipt");
+                response = control.wait(CALCULATE_CHECKSUM)?;
+            }
+        }
+
+        verify(&response, sent, hasher)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Dfu<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Dfu")
+            .field("control_point", &self.control_point)
+            .field("data_point", &self.data_point)
+            .field("packet_size", &self.packet_size)
+            .field("receipt_interval", &self.receipt_interval)
+            .finish()
+    }
+}
+
+fn select<C: RemoteCharacteristic>(
+    control: &mut ControlPointClient<C>,
+    object_type: ObjectType,
+) -> Result<ObjectInfo> {
+    let response = control.execute(SELECT, &[object_type as u8])?;
+
+    let mut reader = Reader::new(&response);
+    Ok(ObjectInfo {
+        max_size: reader.u32().ok_or(Error::InvalidValue)? as usize,
+        offset: reader.u32().ok_or(Error::InvalidValue)? as usize,
+        crc: reader.u32().ok_or(Error::InvalidValue)?,
+    })
+}
+
+fn create<C: RemoteCharacteristic>(
+    control: &mut ControlPointClient<C>,
+    object_type: ObjectType,
+    size: usize,
+) -> Result<()> {
+    let mut parameter = vec![object_type as u8];
+    parameter.extend_from_slice(&(size as u32).to_le_bytes());
+
+    control.execute(CREATE, &parameter).map(drop)
+}
+
+fn execute<C: RemoteCharacteristic>(control: &mut ControlPointClient<C>) -> Result<()> {
+    control.execute(EXECUTE, &[]).map(drop)
+}
+
+/// Offset of the data a checksum response covers.
+fn checksum_offset(response: &[u8]) -> Result<usize> {
+    Reader::new(response)
+        .u32()
+        .map(|offset| offset as usize)
+        .ok_or(Error::InvalidValue)
+}
+
+/// Check a checksum response against the data sent so far.
+fn verify(response: &[u8], sent: usize, hasher: &crc32fast::Hasher) -> Result<()> {
+    let mut reader = Reader::new(response);
+    let offset = reader.u32().ok_or(Error::InvalidValue)? as usize;
+    let actual = reader.u32().ok_or(Error::InvalidValue)?;
+    let expected = hasher.clone().finalize();
+
+    if offset != sent || actual != expected {
+        return Err(DfuError::ChecksumMismatch {
+            offset,
+            expected,
+            actual,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    use super::package::tests::build_package;
+    use super::{Dfu, DfuError, Package, Progress, CONTROL_POINT, DATA_POINT, SERVICE};
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error};
+
+    const MAX_SIZE: usize = 64;
+
+    /// State of a simulated bootloader.
+    #[derive(Default)]
+    struct Target {
+        command: Vec<u8>,
+        data: Vec<u8>,
+        /// Whether the last object created or selected was a data object.
+        writing_data: bool,
+        /// Length of the data once the current object is written.
+        object_end: usize,
+        executed: usize,
+        receipt_interval: u16,
+        packets: u16,
+        /// Bytes written to the data point.
+        received: usize,
+        /// Corrupt the data written at this offset.
+        corrupt_at: Option<usize>,
+    }
+
+    impl Target {
+        fn buffer(&self) -> &[u8] {
+            if self.writing_data {
+                &self.data
+            } else {
+                &self.command
+            }
+        }
+
+        fn checksum(&self) -> Vec<u8> {
+            let mut response = vec![0x60, 0x03, 0x01];
+            response.extend_from_slice(&(self.buffer().len() as u32).to_le_bytes());
+            response.extend_from_slice(&crc32fast::hash(self.buffer()).to_le_bytes());
+            response
+        }
+
+        fn request(&mut self, request: &[u8]) -> Vec<u8> {
+            let (result, parameters) = match *request {
+                [0x01, object_type, s0, s1, s2, s3] => {
+                    let size = u32::from_le_bytes([s0, s1, s2, s3]) as usize;
+                    self.writing_data = object_type == 0x02;
+                    if self.writing_data {
+                        self.data.truncate(self.executed);
+                        self.object_end = self.data.len() + size;
+                        self.packets = 0;
+                    } else {
+                        self.command.clear();
+                    }
+                    (0x01, Vec::new())
+                }
+                [0x02, p0, p1] => {
+                    self.receipt_interval = u16::from_le_bytes([p0, p1]);
+                    (0x01, Vec::new())
+                }
+                [0x03] => return self.checksum(),
+                [0x04] if self.writing_data => {
+                    if self.data.len() == self.object_end || self.data.len() == self.executed {
+                        self.executed = self.data.len();
+                        (0x01, Vec::new())
+                    } else {
+                        (0x08, Vec::new())
+                    }
+                }
+                [0x04] => (0x01, Vec::new()),
+                [0x06, object_type] => {
+                    self.writing_data = object_type == 0x02;
+                    let mut parameters = Vec::new();
+                    parameters.extend_from_slice(&(MAX_SIZE as u32).to_le_bytes());
+                    parameters.extend_from_slice(&(self.buffer().len() as u32).to_le_bytes());
+                    parameters.extend_from_slice(&crc32fast::hash(self.buffer()).to_le_bytes());
+                    (0x01, parameters)
+                }
+                _ => (0x02, Vec::new()),
+            };
+
+            let mut response = vec![0x60, request[0], result];
+            response.extend(parameters);
+            response
+        }
+
+        /// Receive a packet, returning a checksum notification if one is due.
+        fn packet(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+            self.received += packet.len();
+
+            let offset = self.buffer().len();
+            let mut packet = packet.to_vec();
+            let corrupt = self
+                .corrupt_at
+                .filter(|at| (offset..offset + packet.len()).contains(at));
+            if corrupt.is_some() {
+                packet[0] ^= 0xFF;
+                self.corrupt_at = None;
+            }
+
+            if !self.writing_data {
+                self.command.extend(packet);
+                return None;
+            }
+
+            self.data.extend(packet);
+            self.packets += 1;
+            if self.receipt_interval > 0 && self.packets == self.receipt_interval {
+                self.packets = 0;
+                return Some(self.checksum());
+            }
+
+            None
+        }
+    }
+
+    fn dfu_server(target: Arc<Mutex<Target>>) -> GattServer {
+        let control_target = target.clone();
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY,
+                    )
+                    .on_write(move |server, request| {
+                        let response = control_target.lock().unwrap().request(request);
+                        server.notify(SERVICE, CONTROL_POINT, &response);
+                        Ok(())
+                    }),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        DATA_POINT,
+                        CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+                    )
+                    .on_write(move |server, packet| {
+                        if let Some(receipt) = target.lock().unwrap().packet(packet) {
+                            server.notify(SERVICE, CONTROL_POINT, &receipt);
+                        }
+                        Ok(())
+                    }),
+                ),
+        );
+
+        server
+    }
+
+    fn firmware() -> Vec<u8> {
+        (0..200u32).map(|i| (i * 7) as u8).collect()
+    }
+
+    #[test]
+    fn test_update() {
+        let firmware = firmware();
+        let package = build_package(&[
+            (
+                "manifest.json",
+                br#"{"manifest": {"application": {"bin_file": "a.bin", "dat_file": "a.dat"}}}"#,
+            ),
+            ("a.bin", &firmware),
+            ("a.dat", &[0xAA; 30]),
+        ]);
+        let package = Package::from_reader(Cursor::new(package)).unwrap();
+
+        let target = Arc::new(Mutex::new(Target::default()));
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, dfu_server(target.clone()));
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let dfu = Dfu::new(&service)
+            .unwrap()
+            .with_packet_size(16)
+            .with_receipt_interval(2);
+
+        let mut updates = Vec::new();
+        dfu.update(&package.images()[0], |progress| updates.push(progress))
+            .unwrap();
+
+        let target = target.lock().unwrap();
+        assert_eq!(target.command, vec![0xAA; 30]);
+        assert_eq!(target.data, firmware);
+        assert_eq!(target.executed, firmware.len());
+        assert_eq!(target.received, 230);
+        assert_eq!(
+            updates.last(),
+            Some(&Progress {
+                sent: 200,
+                total: 200
+            })
+        );
+        assert_eq!(updates.last().unwrap().percent(), 100);
+    }
+
+    #[test]
+    fn test_resume_and_corruption() {
+        let firmware = firmware();
+        let image = super::Image {
+            kind: super::ImageKind::Application,
+            init_packet: vec![0xAA; 30],
+            firmware: firmware.clone(),
+        };
+
+        // Interrupted partway through the second object.
+        let target = Arc::new(Mutex::new(Target {
+            command: vec![0xAA; 30],
+            data: firmware[..100].to_vec(),
+            writing_data: true,
+            object_end: 128,
+            executed: 64,
+            ..Target::default()
+        }));
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, dfu_server(target.clone()));
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let dfu = Dfu::new(&service).unwrap();
+
+        let mut first = None;
+        dfu.update(&image, |progress| {
+            first.get_or_insert(progress);
+        })
+        .unwrap();
+        assert_eq!(
+            first,
+            Some(Progress {
+                sent: 100,
+                total: 200
+            })
+        );
+        {
+            let target = target.lock().unwrap();
+            assert_eq!(target.data, firmware);
+            assert_eq!(target.received, 100);
+        }
+
+        // Receipts keep coming while the rest of the object is resumed.
+        let target = Arc::new(Mutex::new(Target {
+            command: vec![0xAA; 30],
+            data: firmware[..100].to_vec(),
+            writing_data: true,
+            object_end: 128,
+            executed: 64,
+            ..Target::default()
+        }));
+        sim.add_peripheral(BluetoothAddress(2), dfu_server(target.clone()));
+
+        let device = sim.connect(BluetoothAddress(2)).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let dfu = Dfu::new(&service).unwrap().with_packet_size(2);
+        dfu.update(&image, |_| ()).unwrap();
+        assert_eq!(target.lock().unwrap().data, firmware);
+
+        // The unexecuted object doesn't match, so it is sent again after the
+        // executed one.
+        let mut data = firmware[..100].to_vec();
+        data[80] ^= 0xFF;
+        let target = Arc::new(Mutex::new(Target {
+            command: vec![0xAA; 30],
+            data,
+            writing_data: true,
+            object_end: 128,
+            executed: 64,
+            ..Target::default()
+        }));
+        sim.add_peripheral(BluetoothAddress(3), dfu_server(target.clone()));
+
+        let device = sim.connect(BluetoothAddress(3)).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let dfu = Dfu::new(&service).unwrap();
+        let mut first = None;
+        dfu.update(&image, |progress| {
+            first.get_or_insert(progress);
+        })
+        .unwrap();
+        assert_eq!(
+            first,
+            Some(Progress {
+                sent: 64,
+                total: 200
+            })
+        );
+        {
+            let target = target.lock().unwrap();
+            assert_eq!(target.data, firmware);
+            assert_eq!(target.received, 136);
+        }
+
+        let target = Arc::new(Mutex::new(Target {
+            corrupt_at: Some(90),
+            ..Target::default()
+        }));
+        sim.add_peripheral(BluetoothAddress(1), dfu_server(target));
+
+        let device = sim.connect(BluetoothAddress(1)).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let dfu = Dfu::new(&service).unwrap().with_receipt_interval(0);
+        assert!(matches!(
+            dfu.update(&image, |_| ()),
+            Err(Error::Dfu(DfuError::ChecksumMismatch { offset: 128, .. }))
+        ));
+    }
+}
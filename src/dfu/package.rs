@@ -0,0 +1,199 @@
+//! Reading the `.zip` packages produced by `nrfutil pkg generate`.
+
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use super::DfuError;
+
+/// Kind of firmware contained in an image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    /// Combined SoftDevice and bootloader.
+    SoftDeviceBootloader,
+    SoftDevice,
+    Bootloader,
+    Application,
+}
+
+impl ImageKind {
+    /// Every kind, in the order images must be sent to a device.
+    pub const ALL: [ImageKind; 4] = [
+        ImageKind::SoftDeviceBootloader,
+        ImageKind::SoftDevice,
+        ImageKind::Bootloader,
+        ImageKind::Application,
+    ];
+
+    /// Key of the image in the package manifest.
+    fn key(self) -> &'static str {
+        match self {
+            ImageKind::SoftDeviceBootloader => "softdevice_bootloader",
+            ImageKind::SoftDevice => "softdevice",
+            ImageKind::Bootloader => "bootloader",
+            ImageKind::Application => "application",
+        }
+    }
+}
+
+/// Firmware and the init packet describing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub kind: ImageKind,
+    /// Signed init packet, sent as the command object.
+    pub init_packet: Vec<u8>,
+    /// Firmware binary, sent as data objects.
+    pub firmware: Vec<u8>,
+}
+
+/// Contents of a DFU package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Package {
+    images: Vec<Image>,
+}
+
+impl Package {
+    /// Open a package file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, DfuError> {
+        let file = std::fs::File::open(path).map_err(zip::result::ZipError::from)?;
+
+        Self::from_reader(file)
+    }
+
+    /// Read a package from its `manifest.json` and the files it names.
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self, DfuError> {
+        let mut archive = zip::ZipArchive::new(reader)?;
+
+        let manifest: serde_json::Value =
+            serde_json::from_reader(open_file(&mut archive, "manifest.json")?)?;
+        let manifest = manifest.get("manifest").ok_or(DfuError::InvalidManifest)?;
+
+        let mut images = Vec::new();
+        for &kind in &ImageKind::ALL {
+            let entry = match manifest.get(kind.key()) {
+                Some(entry) => entry,
+                None => continue,
+            };
+
+            let file_name = |key| {
+                entry
+                    .get(key)
+                    .and_then(serde_json::Value::as_str)
+                    .ok_or(DfuError::InvalidManifest)
+            };
+            let init_packet = read_file(&mut archive, file_name("dat_file")?)?;
+            let firmware = read_file(&mut archive, file_name("bin_file")?)?;
+
+            images.push(Image {
+                kind,
+                init_packet,
+                firmware,
+            });
+        }
+
+        if images.is_empty() {
+            return Err(DfuError::InvalidManifest);
+        }
+
+        Ok(Self { images })
+    }
+
+    /// Images in the package, in the order they must be sent.
+    ///
+    /// The device restarts after receiving each image, so each must be sent
+    /// over a new connection.
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+
+    /// Find the image of a given kind.
+    pub fn image(&self, kind: ImageKind) -> Option<&Image> {
+        self.images.iter().find(|image| image.kind == kind)
+    }
+}
+
+fn open_file<'a, R: Read + Seek>(
+    archive: &'a mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<zip::read::ZipFile<'a>, DfuError> {
+    archive.by_name(name).map_err(|err| match err {
+        zip::result::ZipError::FileNotFound => DfuError::MissingFile(name.to_string()),
+        err => err.into(),
+    })
+}
+
+fn read_file<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Vec<u8>, DfuError> {
+    let mut data = Vec::new();
+    open_file(archive, name)?
+        .read_to_end(&mut data)
+        .map_err(zip::result::ZipError::from)?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::{Cursor, Write};
+
+    use super::{ImageKind, Package};
+    use crate::dfu::DfuError;
+
+    /// Build a package containing the given files.
+    pub(crate) fn build_package(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_read_package() {
+        let manifest = br#"{
+            "manifest": {
+                "application": {"bin_file": "app.bin", "dat_file": "app.dat"},
+                "bootloader": {"bin_file": "bl.bin", "dat_file": "bl.dat"}
+            }
+        }"#;
+        let package = build_package(&[
+            ("manifest.json", manifest),
+            ("app.bin", &[1, 2, 3]),
+            ("app.dat", &[4]),
+            ("bl.bin", &[5, 6]),
+            ("bl.dat", &[7]),
+        ]);
+
+        let package = Package::from_reader(Cursor::new(package)).unwrap();
+        let kinds: Vec<_> = package.images().iter().map(|image| image.kind).collect();
+        assert_eq!(kinds, vec![ImageKind::Bootloader, ImageKind::Application]);
+
+        let application = package.image(ImageKind::Application).unwrap();
+        assert_eq!(application.firmware, vec![1, 2, 3]);
+        assert_eq!(application.init_packet, vec![4]);
+        assert!(package.image(ImageKind::SoftDevice).is_none());
+
+        let package = build_package(&[("manifest.json", manifest), ("app.bin", &[1])]);
+        assert!(matches!(
+            Package::from_reader(Cursor::new(package)),
+            Err(DfuError::MissingFile(name)) if name == "bl.dat"
+        ));
+
+        let package = build_package(&[("manifest.json", br#"{"manifest": {}}"#)]);
+        assert!(matches!(
+            Package::from_reader(Cursor::new(package)),
+            Err(DfuError::InvalidManifest)
+        ));
+
+        let package = build_package(&[("manifest.json", b"{")]);
+        assert!(matches!(
+            Package::from_reader(Cursor::new(package)),
+            Err(DfuError::Json(_))
+        ));
+    }
+}
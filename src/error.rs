@@ -0,0 +1,130 @@
+use crate::gatt::{AttError, ControlPointError};
+use crate::pairing::PairingError;
+use crate::Uuid;
+
+/// Errors that can occur when talking to a Bluetooth backend.
+///
+/// Some variants only exist with their crate feature enabled, so matches
+/// need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The Windows Runtime returned an error.
+    WinRt(winrt::Error),
+    /// The device responded to a request with an ATT error.
+    Att(AttError),
+    /// No device could be reached at the requested address.
+    Unreachable,
+    /// Advertising payload was longer than the 31 bytes available.
+    PayloadTooLarge(usize),
+    /// Pairing with the device did not succeed.
+    Pairing(PairingError),
+    /// Characteristic does not have a Presentation Format descriptor.
+    NoPresentationFormat,
+    /// Value could not be decoded or encoded in the expected format.
+    InvalidValue,
+    /// Device does not have a required service or characteristic.
+    MissingAttribute(Uuid),
+    /// Device did not respond in time.
+    Timeout,
+    /// Device rejected a control point request.
+    ControlPoint(ControlPointError),
+    /// Device did not follow the Bluetooth Transport Protocol.
+    Btp(crate::btp::BtpError),
+    /// Device reported an error while being provisioned with Improv.
+    Improv(crate::improv::ImprovError),
+    /// Firmware update package was invalid or not received intact.
+    #[cfg(feature = "dfu")]
+    Dfu(crate::dfu::DfuError),
+    /// Device could not be provisioned.
+    #[cfg(feature = "provisioning")]
+    Provisioning(crate::provisioning::ProvisioningError),
+    /// Device returned an error for an SMP request.
+    #[cfg(feature = "smp")]
+    Smp(crate::smp::SmpError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::WinRt(err) => write!(f, "Windows Runtime error: {:?}", err),
+            Error::Att(err) => write!(f, "device returned {}", err),
+            Error::Unreachable => write!(f, "device was unreachable"),
+            Error::PayloadTooLarge(len) => {
+                write!(f, "advertising payload of {} bytes exceeds 31 bytes", len)
+            }
+            Error::Pairing(err) => write!(f, "{}", err),
+            Error::NoPresentationFormat => {
+                write!(f, "characteristic has no presentation format")
+            }
+            Error::InvalidValue => write!(f, "value did not match its format"),
+            Error::MissingAttribute(uuid) => write!(f, "device has no attribute {}", uuid),
+            Error::Timeout => write!(f, "device did not respond in time"),
+            Error::ControlPoint(err) => write!(f, "{}", err),
+            Error::Btp(err) => write!(f, "{}", err),
+            Error::Improv(err) => write!(f, "{}", err),
+            #[cfg(feature = "dfu")]
+            Error::Dfu(err) => write!(f, "{}", err),
+            #[cfg(feature = "provisioning")]
+            Error::Provisioning(err) => write!(f, "{}", err),
+            #[cfg(feature = "smp")]
+            Error::Smp(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<winrt::Error> for Error {
+    fn from(err: winrt::Error) -> Self {
+        Error::WinRt(err)
+    }
+}
+
+impl From<PairingError> for Error {
+    fn from(err: PairingError) -> Self {
+        Error::Pairing(err)
+    }
+}
+
+impl From<ControlPointError> for Error {
+    fn from(err: ControlPointError) -> Self {
+        Error::ControlPoint(err)
+    }
+}
+
+impl From<crate::btp::BtpError> for Error {
+    fn from(err: crate::btp::BtpError) -> Self {
+        Error::Btp(err)
+    }
+}
+
+impl From<crate::improv::ImprovError> for Error {
+    fn from(err: crate::improv::ImprovError) -> Self {
+        Error::Improv(err)
+    }
+}
+
+#[cfg(feature = "dfu")]
+impl From<crate::dfu::DfuError> for Error {
+    fn from(err: crate::dfu::DfuError) -> Self {
+        Error::Dfu(err)
+    }
+}
+
+#[cfg(feature = "provisioning")]
+impl From<crate::provisioning::ProvisioningError> for Error {
+    fn from(err: crate::provisioning::ProvisioningError) -> Self {
+        Error::Provisioning(err)
+    }
+}
+
+#[cfg(feature = "smp")]
+impl From<crate::smp::SmpError> for Error {
+    fn from(err: crate::smp::SmpError) -> Self {
+        Error::Smp(err)
+    }
+}
+
+/// Result type for operations that may produce an [Error].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,345 @@
+//! Decoding the standard descriptors defined by the GATT specification.
+
+use super::{
+    AGGREGATE_FORMAT, CLIENT_CHARACTERISTIC_CONFIGURATION, EXTENDED_PROPERTIES,
+    PRESENTATION_FORMAT, SERVER_CHARACTERISTIC_CONFIGURATION, USER_DESCRIPTION, VALID_RANGE,
+};
+use crate::{CharacteristicProperties, Uuid};
+
+bitflags::bitflags! {
+    /// Contents of the Characteristic Extended Properties descriptor.
+    pub struct ExtendedProperties: u16 {
+        const RELIABLE_WRITE = 0x0001;
+        const WRITABLE_AUXILIARIES = 0x0002;
+    }
+}
+
+impl From<ExtendedProperties> for CharacteristicProperties {
+    fn from(extended: ExtendedProperties) -> Self {
+        let mut properties = CharacteristicProperties::empty();
+        properties.set(
+            CharacteristicProperties::RELIABLE_WRITES,
+            extended.contains(ExtendedProperties::RELIABLE_WRITE),
+        );
+        properties.set(
+            CharacteristicProperties::WRITABLE_AUXILIARIES,
+            extended.contains(ExtendedProperties::WRITABLE_AUXILIARIES),
+        );
+
+        properties
+    }
+}
+
+bitflags::bitflags! {
+    /// Contents of the Client Characteristic Configuration descriptor.
+    pub struct ClientConfiguration: u16 {
+        const NOTIFY = 0x0001;
+        const INDICATE = 0x0002;
+    }
+}
+
+bitflags::bitflags! {
+    /// Contents of the Server Characteristic Configuration descriptor.
+    pub struct ServerConfiguration: u16 {
+        const BROADCAST = 0x0001;
+    }
+}
+
+/// Format of a characteristic value, from the Characteristic Presentation
+/// Format descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    Boolean,
+    UInt2,
+    UInt4,
+    UInt8,
+    UInt12,
+    UInt16,
+    UInt24,
+    UInt32,
+    UInt48,
+    UInt64,
+    UInt128,
+    SInt8,
+    SInt12,
+    SInt16,
+    SInt24,
+    SInt32,
+    SInt48,
+    SInt64,
+    SInt128,
+    /// IEEE-754 32-bit floating point.
+    Float32,
+    /// IEEE-754 64-bit floating point.
+    Float64,
+    /// IEEE-11073 16-bit SFLOAT.
+    SFloat,
+    /// IEEE-11073 32-bit FLOAT.
+    Float,
+    /// IEEE-20601 format, two 16-bit unsigned integers.
+    DUInt16,
+    Utf8,
+    Utf16,
+    /// Opaque structure.
+    Struct,
+    /// Format not known by this library.
+    Other(u8),
+}
+
+impl Format {
+    /// Get the format from its assigned number.
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0x01 => Format::Boolean,
+            0x02 => Format::UInt2,
+            0x03 => Format::UInt4,
+            0x04 => Format::UInt8,
+            0x05 => Format::UInt12,
+            0x06 => Format::UInt16,
+            0x07 => Format::UInt24,
+            0x08 => Format::UInt32,
+            0x09 => Format::UInt48,
+            0x0A => Format::UInt64,
+            0x0B => Format::UInt128,
+            0x0C => Format::SInt8,
+            0x0D => Format::SInt12,
+            0x0E => Format::SInt16,
+            0x0F => Format::SInt24,
+            0x10 => Format::SInt32,
+            0x11 => Format::SInt48,
+            0x12 => Format::SInt64,
+            0x13 => Format::SInt128,
+            0x14 => Format::Float32,
+            0x15 => Format::Float64,
+            0x16 => Format::SFloat,
+            0x17 => Format::Float,
+            0x18 => Format::DUInt16,
+            0x19 => Format::Utf8,
+            0x1A => Format::Utf16,
+            0x1B => Format::Struct,
+            other => Format::Other(other),
+        }
+    }
+
+    /// Get the assigned number of this format.
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Format::Boolean => 0x01,
+            Format::UInt2 => 0x02,
+            Format::UInt4 => 0x03,
+            Format::UInt8 => 0x04,
+            Format::UInt12 => 0x05,
+            Format::UInt16 => 0x06,
+            Format::UInt24 => 0x07,
+            Format::UInt32 => 0x08,
+            Format::UInt48 => 0x09,
+            Format::UInt64 => 0x0A,
+            Format::UInt128 => 0x0B,
+            Format::SInt8 => 0x0C,
+            Format::SInt12 => 0x0D,
+            Format::SInt16 => 0x0E,
+            Format::SInt24 => 0x0F,
+            Format::SInt32 => 0x10,
+            Format::SInt48 => 0x11,
+            Format::SInt64 => 0x12,
+            Format::SInt128 => 0x13,
+            Format::Float32 => 0x14,
+            Format::Float64 => 0x15,
+            Format::SFloat => 0x16,
+            Format::Float => 0x17,
+            Format::DUInt16 => 0x18,
+            Format::Utf8 => 0x19,
+            Format::Utf16 => 0x1A,
+            Format::Struct => 0x1B,
+            Format::Other(other) => other,
+        }
+    }
+}
+
+/// Contents of the Characteristic Presentation Format descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PresentationFormat {
+    /// Format of the value.
+    pub format: Format,
+    /// Base 10 exponent applied to integer values.
+    pub exponent: i8,
+    /// 16-bit UUID of the unit of the value.
+    pub unit: u16,
+    /// Organization which defines `description`, 0x01 for the Bluetooth SIG.
+    pub namespace: u8,
+    /// Description of the value, defined by `namespace`.
+    pub description: u16,
+}
+
+impl PresentationFormat {
+    /// Decode the descriptor value.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [format, exponent, u0, u1, namespace, d0, d1] => Some(Self {
+                format: Format::from_u8(format),
+                exponent: exponent as i8,
+                unit: u16::from_le_bytes([u0, u1]),
+                namespace,
+                description: u16::from_le_bytes([d0, d1]),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Encode the descriptor value.
+    pub fn encode(&self) -> [u8; 7] {
+        let unit = self.unit.to_le_bytes();
+        let description = self.description.to_le_bytes();
+
+        [
+            self.format.to_u8(),
+            self.exponent as u8,
+            unit[0],
+            unit[1],
+            self.namespace,
+            description[0],
+            description[1],
+        ]
+    }
+}
+
+/// Decoded value of a descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DescriptorValue {
+    /// Characteristic Extended Properties.
+    ExtendedProperties(ExtendedProperties),
+    /// Characteristic User Description.
+    UserDescription(String),
+    /// Client Characteristic Configuration.
+    ClientConfiguration(ClientConfiguration),
+    /// Server Characteristic Configuration.
+    ServerConfiguration(ServerConfiguration),
+    /// Characteristic Presentation Format.
+    PresentationFormat(PresentationFormat),
+    /// Characteristic Aggregate Format, the attribute handles of the
+    /// Presentation Format descriptors which make up the value.
+    AggregateFormat(Vec<u16>),
+    /// Valid Range, with both bounds encoded in the characteristic's format.
+    ValidRange { lower: Vec<u8>, upper: Vec<u8> },
+    /// Descriptor which is not known, or could not be decoded.
+    Unknown(Vec<u8>),
+}
+
+impl DescriptorValue {
+    /// Decode the value of the descriptor with the given UUID.
+    ///
+    /// Malformed values for known descriptors are returned as
+    /// [Unknown](DescriptorValue::Unknown).
+    pub fn decode(uuid: Uuid, data: &[u8]) -> Self {
+        let flags = match *data {
+            [b0, b1] => Some(u16::from_le_bytes([b0, b1])),
+            _ => None,
+        };
+
+        let value = match (uuid, flags) {
+            (EXTENDED_PROPERTIES, Some(flags)) => Some(DescriptorValue::ExtendedProperties(
+                ExtendedProperties::from_bits_truncate(flags),
+            )),
+            (USER_DESCRIPTION, _) => std::str::from_utf8(data)
+                .ok()
+                .map(|s| DescriptorValue::UserDescription(s.to_string())),
+            (CLIENT_CHARACTERISTIC_CONFIGURATION, Some(flags)) => {
+                Some(DescriptorValue::ClientConfiguration(
+                    ClientConfiguration::from_bits_truncate(flags),
+                ))
+            }
+            (SERVER_CHARACTERISTIC_CONFIGURATION, Some(flags)) => {
+                Some(DescriptorValue::ServerConfiguration(
+                    ServerConfiguration::from_bits_truncate(flags),
+                ))
+            }
+            (PRESENTATION_FORMAT, _) => {
+                PresentationFormat::decode(data).map(DescriptorValue::PresentationFormat)
+            }
+            (AGGREGATE_FORMAT, _) if data.len() % 2 == 0 => Some(DescriptorValue::AggregateFormat(
+                data.chunks(2)
+                    .map(|handle| u16::from_le_bytes([handle[0], handle[1]]))
+                    .collect(),
+            )),
+            (VALID_RANGE, _) if !data.is_empty() && data.len() % 2 == 0 => {
+                let (lower, upper) = data.split_at(data.len() / 2);
+
+                Some(DescriptorValue::ValidRange {
+                    lower: lower.to_vec(),
+                    upper: upper.to_vec(),
+                })
+            }
+            _ => None,
+        };
+
+        value.unwrap_or_else(|| DescriptorValue::Unknown(data.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ClientConfiguration, DescriptorValue, ExtendedProperties, Format, PresentationFormat,
+    };
+    use crate::gatt::{
+        AGGREGATE_FORMAT, CLIENT_CHARACTERISTIC_CONFIGURATION, EXTENDED_PROPERTIES,
+        PRESENTATION_FORMAT, USER_DESCRIPTION, VALID_RANGE,
+    };
+    use crate::CharacteristicProperties;
+
+    #[test]
+    fn test_decode_descriptors() {
+        let extended = DescriptorValue::decode(EXTENDED_PROPERTIES, &[0x03, 0x00]);
+        assert_eq!(
+            extended,
+            DescriptorValue::ExtendedProperties(
+                ExtendedProperties::RELIABLE_WRITE | ExtendedProperties::WRITABLE_AUXILIARIES
+            )
+        );
+        assert_eq!(
+            CharacteristicProperties::from(ExtendedProperties::RELIABLE_WRITE),
+            CharacteristicProperties::RELIABLE_WRITES
+        );
+
+        assert_eq!(
+            DescriptorValue::decode(USER_DESCRIPTION, b"Temperature"),
+            DescriptorValue::UserDescription("Temperature".to_string())
+        );
+        assert_eq!(
+            DescriptorValue::decode(CLIENT_CHARACTERISTIC_CONFIGURATION, &[0x02, 0x00]),
+            DescriptorValue::ClientConfiguration(ClientConfiguration::INDICATE)
+        );
+        assert_eq!(
+            DescriptorValue::decode(CLIENT_CHARACTERISTIC_CONFIGURATION, &[0x02]),
+            DescriptorValue::Unknown(vec![0x02])
+        );
+
+        // Temperature in Celsius with two decimal places, from the Bluetooth
+        // SIG namespace.
+        let data = [0x0E, 0xFE, 0x2F, 0x27, 0x01, 0x00, 0x00];
+        let format = PresentationFormat {
+            format: Format::SInt16,
+            exponent: -2,
+            unit: 0x272F,
+            namespace: 0x01,
+            description: 0x0000,
+        };
+        assert_eq!(
+            DescriptorValue::decode(PRESENTATION_FORMAT, &data),
+            DescriptorValue::PresentationFormat(format)
+        );
+        assert_eq!(format.encode(), data);
+
+        assert_eq!(
+            DescriptorValue::decode(AGGREGATE_FORMAT, &[0x10, 0x00, 0x14, 0x00]),
+            DescriptorValue::AggregateFormat(vec![0x0010, 0x0014])
+        );
+        assert_eq!(
+            DescriptorValue::decode(VALID_RANGE, &[0x00, 0x00, 0xE8, 0x03]),
+            DescriptorValue::ValidRange {
+                lower: vec![0x00, 0x00],
+                upper: vec![0xE8, 0x03],
+            }
+        );
+    }
+}
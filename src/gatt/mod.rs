@@ -0,0 +1,366 @@
+//! GATT abstractions shared by every backend.
+//!
+//! Code written against [RemoteService], [RemoteCharacteristic], and
+//! [RemoteDescriptor] works with both real devices and the devices provided
+//! by the [sim](crate::sim) module.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::{CharacteristicProperties, Error, Result, Uuid};
+
+pub mod descriptor;
+pub mod server;
+pub mod types;
+pub mod value;
+
+pub use descriptor::{
+    ClientConfiguration, DescriptorValue, ExtendedProperties, Format, PresentationFormat,
+    ServerConfiguration,
+};
+pub use server::{GattServer, LocalCharacteristic, LocalDescriptor, LocalService};
+pub use value::{TypedValue, Value};
+
+/// UUID of the Characteristic Extended Properties descriptor.
+pub const EXTENDED_PROPERTIES: Uuid = Uuid::from_u16(0x2900);
+/// UUID of the Characteristic User Description descriptor.
+pub const USER_DESCRIPTION: Uuid = Uuid::from_u16(0x2901);
+/// UUID of the Client Characteristic Configuration descriptor.
+pub const CLIENT_CHARACTERISTIC_CONFIGURATION: Uuid = Uuid::from_u16(0x2902);
+/// UUID of the Server Characteristic Configuration descriptor.
+pub const SERVER_CHARACTERISTIC_CONFIGURATION: Uuid = Uuid::from_u16(0x2903);
+/// UUID of the Characteristic Presentation Format descriptor.
+pub const PRESENTATION_FORMAT: Uuid = Uuid::from_u16(0x2904);
+/// UUID of the Characteristic Aggregate Format descriptor.
+pub const AGGREGATE_FORMAT: Uuid = Uuid::from_u16(0x2905);
+/// UUID of the Valid Range descriptor.
+pub const VALID_RANGE: Uuid = Uuid::from_u16(0x2906);
+
+/// How a client wants to be told about changes to a characteristic value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NotifyMode {
+    /// No updates are sent.
+    None,
+    /// Updates are sent without confirmation.
+    Notify,
+    /// Updates are sent and confirmed by the client.
+    Indicate,
+}
+
+impl NotifyMode {
+    /// Value of the Client Characteristic Configuration descriptor for this
+    /// mode.
+    pub fn cccd_value(self) -> [u8; 2] {
+        match self {
+            NotifyMode::None => [0x00, 0x00],
+            NotifyMode::Notify => [0x01, 0x00],
+            NotifyMode::Indicate => [0x02, 0x00],
+        }
+    }
+}
+
+impl From<ClientConfiguration> for NotifyMode {
+    fn from(configuration: ClientConfiguration) -> Self {
+        if configuration.contains(ClientConfiguration::INDICATE) {
+            NotifyMode::Indicate
+        } else if configuration.contains(ClientConfiguration::NOTIFY) {
+            NotifyMode::Notify
+        } else {
+            NotifyMode::None
+        }
+    }
+}
+
+/// Users of a characteristic's value changes on one connection.
+///
+/// Every [CharacteristicIO](crate::CharacteristicIO) on the same
+/// characteristic shares one of these, so value changes are only turned off
+/// once the last of them is dropped, and not at all if they were turned on
+/// with [set_notify](RemoteCharacteristic::set_notify).
+#[derive(Default)]
+pub struct Subscription {
+    /// Number of [CharacteristicIO](crate::CharacteristicIO)s listening.
+    pub(crate) users: usize,
+    /// If value changes were turned on with
+    /// [set_notify](RemoteCharacteristic::set_notify).
+    pub(crate) explicit: bool,
+    /// Channel of each user, for backends which deliver value changes to a
+    /// single listener.
+    pub(crate) senders: Arc<Mutex<Vec<mpsc::Sender<Vec<u8>>>>>,
+    /// Removes that listener once the last user is dropped.
+    pub(crate) unlisten: Option<Box<dyn FnOnce() -> Result<()> + Send>>,
+}
+
+impl std::fmt::Debug for Subscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscription")
+            .field("users", &self.users)
+            .field("explicit", &self.explicit)
+            .field("listening", &self.unlisten.is_some())
+            .finish()
+    }
+}
+
+/// Subscriptions of every characteristic on a connection.
+#[derive(Debug)]
+pub(crate) struct Subscriptions<K> {
+    subscriptions: Mutex<HashMap<K, Arc<Mutex<Subscription>>>>,
+}
+
+impl<K: Eq + Hash> Subscriptions<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the subscription for a characteristic.
+    ///
+    /// Subscriptions without users or an explicit setting are forgotten, so
+    /// the map only grows with the characteristics in use.
+    pub(crate) fn get(&self, key: K) -> Arc<Mutex<Subscription>> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        // Only held here, so nobody else can have it locked.
+        subscriptions.retain(|_key, subscription| {
+            Arc::strong_count(subscription) > 1 || subscription.lock().unwrap().explicit
+        });
+
+        subscriptions.entry(key).or_default().clone()
+    }
+}
+
+/// Error code returned by an ATT server in response to a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AttError(pub u8);
+
+impl AttError {
+    pub const INVALID_HANDLE: AttError = AttError(0x01);
+    pub const READ_NOT_PERMITTED: AttError = AttError(0x02);
+    pub const WRITE_NOT_PERMITTED: AttError = AttError(0x03);
+    pub const INSUFFICIENT_AUTHENTICATION: AttError = AttError(0x05);
+    pub const REQUEST_NOT_SUPPORTED: AttError = AttError(0x06);
+    pub const INVALID_OFFSET: AttError = AttError(0x07);
+    pub const INSUFFICIENT_AUTHORIZATION: AttError = AttError(0x08);
+    pub const INVALID_ATTRIBUTE_VALUE_LENGTH: AttError = AttError(0x0D);
+    pub const UNLIKELY_ERROR: AttError = AttError(0x0E);
+    pub const INSUFFICIENT_ENCRYPTION: AttError = AttError(0x0F);
+    pub const VALUE_NOT_ALLOWED: AttError = AttError(0x13);
+    pub const CCCD_IMPROPERLY_CONFIGURED: AttError = AttError(0xFD);
+    pub const PROCEDURE_ALREADY_IN_PROGRESS: AttError = AttError(0xFE);
+    pub const OUT_OF_RANGE: AttError = AttError(0xFF);
+}
+
+impl std::fmt::Display for AttError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ATT error 0x{:02X}", self.0)
+    }
+}
+
+/// Failed response from a service's control point.
+///
+/// Most services share the meaning of the result codes provided as constants,
+/// others are defined by each service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ControlPointError {
+    /// Op code of the request which failed.
+    pub op_code: u8,
+    /// Result code sent by the device.
+    pub result: u8,
+}
+
+impl ControlPointError {
+    pub const OP_CODE_NOT_SUPPORTED: u8 = 0x02;
+    pub const INVALID_PARAMETER: u8 = 0x03;
+    pub const OPERATION_FAILED: u8 = 0x04;
+}
+
+impl std::fmt::Display for ControlPointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "control point request 0x{:02X} failed with result 0x{:02X}",
+            self.op_code, self.result
+        )
+    }
+}
+
+/// A service provided by a connected device.
+pub trait RemoteService {
+    /// Characteristic type provided by this service.
+    type Characteristic: RemoteCharacteristic;
+
+    /// UUID identifying this service.
+    fn uuid(&self) -> Result<Uuid>;
+
+    /// Get the list of available characteristics on this service.
+    fn characteristics(&self) -> Result<Vec<Self::Characteristic>>;
+
+    /// Find the first characteristic with the given UUID.
+    fn characteristic(&self, uuid: Uuid) -> Result<Option<Self::Characteristic>> {
+        for characteristic in self.characteristics()? {
+            if characteristic.uuid()? == uuid {
+                return Ok(Some(characteristic));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// A characteristic provided by a connected device.
+pub trait RemoteCharacteristic {
+    /// Descriptor type provided by this characteristic.
+    type Descriptor: RemoteDescriptor;
+
+    /// UUID identifying this characteristic.
+    fn uuid(&self) -> Result<Uuid>;
+
+    /// Get the properties of this characteristic.
+    fn properties(&self) -> Option<CharacteristicProperties>;
+
+    /// Read the Characteristic Extended Properties descriptor, if the
+    /// characteristic has the `EXTENDED_PROPERTIES` property.
+    fn extended_properties(&self) -> Result<ExtendedProperties> {
+        let has_extended = self
+            .properties()
+            .map(|props| props.contains(CharacteristicProperties::EXTENDED_PROPERTIES))
+            .unwrap_or(false);
+        if !has_extended {
+            return Ok(ExtendedProperties::empty());
+        }
+
+        let descriptor = match self.descriptor(EXTENDED_PROPERTIES)? {
+            Some(descriptor) => descriptor,
+            None => return Ok(ExtendedProperties::empty()),
+        };
+
+        match descriptor.read_decoded()? {
+            DescriptorValue::ExtendedProperties(extended) => Ok(extended),
+            _ => Ok(ExtendedProperties::empty()),
+        }
+    }
+
+    /// Read the current value from the device.
+    fn read_value(&self) -> Result<Vec<u8>>;
+
+    /// Write a value to the device.
+    fn write_value(&self, data: &[u8]) -> Result<()>;
+
+    /// Read the Characteristic Presentation Format descriptor.
+    fn presentation_format(&self) -> Result<PresentationFormat> {
+        let descriptor = self
+            .descriptor(PRESENTATION_FORMAT)?
+            .ok_or(Error::NoPresentationFormat)?;
+
+        match descriptor.read_decoded()? {
+            DescriptorValue::PresentationFormat(format) => Ok(format),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read the current value from the device and decode it using the
+    /// characteristic's Presentation Format descriptor.
+    fn read_typed(&self) -> Result<TypedValue> {
+        let format = self.presentation_format()?;
+        let value = Value::decode(format.format, &self.read_value()?).ok_or(Error::InvalidValue)?;
+
+        Ok(TypedValue { value, format })
+    }
+
+    /// Encode a value using the characteristic's Presentation Format
+    /// descriptor and write it to the device.
+    ///
+    /// The value is written as given, without applying the exponent.
+    fn write_typed(&self, value: &Value) -> Result<()> {
+        let format = self.presentation_format()?;
+        let data = value.encode(format.format).ok_or(Error::InvalidValue)?;
+
+        self.write_value(&data)
+    }
+
+    /// Ask the device to send value changes using the given mode, returning a
+    /// channel which receives each new value.
+    ///
+    /// `subscription` is this characteristic's
+    /// [subscription](RemoteCharacteristic::subscription), locked by the
+    /// caller.
+    fn subscribe(
+        &self,
+        subscription: &mut Subscription,
+        mode: NotifyMode,
+    ) -> Result<mpsc::Receiver<Vec<u8>>>;
+
+    /// Stop listening for value changes after the last user is gone, and ask
+    /// the device to stop sending them unless they were turned on with
+    /// [set_notify](RemoteCharacteristic::set_notify).
+    fn unsubscribe(&self, subscription: &mut Subscription) -> Result<()>;
+
+    /// Get the users of this characteristic's value changes, shared with
+    /// every other handle to the same characteristic on this connection.
+    fn subscription(&self) -> Result<Arc<Mutex<Subscription>>>;
+
+    /// Write the Client Characteristic Configuration descriptor without
+    /// listening for values.
+    ///
+    /// The setting stays until changed, so a [CharacteristicIO](crate::CharacteristicIO)
+    /// created afterwards leaves it in place when dropped. Settings made any
+    /// other way, such as by another process, are turned off once the last
+    /// [CharacteristicIO](crate::CharacteristicIO) using them is dropped.
+    fn set_notify(&self, mode: NotifyMode) -> Result<()> {
+        let descriptor = self
+            .descriptor(CLIENT_CHARACTERISTIC_CONFIGURATION)?
+            .ok_or(Error::MissingAttribute(CLIENT_CHARACTERISTIC_CONFIGURATION))?;
+
+        descriptor.write_value(&mode.cccd_value())?;
+        self.subscription()?.lock().unwrap().explicit = mode != NotifyMode::None;
+
+        Ok(())
+    }
+
+    /// Read the Client Characteristic Configuration descriptor.
+    fn get_notify(&self) -> Result<NotifyMode> {
+        let descriptor = match self.descriptor(CLIENT_CHARACTERISTIC_CONFIGURATION)? {
+            Some(descriptor) => descriptor,
+            None => return Ok(NotifyMode::None),
+        };
+
+        match descriptor.read_decoded()? {
+            DescriptorValue::ClientConfiguration(configuration) => Ok(configuration.into()),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Get the list of descriptors on this characteristic.
+    fn descriptors(&self) -> Result<Vec<Self::Descriptor>>;
+
+    /// Find the first descriptor with the given UUID.
+    fn descriptor(&self, uuid: Uuid) -> Result<Option<Self::Descriptor>> {
+        for descriptor in self.descriptors()? {
+            if descriptor.uuid()? == uuid {
+                return Ok(Some(descriptor));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// A descriptor provided by a connected device.
+pub trait RemoteDescriptor {
+    /// UUID identifying this descriptor.
+    fn uuid(&self) -> Result<Uuid>;
+
+    /// Read the current value from the device.
+    fn read_value(&self) -> Result<Vec<u8>>;
+
+    /// Write a value to the device.
+    fn write_value(&self, data: &[u8]) -> Result<()>;
+
+    /// Read the current value from the device and decode it based on the
+    /// descriptor's UUID.
+    fn read_decoded(&self) -> Result<DescriptorValue> {
+        Ok(DescriptorValue::decode(self.uuid()?, &self.read_value()?))
+    }
+}
@@ -0,0 +1,581 @@
+//! Hosting services so this device can act as a peripheral.
+//!
+//! A [GattServer] holds the services it provides along with the handlers
+//! that answer requests from connected clients. Servers can currently only be
+//! hosted by the [Simulator](crate::sim::Simulator), which lets code written
+//! for the central role talk to them in-process.
+//!
+//! # Example
+//!
+//! ```
+//! use wible::gatt::{AttError, GattServer, LocalCharacteristic, LocalService};
+//! use wible::{CharacteristicProperties, Uuid};
+//!
+//! let server = GattServer::new();
+//! server.add_service(
+//!     LocalService::new(Uuid::from_u16(0x180F)).with_characteristic(
+//!         LocalCharacteristic::new(
+//!             Uuid::from_u16(0x2A19),
+//!             CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+//!         )
+//!         .with_value(&[100]),
+//!     ),
+//! );
+//!
+//! server.notify(Uuid::from_u16(0x180F), Uuid::from_u16(0x2A19), &[99]);
+//! ```
+
+use std::sync::{mpsc, Arc, Mutex};
+
+use super::{
+    AttError, ExtendedProperties, NotifyMode, CLIENT_CHARACTERISTIC_CONFIGURATION,
+    EXTENDED_PROPERTIES,
+};
+use crate::{CharacteristicProperties, ProtectionLevel, Uuid};
+
+type ReadHandler = Arc<dyn Fn(&GattServer) -> Result<Vec<u8>, AttError> + Send + Sync>;
+type WriteHandler = Arc<dyn Fn(&GattServer, &[u8]) -> Result<(), AttError> + Send + Sync>;
+type SubscribeHandler = Arc<dyn Fn(&GattServer, NotifyMode) + Send + Sync>;
+
+/// A descriptor hosted by a [GattServer].
+#[derive(Clone, Debug)]
+pub struct LocalDescriptor {
+    uuid: Uuid,
+    value: Vec<u8>,
+    writable: bool,
+}
+
+impl LocalDescriptor {
+    /// Create a descriptor with a fixed value.
+    pub fn new(uuid: Uuid, value: &[u8]) -> Self {
+        Self {
+            uuid,
+            value: value.to_vec(),
+            writable: false,
+        }
+    }
+
+    /// Allow clients to replace the value.
+    pub fn writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+
+    /// UUID identifying this descriptor.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+/// A client's Client Characteristic Configuration, and the channels values
+/// are sent to while it is listening.
+struct Subscriber {
+    client: usize,
+    mode: NotifyMode,
+    txs: Vec<mpsc::Sender<Vec<u8>>>,
+}
+
+/// A characteristic hosted by a [GattServer].
+///
+/// Without handlers, reads return the most recently written or notified
+/// value and writes replace it. Characteristics which can notify or indicate
+/// get a Client Characteristic Configuration descriptor automatically, and
+/// those with reliable writes or writable auxiliaries get a Characteristic
+/// Extended Properties descriptor.
+pub struct LocalCharacteristic {
+    uuid: Uuid,
+    properties: CharacteristicProperties,
+    security: ProtectionLevel,
+    value: Vec<u8>,
+    descriptors: Vec<LocalDescriptor>,
+    on_read: Option<ReadHandler>,
+    on_write: Option<WriteHandler>,
+    on_subscribe: Option<SubscribeHandler>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl LocalCharacteristic {
+    /// Create a characteristic with the given properties and an empty value.
+    pub fn new(uuid: Uuid, properties: CharacteristicProperties) -> Self {
+        Self {
+            uuid,
+            properties,
+            security: ProtectionLevel::None,
+            value: Vec::new(),
+            descriptors: Vec::new(),
+            on_read: None,
+            on_write: None,
+            on_subscribe: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Set the initial value.
+    pub fn with_value(mut self, value: &[u8]) -> Self {
+        self.value = value.to_vec();
+        self
+    }
+
+    /// Add a descriptor.
+    pub fn with_descriptor(mut self, descriptor: LocalDescriptor) -> Self {
+        self.descriptors.push(descriptor);
+        self
+    }
+
+    /// Require clients to be paired with at least the given protection before
+    /// the value or its descriptors can be read, written, or subscribed to.
+    ///
+    /// The Client Characteristic Configuration can always be read.
+    pub fn with_security(mut self, security: ProtectionLevel) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Answer read requests with the given handler instead of the stored
+    /// value.
+    pub fn on_read<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&GattServer) -> Result<Vec<u8>, AttError> + Send + Sync + 'static,
+    {
+        self.on_read = Some(Arc::new(handler));
+        self
+    }
+
+    /// Answer write requests with the given handler instead of storing the
+    /// value.
+    pub fn on_write<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&GattServer, &[u8]) -> Result<(), AttError> + Send + Sync + 'static,
+    {
+        self.on_write = Some(Arc::new(handler));
+        self
+    }
+
+    /// Call the given handler after a client subscribes or unsubscribes, such
+    /// as to send a value that should follow the subscription.
+    pub fn on_subscribe<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&GattServer, NotifyMode) + Send + Sync + 'static,
+    {
+        self.on_subscribe = Some(Arc::new(handler));
+        self
+    }
+
+    /// UUID identifying this characteristic.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Properties of this characteristic.
+    pub fn properties(&self) -> CharacteristicProperties {
+        self.properties
+    }
+
+    fn can_subscribe(&self) -> bool {
+        self.properties
+            .intersects(CharacteristicProperties::NOTIFY | CharacteristicProperties::INDICATE)
+    }
+
+    fn has_descriptor(&self, uuid: Uuid) -> bool {
+        self.descriptors
+            .iter()
+            .any(|descriptor| descriptor.uuid == uuid)
+    }
+
+    fn check_security(&self, link: ProtectionLevel) -> Result<(), AttError> {
+        if link >= self.security {
+            Ok(())
+        } else if self.security == ProtectionLevel::Encryption {
+            Err(AttError::INSUFFICIENT_ENCRYPTION)
+        } else {
+            Err(AttError::INSUFFICIENT_AUTHENTICATION)
+        }
+    }
+}
+
+impl std::fmt::Debug for LocalCharacteristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalCharacteristic")
+            .field("uuid", &self.uuid)
+            .field("properties", &self.properties)
+            .field("security", &self.security)
+            .field("descriptors", &self.descriptors)
+            .finish()
+    }
+}
+
+/// A service hosted by a [GattServer].
+#[derive(Debug)]
+pub struct LocalService {
+    uuid: Uuid,
+    characteristics: Vec<LocalCharacteristic>,
+}
+
+impl LocalService {
+    /// Create a service without any characteristics.
+    pub fn new(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            characteristics: Vec::new(),
+        }
+    }
+
+    /// Add a characteristic.
+    pub fn with_characteristic(mut self, characteristic: LocalCharacteristic) -> Self {
+        self.characteristics.push(characteristic);
+        self
+    }
+
+    /// UUID identifying this service.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+/// Services provided by this device to connected clients.
+///
+/// Cloning a server gives another handle to the same services, so handlers
+/// and tests can notify clients while the server is being hosted.
+#[derive(Clone, Default)]
+pub struct GattServer {
+    services: Arc<Mutex<Vec<LocalService>>>,
+}
+
+impl GattServer {
+    /// Create a server without any services.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a service.
+    pub fn add_service(&self, mut service: LocalService) {
+        for characteristic in &mut service.characteristics {
+            let mut extended = ExtendedProperties::empty();
+            extended.set(
+                ExtendedProperties::RELIABLE_WRITE,
+                characteristic
+                    .properties
+                    .contains(CharacteristicProperties::RELIABLE_WRITES),
+            );
+            extended.set(
+                ExtendedProperties::WRITABLE_AUXILIARIES,
+                characteristic
+                    .properties
+                    .contains(CharacteristicProperties::WRITABLE_AUXILIARIES),
+            );
+
+            if !extended.is_empty() && !characteristic.has_descriptor(EXTENDED_PROPERTIES) {
+                characteristic.properties |= CharacteristicProperties::EXTENDED_PROPERTIES;
+                characteristic.descriptors.insert(
+                    0,
+                    LocalDescriptor::new(EXTENDED_PROPERTIES, &extended.bits().to_le_bytes()),
+                );
+            }
+
+            if characteristic.can_subscribe()
+                && !characteristic.has_descriptor(CLIENT_CHARACTERISTIC_CONFIGURATION)
+            {
+                characteristic.descriptors.push(LocalDescriptor::new(
+                    CLIENT_CHARACTERISTIC_CONFIGURATION,
+                    &NotifyMode::None.cccd_value(),
+                ));
+            }
+        }
+
+        self.services.lock().unwrap().push(service);
+    }
+
+    /// Get the stored value of a characteristic.
+    pub fn value(&self, service: Uuid, characteristic: Uuid) -> Option<Vec<u8>> {
+        let services = self.services.lock().unwrap();
+        let (s, c) = find(&services, service, characteristic)?;
+
+        Some(services[s].characteristics[c].value.clone())
+    }
+
+    /// Get the stored value of a descriptor, such as one written by a client.
+    pub fn descriptor_value(
+        &self,
+        service: Uuid,
+        characteristic: Uuid,
+        descriptor: Uuid,
+    ) -> Option<Vec<u8>> {
+        let services = self.services.lock().unwrap();
+        let (s, c) = find(&services, service, characteristic)?;
+
+        services[s].characteristics[c]
+            .descriptors
+            .iter()
+            .find(|local| local.uuid == descriptor)
+            .map(|local| local.value.clone())
+    }
+
+    /// Replace the stored value of a characteristic without telling clients.
+    ///
+    /// Returns false if the characteristic does not exist.
+    pub fn set_value(&self, service: Uuid, characteristic: Uuid, value: &[u8]) -> bool {
+        let mut services = self.services.lock().unwrap();
+
+        match find(&services, service, characteristic) {
+            Some((s, c)) => {
+                services[s].characteristics[c].value = value.to_vec();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Update the value of a characteristic and send it to every client
+    /// subscribed for notifications.
+    ///
+    /// Returns the number of clients the value was sent to.
+    pub fn notify(&self, service: Uuid, characteristic: Uuid, value: &[u8]) -> usize {
+        self.send(service, characteristic, value, NotifyMode::Notify)
+    }
+
+    /// Update the value of a characteristic and send it to every client
+    /// subscribed for indications.
+    ///
+    /// Returns the number of clients the indication was sent to.
+    pub fn indicate(&self, service: Uuid, characteristic: Uuid, value: &[u8]) -> usize {
+        self.send(service, characteristic, value, NotifyMode::Indicate)
+    }
+
+    fn send(&self, service: Uuid, characteristic: Uuid, value: &[u8], mode: NotifyMode) -> usize {
+        let mut services = self.services.lock().unwrap();
+        let (s, c) = match find(&services, service, characteristic) {
+            Some(position) => position,
+            None => {
+                log::warn!("Unable to send value for unknown {:?}", characteristic);
+                return 0;
+            }
+        };
+
+        let characteristic = &mut services[s].characteristics[c];
+        characteristic.value = value.to_vec();
+
+        let mut sent = 0;
+        for subscriber in &mut characteristic.subscribers {
+            if subscriber.mode != mode {
+                continue;
+            }
+
+            subscriber.txs.retain(|tx| tx.send(value.to_vec()).is_ok());
+            if !subscriber.txs.is_empty() {
+                sent += 1;
+            }
+        }
+
+        sent
+    }
+
+    pub(crate) fn service_uuids(&self) -> Vec<Uuid> {
+        let services = self.services.lock().unwrap();
+
+        services.iter().map(|service| service.uuid).collect()
+    }
+
+    pub(crate) fn characteristics(&self, s: usize) -> Vec<(Uuid, CharacteristicProperties)> {
+        let services = self.services.lock().unwrap();
+
+        services[s]
+            .characteristics
+            .iter()
+            .map(|characteristic| (characteristic.uuid, characteristic.properties))
+            .collect()
+    }
+
+    pub(crate) fn descriptor_uuids(&self, s: usize, c: usize) -> Vec<Uuid> {
+        let services = self.services.lock().unwrap();
+
+        services[s].characteristics[c]
+            .descriptors
+            .iter()
+            .map(|descriptor| descriptor.uuid)
+            .collect()
+    }
+
+    pub(crate) fn read(
+        &self,
+        link: ProtectionLevel,
+        s: usize,
+        c: usize,
+    ) -> Result<Vec<u8>, AttError> {
+        let handler = {
+            let services = self.services.lock().unwrap();
+            let characteristic = &services[s].characteristics[c];
+
+            if !characteristic
+                .properties
+                .contains(CharacteristicProperties::READ)
+            {
+                return Err(AttError::READ_NOT_PERMITTED);
+            }
+            characteristic.check_security(link)?;
+
+            match &characteristic.on_read {
+                Some(handler) => handler.clone(),
+                None => return Ok(characteristic.value.clone()),
+            }
+        };
+
+        handler(self)
+    }
+
+    pub(crate) fn write(
+        &self,
+        link: ProtectionLevel,
+        s: usize,
+        c: usize,
+        value: &[u8],
+    ) -> Result<(), AttError> {
+        let handler = {
+            let mut services = self.services.lock().unwrap();
+            let characteristic = &mut services[s].characteristics[c];
+
+            let writable =
+                CharacteristicProperties::WRITE | CharacteristicProperties::WRITE_WITHOUT_RESPONSE;
+            if !characteristic.properties.intersects(writable) {
+                return Err(AttError::WRITE_NOT_PERMITTED);
+            }
+            characteristic.check_security(link)?;
+
+            match &characteristic.on_write {
+                Some(handler) => handler.clone(),
+                None => {
+                    characteristic.value = value.to_vec();
+                    return Ok(());
+                }
+            }
+        };
+
+        handler(self, value)
+    }
+
+    pub(crate) fn read_descriptor(
+        &self,
+        client: usize,
+        link: ProtectionLevel,
+        s: usize,
+        c: usize,
+        d: usize,
+    ) -> Result<Vec<u8>, AttError> {
+        let services = self.services.lock().unwrap();
+        let characteristic = &services[s].characteristics[c];
+        let descriptor = &characteristic.descriptors[d];
+
+        if descriptor.uuid != CLIENT_CHARACTERISTIC_CONFIGURATION {
+            characteristic.check_security(link)?;
+            return Ok(descriptor.value.clone());
+        }
+
+        let mode = characteristic
+            .subscribers
+            .iter()
+            .find(|subscriber| subscriber.client == client)
+            .map(|subscriber| subscriber.mode)
+            .unwrap_or(NotifyMode::None);
+
+        Ok(mode.cccd_value().to_vec())
+    }
+
+    pub(crate) fn write_descriptor(
+        &self,
+        client: usize,
+        link: ProtectionLevel,
+        s: usize,
+        c: usize,
+        d: usize,
+        value: &[u8],
+    ) -> Result<(), AttError> {
+        let mut services = self.services.lock().unwrap();
+        let characteristic = &mut services[s].characteristics[c];
+
+        if characteristic.descriptors[d].uuid == CLIENT_CHARACTERISTIC_CONFIGURATION {
+            let mode = match *value {
+                [0x00, 0x00] => NotifyMode::None,
+                [0x01, 0x00] => NotifyMode::Notify,
+                [0x02, 0x00] => NotifyMode::Indicate,
+                [_, _] => return Err(AttError::VALUE_NOT_ALLOWED),
+                _ => return Err(AttError::INVALID_ATTRIBUTE_VALUE_LENGTH),
+            };
+            drop(services);
+
+            return self.subscribe(client, link, s, c, mode, None);
+        }
+
+        if !characteristic.descriptors[d].writable {
+            return Err(AttError::WRITE_NOT_PERMITTED);
+        }
+        characteristic.check_security(link)?;
+
+        characteristic.descriptors[d].value = value.to_vec();
+
+        Ok(())
+    }
+
+    pub(crate) fn subscribe(
+        &self,
+        client: usize,
+        link: ProtectionLevel,
+        s: usize,
+        c: usize,
+        mode: NotifyMode,
+        tx: Option<mpsc::Sender<Vec<u8>>>,
+    ) -> Result<(), AttError> {
+        let mut services = self.services.lock().unwrap();
+        let characteristic = &mut services[s].characteristics[c];
+
+        let required = match mode {
+            NotifyMode::None => CharacteristicProperties::empty(),
+            NotifyMode::Notify => CharacteristicProperties::NOTIFY,
+            NotifyMode::Indicate => CharacteristicProperties::INDICATE,
+        };
+        if !characteristic.can_subscribe() || !characteristic.properties.contains(required) {
+            return Err(AttError::REQUEST_NOT_SUPPORTED);
+        }
+        if mode != NotifyMode::None {
+            characteristic.check_security(link)?;
+        }
+
+        let mut txs = characteristic
+            .subscribers
+            .iter()
+            .position(|subscriber| subscriber.client == client)
+            .map(|index| characteristic.subscribers.remove(index).txs)
+            .unwrap_or_default();
+        txs.extend(tx);
+
+        if mode != NotifyMode::None {
+            characteristic
+                .subscribers
+                .push(Subscriber { client, mode, txs });
+        }
+
+        let handler = characteristic.on_subscribe.clone();
+        drop(services);
+        if let Some(handler) = handler {
+            handler(self, mode);
+        }
+
+        Ok(())
+    }
+}
+
+fn find(services: &[LocalService], service: Uuid, characteristic: Uuid) -> Option<(usize, usize)> {
+    let s = services.iter().position(|s| s.uuid == service)?;
+    let c = services[s]
+        .characteristics
+        .iter()
+        .position(|c| c.uuid == characteristic)?;
+
+    Some((s, c))
+}
+
+impl std::fmt::Debug for GattServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GattServer")
+            .field("services", &*self.services.lock().unwrap())
+            .finish()
+    }
+}
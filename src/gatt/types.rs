@@ -0,0 +1,562 @@
+//! Primitive types shared by the standard characteristics.
+//!
+//! Health profiles encode measurements as IEEE-11073 [SFloat] and [Float]
+//! values and timestamps as a [DateTime]. Each type keeps its encoded form,
+//! so decoding and encoding again gives back the same bytes.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Special values of the IEEE-11073 number formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Special {
+    /// Not a Number.
+    NaN,
+    /// Not at this Resolution.
+    NRes,
+    PositiveInfinity,
+    NegativeInfinity,
+    /// Reserved for future use.
+    Reserved,
+}
+
+/// Multiply by a power of ten, dividing for negative exponents so decimal
+/// values like 11.4 come out exactly.
+pub(crate) fn scale(value: f64, exponent: i32) -> f64 {
+    if exponent < 0 {
+        value / 10f64.powi(-exponent)
+    } else {
+        value * 10f64.powi(exponent)
+    }
+}
+
+/// Split a value into a decimal mantissa and exponent, preferring the
+/// shortest exact mantissa and otherwise the most precise one that fits.
+fn split_decimal(
+    value: f64,
+    mantissa_max: i64,
+    exponents: std::ops::RangeInclusive<i32>,
+) -> Option<(i64, i32)> {
+    if value == 0.0 {
+        return Some((0, 0));
+    }
+
+    let mut closest = None;
+
+    for exponent in exponents.rev() {
+        let mantissa = scale(value, -exponent);
+        if mantissa.abs() > mantissa_max as f64 {
+            break;
+        }
+
+        let rounded = mantissa.round();
+        if rounded != 0.0 && (mantissa - rounded).abs() < 1e-9 {
+            return Some((rounded as i64, exponent));
+        }
+        closest = Some((rounded as i64, exponent));
+    }
+
+    closest
+}
+
+/// IEEE-11073 16-bit SFLOAT, a 12-bit mantissa and 4-bit base 10 exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SFloat(pub u16);
+
+impl SFloat {
+    pub const NAN: SFloat = SFloat(0x07FF);
+    pub const NRES: SFloat = SFloat(0x0800);
+    pub const POSITIVE_INFINITY: SFloat = SFloat(0x07FE);
+    pub const NEGATIVE_INFINITY: SFloat = SFloat(0x0802);
+
+    /// Largest mantissa which is not a special value.
+    const MANTISSA_MAX: i16 = 0x07FD;
+
+    /// Create a value from its mantissa and exponent.
+    ///
+    /// Returns `None` if either is out of range.
+    pub fn new(mantissa: i16, exponent: i8) -> Option<Self> {
+        if !(-Self::MANTISSA_MAX..=Self::MANTISSA_MAX).contains(&mantissa)
+            || !(-8..=7).contains(&exponent)
+        {
+            return None;
+        }
+
+        Some(SFloat((exponent as u16) << 12 | (mantissa as u16 & 0x0FFF)))
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 2]) -> Self {
+        SFloat(u16::from_le_bytes(bytes))
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 2] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn mantissa(self) -> i16 {
+        (self.0 << 4) as i16 >> 4
+    }
+
+    pub fn exponent(self) -> i8 {
+        (self.0 as i16 >> 12) as i8
+    }
+
+    /// Get which special value this is, if any.
+    pub fn special(self) -> Option<Special> {
+        match self.0 {
+            0x07FF => Some(Special::NaN),
+            0x0800 => Some(Special::NRes),
+            0x07FE => Some(Special::PositiveInfinity),
+            0x0802 => Some(Special::NegativeInfinity),
+            0x0801 => Some(Special::Reserved),
+            _ => None,
+        }
+    }
+
+    /// Convert to a float, with NaN, NRes, and reserved values as NaN.
+    pub fn to_f64(self) -> f64 {
+        match self.special() {
+            Some(Special::PositiveInfinity) => f64::INFINITY,
+            Some(Special::NegativeInfinity) => f64::NEG_INFINITY,
+            Some(_) => f64::NAN,
+            None => scale(f64::from(self.mantissa()), i32::from(self.exponent())),
+        }
+    }
+
+    /// Convert from a float, using the shortest exact representation or
+    /// rounding to the closest one.
+    ///
+    /// Returns `None` if the value is too large.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if value.is_nan() {
+            return Some(Self::NAN);
+        } else if value == f64::INFINITY {
+            return Some(Self::POSITIVE_INFINITY);
+        } else if value == f64::NEG_INFINITY {
+            return Some(Self::NEGATIVE_INFINITY);
+        }
+
+        let (mantissa, exponent) = split_decimal(value, i64::from(Self::MANTISSA_MAX), -8..=7)?;
+
+        Self::new(mantissa as i16, exponent as i8)
+    }
+}
+
+/// IEEE-11073 32-bit FLOAT, a 24-bit mantissa and 8-bit base 10 exponent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Float(pub u32);
+
+impl Float {
+    pub const NAN: Float = Float(0x007F_FFFF);
+    pub const NRES: Float = Float(0x0080_0000);
+    pub const POSITIVE_INFINITY: Float = Float(0x007F_FFFE);
+    pub const NEGATIVE_INFINITY: Float = Float(0x0080_0002);
+
+    /// Largest mantissa which is not a special value.
+    const MANTISSA_MAX: i32 = 0x007F_FFFD;
+
+    /// Create a value from its mantissa and exponent.
+    ///
+    /// Returns `None` if the mantissa is out of range.
+    pub fn new(mantissa: i32, exponent: i8) -> Option<Self> {
+        if !(-Self::MANTISSA_MAX..=Self::MANTISSA_MAX).contains(&mantissa) {
+            return None;
+        }
+
+        Some(Float(
+            (exponent as u8 as u32) << 24 | (mantissa as u32 & 0x00FF_FFFF),
+        ))
+    }
+
+    pub fn from_le_bytes(bytes: [u8; 4]) -> Self {
+        Float(u32::from_le_bytes(bytes))
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 4] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn mantissa(self) -> i32 {
+        (self.0 << 8) as i32 >> 8
+    }
+
+    pub fn exponent(self) -> i8 {
+        (self.0 >> 24) as i8
+    }
+
+    /// Get which special value this is, if any.
+    pub fn special(self) -> Option<Special> {
+        match self.0 {
+            0x007F_FFFF => Some(Special::NaN),
+            0x0080_0000 => Some(Special::NRes),
+            0x007F_FFFE => Some(Special::PositiveInfinity),
+            0x0080_0002 => Some(Special::NegativeInfinity),
+            0x0080_0001 => Some(Special::Reserved),
+            _ => None,
+        }
+    }
+
+    /// Convert to a float, with NaN, NRes, and reserved values as NaN.
+    pub fn to_f64(self) -> f64 {
+        match self.special() {
+            Some(Special::PositiveInfinity) => f64::INFINITY,
+            Some(Special::NegativeInfinity) => f64::NEG_INFINITY,
+            Some(_) => f64::NAN,
+            None => scale(f64::from(self.mantissa()), i32::from(self.exponent())),
+        }
+    }
+
+    /// Convert from a float, using the shortest exact representation or
+    /// rounding to the closest one.
+    ///
+    /// Returns `None` if the value is too large.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if value.is_nan() {
+            return Some(Self::NAN);
+        } else if value == f64::INFINITY {
+            return Some(Self::POSITIVE_INFINITY);
+        } else if value == f64::NEG_INFINITY {
+            return Some(Self::NEGATIVE_INFINITY);
+        }
+
+        let (mantissa, exponent) = split_decimal(value, i64::from(Self::MANTISSA_MAX), -128..=127)?;
+
+        Self::new(mantissa as i32, exponent as i8)
+    }
+}
+
+/// Contents of the Date Time characteristic.
+///
+/// Zero in any field means the value is not known.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DateTime {
+    pub year: u16,
+    /// Month of the year, from 1 to 12.
+    pub month: u8,
+    /// Day of the month, from 1 to 31.
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl DateTime {
+    /// Length of the encoded value.
+    pub const LEN: usize = 7;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [y0, y1, month, day, hours, minutes, seconds] => Some(Self {
+                year: u16::from_le_bytes([y0, y1]),
+                month,
+                day,
+                hours,
+                minutes,
+                seconds,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; 7] {
+        let year = self.year.to_le_bytes();
+
+        [
+            year[0],
+            year[1],
+            self.month,
+            self.day,
+            self.hours,
+            self.minutes,
+            self.seconds,
+        ]
+    }
+}
+
+/// Contents of the Day Date Time characteristic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DayDateTime {
+    pub date_time: DateTime,
+    /// Day of the week, from 1 for Monday to 7 for Sunday, or 0 if not known.
+    pub day_of_week: u8,
+}
+
+impl DayDateTime {
+    /// Length of the encoded value.
+    pub const LEN: usize = 8;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+
+        Some(Self {
+            date_time: DateTime::decode(&data[..DateTime::LEN])?,
+            day_of_week: data[DateTime::LEN],
+        })
+    }
+
+    pub fn encode(&self) -> [u8; 8] {
+        let mut data = [0u8; 8];
+        data[..DateTime::LEN].copy_from_slice(&self.date_time.encode());
+        data[DateTime::LEN] = self.day_of_week;
+
+        data
+    }
+}
+
+/// Contents of the Exact Time 256 characteristic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ExactTime256 {
+    pub day_date_time: DayDateTime,
+    /// Fractions of a second, in units of 1/256.
+    pub fractions256: u8,
+}
+
+impl ExactTime256 {
+    /// Length of the encoded value.
+    pub const LEN: usize = 9;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+
+        Some(Self {
+            day_date_time: DayDateTime::decode(&data[..DayDateTime::LEN])?,
+            fractions256: data[DayDateTime::LEN],
+        })
+    }
+
+    pub fn encode(&self) -> [u8; 9] {
+        let mut data = [0u8; 9];
+        data[..DayDateTime::LEN].copy_from_slice(&self.day_date_time.encode());
+        data[DayDateTime::LEN] = self.fractions256;
+
+        data
+    }
+
+    /// Convert from a system time, shifted by an offset from UTC in seconds.
+    ///
+    /// Returns `None` for times before 1970 or after 9999.
+    pub fn from_system_time(time: SystemTime, utc_offset: i32) -> Option<Self> {
+        let since_epoch = time.duration_since(UNIX_EPOCH).ok()?;
+        let seconds = since_epoch.as_secs() as i64 + i64::from(utc_offset);
+        if seconds < 0 {
+            return None;
+        }
+
+        let days = seconds / 86_400;
+        let (year, month, day) = civil_from_days(days);
+        if year > 9999 {
+            return None;
+        }
+
+        let seconds_of_day = seconds % 86_400;
+
+        Some(Self {
+            day_date_time: DayDateTime {
+                date_time: DateTime {
+                    year: year as u16,
+                    month,
+                    day,
+                    hours: (seconds_of_day / 3600) as u8,
+                    minutes: (seconds_of_day / 60 % 60) as u8,
+                    seconds: (seconds_of_day % 60) as u8,
+                },
+                // 1970-01-01 was a Thursday.
+                day_of_week: ((days + 3) % 7 + 1) as u8,
+            },
+            fractions256: (u64::from(since_epoch.subsec_nanos()) * 256 / 1_000_000_000) as u8,
+        })
+    }
+
+    /// Convert to a system time, given the offset from UTC in seconds the
+    /// time is shifted by.
+    ///
+    /// Returns `None` if the date is unknown, invalid, or before 1970.
+    pub fn to_system_time(&self, utc_offset: i32) -> Option<SystemTime> {
+        let date_time = &self.day_date_time.date_time;
+        if date_time.year == 0
+            || !(1..=12).contains(&date_time.month)
+            || !(1..=31).contains(&date_time.day)
+            || date_time.hours > 23
+            || date_time.minutes > 59
+            || date_time.seconds > 59
+        {
+            return None;
+        }
+
+        let days = days_from_civil(i64::from(date_time.year), date_time.month, date_time.day);
+        let seconds = days * 86_400
+            + i64::from(date_time.hours) * 3600
+            + i64::from(date_time.minutes) * 60
+            + i64::from(date_time.seconds)
+            - i64::from(utc_offset);
+        if seconds < 0 {
+            return None;
+        }
+
+        let nanos = (u64::from(self.fractions256) * 1_000_000_000 / 256) as u32;
+
+        Some(UNIX_EPOCH + Duration::new(seconds as u64, nanos))
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Date in the proleptic Gregorian calendar of a number of days since
+/// 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month as u8, day as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{DateTime, DayDateTime, ExactTime256, Float, SFloat, Special};
+
+    #[test]
+    fn test_sfloat() {
+        let value = SFloat::from_le_bytes([0x72, 0xF0]);
+        assert_eq!(value.mantissa(), 114);
+        assert_eq!(value.exponent(), -1);
+        assert_eq!(value.to_f64(), 11.4);
+        assert_eq!(SFloat::from_f64(11.4), Some(value));
+        assert_eq!(SFloat::new(114, -1), Some(value));
+
+        assert_eq!(SFloat(0x0FFE).to_f64(), -2.0);
+        assert_eq!(SFloat::from_f64(-2.0), Some(SFloat(0x0FFE)));
+        assert_eq!(SFloat::from_f64(120_000.0), Some(SFloat(0x400C)));
+        assert_eq!(SFloat::from_f64(1e12), None);
+        assert_eq!(SFloat::new(2046, 0), None);
+        assert_eq!(SFloat::new(i16::MIN, 0), None);
+
+        assert_eq!(SFloat(0x07FF).special(), Some(Special::NaN));
+        assert_eq!(SFloat(0x0800).special(), Some(Special::NRes));
+        assert_eq!(SFloat(0x07FE).special(), Some(Special::PositiveInfinity));
+        assert_eq!(SFloat(0x0802).special(), Some(Special::NegativeInfinity));
+        assert_eq!(SFloat(0x0801).special(), Some(Special::Reserved));
+        assert!(SFloat::NRES.to_f64().is_nan());
+        assert_eq!(SFloat::POSITIVE_INFINITY.to_f64(), f64::INFINITY);
+        assert_eq!(SFloat::from_f64(f64::NEG_INFINITY), Some(SFloat(0x0802)));
+    }
+
+    #[test]
+    fn test_float() {
+        // 36.4 degrees, the body temperature example from the Health
+        // Thermometer specification.
+        let value = Float::from_le_bytes([0x6C, 0x01, 0x00, 0xFF]);
+        assert_eq!(value.mantissa(), 364);
+        assert_eq!(value.exponent(), -1);
+        assert_eq!(value.to_f64(), 36.4);
+        assert_eq!(Float::from_f64(36.4), Some(value));
+        assert_eq!(value.to_le_bytes(), [0x6C, 0x01, 0x00, 0xFF]);
+
+        assert_eq!(Float(0x00FF_FFFF).to_f64(), -1.0);
+        assert_eq!(Float::new(-1, 0), Some(Float(0x00FF_FFFF)));
+        assert_eq!(Float::new(0x0080_0000, 0), None);
+        assert_eq!(Float::new(i32::MIN, 0), None);
+
+        assert_eq!(Float(0x007F_FFFF).special(), Some(Special::NaN));
+        assert_eq!(Float(0x0080_0000).special(), Some(Special::NRes));
+        assert_eq!(
+            Float(0x007F_FFFE).special(),
+            Some(Special::PositiveInfinity)
+        );
+        assert_eq!(
+            Float(0x0080_0002).special(),
+            Some(Special::NegativeInfinity)
+        );
+        assert_eq!(Float(0x0080_0001).special(), Some(Special::Reserved));
+        assert!(Float::NAN.to_f64().is_nan());
+        assert_eq!(Float::from_f64(f64::NAN), Some(Float::NAN));
+    }
+
+    #[test]
+    fn test_date_time() {
+        let data = [0xE8, 0x07, 0x03, 0x0F, 0x0C, 0x22, 0x38, 0x05, 0x80];
+        let date_time = DateTime {
+            year: 2024,
+            month: 3,
+            day: 15,
+            hours: 12,
+            minutes: 34,
+            seconds: 56,
+        };
+
+        assert_eq!(DateTime::decode(&data[..7]), Some(date_time));
+        assert_eq!(date_time.encode(), data[..7]);
+        assert_eq!(DateTime::decode(&data[..6]), None);
+
+        let day_date_time = DayDateTime {
+            date_time,
+            day_of_week: 5,
+        };
+        assert_eq!(DayDateTime::decode(&data[..8]), Some(day_date_time));
+        assert_eq!(day_date_time.encode(), data[..8]);
+
+        let exact_time = ExactTime256 {
+            day_date_time,
+            fractions256: 0x80,
+        };
+        assert_eq!(ExactTime256::decode(&data), Some(exact_time));
+        assert_eq!(exact_time.encode(), data);
+    }
+
+    #[test]
+    fn test_system_time() {
+        // 2024-03-15 12:34:56.5 UTC, a Friday.
+        let time = UNIX_EPOCH + Duration::from_millis(1_710_506_096_500);
+        let exact_time = ExactTime256::from_system_time(time, 0).unwrap();
+        assert_eq!(
+            exact_time.encode(),
+            [0xE8, 0x07, 0x03, 0x0F, 0x0C, 0x22, 0x38, 0x05, 0x80]
+        );
+        assert_eq!(exact_time.to_system_time(0), Some(time));
+
+        // An hour behind UTC, the day before.
+        let exact_time =
+            ExactTime256::from_system_time(UNIX_EPOCH + Duration::from_secs(86_400 * 60), -3600)
+                .unwrap();
+        let date_time = exact_time.day_date_time.date_time;
+        assert_eq!(
+            (date_time.month, date_time.day, date_time.hours),
+            (3, 1, 23)
+        );
+        assert_eq!(exact_time.day_date_time.day_of_week, 7);
+        assert_eq!(
+            exact_time.to_system_time(-3600),
+            Some(UNIX_EPOCH + Duration::from_secs(86_400 * 60))
+        );
+
+        assert_eq!(ExactTime256::default().to_system_time(0), None);
+        assert_eq!(ExactTime256::from_system_time(UNIX_EPOCH, -1), None);
+    }
+}
@@ -0,0 +1,277 @@
+//! Characteristic values in the formats defined by the GATT specification.
+
+use super::types::{scale, Float, SFloat};
+use super::{Format, PresentationFormat};
+
+/// A characteristic value decoded from one of the GATT formats.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Boolean(bool),
+    /// Any unsigned integer format, from 2 to 128 bits.
+    Unsigned(u128),
+    /// Any signed integer format, from 12 to 128 bits.
+    Signed(i128),
+    Float32(f32),
+    Float64(f64),
+    /// IEEE-11073 16-bit SFLOAT.
+    SFloat(SFloat),
+    /// IEEE-11073 32-bit FLOAT.
+    Float(Float),
+    /// IEEE-20601 format, two 16-bit unsigned integers.
+    DUInt16(u16, u16),
+    Utf8(String),
+    Utf16(String),
+    /// Opaque structure.
+    Struct(Vec<u8>),
+}
+
+/// Size in bytes and meaningful bits of each integer format.
+fn integer_size(format: Format) -> Option<(usize, u32, bool)> {
+    let size = match format {
+        Format::UInt2 => (1, 2, false),
+        Format::UInt4 => (1, 4, false),
+        Format::UInt8 => (1, 8, false),
+        Format::UInt12 => (2, 12, false),
+        Format::UInt16 => (2, 16, false),
+        Format::UInt24 => (3, 24, false),
+        Format::UInt32 => (4, 32, false),
+        Format::UInt48 => (6, 48, false),
+        Format::UInt64 => (8, 64, false),
+        Format::UInt128 => (16, 128, false),
+        Format::SInt8 => (1, 8, true),
+        Format::SInt12 => (2, 12, true),
+        Format::SInt16 => (2, 16, true),
+        Format::SInt24 => (3, 24, true),
+        Format::SInt32 => (4, 32, true),
+        Format::SInt48 => (6, 48, true),
+        Format::SInt64 => (8, 64, true),
+        Format::SInt128 => (16, 128, true),
+        _ => return None,
+    };
+
+    Some(size)
+}
+
+fn read_le(data: &[u8]) -> u128 {
+    let mut buf = [0u8; 16];
+    buf[..data.len()].copy_from_slice(data);
+
+    u128::from_le_bytes(buf)
+}
+
+impl Value {
+    /// Decode a value in the given format.
+    ///
+    /// Returns `None` if the data is the wrong length for the format, or the
+    /// format is not known.
+    pub fn decode(format: Format, data: &[u8]) -> Option<Self> {
+        if let Some((len, bits, signed)) = integer_size(format) {
+            if data.len() != len {
+                return None;
+            }
+
+            let raw = read_le(data);
+            let shift = 128 - bits;
+
+            return if signed {
+                Some(Value::Signed((raw << shift) as i128 >> shift))
+            } else {
+                Some(Value::Unsigned(raw << shift >> shift))
+            };
+        }
+
+        let value = match (format, data) {
+            (Format::Boolean, [0]) => Value::Boolean(false),
+            (Format::Boolean, [1]) => Value::Boolean(true),
+            (Format::Float32, [b0, b1, b2, b3]) => {
+                Value::Float32(f32::from_le_bytes([*b0, *b1, *b2, *b3]))
+            }
+            (Format::Float64, data) if data.len() == 8 => {
+                let mut buf = [0u8; 8];
+                buf.copy_from_slice(data);
+                Value::Float64(f64::from_le_bytes(buf))
+            }
+            (Format::SFloat, [b0, b1]) => Value::SFloat(SFloat::from_le_bytes([*b0, *b1])),
+            (Format::Float, [b0, b1, b2, b3]) => {
+                Value::Float(Float::from_le_bytes([*b0, *b1, *b2, *b3]))
+            }
+            (Format::DUInt16, [b0, b1, b2, b3]) => Value::DUInt16(
+                u16::from_le_bytes([*b0, *b1]),
+                u16::from_le_bytes([*b2, *b3]),
+            ),
+            (Format::Utf8, data) => Value::Utf8(String::from_utf8(data.to_vec()).ok()?),
+            (Format::Utf16, data) if data.len() % 2 == 0 => {
+                let units: Vec<_> = data
+                    .chunks(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                    .collect();
+                Value::Utf16(String::from_utf16(&units).ok()?)
+            }
+            (Format::Struct, data) => Value::Struct(data.to_vec()),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Encode this value in the given format.
+    ///
+    /// Returns `None` if the value is the wrong kind for the format, or does
+    /// not fit.
+    pub fn encode(&self, format: Format) -> Option<Vec<u8>> {
+        if let Some((len, bits, signed)) = integer_size(format) {
+            let raw = match (self, signed) {
+                (Value::Unsigned(value), false) if bits == 128 || *value >> bits == 0 => *value,
+                (Value::Signed(value), true) => {
+                    let shift = 128 - bits;
+                    if (value << shift) >> shift != *value {
+                        return None;
+                    }
+                    (*value as u128) << shift >> shift
+                }
+                _ => return None,
+            };
+
+            return Some(raw.to_le_bytes()[..len].to_vec());
+        }
+
+        let data = match (format, self) {
+            (Format::Boolean, Value::Boolean(value)) => vec![*value as u8],
+            (Format::Float32, Value::Float32(value)) => value.to_le_bytes().to_vec(),
+            (Format::Float64, Value::Float64(value)) => value.to_le_bytes().to_vec(),
+            (Format::SFloat, Value::SFloat(value)) => value.to_le_bytes().to_vec(),
+            (Format::Float, Value::Float(value)) => value.to_le_bytes().to_vec(),
+            (Format::DUInt16, Value::DUInt16(first, second)) => {
+                let mut data = first.to_le_bytes().to_vec();
+                data.extend_from_slice(&second.to_le_bytes());
+                data
+            }
+            (Format::Utf8, Value::Utf8(value)) => value.as_bytes().to_vec(),
+            (Format::Utf16, Value::Utf16(value)) => value
+                .encode_utf16()
+                .flat_map(|unit| unit.to_le_bytes().to_vec())
+                .collect(),
+            (Format::Struct, Value::Struct(value)) => value.clone(),
+            _ => return None,
+        };
+
+        Some(data)
+    }
+
+    /// Get the value as a number, if it is numeric.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Unsigned(value) => Some(*value as f64),
+            Value::Signed(value) => Some(*value as f64),
+            Value::Float32(value) => Some(f64::from(*value)),
+            Value::Float64(value) => Some(*value),
+            Value::SFloat(value) => Some(value.to_f64()),
+            Value::Float(value) => Some(value.to_f64()),
+            _ => None,
+        }
+    }
+}
+
+/// A value decoded using a characteristic's Presentation Format descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypedValue {
+    /// The decoded value, before applying the exponent.
+    pub value: Value,
+    /// Format description the value was decoded with.
+    pub format: PresentationFormat,
+}
+
+impl TypedValue {
+    /// Get the value as a number with the exponent applied, if it is numeric.
+    ///
+    /// The exponent only applies to integer formats.
+    pub fn scaled(&self) -> Option<f64> {
+        let value = self.value.as_f64()?;
+
+        match self.value {
+            Value::Unsigned(_) | Value::Signed(_) => {
+                Some(scale(value, i32::from(self.format.exponent)))
+            }
+            _ => Some(value),
+        }
+    }
+
+    /// 16-bit UUID of the unit of the value.
+    pub fn unit(&self) -> u16 {
+        self.format.unit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+    use crate::gatt::types::{Float, SFloat};
+    use crate::gatt::Format;
+
+    #[test]
+    fn test_round_trip_values() {
+        let cases: &[(Format, &[u8], Value)] = &[
+            (Format::Boolean, &[0x01], Value::Boolean(true)),
+            (Format::UInt4, &[0x0A], Value::Unsigned(10)),
+            (
+                Format::UInt24,
+                &[0x01, 0x02, 0x03],
+                Value::Unsigned(0x03_0201),
+            ),
+            (Format::SInt12, &[0xFF, 0x0F], Value::Signed(-1)),
+            (Format::SInt16, &[0x66, 0x08], Value::Signed(2150)),
+            (
+                Format::SInt48,
+                &[0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+                Value::Signed(-2),
+            ),
+            (
+                Format::Float32,
+                &[0x00, 0x00, 0xC0, 0x3F],
+                Value::Float32(1.5),
+            ),
+            (Format::SFloat, &[0x72, 0xF0], Value::SFloat(SFloat(0xF072))),
+            (
+                Format::Float,
+                &[0x6C, 0x01, 0x00, 0xFF],
+                Value::Float(Float(0xFF00_016C)),
+            ),
+            (
+                Format::DUInt16,
+                &[0x01, 0x00, 0x02, 0x00],
+                Value::DUInt16(1, 2),
+            ),
+            (Format::Utf8, b"wible", Value::Utf8("wible".to_string())),
+            (
+                Format::Utf16,
+                &[0x68, 0x00, 0x69, 0x00],
+                Value::Utf16("hi".to_string()),
+            ),
+            (
+                Format::Struct,
+                &[0xAA, 0xBB],
+                Value::Struct(vec![0xAA, 0xBB]),
+            ),
+        ];
+
+        for (format, data, value) in cases {
+            assert_eq!(
+                Value::decode(*format, data).as_ref(),
+                Some(value),
+                "decoding {:?}",
+                format
+            );
+            assert_eq!(
+                value.encode(*format).as_deref(),
+                Some(*data),
+                "encoding {:?}",
+                format
+            );
+        }
+
+        assert_eq!(Value::decode(Format::UInt16, &[0x01]), None);
+        assert_eq!(Value::Unsigned(16).encode(Format::UInt4), None);
+        assert_eq!(Value::Signed(-129).encode(Format::SInt8), None);
+        assert_eq!(Value::Signed(1).encode(Format::UInt8), None);
+    }
+}
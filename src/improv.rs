@@ -0,0 +1,526 @@
+//! Wi-Fi provisioning for devices using the Improv protocol over BLE, such as
+//! those running ESPHome or WLED.
+//!
+//! Commands are written to the RPC Command characteristic as a command byte,
+//! a length, the data, and a checksum. The device reports its progress
+//! through the Current State and Error State characteristics, and answers
+//! commands through the RPC Result characteristic in the same framing.
+//!
+//! ```no_run
+//! use wible::improv::Improv;
+//! # fn provision(device: &wible::Device) -> Result<(), Box<dyn std::error::Error>> {
+//! let service = device.service(wible::improv::SERVICE)?.expect("not an Improv device");
+//!
+//! let improv = Improv::new(&service)?;
+//! if let Some(url) = improv.provision(b"home", b"hunter22")? {
+//!     println!("finish setting up at {}", url);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+use crate::services::{Notifications, Reader};
+use crate::{CharacteristicIO, Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Improv service.
+pub const SERVICE: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8000);
+/// UUID of the Current State characteristic.
+pub const CURRENT_STATE: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8001);
+/// UUID of the Error State characteristic.
+pub const ERROR_STATE: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8002);
+/// UUID of the RPC Command characteristic.
+pub const RPC_COMMAND: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8003);
+/// UUID of the RPC Result characteristic.
+pub const RPC_RESULT: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8004);
+/// UUID of the Capabilities characteristic.
+pub const CAPABILITIES: Uuid = Uuid(0x0046_7768_6228_2272_4663_2774_7826_8005);
+
+const WIFI_SETTINGS: u8 = 0x01;
+const IDENTIFY: u8 = 0x02;
+
+/// How long to wait for each notification before checking the others.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long to wait for a URL after the device is provisioned.
+const RESULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+bitflags::bitflags! {
+    /// Features supported by a device.
+    pub struct Capabilities: u8 {
+        /// Device can identify itself, such as by blinking a light.
+        const IDENTIFY = 1 << 0;
+    }
+}
+
+/// Provisioning state of a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum State {
+    /// Device is waiting for the user to authorize provisioning, such as by
+    /// pressing a button.
+    AuthorizationRequired,
+    /// Device is ready to receive Wi-Fi settings.
+    Authorized,
+    /// Device is connecting to the network.
+    Provisioning,
+    /// Device is connected to the network.
+    Provisioned,
+    Reserved(u8),
+}
+
+impl State {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0x01 => State::AuthorizationRequired,
+            0x02 => State::Authorized,
+            0x03 => State::Provisioning,
+            0x04 => State::Provisioned,
+            value => State::Reserved(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            State::AuthorizationRequired => 0x01,
+            State::Authorized => 0x02,
+            State::Provisioning => 0x03,
+            State::Provisioned => 0x04,
+            State::Reserved(value) => value,
+        }
+    }
+}
+
+/// Error reported by a device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImprovError {
+    /// RPC command was malformed or failed its checksum.
+    InvalidRpc,
+    UnknownCommand,
+    /// Device could not connect to the network with the settings sent.
+    UnableToConnect,
+    /// Command requires the user to authorize provisioning first.
+    NotAuthorized,
+    Unknown(u8),
+}
+
+impl ImprovError {
+    /// Decode an Error State value, where 0 means there is no error.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => None,
+            0x01 => Some(ImprovError::InvalidRpc),
+            0x02 => Some(ImprovError::UnknownCommand),
+            0x03 => Some(ImprovError::UnableToConnect),
+            0x04 => Some(ImprovError::NotAuthorized),
+            value => Some(ImprovError::Unknown(value)),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ImprovError::InvalidRpc => 0x01,
+            ImprovError::UnknownCommand => 0x02,
+            ImprovError::UnableToConnect => 0x03,
+            ImprovError::NotAuthorized => 0x04,
+            ImprovError::Unknown(value) => value,
+        }
+    }
+}
+
+impl std::fmt::Display for ImprovError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImprovError::InvalidRpc => write!(f, "device received an invalid RPC packet"),
+            ImprovError::UnknownCommand => write!(f, "device does not support the command"),
+            ImprovError::UnableToConnect => write!(f, "device was unable to connect to Wi-Fi"),
+            ImprovError::NotAuthorized => write!(f, "provisioning was not authorized"),
+            ImprovError::Unknown(code) => write!(f, "device returned error {:#04X}", code),
+        }
+    }
+}
+
+impl std::error::Error for ImprovError {}
+
+/// Command, or the result of one, with its data as a list of strings.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rpc {
+    pub command: u8,
+    pub strings: Vec<Vec<u8>>,
+}
+
+impl Rpc {
+    /// Command sending the network to connect to.
+    pub fn wifi_settings(ssid: &[u8], password: &[u8]) -> Self {
+        Self {
+            command: WIFI_SETTINGS,
+            strings: vec![ssid.to_vec(), password.to_vec()],
+        }
+    }
+
+    /// Command asking the device to identify itself.
+    pub fn identify() -> Self {
+        Self {
+            command: IDENTIFY,
+            strings: Vec::new(),
+        }
+    }
+
+    /// Decode a packet, checking its length and checksum.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (&checksum, packet) = data.split_last()?;
+        if packet.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != checksum {
+            return None;
+        }
+
+        let mut reader = Reader::new(packet);
+        let command = reader.u8()?;
+        let len = reader.u8()?;
+        let mut data = Reader::new(reader.bytes(len as usize)?);
+        if !reader.is_empty() {
+            return None;
+        }
+
+        let mut strings = Vec::new();
+        while !data.is_empty() {
+            let len = data.u8()?;
+            strings.push(data.bytes(len as usize)?.to_vec());
+        }
+
+        Some(Self { command, strings })
+    }
+
+    /// Encode as a packet, or `None` if a string or the data is longer than
+    /// 255 bytes.
+    pub fn encode(&self) -> Option<Vec<u8>> {
+        let mut data = Vec::new();
+        for string in &self.strings {
+            data.push(u8::try_from(string.len()).ok()?);
+            data.extend_from_slice(string);
+        }
+
+        let mut packet = vec![self.command, u8::try_from(data.len()).ok()?];
+        packet.extend_from_slice(&data);
+        packet.push(packet.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)));
+
+        Some(packet)
+    }
+}
+
+/// Client for the Improv service.
+pub struct Improv<C: RemoteCharacteristic> {
+    current_state: C,
+    error_state: C,
+    rpc_command: C,
+    rpc_result: C,
+    capabilities: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> Improv<C> {
+    /// Find the characteristics of the Improv service.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let find = |uuid| {
+            service
+                .characteristic(uuid)?
+                .ok_or(Error::MissingAttribute(uuid))
+        };
+
+        Ok(Self {
+            current_state: find(CURRENT_STATE)?,
+            error_state: find(ERROR_STATE)?,
+            rpc_command: find(RPC_COMMAND)?,
+            rpc_result: find(RPC_RESULT)?,
+            capabilities: service.characteristic(CAPABILITIES)?,
+            timeout: Duration::from_secs(30),
+        })
+    }
+
+    /// Set how long to wait for the device to connect to a network.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Read the features supported by the device, which are empty for
+    /// devices without the Capabilities characteristic.
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        let capabilities = match &self.capabilities {
+            Some(capabilities) => capabilities,
+            None => return Ok(Capabilities::empty()),
+        };
+
+        match *capabilities.read_value()? {
+            [value, ..] => Ok(Capabilities::from_bits_truncate(value)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read the current state.
+    pub fn state(&self) -> Result<State> {
+        match *self.current_state.read_value()? {
+            [value] => Ok(State::from_u8(value)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read the last error, if any.
+    pub fn error(&self) -> Result<Option<ImprovError>> {
+        match *self.error_state.read_value()? {
+            [value] => Ok(ImprovError::from_u8(value)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Subscribe to changes of state.
+    pub fn states(&self) -> Result<Notifications<'_, C, State>> {
+        Notifications::new(&self.current_state, |data| match *data {
+            [value] => Some(State::from_u8(value)),
+            _ => None,
+        })
+    }
+
+    /// Ask the device to identify itself.
+    pub fn identify(&self) -> Result<()> {
+        self.send(&Rpc::identify())
+    }
+
+    /// Send a command without waiting for its result.
+    pub fn send(&self, rpc: &Rpc) -> Result<()> {
+        let packet = rpc.encode().ok_or(Error::InvalidValue)?;
+        log::debug!("Sending RPC {:?}", packet);
+
+        self.rpc_command.write_value(&packet)
+    }
+
+    /// Send Wi-Fi settings and wait until the device connects, returning the
+    /// URL the user should be sent to next, if the device gave one shortly
+    /// after connecting.
+    ///
+    /// Devices which need authorization fail with
+    /// [NotAuthorized](ImprovError::NotAuthorized) until the user gives it.
+    pub fn provision(&self, ssid: &[u8], password: &[u8]) -> Result<Option<String>> {
+        let mut states = CharacteristicIO::new(&self.current_state)?;
+        let mut errors = CharacteristicIO::new(&self.error_state)?;
+        let mut results = CharacteristicIO::new(&self.rpc_result)?;
+
+        self.send(&Rpc::wifi_settings(ssid, password))?;
+
+        let deadline = Instant::now() + self.timeout;
+        let mut result_deadline: Option<Instant> = None;
+        loop {
+            while let Some(error) = errors.next_notification_timeout(Duration::from_secs(0)) {
+                if let Some(error) = error.first().and_then(|&value| ImprovError::from_u8(value)) {
+                    return Err(error.into());
+                }
+            }
+
+            if let Some(result_deadline) = result_deadline {
+                let remaining = match result_deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) => remaining,
+                    None => {
+                        log::debug!("Device sent no result after provisioning");
+                        return Ok(None);
+                    }
+                };
+
+                let result = results
+                    .next_notification_timeout(remaining.min(POLL_INTERVAL))
+                    .and_then(|data| Rpc::decode(&data));
+                match result {
+                    Some(result) if result.command == WIFI_SETTINGS => {
+                        return result
+                            .strings
+                            .into_iter()
+                            .next()
+                            .map(|url| String::from_utf8(url).map_err(|_| Error::InvalidValue))
+                            .transpose();
+                    }
+                    Some(result) => log::debug!("Ignoring result {:?}", result),
+                    None => {}
+                }
+
+                continue;
+            }
+
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(Error::Timeout)?;
+
+            if let Some(state) = states.next_notification_timeout(remaining.min(POLL_INTERVAL)) {
+                match *state {
+                    [value] => {
+                        let state = State::from_u8(value);
+                        log::debug!("Device is {:?}", state);
+                        if state == State::Provisioned {
+                            result_deadline = Some(Instant::now() + RESULT_TIMEOUT);
+                        }
+                    }
+                    _ => return Err(Error::InvalidValue),
+                }
+            }
+        }
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Improv<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Improv")
+            .field("current_state", &self.current_state)
+            .field("error_state", &self.error_state)
+            .field("rpc_command", &self.rpc_command)
+            .field("rpc_result", &self.rpc_result)
+            .field("capabilities", &self.capabilities)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties};
+
+    /// Wi-Fi settings command sent by the Improv web client.
+    const WIFI_SETTINGS_PACKET: [u8; 33] = [
+        0x01, 0x1E, 0x0C, 0x4D, 0x79, 0x57, 0x69, 0x72, 0x65, 0x6C, 0x65, 0x73, 0x73, 0x41, 0x50,
+        0x10, 0x6D, 0x79, 0x73, 0x65, 0x63, 0x75, 0x72, 0x65, 0x70, 0x61, 0x73, 0x73, 0x77, 0x6F,
+        0x72, 0x64, 0xC0,
+    ];
+    /// Result sent by ESPHome after connecting.
+    const REDIRECT_PACKET: [u8; 23] = [
+        0x01, 0x14, 0x13, 0x68, 0x74, 0x74, 0x70, 0x3A, 0x2F, 0x2F, 0x31, 0x39, 0x32, 0x2E, 0x31,
+        0x36, 0x38, 0x2E, 0x31, 0x2E, 0x35, 0x30, 0xDB,
+    ];
+
+    #[test]
+    fn test_framing() {
+        let rpc = Rpc::wifi_settings(b"MyWirelessAP", b"mysecurepassword");
+        assert_eq!(rpc.encode().unwrap(), WIFI_SETTINGS_PACKET);
+        assert_eq!(Rpc::decode(&WIFI_SETTINGS_PACKET), Some(rpc));
+        assert_eq!(Rpc::identify().encode().unwrap(), [0x02, 0x00, 0x02]);
+
+        let result = Rpc::decode(&REDIRECT_PACKET).unwrap();
+        assert_eq!(result.command, WIFI_SETTINGS);
+        assert_eq!(result.strings, vec![b"http://192.168.1.50".to_vec()]);
+
+        let mut corrupt = REDIRECT_PACKET;
+        corrupt[5] ^= 0x20;
+        assert_eq!(Rpc::decode(&corrupt), None);
+        assert_eq!(Rpc::decode(&[0x02, 0x01, 0x03]), None);
+        assert_eq!(Rpc::decode(&[]), None);
+
+        let long = Rpc::wifi_settings(&[b'a'; 200], &[b'b'; 100]);
+        assert_eq!(long.encode(), None);
+    }
+
+    fn improv_server(state: State) -> GattServer {
+        let notify = CharacteristicProperties::READ | CharacteristicProperties::NOTIFY;
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(CURRENT_STATE, notify).with_value(&[state.to_u8()]),
+                )
+                .with_characteristic(LocalCharacteristic::new(ERROR_STATE, notify).with_value(&[0]))
+                .with_characteristic(
+                    LocalCharacteristic::new(RPC_COMMAND, CharacteristicProperties::WRITE)
+                        .on_write(|server, packet| {
+                            let fail = |error: ImprovError| {
+                                server.notify(SERVICE, ERROR_STATE, &[error.to_u8()]);
+                                Ok(())
+                            };
+                            let rpc = match Rpc::decode(packet) {
+                                Some(rpc) => rpc,
+                                None => return fail(ImprovError::InvalidRpc),
+                            };
+                            server.notify(SERVICE, ERROR_STATE, &[0]);
+
+                            let state = server.value(SERVICE, CURRENT_STATE).unwrap();
+                            if state == [State::AuthorizationRequired.to_u8()] {
+                                return fail(ImprovError::NotAuthorized);
+                            }
+
+                            match rpc.command {
+                                IDENTIFY => {
+                                    server.set_value(SERVICE, RPC_COMMAND, packet);
+                                }
+                                WIFI_SETTINGS => {
+                                    server.notify(SERVICE, CURRENT_STATE, &[0x03]);
+                                    if rpc.strings[1] != b"hunter22" {
+                                        server.notify(SERVICE, CURRENT_STATE, &[0x02]);
+                                        return fail(ImprovError::UnableToConnect);
+                                    }
+
+                                    server.notify(SERVICE, CURRENT_STATE, &[0x04]);
+                                    if rpc.strings[0] != b"no-url" {
+                                        server.notify(SERVICE, RPC_RESULT, &REDIRECT_PACKET);
+                                    }
+                                }
+                                _ => return fail(ImprovError::UnknownCommand),
+                            };
+                            Ok(())
+                        }),
+                )
+                .with_characteristic(LocalCharacteristic::new(RPC_RESULT, notify))
+                .with_characteristic(
+                    LocalCharacteristic::new(CAPABILITIES, CharacteristicProperties::READ)
+                        .with_value(&[0x01]),
+                ),
+        );
+
+        server
+    }
+
+    #[test]
+    fn test_simulated_device() {
+        let server = improv_server(State::AuthorizationRequired);
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let improv = Improv::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_secs(2));
+        assert_eq!(improv.capabilities().unwrap(), Capabilities::IDENTIFY);
+        assert_eq!(improv.state().unwrap(), State::AuthorizationRequired);
+        assert_eq!(improv.error().unwrap(), None);
+
+        assert!(matches!(
+            improv.provision(b"home", b"hunter22"),
+            Err(Error::Improv(ImprovError::NotAuthorized))
+        ));
+        assert_eq!(improv.error().unwrap(), Some(ImprovError::NotAuthorized));
+
+        // The user presses the button on the device.
+        let mut states = improv.states().unwrap();
+        server.notify(SERVICE, CURRENT_STATE, &[State::Authorized.to_u8()]);
+        assert_eq!(
+            states.next_timeout(Duration::from_secs(1)),
+            Some(State::Authorized)
+        );
+        drop(states);
+
+        improv.identify().unwrap();
+        assert_eq!(
+            server.value(SERVICE, RPC_COMMAND).unwrap(),
+            [0x02, 0x00, 0x02]
+        );
+
+        assert!(matches!(
+            improv.provision(b"home", b"wrong"),
+            Err(Error::Improv(ImprovError::UnableToConnect))
+        ));
+        assert_eq!(improv.state().unwrap(), State::Authorized);
+
+        let url = improv.provision(b"home", b"hunter22").unwrap();
+        assert_eq!(url.as_deref(), Some("http://192.168.1.50"));
+        assert_eq!(improv.state().unwrap(), State::Provisioned);
+
+        // Devices don't have to send a result once they are connected.
+        assert_eq!(improv.provision(b"no-url", b"hunter22").unwrap(), None);
+    }
+}
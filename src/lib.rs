@@ -7,8 +7,11 @@
 //! and characteristics until you find the interfaces you need, then getting
 //! a [CharacteristicIO] to [Read](std::io::Read) and [Write](std::io::Write) on
 //! the device.
+//!
+//! Devices can also be impersonated in-process with the [sim] module, which
+//! is useful for testing code without any Bluetooth hardware.
 
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex};
 
 use winrt::import;
 
@@ -19,6 +22,7 @@ import!(
         windows::devices::bluetooth::*
         windows::devices::bluetooth::advertisement::*
         windows::devices::bluetooth::generic_attribute_profile::*
+        windows::devices::enumeration::{DeviceInformationCustomPairing, DevicePairingRequestedEventArgs}
         windows::storage::streams::{DataReader, DataWriter}
 );
 
@@ -26,17 +30,59 @@ use windows::devices::bluetooth::advertisement::{
     BluetoothLEAdvertisementReceivedEventArgs, BluetoothLEAdvertisementWatcher,
 };
 use windows::devices::bluetooth::generic_attribute_profile::{
-    GattCharacteristic, GattClientCharacteristicConfigurationDescriptorValue, GattDescriptor,
-    GattDeviceService, GattValueChangedEventArgs,
+    GattCharacteristic, GattClientCharacteristicConfigurationDescriptorValue,
+    GattCommunicationStatus, GattDescriptor, GattDeviceService, GattValueChangedEventArgs,
+};
+use windows::devices::bluetooth::{BluetoothAddressType, BluetoothCacheMode, BluetoothLEDevice};
+use windows::devices::enumeration::{
+    DeviceInformationCustomPairing, DevicePairingKinds, DevicePairingProtectionLevel,
+    DevicePairingRequestedEventArgs, DevicePairingResultStatus, DeviceUnpairingResultStatus,
 };
-use windows::devices::bluetooth::{BluetoothCacheMode, BluetoothLEDevice};
-use windows::foundation::TypedEventHandler;
+use windows::foundation::{IReference, TypedEventHandler};
 use windows::storage::streams::{DataReader, DataWriter};
 use winrt::AbiTransferable;
 
+use gatt::{AttError, Subscription, Subscriptions};
+use services::battery::{self, Battery};
+use services::device_information::{self, DeviceInformation};
+
+mod address;
+pub mod advertisement;
+pub mod beacon;
+pub mod btp;
+#[cfg(feature = "dfu")]
+pub mod dfu;
+mod error;
+pub mod gatt;
+pub mod improv;
+pub mod pairing;
+pub mod privacy;
+#[cfg(feature = "provisioning")]
+pub mod provisioning;
+pub mod services;
+pub mod sim;
+#[cfg(feature = "smp")]
+pub mod smp;
+mod uuid;
+
+pub use address::{BluetoothAddress, BluetoothAddressParseError, TypedAddress};
+pub use advertisement::{
+    AdStructure, AdvertisementData, AdvertisementParseError, AdvertisementPublisher,
+    AdvertisingFlags, PublisherBackend,
+};
+pub use beacon::{Eddystone, EddystoneUrlError, IBeacon};
+pub use error::{Error, Result};
+pub use gatt::{
+    ControlPointError, NotifyMode, RemoteCharacteristic, RemoteDescriptor, RemoteService,
+};
+pub use pairing::{IoCapability, JustWorks, PairingError, PairingHandler, ProtectionLevel};
+pub use privacy::{AddressType, Irk, IrkStore};
+pub use uuid::{Uuid, UuidParseError};
+
 /// BLE advertisement.
 pub struct Advertisement {
     inner: BluetoothLEAdvertisementReceivedEventArgs,
+    identity: Option<BluetoothAddress>,
 }
 
 impl std::ops::Deref for Advertisement {
@@ -48,8 +94,13 @@ impl std::ops::Deref for Advertisement {
 }
 
 impl Advertisement {
-    fn new(inner: BluetoothLEAdvertisementReceivedEventArgs) -> Self {
-        Self { inner }
+    fn new(inner: BluetoothLEAdvertisementReceivedEventArgs, irks: &IrkStore) -> Self {
+        let identity = inner
+            .bluetooth_address()
+            .ok()
+            .and_then(|address| irks.resolve(BluetoothAddress(address)));
+
+        Self { inner, identity }
     }
 
     /// Get the MAC address of the device which sent this advertisement.
@@ -59,6 +110,27 @@ impl Advertisement {
         Ok(address)
     }
 
+    /// Get the MAC address of the device which sent this advertisement, along
+    /// with if it is a public or random address.
+    pub fn typed_address(&self) -> winrt::Result<TypedAddress> {
+        let address = self.address()?;
+
+        let random = self.inner.bluetooth_address_type()? == BluetoothAddressType::Random;
+
+        Ok(TypedAddress::new(address, random))
+    }
+
+    /// Get the kind of address this advertisement was sent from.
+    pub fn address_type(&self) -> winrt::Result<AddressType> {
+        Ok(self.typed_address()?.address_type)
+    }
+
+    /// Get the identity address of the device which sent this advertisement,
+    /// if its address was resolved by the watcher's [IrkStore].
+    pub fn identity(&self) -> Option<BluetoothAddress> {
+        self.identity
+    }
+
     /// Device signal strength as seen for this advertisement as dBm.
     pub fn signal_strength(&self) -> winrt::Result<i16> {
         self.inner.raw_signal_strength_in_dbm()
@@ -68,6 +140,23 @@ impl Advertisement {
     pub fn device(&self) -> winrt::Result<Device> {
         Device::from_address(self.address()?)
     }
+
+    /// Get the AD structures contained within this advertisement.
+    pub fn data(&self) -> winrt::Result<AdvertisementData> {
+        let sections = self.inner.advertisement()?.data_sections()?;
+
+        let mut data = AdvertisementData::new();
+        for section in sections {
+            let value = section.data()?;
+            let reader = DataReader::from_buffer(&value)?;
+            let mut buf = vec![0u8; value.length()? as usize];
+            reader.read_bytes(&mut buf)?;
+
+            data = data.with_structure(AdStructure::from_raw(section.data_type()?, &buf));
+        }
+
+        Ok(data)
+    }
 }
 
 impl std::fmt::Debug for Advertisement {
@@ -82,6 +171,7 @@ impl std::fmt::Debug for Advertisement {
 #[derive(Debug)]
 pub struct Device {
     inner: BluetoothLEDevice,
+    subscriptions: Arc<Subscriptions<u16>>,
 }
 
 impl std::ops::Deref for Device {
@@ -93,29 +183,244 @@ impl std::ops::Deref for Device {
 }
 
 impl Device {
+    fn new(inner: BluetoothLEDevice) -> Self {
+        Self {
+            inner,
+            subscriptions: Arc::new(Subscriptions::new()),
+        }
+    }
+
     /// Get a device connection by MAC address.
     pub fn from_address(addr: BluetoothAddress) -> winrt::Result<Self> {
         let inner = BluetoothLEDevice::from_bluetooth_address_async(addr.0)?.get()?;
 
-        Ok(Device { inner })
+        Ok(Device::new(inner))
+    }
+
+    /// Get a device connection by MAC address, only matching devices using
+    /// the same type of address.
+    pub fn from_typed_address(addr: TypedAddress) -> winrt::Result<Self> {
+        let kind = if addr.address_type.is_random() {
+            BluetoothAddressType::Random
+        } else {
+            BluetoothAddressType::Public
+        };
+
+        let inner = BluetoothLEDevice::from_bluetooth_address_with_bluetooth_address_type_async(
+            addr.address.0,
+            kind,
+        )?
+        .get()?;
+
+        Ok(Device::new(inner))
     }
 
     /// Get a list of services provided by this device.
     pub fn services(&self) -> winrt::Result<Vec<Service>> {
         let services = self.inner.get_gatt_services_async()?.get()?.services()?;
 
-        Ok(services.into_iter().map(Service::new).collect())
+        Ok(services
+            .into_iter()
+            .map(|inner| Service::new(inner, self.subscriptions.clone()))
+            .collect())
+    }
+
+    /// Find the first service with the given UUID.
+    pub fn service(&self, uuid: Uuid) -> Result<Option<Service>> {
+        for service in self.services()? {
+            if service.uuid()? == uuid {
+                return Ok(Some(service));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Read the Device Information service.
+    pub fn device_info(&self) -> Result<DeviceInformation> {
+        let service = self
+            .service(device_information::SERVICE)?
+            .ok_or(Error::MissingAttribute(device_information::SERVICE))?;
+
+        DeviceInformation::read(&service)
+    }
+
+    /// Get a client for the Battery service.
+    pub fn battery(&self) -> Result<Battery<Characteristic>> {
+        let service = self
+            .service(battery::SERVICE)?
+            .ok_or(Error::MissingAttribute(battery::SERVICE))?;
+
+        Battery::new(&service)
+    }
+
+    /// Pair with this device, using the handler to answer any prompts.
+    ///
+    /// The handler is called from another thread while pairing is in
+    /// progress. Returns the protection level that was used, or
+    /// [PairingError::Failed] without asking the handler if Windows gives a
+    /// passkey that is not a number.
+    pub fn pair<H>(&self, protection_level: ProtectionLevel, handler: H) -> Result<ProtectionLevel>
+    where
+        H: PairingHandler + Send + 'static,
+    {
+        type Handler =
+            TypedEventHandler<DeviceInformationCustomPairing, DevicePairingRequestedEventArgs>;
+
+        let kinds = match handler.io_capability() {
+            IoCapability::NoInputNoOutput => DevicePairingKinds::ConfirmOnly,
+            IoCapability::DisplayOnly => {
+                DevicePairingKinds::ConfirmOnly | DevicePairingKinds::DisplayPin
+            }
+            IoCapability::DisplayYesNo => {
+                DevicePairingKinds::ConfirmOnly
+                    | DevicePairingKinds::DisplayPin
+                    | DevicePairingKinds::ConfirmPinMatch
+            }
+            IoCapability::KeyboardOnly => {
+                DevicePairingKinds::ConfirmOnly | DevicePairingKinds::ProvidePin
+            }
+            IoCapability::KeyboardDisplay => {
+                DevicePairingKinds::ConfirmOnly
+                    | DevicePairingKinds::DisplayPin
+                    | DevicePairingKinds::ProvidePin
+                    | DevicePairingKinds::ConfirmPinMatch
+            }
+        };
+
+        let level = match protection_level {
+            ProtectionLevel::None => DevicePairingProtectionLevel::None,
+            ProtectionLevel::Encryption => DevicePairingProtectionLevel::Encryption,
+            ProtectionLevel::EncryptionAndAuthentication => {
+                DevicePairingProtectionLevel::EncryptionAndAuthentication
+            }
+        };
+
+        let handler = Mutex::new(handler);
+        let invalid_pin = Arc::new(Mutex::new(None));
+        let requested_invalid_pin = invalid_pin.clone();
+        let requested = Handler::new(move |_pairing, args| {
+            let kind = args.pairing_kind()?;
+            log::debug!("Got pairing request {:?}", kind);
+
+            let mut handler = handler.lock().unwrap();
+            match kind {
+                DevicePairingKinds::ConfirmOnly => {
+                    if handler.confirm_pairing() {
+                        args.accept()?;
+                    }
+                }
+                DevicePairingKinds::DisplayPin => {
+                    let pin = args.pin()?.to_string();
+                    let passkey = match parse_passkey(&pin) {
+                        Some(passkey) => passkey,
+                        None => {
+                            *requested_invalid_pin.lock().unwrap() = Some(pin);
+                            return Ok(());
+                        }
+                    };
+                    if handler.display_passkey(passkey) {
+                        args.accept()?;
+                    }
+                }
+                DevicePairingKinds::ProvidePin => {
+                    if let Some(passkey) = handler.request_passkey() {
+                        args.accept_with_pin(format!("{:06}", passkey).as_str())?;
+                    }
+                }
+                DevicePairingKinds::ConfirmPinMatch => {
+                    let pin = args.pin()?.to_string();
+                    let value = match parse_passkey(&pin) {
+                        Some(value) => value,
+                        None => {
+                            *requested_invalid_pin.lock().unwrap() = Some(pin);
+                            return Ok(());
+                        }
+                    };
+                    if handler.confirm_numeric_comparison(value) {
+                        args.accept()?;
+                    }
+                }
+                kind => log::warn!("Unsupported pairing request {:?}", kind),
+            }
+
+            Ok(())
+        });
+
+        let custom = self.inner.device_information()?.pairing()?.custom()?;
+        let token = custom.pairing_requested(requested)?;
+        let result = custom.pair_with_protection_level_async(kinds, level)?.get();
+        custom.remove_pairing_requested(token)?;
+        let result = result?;
+
+        // The request was left unanswered so pairing failed, but it was never
+        // shown to the handler.
+        if let Some(pin) = invalid_pin.lock().unwrap().take() {
+            return Err(PairingError::Failed(format!("invalid passkey {:?}", pin)).into());
+        }
+
+        match result.status()? {
+            DevicePairingResultStatus::Paired | DevicePairingResultStatus::AlreadyPaired => {}
+            DevicePairingResultStatus::RejectedByHandler
+            | DevicePairingResultStatus::PairingCanceled
+            | DevicePairingResultStatus::ConnectionRejected => {
+                return Err(PairingError::Rejected.into())
+            }
+            DevicePairingResultStatus::AuthenticationFailure => {
+                return Err(PairingError::PasskeyMismatch.into())
+            }
+            DevicePairingResultStatus::ProtectionLevelCouldNotBeMet => {
+                return Err(PairingError::ProtectionLevelNotMet.into())
+            }
+            status => return Err(PairingError::Failed(format!("{:?}", status)).into()),
+        }
+
+        let level = match result.protection_level_used()? {
+            DevicePairingProtectionLevel::Encryption => ProtectionLevel::Encryption,
+            DevicePairingProtectionLevel::EncryptionAndAuthentication => {
+                ProtectionLevel::EncryptionAndAuthentication
+            }
+            _ => ProtectionLevel::None,
+        };
+
+        Ok(level)
+    }
+
+    /// Remove the bond with this device, if there is one.
+    pub fn unpair(&self) -> Result<()> {
+        let status = self
+            .inner
+            .device_information()?
+            .pairing()?
+            .unpair_async()?
+            .get()?
+            .status()?;
+
+        match status {
+            DeviceUnpairingResultStatus::Unpaired
+            | DeviceUnpairingResultStatus::AlreadyUnpaired => Ok(()),
+            status => Err(PairingError::Failed(format!("{:?}", status)).into()),
+        }
+    }
+
+    /// Check if this device is paired.
+    pub fn is_paired(&self) -> Result<bool> {
+        Ok(self.inner.device_information()?.pairing()?.is_paired()?)
     }
 }
 
 /// Discovered BLE service.
 pub struct Service {
     inner: GattDeviceService,
+    subscriptions: Arc<Subscriptions<u16>>,
 }
 
 impl Service {
-    fn new(inner: GattDeviceService) -> Self {
-        Self { inner }
+    fn new(inner: GattDeviceService, subscriptions: Arc<Subscriptions<u16>>) -> Self {
+        Self {
+            inner,
+            subscriptions,
+        }
     }
 
     /// Get the list of available characteristics on this service.
@@ -128,11 +433,36 @@ impl Service {
 
         Ok(characteristics
             .into_iter()
-            .map(Characteristic::new)
+            .map(|inner| Characteristic::new(inner, self.subscriptions.clone()))
             .collect())
     }
 }
 
+impl RemoteService for Service {
+    type Characteristic = Characteristic;
+
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(Uuid::from(&self.inner.uuid()?))
+    }
+
+    fn characteristics(&self) -> Result<Vec<Characteristic>> {
+        Ok(Service::characteristics(self)?)
+    }
+
+    fn characteristic(&self, uuid: Uuid) -> Result<Option<Characteristic>> {
+        let characteristics = self
+            .inner
+            .get_characteristics_for_uuid_async(&uuid.into())?
+            .get()?
+            .characteristics()?;
+
+        Ok(characteristics
+            .into_iter()
+            .next()
+            .map(|inner| Characteristic::new(inner, self.subscriptions.clone())))
+    }
+}
+
 impl std::fmt::Debug for Service {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Service")
@@ -152,6 +482,7 @@ impl std::ops::Deref for Service {
 /// Discovered BLE characteristic.
 pub struct Characteristic {
     inner: GattCharacteristic,
+    subscriptions: Arc<Subscriptions<u16>>,
 }
 
 // These values are in GattCharacteristicProperties but not accessible in a
@@ -175,8 +506,11 @@ bitflags::bitflags! {
 
 /// Discovered BLE characteristic.
 impl Characteristic {
-    fn new(inner: GattCharacteristic) -> Self {
-        Self { inner }
+    fn new(inner: GattCharacteristic, subscriptions: Arc<Subscriptions<u16>>) -> Self {
+        Self {
+            inner,
+            subscriptions,
+        }
     }
 
     /// Get the properties of this characteristic.
@@ -230,8 +564,65 @@ impl Characteristic {
     /// the [Read](std::io::Read) and [Write](std::io::Write) traits.
     ///
     /// It also configures notifications for characteristics that support it.
-    pub fn io(&self) -> winrt::Result<CharacteristicIO> {
-        CharacteristicIO::new(&self)
+    pub fn io(&self) -> Result<CharacteristicIO<'_>> {
+        CharacteristicIO::new(self)
+    }
+
+    /// Write the Client Characteristic Configuration descriptor without
+    /// listening for values.
+    ///
+    /// The setting is left in place when a [CharacteristicIO] is dropped.
+    pub fn set_notify(&self, mode: NotifyMode) -> Result<()> {
+        self.write_notify(mode)?;
+        RemoteCharacteristic::subscription(self)?
+            .lock()
+            .unwrap()
+            .explicit = mode != NotifyMode::None;
+
+        Ok(())
+    }
+
+    fn write_notify(&self, mode: NotifyMode) -> Result<()> {
+        let value = match mode {
+            NotifyMode::None => GattClientCharacteristicConfigurationDescriptorValue::None,
+            NotifyMode::Notify => GattClientCharacteristicConfigurationDescriptorValue::Notify,
+            NotifyMode::Indicate => GattClientCharacteristicConfigurationDescriptorValue::Indicate,
+        };
+
+        log::debug!("Setting {:?} configuration descriptor on {:?}", mode, &self);
+
+        let result = self
+            .inner
+            .write_client_characteristic_configuration_descriptor_with_result_async(value)?
+            .get()?;
+
+        check_status(
+            result.status()?,
+            result.protocol_error(),
+            AttError::WRITE_NOT_PERMITTED,
+        )
+    }
+
+    /// Read the Client Characteristic Configuration descriptor.
+    pub fn get_notify(&self) -> Result<NotifyMode> {
+        let result = self
+            .inner
+            .read_client_characteristic_configuration_descriptor_async()?
+            .get()?;
+
+        check_status(
+            result.status()?,
+            result.protocol_error(),
+            AttError::READ_NOT_PERMITTED,
+        )?;
+
+        let mode = match result.client_characteristic_configuration_descriptor()? {
+            GattClientCharacteristicConfigurationDescriptorValue::Notify => NotifyMode::Notify,
+            GattClientCharacteristicConfigurationDescriptorValue::Indicate => NotifyMode::Indicate,
+            _ => NotifyMode::None,
+        };
+
+        Ok(mode)
     }
 
     /// Get the list of descriptors on this characteristic.
@@ -242,6 +633,98 @@ impl Characteristic {
     }
 }
 
+impl RemoteCharacteristic for Characteristic {
+    type Descriptor = Descriptor;
+
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(Uuid::from(&self.inner.uuid()?))
+    }
+
+    fn properties(&self) -> Option<CharacteristicProperties> {
+        Characteristic::properties(self)
+    }
+
+    fn read_value(&self) -> Result<Vec<u8>> {
+        Ok(self.read()?)
+    }
+
+    fn write_value(&self, data: &[u8]) -> Result<()> {
+        Ok(self.write(data)?)
+    }
+
+    fn subscribe(
+        &self,
+        subscription: &mut Subscription,
+        mode: NotifyMode,
+    ) -> Result<mpsc::Receiver<Vec<u8>>> {
+        type Handler = TypedEventHandler<GattCharacteristic, GattValueChangedEventArgs>;
+
+        // Every user shares one handler, which is registered on the first
+        // characteristic handle and removed with the last user.
+        if subscription.unlisten.is_none() {
+            let senders = subscription.senders.clone();
+            let handler = Handler::new(move |_characteristic, value| {
+                log::trace!("Got subscribe notify {:?}", value);
+
+                let value = value.characteristic_value()?;
+                let reader = DataReader::from_buffer(&value)?;
+                let mut buf = vec![0u8; value.length()? as usize];
+                reader.read_bytes(&mut buf)?;
+
+                senders
+                    .lock()
+                    .unwrap()
+                    .retain(|tx| tx.send(buf.clone()).is_ok());
+
+                Ok(())
+            });
+
+            self.write_notify(mode)?;
+            let token = self.inner.value_changed(handler)?;
+
+            let inner = self.inner.clone();
+            subscription.unlisten = Some(Box::new(move || {
+                inner.remove_value_changed(token)?;
+                Ok(())
+            }));
+        }
+
+        let (tx, rx) = mpsc::channel();
+        subscription.senders.lock().unwrap().push(tx);
+
+        Ok(rx)
+    }
+
+    fn unsubscribe(&self, subscription: &mut Subscription) -> Result<()> {
+        subscription.senders.lock().unwrap().clear();
+        if let Some(unlisten) = subscription.unlisten.take() {
+            unlisten()?;
+        }
+
+        if subscription.explicit {
+            return Ok(());
+        }
+
+        self.write_notify(NotifyMode::None)
+    }
+
+    fn subscription(&self) -> Result<Arc<Mutex<Subscription>>> {
+        Ok(self.subscriptions.get(self.inner.attribute_handle()?))
+    }
+
+    fn set_notify(&self, mode: NotifyMode) -> Result<()> {
+        Characteristic::set_notify(self, mode)
+    }
+
+    fn get_notify(&self) -> Result<NotifyMode> {
+        Characteristic::get_notify(self)
+    }
+
+    fn descriptors(&self) -> Result<Vec<Descriptor>> {
+        Ok(Characteristic::descriptors(self)?)
+    }
+}
+
 impl std::fmt::Debug for Characteristic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Characteristic")
@@ -262,8 +745,15 @@ impl std::ops::Deref for Characteristic {
 /// An accessible way to read and write data from a characteristic.
 ///
 /// It provides the [Read](std::io::Read) and [Write](std::io::Write) traits
-/// for easy access to device I/O. It also configures notifications when
-/// supported by the characteristic and cleans up after itself on drop.
+/// for easy access to device I/O. It also configures notifications, or
+/// indications if notifications are not supported, and cleans up after itself
+/// on drop.
+///
+/// Instances on the same characteristic share its notifications, which are
+/// turned off when the last one is dropped. Notifications turned on with
+/// [set_notify](RemoteCharacteristic::set_notify) are left on, while any
+/// other existing configuration, such as one left behind by another process,
+/// is cleaned up like one this instance made.
 ///
 /// Reading currently has a non-configurable 1 second timeout when waiting for
 /// notifications. If no data is received, it may return a 0-length response.
@@ -273,67 +763,76 @@ impl std::ops::Deref for Characteristic {
 ///
 /// Will panic if read or write is used on a characteristic that does not
 /// support reading or writing, respectively.
-pub struct CharacteristicIO<'a> {
-    characteristic: &'a Characteristic,
+pub struct CharacteristicIO<'a, C: RemoteCharacteristic = Characteristic> {
+    characteristic: &'a C,
     buf: Vec<u8>,
 
     rx: Option<mpsc::Receiver<Vec<u8>>>,
+    subscription: Option<Arc<Mutex<Subscription>>>,
 }
 
-impl<'a> CharacteristicIO<'a> {
+impl<'a, C: RemoteCharacteristic> CharacteristicIO<'a, C> {
     /// Create a new instance, configuring notifications if supported.
-    fn new(characteristic: &'a Characteristic) -> winrt::Result<Self> {
-        let rx = match characteristic.properties() {
-            Some(props) if props.contains(CharacteristicProperties::NOTIFY) => {
-                Some(Self::configure_notify(&characteristic)?)
+    ///
+    /// Notifications already enabled on the device are used as they are.
+    pub(crate) fn new(characteristic: &'a C) -> Result<Self> {
+        let props = characteristic
+            .properties()
+            .unwrap_or_else(CharacteristicProperties::empty);
+        if !props.intersects(CharacteristicProperties::NOTIFY | CharacteristicProperties::INDICATE)
+        {
+            return Ok(Self {
+                characteristic,
+                buf: Default::default(),
+                rx: None,
+                subscription: None,
+            });
+        }
+
+        let shared = characteristic.subscription()?;
+        let mut subscription = shared.lock().unwrap();
+
+        let configured = characteristic.get_notify().unwrap_or_else(|err| {
+            log::warn!("Unable to read notify configuration: {:?}", err);
+            NotifyMode::None
+        });
+        let mode = match configured {
+            NotifyMode::None if props.contains(CharacteristicProperties::NOTIFY) => {
+                NotifyMode::Notify
             }
-            _ => None,
+            NotifyMode::None => NotifyMode::Indicate,
+            mode => mode,
         };
 
+        let rx = characteristic.subscribe(&mut subscription, mode)?;
+        subscription.users += 1;
+        drop(subscription);
+
         Ok(Self {
             characteristic,
             buf: Default::default(),
-            rx,
+            rx: Some(rx),
+            subscription: Some(shared),
         })
     }
 
-    /// Create a channel for getting updates from notifications.
-    fn configure_notify(characteristic: &Characteristic) -> winrt::Result<mpsc::Receiver<Vec<u8>>> {
-        type Handler = TypedEventHandler<GattCharacteristic, GattValueChangedEventArgs>;
-        let notify = GattClientCharacteristicConfigurationDescriptorValue::Notify;
-
-        let (tx, rx) = mpsc::channel();
-
-        let handler = Handler::new(move |_characteristic, value| {
-            log::trace!("Got subscribe notify {:?}", value);
-
-            let value = value.characteristic_value()?;
-            let reader = DataReader::from_buffer(&value)?;
-            let mut buf = vec![0u8; value.length()? as usize];
-            reader.read_bytes(&mut buf)?;
-
-            if let Err(err) = tx.send(buf) {
-                log::error!("Unable to send subscribed notify: {:?}", err);
-            }
-
-            Ok(())
-        });
-
-        log::debug!(
-            "Setting notify configuration descriptor on {:?}",
-            &characteristic
-        );
-
-        characteristic
-            .write_client_characteristic_configuration_descriptor_async(notify)?
-            .get()?;
-        characteristic.value_changed(handler)?;
+    /// Wait for the next notification as a whole value, instead of as part
+    /// of the byte stream provided by [Read](std::io::Read).
+    ///
+    /// Returns `None` if notifications are not configured or the device
+    /// stopped sending them.
+    pub fn next_notification(&mut self) -> Option<Vec<u8>> {
+        self.rx.as_ref()?.recv().ok()
+    }
 
-        Ok(rx)
+    /// Wait up to the given duration for the next notification as a whole
+    /// value.
+    pub fn next_notification_timeout(&mut self, timeout: std::time::Duration) -> Option<Vec<u8>> {
+        self.rx.as_ref()?.recv_timeout(timeout).ok()
     }
 }
 
-impl std::fmt::Debug for CharacteristicIO<'_> {
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for CharacteristicIO<'_, C> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CharacterIO")
             .field("characteristic", &self.characteristic)
@@ -342,7 +841,7 @@ impl std::fmt::Debug for CharacteristicIO<'_> {
     }
 }
 
-impl std::io::Read for CharacteristicIO<'_> {
+impl<C: RemoteCharacteristic> std::io::Read for CharacteristicIO<'_, C> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if let Some(props) = self.characteristic.properties() {
             if !props.contains(CharacteristicProperties::READ) {
@@ -368,7 +867,7 @@ impl std::io::Read for CharacteristicIO<'_> {
         } else if self.buf.is_empty() {
             let data = self
                 .characteristic
-                .read()
+                .read_value()
                 .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
             self.buf.extend(data);
         }
@@ -381,7 +880,7 @@ impl std::io::Read for CharacteristicIO<'_> {
     }
 }
 
-impl std::io::Write for CharacteristicIO<'_> {
+impl<C: RemoteCharacteristic> std::io::Write for CharacteristicIO<'_, C> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         if let Some(props) = self.characteristic.properties() {
             if !props.contains(CharacteristicProperties::WRITE) {
@@ -390,7 +889,7 @@ impl std::io::Write for CharacteristicIO<'_> {
         }
 
         self.characteristic
-            .write(&buf)
+            .write_value(buf)
             .map_err(|_err| std::io::Error::from(std::io::ErrorKind::Other))?;
         Ok(buf.len())
     }
@@ -406,23 +905,22 @@ impl std::io::Write for CharacteristicIO<'_> {
     }
 }
 
-impl Drop for CharacteristicIO<'_> {
+impl<C: RemoteCharacteristic> Drop for CharacteristicIO<'_, C> {
     fn drop(&mut self) {
-        if self.rx.is_none() {
+        let subscription = match &self.subscription {
+            Some(subscription) => subscription,
+            None => return,
+        };
+
+        let mut subscription = subscription.lock().unwrap();
+        subscription.users -= 1;
+        if subscription.users > 0 {
             return;
         }
 
         log::debug!("Dropping CharacteristicIO, removing notify");
 
-        let err = self
-            .characteristic
-            .write_client_characteristic_configuration_descriptor_async(
-                GattClientCharacteristicConfigurationDescriptorValue::None,
-            )
-            .map(|val| val.get())
-            .err();
-
-        if let Some(err) = err {
+        if let Err(err) = self.characteristic.unsubscribe(&mut subscription) {
             log::error!(
                 "Unable to remove notify on CharacteristicIO drop: {:?}",
                 err
@@ -452,6 +950,38 @@ impl Descriptor {
 
         Ok(buf)
     }
+
+    /// Write data to the descriptor.
+    ///
+    /// Fails with [Error::Att] if the device rejects the value.
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        log::trace!("Writing data to {:?}", &self);
+
+        let writer = DataWriter::new()?;
+        writer.write_bytes(data)?;
+        let buf = writer.detach_buffer()?;
+        let result = self.inner.write_value_with_result_async(&buf)?.get()?;
+
+        check_status(
+            result.status()?,
+            result.protocol_error(),
+            AttError::WRITE_NOT_PERMITTED,
+        )
+    }
+}
+
+impl RemoteDescriptor for Descriptor {
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(Uuid::from(&self.inner.uuid()?))
+    }
+
+    fn read_value(&self) -> Result<Vec<u8>> {
+        Ok(self.read()?)
+    }
+
+    fn write_value(&self, data: &[u8]) -> Result<()> {
+        self.write(data)
+    }
 }
 
 impl std::fmt::Debug for Descriptor {
@@ -462,6 +992,39 @@ impl std::fmt::Debug for Descriptor {
     }
 }
 
+/// Parse a passkey or comparison value, which is at most six digits.
+fn parse_passkey(pin: &str) -> Option<u32> {
+    if pin.is_empty() || pin.len() > 6 || !pin.bytes().all(|byte| byte.is_ascii_digit()) {
+        log::warn!("Rejecting pairing with invalid passkey {:?}", pin);
+        return None;
+    }
+
+    pin.parse().ok()
+}
+
+/// Convert the status of a GATT request into a result.
+///
+/// Protocol errors carry the ATT error sent by the device, while requests
+/// refused by Windows itself are reported as `denied`.
+fn check_status(
+    status: GattCommunicationStatus,
+    protocol_error: winrt::Result<IReference<u8>>,
+    denied: AttError,
+) -> Result<()> {
+    match status {
+        GattCommunicationStatus::Success => Ok(()),
+        GattCommunicationStatus::Unreachable => Err(Error::Unreachable),
+        GattCommunicationStatus::AccessDenied => Err(Error::Att(denied)),
+        _ => {
+            let code = protocol_error
+                .and_then(|code| code.value())
+                .unwrap_or(AttError::UNLIKELY_ERROR.0);
+
+            Err(Error::Att(AttError(code)))
+        }
+    }
+}
+
 /// Utility to allow iteration through advertisements.
 ///
 /// # Example
@@ -496,6 +1059,12 @@ pub struct AdvertisementWatcher {
 impl AdvertisementWatcher {
     /// Start listening for advertisements.
     pub fn new() -> winrt::Result<Self> {
+        Self::with_irk_store(IrkStore::new())
+    }
+
+    /// Start listening for advertisements, resolving the identity of devices
+    /// which send from Resolvable Private Addresses using the given keys.
+    pub fn with_irk_store(irks: IrkStore) -> winrt::Result<Self> {
         let (tx, rx) = mpsc::channel();
 
         type Handler = TypedEventHandler<
@@ -506,7 +1075,7 @@ impl AdvertisementWatcher {
         let handler = Handler::new(move |_sender, advertisement| {
             log::trace!("Got Bluetooth advertisement: {:?}", advertisement);
 
-            if let Err(err) = tx.send(Advertisement::new(advertisement.to_owned())) {
+            if let Err(err) = tx.send(Advertisement::new(advertisement.to_owned(), &irks)) {
                 log::error!("Unable to send advertisement: {:?}", err);
             }
 
@@ -538,113 +1107,3 @@ impl Drop for AdvertisementWatcher {
         }
     }
 }
-
-/// A MAC address for Bluetooth devices.
-#[derive(Copy, Clone, Eq, PartialEq, Hash)]
-pub struct BluetoothAddress(pub u64);
-
-impl BluetoothAddress {
-    /// Get the bytes contained within the address.
-    pub fn bytes(&self) -> [u8; 6] {
-        let mut buf = [0u8; 6];
-        buf.copy_from_slice(&self.0.to_be_bytes()[2..]);
-
-        buf
-    }
-
-    /// Get an uppercase and colon separated String representing the address.
-    pub fn hex_string(&self) -> String {
-        self.bytes()
-            .iter()
-            .map(|b| format!("{:02X}", b))
-            .collect::<Vec<_>>()
-            .join(":")
-    }
-}
-
-/// Error generated when parsing a MAC address from a string.
-#[derive(Debug, PartialEq)]
-pub enum BluetoothAddressParseError {
-    /// MAC address has a number of segments not equal to 6.
-    IncorrectSegments,
-    /// MAC segment contains a value that is not a valid base-16 number.
-    InvalidNumber,
-}
-
-impl std::fmt::Display for BluetoothAddressParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BluetoothAddressParseError::IncorrectSegments => {
-                write!(f, "MAC address has incorrect number of segments")
-            }
-            BluetoothAddressParseError::InvalidNumber => {
-                write!(f, "MAC segment was not valid base-16 number")
-            }
-        }
-    }
-}
-
-impl std::error::Error for BluetoothAddressParseError {}
-
-impl std::str::FromStr for BluetoothAddress {
-    type Err = BluetoothAddressParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut bytes = [0u8; 8];
-
-        let parts: Vec<_> = s.split(':').collect();
-        if parts.len() != 6 {
-            return Err(BluetoothAddressParseError::IncorrectSegments);
-        }
-
-        for (index, part) in parts.iter().rev().enumerate() {
-            let val = match u8::from_str_radix(part, 16) {
-                Ok(val) => val,
-                Err(_err) => {
-                    return Err(BluetoothAddressParseError::InvalidNumber);
-                }
-            };
-
-            bytes[index] = val;
-        }
-
-        Ok(BluetoothAddress(u64::from_le_bytes(bytes)))
-    }
-}
-
-impl std::fmt::Display for BluetoothAddress {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.hex_string())
-    }
-}
-
-impl std::fmt::Debug for BluetoothAddress {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = self.hex_string();
-
-        f.debug_tuple("BluetoothAddress").field(&s).finish()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{BluetoothAddress, BluetoothAddressParseError};
-
-    #[test]
-    fn test_parse_mac() {
-        let input = "C8:FD:19:12:7F:CD";
-        let expected = Ok(BluetoothAddress(220989372923853));
-        let parsed = input.parse();
-        assert_eq!(parsed, expected, "unable to parse MAC address");
-
-        let input = "test";
-        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::IncorrectSegments);
-        let parsed = input.parse();
-        assert_eq!(parsed, expected, "invalid mac was accepted");
-
-        let input = "C8:FD:ZZ:12:7F:CD";
-        let expected = Err::<BluetoothAddress, _>(BluetoothAddressParseError::InvalidNumber);
-        let parsed = input.parse();
-        assert_eq!(parsed, expected, "invalid mac was accepted");
-    }
-}
@@ -0,0 +1,193 @@
+//! Pairing and bonding with devices.
+//!
+//! Pairing is driven by a [PairingHandler], which answers the prompts that
+//! come up depending on what input and output the devices involved have.
+
+/// Security required for, or achieved by, a connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtectionLevel {
+    /// No encryption.
+    None,
+    /// Encrypted, but without protection from man-in-the-middle attacks.
+    Encryption,
+    /// Encrypted with keys exchanged using an authenticated method.
+    EncryptionAndAuthentication,
+}
+
+/// Input and output available to the user of a device during pairing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoCapability {
+    /// Can show a number, but cannot take input.
+    DisplayOnly,
+    /// Can show a number and take a yes or no answer.
+    DisplayYesNo,
+    /// Can take a number, but cannot show one.
+    KeyboardOnly,
+    /// No way of interacting with the user.
+    NoInputNoOutput,
+    /// Can show and take a number.
+    KeyboardDisplay,
+}
+
+/// How the user is involved in pairing, as seen by the initiating device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairingMethod {
+    /// The user is at most asked to confirm pairing.
+    JustWorks,
+    /// Both devices show the same number and the user confirms they match.
+    NumericComparison,
+    /// The initiator shows a passkey which the user enters on the responder.
+    PasskeyDisplayed,
+    /// The responder shows a passkey which the user enters on the initiator.
+    PasskeyEntered,
+    /// The user enters the same passkey on both devices.
+    PasskeyEnteredOnBoth,
+}
+
+impl PairingMethod {
+    /// Determine the method used for LE Secure Connections pairing between
+    /// devices with the given capabilities.
+    pub fn select(initiator: IoCapability, responder: IoCapability) -> Self {
+        use IoCapability::*;
+
+        match (initiator, responder) {
+            (NoInputNoOutput, _) | (_, NoInputNoOutput) => PairingMethod::JustWorks,
+            (DisplayOnly, DisplayOnly)
+            | (DisplayOnly, DisplayYesNo)
+            | (DisplayYesNo, DisplayOnly) => PairingMethod::JustWorks,
+            (DisplayYesNo, DisplayYesNo)
+            | (DisplayYesNo, KeyboardDisplay)
+            | (KeyboardDisplay, DisplayYesNo)
+            | (KeyboardDisplay, KeyboardDisplay) => PairingMethod::NumericComparison,
+            (KeyboardOnly, KeyboardOnly) => PairingMethod::PasskeyEnteredOnBoth,
+            (KeyboardOnly, _) | (KeyboardDisplay, DisplayOnly) => PairingMethod::PasskeyEntered,
+            (_, KeyboardOnly) | (DisplayOnly, KeyboardDisplay) => PairingMethod::PasskeyDisplayed,
+        }
+    }
+
+    /// Protection achieved by pairing with this method.
+    pub fn protection_level(self) -> ProtectionLevel {
+        match self {
+            PairingMethod::JustWorks => ProtectionLevel::Encryption,
+            _ => ProtectionLevel::EncryptionAndAuthentication,
+        }
+    }
+}
+
+/// Answers prompts shown to the user while pairing.
+///
+/// Every prompt is rejected by default, except for confirming Just Works
+/// pairing. Implement the prompts matching the capability returned from
+/// [io_capability](PairingHandler::io_capability).
+pub trait PairingHandler {
+    /// Input and output available to the user.
+    fn io_capability(&self) -> IoCapability {
+        IoCapability::NoInputNoOutput
+    }
+
+    /// Confirm Just Works pairing, without any passkey.
+    fn confirm_pairing(&mut self) -> bool {
+        true
+    }
+
+    /// Get the passkey shown by the device.
+    fn request_passkey(&mut self) -> Option<u32> {
+        None
+    }
+
+    /// Show a passkey which must be entered on the device, returning true once
+    /// it has been shown.
+    fn display_passkey(&mut self, _passkey: u32) -> bool {
+        false
+    }
+
+    /// Confirm that the given value matches the value shown on the device.
+    fn confirm_numeric_comparison(&mut self, _value: u32) -> bool {
+        false
+    }
+}
+
+impl<H: PairingHandler + ?Sized> PairingHandler for &mut H {
+    fn io_capability(&self) -> IoCapability {
+        (**self).io_capability()
+    }
+
+    fn confirm_pairing(&mut self) -> bool {
+        (**self).confirm_pairing()
+    }
+
+    fn request_passkey(&mut self) -> Option<u32> {
+        (**self).request_passkey()
+    }
+
+    fn display_passkey(&mut self, passkey: u32) -> bool {
+        (**self).display_passkey(passkey)
+    }
+
+    fn confirm_numeric_comparison(&mut self, value: u32) -> bool {
+        (**self).confirm_numeric_comparison(value)
+    }
+}
+
+/// Handler which accepts Just Works pairing and nothing else.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JustWorks;
+
+impl PairingHandler for JustWorks {}
+
+/// Reasons pairing with a device may fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PairingError {
+    /// A pairing prompt was rejected, either by the handler or by the device.
+    Rejected,
+    /// The passkey or comparison value did not match on both devices.
+    PasskeyMismatch,
+    /// The requested protection level could not be met with the available
+    /// input and output.
+    ProtectionLevelNotMet,
+    /// Pairing failed for another reason, reported by the platform.
+    Failed(String),
+}
+
+impl std::fmt::Display for PairingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PairingError::Rejected => write!(f, "pairing was rejected"),
+            PairingError::PasskeyMismatch => write!(f, "passkey did not match"),
+            PairingError::ProtectionLevelNotMet => {
+                write!(f, "requested protection level could not be met")
+            }
+            PairingError::Failed(reason) => write!(f, "pairing failed: {}", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IoCapability::*, PairingMethod, PairingMethod::*};
+
+    #[test]
+    fn test_select_method() {
+        let cases = [
+            (NoInputNoOutput, KeyboardDisplay, JustWorks),
+            (DisplayYesNo, DisplayOnly, JustWorks),
+            (DisplayYesNo, DisplayYesNo, NumericComparison),
+            (KeyboardDisplay, KeyboardDisplay, NumericComparison),
+            (KeyboardDisplay, DisplayOnly, PasskeyEntered),
+            (KeyboardOnly, DisplayYesNo, PasskeyEntered),
+            (DisplayOnly, KeyboardDisplay, PasskeyDisplayed),
+            (KeyboardDisplay, KeyboardOnly, PasskeyDisplayed),
+            (KeyboardOnly, KeyboardOnly, PasskeyEnteredOnBoth),
+        ];
+
+        for (initiator, responder, method) in &cases {
+            assert_eq!(
+                PairingMethod::select(*initiator, *responder),
+                *method,
+                "{:?} initiating with {:?}",
+                initiator,
+                responder
+            );
+        }
+    }
+}
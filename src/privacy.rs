@@ -0,0 +1,217 @@
+//! Resolving private addresses back to the devices that sent them.
+//!
+//! Many devices advertise from a Resolvable Private Address which changes
+//! every few minutes. Given the Identity Resolving Key (IRK) shared while
+//! bonding, an [IrkStore] can tell which device sent each address.
+
+use std::sync::{Arc, RwLock};
+
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockCipher, NewBlockCipher};
+use aes::Aes128;
+
+use crate::BluetoothAddress;
+
+/// Kind of a Bluetooth device address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    /// Address assigned to the device by the manufacturer.
+    Public,
+    /// Random address that stays the same at least until the device restarts.
+    RandomStatic,
+    /// Random address which can be resolved with an IRK.
+    ResolvablePrivate,
+    /// Random address which cannot be resolved.
+    NonResolvablePrivate,
+}
+
+impl AddressType {
+    /// Check if this is any of the kinds of random address.
+    pub fn is_random(&self) -> bool {
+        *self != AddressType::Public
+    }
+}
+
+impl BluetoothAddress {
+    /// Classify this address, given if it was sent as a random address.
+    ///
+    /// Random addresses are told apart by their two most significant bits.
+    /// The reserved pattern is treated as non-resolvable.
+    pub fn address_type(&self, random: bool) -> AddressType {
+        if !random {
+            return AddressType::Public;
+        }
+
+        match self.0 >> 46 & 0b11 {
+            0b11 => AddressType::RandomStatic,
+            0b01 => AddressType::ResolvablePrivate,
+            _ => AddressType::NonResolvablePrivate,
+        }
+    }
+
+    /// Check if this address is in the format of a Resolvable Private Address.
+    pub fn is_resolvable(&self) -> bool {
+        self.address_type(true) == AddressType::ResolvablePrivate
+    }
+}
+
+/// An Identity Resolving Key.
+///
+/// Bytes are stored most significant first, as written in the specification.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Irk(pub [u8; 16]);
+
+impl Irk {
+    /// Create a Resolvable Private Address from the given random part.
+    ///
+    /// Only the lower 22 bits of `prand` are used, the rest are set to mark
+    /// the address as resolvable.
+    pub fn resolvable_address(&self, prand: u32) -> BluetoothAddress {
+        let prand = (prand & 0x3F_FFFF) | 0x40_0000;
+        let hash = ah(&self.0, prand);
+
+        BluetoothAddress(u64::from(prand) << 24 | u64::from(hash))
+    }
+
+    /// Check if the address was created from this key.
+    pub fn resolves(&self, address: BluetoothAddress) -> bool {
+        if !address.is_resolvable() {
+            return false;
+        }
+
+        let prand = (address.0 >> 24) as u32 & 0xFF_FFFF;
+        let hash = address.0 as u32 & 0xFF_FFFF;
+
+        ah(&self.0, prand) == hash
+    }
+}
+
+impl std::fmt::Debug for Irk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Irk").field(&"..").finish()
+    }
+}
+
+/// The random address hash function `ah` from the Security Manager
+/// specification.
+///
+/// Only the lower 24 bits of `r` are used, and the result is 24 bits.
+pub fn ah(k: &[u8; 16], r: u32) -> u32 {
+    let cipher = Aes128::new(GenericArray::from_slice(k));
+
+    let mut block = GenericArray::clone_from_slice(&[0u8; 16]);
+    block[13..].copy_from_slice(&r.to_be_bytes()[1..]);
+    cipher.encrypt_block(&mut block);
+
+    u32::from_be_bytes([0, block[13], block[14], block[15]])
+}
+
+/// Known IRKs and the identity addresses of the devices they belong to.
+///
+/// Cloning a store gives another handle to the same keys, so keys can be
+/// added while a watcher is using it.
+#[derive(Clone, Default)]
+pub struct IrkStore {
+    keys: Arc<RwLock<Vec<(Irk, BluetoothAddress)>>>,
+}
+
+impl IrkStore {
+    /// Create a store without any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the IRK for the device with the given identity address, replacing
+    /// any key already known for it.
+    pub fn insert(&self, identity: BluetoothAddress, irk: Irk) {
+        let mut keys = self.keys.write().unwrap();
+
+        keys.retain(|(_irk, address)| *address != identity);
+        keys.push((irk, identity));
+    }
+
+    /// Remove the IRK for the device with the given identity address.
+    pub fn remove(&self, identity: BluetoothAddress) {
+        self.keys
+            .write()
+            .unwrap()
+            .retain(|(_irk, address)| *address != identity);
+    }
+
+    /// Find the identity address of the device which sent the given address,
+    /// if it was created from a known key.
+    pub fn resolve(&self, address: BluetoothAddress) -> Option<BluetoothAddress> {
+        if !address.is_resolvable() {
+            return None;
+        }
+
+        self.keys
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(irk, _identity)| irk.resolves(address))
+            .map(|(_irk, identity)| *identity)
+    }
+}
+
+impl std::fmt::Debug for IrkStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IrkStore")
+            .field("identities", &self.keys.read().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ah, AddressType, Irk, IrkStore};
+    use crate::BluetoothAddress;
+
+    // Sample data from the Core Specification, Vol 3, Part H, D.7.
+    const IRK: Irk = Irk([
+        0xEC, 0x02, 0x34, 0xA3, 0x57, 0xC8, 0xAD, 0x05, 0x34, 0x10, 0x10, 0xA6, 0x0A, 0x39, 0x7D,
+        0x9B,
+    ]);
+    const PRAND: u32 = 0x70_8194;
+    const HASH: u32 = 0x0D_FBAA;
+
+    #[test]
+    fn test_ah() {
+        assert_eq!(ah(&IRK.0, PRAND), HASH);
+
+        let address = IRK.resolvable_address(PRAND);
+        assert_eq!(address, BluetoothAddress(0x7081_940D_FBAA));
+        assert_eq!(address.address_type(true), AddressType::ResolvablePrivate);
+        assert!(IRK.resolves(address));
+        assert!(!IRK.resolves(BluetoothAddress(0x7081_940D_FBAB)));
+    }
+
+    #[test]
+    fn test_address_type() {
+        let address = BluetoothAddress(0xC8FD_1912_7FCD);
+        assert_eq!(address.address_type(false), AddressType::Public);
+        assert_eq!(address.address_type(true), AddressType::RandomStatic);
+        assert_eq!(
+            BluetoothAddress(0x08FD_1912_7FCD).address_type(true),
+            AddressType::NonResolvablePrivate
+        );
+    }
+
+    #[test]
+    fn test_irk_store() {
+        let identity = BluetoothAddress(0xC8FD_1912_7FCD);
+        let store = IrkStore::new();
+        assert_eq!(store.resolve(IRK.resolvable_address(PRAND)), None);
+
+        store.insert(identity, IRK);
+        assert_eq!(store.resolve(IRK.resolvable_address(PRAND)), Some(identity));
+        assert_eq!(
+            store.resolve(IRK.resolvable_address(0x12_3456)),
+            Some(identity)
+        );
+        assert_eq!(store.resolve(identity), None);
+
+        store.remove(identity);
+        assert_eq!(store.resolve(IRK.resolvable_address(PRAND)), None);
+    }
+}
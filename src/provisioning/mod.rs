@@ -0,0 +1,683 @@
+//! Wi-Fi provisioning for ESP32 devices using Espressif's protocomm over BLE.
+//!
+//! The provisioning service has one characteristic per endpoint, named by its
+//! Characteristic User Description. Requests are protobuf messages written to
+//! an endpoint, and the response is read back from it. Every endpoint except
+//! `proto-ver` is protected by the session established on `prov-session`.
+//!
+//! ```no_run
+//! use wible::provisioning::{Provisioner, Security};
+//! # fn provision(device: &wible::Device) -> Result<(), Box<dyn std::error::Error>> {
+//! let service = device.service(wible::provisioning::SERVICE)?.expect("not provisioning");
+//! let security = Security::Security1 {
+//!     proof_of_possession: Some("abcd1234".to_string()),
+//! };
+//!
+//! let mut provisioner = Provisioner::new(&service, &security)?;
+//! provisioner.set_wifi_config(b"home", b"hunter22")?;
+//! provisioner.apply_wifi_config()?;
+//!
+//! let connection = provisioner.wait_for_connection(std::time::Duration::from_secs(30))?;
+//! println!("connected as {}", connection.ip_address);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use crate::gatt::{DescriptorValue, USER_DESCRIPTION};
+use crate::{Error, RemoteCharacteristic, RemoteDescriptor, RemoteService, Result, Uuid};
+
+mod proto;
+mod security;
+
+use proto::{Encoder, Fields};
+pub use security::Security;
+use security::Session;
+
+/// UUID of the provisioning service advertised by ESP-IDF.
+pub const SERVICE: Uuid = Uuid(0x021A_9004_0382_4AEA_BFF4_6B3F_1C5A_DFB4);
+
+/// Endpoint used to establish the session.
+pub const SESSION_ENDPOINT: &str = "prov-session";
+/// Endpoint used to configure the Wi-Fi station.
+pub const CONFIG_ENDPOINT: &str = "prov-config";
+/// Endpoint used to scan for access points.
+pub const SCAN_ENDPOINT: &str = "prov-scan";
+/// Endpoint describing the protocol version and capabilities.
+pub const VERSION_ENDPOINT: &str = "proto-ver";
+
+const MSG: u32 = 1;
+const STATUS: u32 = 1;
+
+const CMD_GET_STATUS: u32 = 10;
+const RESP_GET_STATUS: u32 = 11;
+const CMD_SET_CONFIG: u32 = 12;
+const RESP_SET_CONFIG: u32 = 13;
+const CMD_APPLY_CONFIG: u32 = 14;
+const RESP_APPLY_CONFIG: u32 = 15;
+
+const SCAN_STATUS: u32 = 2;
+const CMD_SCAN_START: u32 = 10;
+const RESP_SCAN_START: u32 = 11;
+const CMD_SCAN_STATUS: u32 = 12;
+const RESP_SCAN_STATUS: u32 = 13;
+const CMD_SCAN_RESULT: u32 = 14;
+const RESP_SCAN_RESULT: u32 = 15;
+
+/// Number of scan results requested at once, which keeps responses within
+/// the size the device will send.
+const SCAN_RESULTS_PER_REQUEST: u64 = 4;
+
+/// How often the Wi-Fi status is checked while waiting for a connection.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Errors reported while provisioning a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProvisioningError {
+    /// Device returned a status other than success.
+    Status(u32),
+    /// Device has no endpoint with this name.
+    MissingEndpoint(String),
+    /// Device did not prove it knows the session key, so may not have the
+    /// same proof of possession.
+    VerificationFailed,
+    /// Device could not connect to the configured access point.
+    ConnectionFailed(FailReason),
+}
+
+impl std::fmt::Display for ProvisioningError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProvisioningError::Status(status) => {
+                let name = match status {
+                    1 => "invalid security scheme",
+                    2 => "invalid protocol",
+                    3 => "too many sessions",
+                    4 => "invalid argument",
+                    5 => "internal error",
+                    6 => "crypto error",
+                    7 => "invalid session",
+                    _ => "unknown error",
+                };
+                write!(f, "device returned {} ({})", name, status)
+            }
+            ProvisioningError::MissingEndpoint(name) => {
+                write!(f, "device has no endpoint {}", name)
+            }
+            ProvisioningError::VerificationFailed => write!(f, "device failed verification"),
+            ProvisioningError::ConnectionFailed(reason) => {
+                write!(f, "device failed to connect to Wi-Fi: {:?}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProvisioningError {}
+
+/// Reason the device could not connect to an access point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailReason {
+    AuthenticationFailed,
+    NetworkNotFound,
+    Reserved(u8),
+}
+
+impl FailReason {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => FailReason::AuthenticationFailed,
+            1 => FailReason::NetworkNotFound,
+            value => FailReason::Reserved(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            FailReason::AuthenticationFailed => 0,
+            FailReason::NetworkNotFound => 1,
+            FailReason::Reserved(value) => value,
+        }
+    }
+}
+
+/// Authentication used by an access point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMode {
+    Open,
+    Wep,
+    WpaPsk,
+    Wpa2Psk,
+    WpaWpa2Psk,
+    Wpa2Enterprise,
+    Wpa3Psk,
+    Wpa2Wpa3Psk,
+    Reserved(u8),
+}
+
+impl AuthMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => AuthMode::Open,
+            1 => AuthMode::Wep,
+            2 => AuthMode::WpaPsk,
+            3 => AuthMode::Wpa2Psk,
+            4 => AuthMode::WpaWpa2Psk,
+            5 => AuthMode::Wpa2Enterprise,
+            6 => AuthMode::Wpa3Psk,
+            7 => AuthMode::Wpa2Wpa3Psk,
+            value => AuthMode::Reserved(value),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            AuthMode::Open => 0,
+            AuthMode::Wep => 1,
+            AuthMode::WpaPsk => 2,
+            AuthMode::Wpa2Psk => 3,
+            AuthMode::WpaWpa2Psk => 4,
+            AuthMode::Wpa2Enterprise => 5,
+            AuthMode::Wpa3Psk => 6,
+            AuthMode::Wpa2Wpa3Psk => 7,
+            AuthMode::Reserved(value) => value,
+        }
+    }
+}
+
+/// Access point the device is connected to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WifiConnection {
+    pub ip_address: Ipv4Addr,
+    pub auth_mode: AuthMode,
+    pub ssid: Vec<u8>,
+    pub bssid: Vec<u8>,
+    pub channel: u8,
+}
+
+/// State of the device's Wi-Fi station.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WifiStatus {
+    Connected(WifiConnection),
+    Connecting,
+    Disconnected,
+    Failed(FailReason),
+}
+
+impl WifiStatus {
+    fn decode(response: &Fields<'_>) -> Option<Self> {
+        match response.varint(2) {
+            0 => {
+                let connected = response.message(11)?;
+                let ip_address = std::str::from_utf8(connected.bytes(1)).ok()?;
+
+                Some(WifiStatus::Connected(WifiConnection {
+                    ip_address: ip_address.parse().ok()?,
+                    auth_mode: AuthMode::from_u8(connected.varint(2) as u8),
+                    ssid: connected.bytes(3).to_vec(),
+                    bssid: connected.bytes(4).to_vec(),
+                    channel: connected.varint(5) as u8,
+                }))
+            }
+            1 => Some(WifiStatus::Connecting),
+            2 => Some(WifiStatus::Disconnected),
+            3 => Some(WifiStatus::Failed(FailReason::from_u8(
+                response.varint(10) as u8
+            ))),
+            _ => None,
+        }
+    }
+}
+
+/// Access point found by a scan.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanResult {
+    pub ssid: Vec<u8>,
+    pub bssid: Vec<u8>,
+    pub channel: u8,
+    pub rssi: i8,
+    pub auth_mode: AuthMode,
+}
+
+/// Client for provisioning a device over an established session.
+pub struct Provisioner<C: RemoteCharacteristic> {
+    endpoints: HashMap<String, C>,
+    session: Session,
+}
+
+impl<C: RemoteCharacteristic> Provisioner<C> {
+    /// Find the endpoints of the provisioning service and establish a
+    /// session.
+    pub fn new<S: RemoteService<Characteristic = C>>(
+        service: &S,
+        security: &Security,
+    ) -> Result<Self> {
+        let mut endpoints = HashMap::new();
+        for characteristic in service.characteristics()? {
+            let descriptor = match characteristic.descriptor(USER_DESCRIPTION)? {
+                Some(descriptor) => descriptor,
+                None => continue,
+            };
+
+            if let DescriptorValue::UserDescription(name) = descriptor.read_decoded()? {
+                endpoints.insert(name, characteristic);
+            }
+        }
+
+        let mut provisioner = Self {
+            endpoints,
+            session: Session::Plain,
+        };
+        provisioner.session = Session::establish(security, |request| {
+            provisioner.exchange_plain(SESSION_ENDPOINT, request)
+        })?;
+
+        Ok(provisioner)
+    }
+
+    /// Read the protocol version and capabilities, which are JSON such as
+    /// `{"prov":{"ver":"v1.1","cap":["wifi_scan"]}}`.
+    pub fn version(&self) -> Result<String> {
+        let response = self.exchange_plain(VERSION_ENDPOINT, b"---")?;
+
+        String::from_utf8(response).map_err(|_| Error::InvalidValue)
+    }
+
+    /// Read the state of the device's Wi-Fi station.
+    pub fn wifi_status(&mut self) -> Result<WifiStatus> {
+        let response = self.config_request(0, CMD_GET_STATUS, Encoder::new(), RESP_GET_STATUS)?;
+        let response = Fields::decode(&response).ok_or(Error::InvalidValue)?;
+
+        WifiStatus::decode(&response).ok_or(Error::InvalidValue)
+    }
+
+    /// Send the credentials of the access point to connect to.
+    pub fn set_wifi_config(&mut self, ssid: &[u8], passphrase: &[u8]) -> Result<()> {
+        let command = Encoder::new().bytes(1, ssid).bytes(2, passphrase);
+        self.config_request(2, CMD_SET_CONFIG, command, RESP_SET_CONFIG)
+            .map(drop)
+    }
+
+    /// Start connecting to the access point previously configured.
+    pub fn apply_wifi_config(&mut self) -> Result<()> {
+        self.config_request(4, CMD_APPLY_CONFIG, Encoder::new(), RESP_APPLY_CONFIG)
+            .map(drop)
+    }
+
+    /// Wait until the device connects to the configured access point.
+    pub fn wait_for_connection(&mut self, timeout: Duration) -> Result<WifiConnection> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.wifi_status()? {
+                WifiStatus::Connected(connection) => return Ok(connection),
+                WifiStatus::Failed(reason) => {
+                    return Err(ProvisioningError::ConnectionFailed(reason).into())
+                }
+                WifiStatus::Connecting | WifiStatus::Disconnected => {}
+            }
+
+            if Instant::now() + POLL_INTERVAL > deadline {
+                return Err(Error::Timeout);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Scan for access points the device can see.
+    pub fn scan(&mut self) -> Result<Vec<ScanResult>> {
+        let command = Encoder::new()
+            .varint(1, 1)
+            .varint(2, 0)
+            .varint(3, 5)
+            .varint(4, 120);
+        self.scan_request(0, CMD_SCAN_START, command, RESP_SCAN_START)?;
+
+        let response = self.scan_request(2, CMD_SCAN_STATUS, Encoder::new(), RESP_SCAN_STATUS)?;
+        let response = Fields::decode(&response).ok_or(Error::InvalidValue)?;
+        if !response.bool(1) {
+            return Err(Error::Timeout);
+        }
+
+        let count = response.varint(2);
+        let mut results = Vec::new();
+        while (results.len() as u64) < count {
+            let start = results.len() as u64;
+            let command = Encoder::new()
+                .varint(1, start)
+                .varint(2, (count - start).min(SCAN_RESULTS_PER_REQUEST));
+            let response = self.scan_request(4, CMD_SCAN_RESULT, command, RESP_SCAN_RESULT)?;
+            let response = Fields::decode(&response).ok_or(Error::InvalidValue)?;
+
+            let entries = response.messages(1).ok_or(Error::InvalidValue)?;
+            if entries.is_empty() {
+                return Err(Error::InvalidValue);
+            }
+
+            results.extend(entries.iter().map(|entry| ScanResult {
+                ssid: entry.bytes(1).to_vec(),
+                channel: entry.varint(2) as u8,
+                rssi: entry.int32(3) as i8,
+                bssid: entry.bytes(4).to_vec(),
+                auth_mode: AuthMode::from_u8(entry.varint(5) as u8),
+            }));
+        }
+
+        Ok(results)
+    }
+
+    /// Send a request to an endpoint through the session, such as an
+    /// application's custom endpoint, and return the response.
+    pub fn exchange(&mut self, endpoint: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let mut request = data.to_vec();
+        self.session.apply(&mut request);
+
+        let mut response = self.exchange_plain(endpoint, &request)?;
+        self.session.apply(&mut response);
+
+        Ok(response)
+    }
+
+    fn exchange_plain(&self, endpoint: &str, data: &[u8]) -> Result<Vec<u8>> {
+        let characteristic = self
+            .endpoints
+            .get(endpoint)
+            .ok_or_else(|| ProvisioningError::MissingEndpoint(endpoint.to_string()))?;
+
+        characteristic.write_value(data)?;
+        characteristic.read_value()
+    }
+
+    /// Send a `WiFiConfigPayload` and return its response, checking its
+    /// status.
+    fn config_request(
+        &mut self,
+        msg: u64,
+        field: u32,
+        command: Encoder,
+        response_field: u32,
+    ) -> Result<Vec<u8>> {
+        let request = Encoder::new()
+            .varint(MSG, msg)
+            .message(field, command)
+            .finish();
+        let response = self.exchange(CONFIG_ENDPOINT, &request)?;
+        let payload = Fields::decode(&response).ok_or(Error::InvalidValue)?;
+        let response = payload.bytes(response_field);
+
+        match Fields::decode(response)
+            .ok_or(Error::InvalidValue)?
+            .varint(STATUS)
+        {
+            0 => Ok(response.to_vec()),
+            status => Err(ProvisioningError::Status(status as u32).into()),
+        }
+    }
+
+    /// Send a `WiFiScanPayload`, which carries its status outside the
+    /// response, and return its response.
+    fn scan_request(
+        &mut self,
+        msg: u64,
+        field: u32,
+        command: Encoder,
+        response_field: u32,
+    ) -> Result<Vec<u8>> {
+        let request = Encoder::new()
+            .varint(MSG, msg)
+            .message(field, command)
+            .finish();
+        let response = self.exchange(SCAN_ENDPOINT, &request)?;
+        let payload = Fields::decode(&response).ok_or(Error::InvalidValue)?;
+
+        match payload.varint(SCAN_STATUS) {
+            0 => Ok(payload.bytes(response_field).to_vec()),
+            status => Err(ProvisioningError::Status(status as u32).into()),
+        }
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Provisioner<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Provisioner")
+            .field("endpoints", &self.endpoints)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::proto::{Encoder, Fields};
+    use super::security::tests::Peer;
+    use super::{
+        AuthMode, FailReason, Provisioner, ProvisioningError, Security, WifiStatus,
+        CONFIG_ENDPOINT, SCAN_ENDPOINT, SERVICE, SESSION_ENDPOINT, VERSION_ENDPOINT,
+    };
+    use crate::gatt::{
+        GattServer, LocalCharacteristic, LocalDescriptor, LocalService, USER_DESCRIPTION,
+    };
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error, Uuid};
+
+    const ACCESS_POINTS: u64 = 6;
+    const VERSION: &[u8] = br#"{"prov":{"ver":"v1.1","cap":["wifi_scan"]}}"#;
+
+    /// State of a simulated ESP32 running the provisioning manager.
+    struct Target {
+        peer: Peer,
+        responses: HashMap<&'static str, Vec<u8>>,
+        ssid: Vec<u8>,
+        passphrase: Vec<u8>,
+        applied: bool,
+        polls: usize,
+    }
+
+    impl Target {
+        fn new(security: Security) -> Self {
+            Self {
+                peer: Peer::new(security),
+                responses: HashMap::new(),
+                ssid: Vec::new(),
+                passphrase: Vec::new(),
+                applied: false,
+                polls: 0,
+            }
+        }
+
+        fn write(&mut self, endpoint: &'static str, data: &[u8]) {
+            let response = match endpoint {
+                SESSION_ENDPOINT => self.peer.session(data),
+                VERSION_ENDPOINT => VERSION.to_vec(),
+                _ => {
+                    assert!(self.peer.established);
+                    let mut request = data.to_vec();
+                    self.peer.apply(&mut request);
+
+                    let mut response = if endpoint == CONFIG_ENDPOINT {
+                        self.config(&request)
+                    } else {
+                        self.scan(&request)
+                    };
+                    self.peer.apply(&mut response);
+                    response
+                }
+            };
+
+            self.responses.insert(endpoint, response);
+        }
+
+        fn config(&mut self, request: &[u8]) -> Vec<u8> {
+            let request = Fields::decode(request).unwrap();
+            let (msg, field, response) = match request.varint(1) {
+                0 => {
+                    let response = Encoder::new().varint(1, 0);
+                    let response = if !self.applied {
+                        response.varint(2, 2)
+                    } else if self.polls == 0 {
+                        self.polls += 1;
+                        response.varint(2, 1)
+                    } else if self.passphrase != b"hunter22" {
+                        response.varint(2, 3).varint(10, 0)
+                    } else {
+                        let connected = Encoder::new()
+                            .bytes(1, b"192.168.1.42")
+                            .varint(2, 3)
+                            .bytes(3, &self.ssid)
+                            .bytes(4, &[0x24, 0x0A, 0xC4, 0x01, 0x02, 0x03])
+                            .varint(5, 6);
+                        response.varint(2, 0).message(11, connected)
+                    };
+                    (1, 11, response)
+                }
+                2 => {
+                    let command = request.message(12).unwrap();
+                    self.ssid = command.bytes(1).to_vec();
+                    self.passphrase = command.bytes(2).to_vec();
+                    self.applied = false;
+                    (3, 13, Encoder::new().varint(1, 0))
+                }
+                4 => {
+                    self.applied = true;
+                    self.polls = 0;
+                    (5, 15, Encoder::new().varint(1, 0))
+                }
+                msg => panic!("unexpected config message {}", msg),
+            };
+
+            Encoder::new()
+                .varint(1, msg)
+                .message(field, response)
+                .finish()
+        }
+
+        fn scan(&mut self, request: &[u8]) -> Vec<u8> {
+            let request = Fields::decode(request).unwrap();
+            let (msg, field, response) = match request.varint(1) {
+                0 => {
+                    assert!(request.message(10).unwrap().bool(1));
+                    (1, 11, Encoder::new())
+                }
+                2 => (3, 13, Encoder::new().varint(1, 1).varint(2, ACCESS_POINTS)),
+                4 => {
+                    let command = request.message(14).unwrap();
+                    let start = command.varint(1);
+                    let count = command.varint(2);
+                    assert!(count <= 4 && start + count <= ACCESS_POINTS);
+
+                    let mut response = Encoder::new();
+                    for index in start..start + count {
+                        let entry = Encoder::new()
+                            .bytes(1, format!("network {}", index).as_bytes())
+                            .varint(2, index + 1)
+                            .int32(3, -40 - index as i32)
+                            .bytes(4, &[0x24, 0x0A, 0xC4, 0, 0, index as u8])
+                            .varint(5, 3);
+                        response = response.message(1, entry);
+                    }
+                    (5, 15, response)
+                }
+                msg => panic!("unexpected scan message {}", msg),
+            };
+
+            Encoder::new()
+                .varint(1, msg)
+                .varint(2, 0)
+                .message(field, response)
+                .finish()
+        }
+    }
+
+    fn provisioning_server(target: Arc<Mutex<Target>>) -> GattServer {
+        let endpoints = [
+            SCAN_ENDPOINT,
+            SESSION_ENDPOINT,
+            CONFIG_ENDPOINT,
+            VERSION_ENDPOINT,
+        ];
+
+        let mut service = LocalService::new(SERVICE);
+        for (index, &endpoint) in endpoints.iter().enumerate() {
+            let uuid = Uuid(0x021A_FF50_0382_4AEA_BFF4_6B3F_1C5A_DFB4 + ((index as u128) << 96));
+            let writer = target.clone();
+            let reader = target.clone();
+            service = service.with_characteristic(
+                LocalCharacteristic::new(
+                    uuid,
+                    CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+                )
+                .with_descriptor(LocalDescriptor::new(USER_DESCRIPTION, endpoint.as_bytes()))
+                .on_write(move |_, data| {
+                    writer.lock().unwrap().write(endpoint, data);
+                    Ok(())
+                })
+                .on_read(move |_| {
+                    Ok(reader
+                        .lock()
+                        .unwrap()
+                        .responses
+                        .remove(endpoint)
+                        .unwrap_or_default())
+                }),
+            );
+        }
+
+        let server = GattServer::new();
+        server.add_service(service);
+        server
+    }
+
+    #[test]
+    fn test_simulated_device() {
+        let security = Security::Security1 {
+            proof_of_possession: Some("abcd1234".to_string()),
+        };
+        let target = Arc::new(Mutex::new(Target::new(security.clone())));
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x240A_C401_0203);
+        sim.add_peripheral(address, provisioning_server(target.clone()));
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let mut provisioner = Provisioner::new(&service, &security).unwrap();
+        assert!(target.lock().unwrap().peer.established);
+
+        assert_eq!(provisioner.version().unwrap().as_bytes(), VERSION);
+        assert_eq!(provisioner.wifi_status().unwrap(), WifiStatus::Disconnected);
+
+        let results = provisioner.scan().unwrap();
+        assert_eq!(results.len(), ACCESS_POINTS as usize);
+        assert_eq!(results[5].ssid, b"network 5");
+        assert_eq!(results[5].channel, 6);
+        assert_eq!(results[5].rssi, -45);
+        assert_eq!(results[5].auth_mode, AuthMode::Wpa2Psk);
+
+        provisioner.set_wifi_config(b"home", b"wrong").unwrap();
+        provisioner.apply_wifi_config().unwrap();
+        assert!(matches!(
+            provisioner.wait_for_connection(Duration::from_secs(5)),
+            Err(Error::Provisioning(ProvisioningError::ConnectionFailed(
+                FailReason::AuthenticationFailed
+            )))
+        ));
+
+        provisioner.set_wifi_config(b"home", b"hunter22").unwrap();
+        provisioner.apply_wifi_config().unwrap();
+        let connection = provisioner
+            .wait_for_connection(Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(connection.ip_address, Ipv4Addr::new(192, 168, 1, 42));
+        assert_eq!(connection.ssid, b"home");
+        assert_eq!(connection.channel, 6);
+
+        assert!(matches!(
+            provisioner.exchange("custom-data", b"hello"),
+            Err(Error::Provisioning(ProvisioningError::MissingEndpoint(name))) if name == "custom-data"
+        ));
+    }
+}
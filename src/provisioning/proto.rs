@@ -0,0 +1,219 @@
+//! The subset of the protobuf wire format used by protocomm messages.
+
+use std::convert::TryInto;
+
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+/// Builds a message one field at a time.
+#[derive(Default)]
+pub(crate) struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    fn raw_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u64) {
+        self.raw_varint(u64::from(field) << 3 | wire_type);
+    }
+
+    /// Add an integer, enum, or bool field.
+    pub(crate) fn varint(mut self, field: u32, value: u64) -> Self {
+        self.key(field, VARINT);
+        self.raw_varint(value);
+        self
+    }
+
+    /// Add a signed 32-bit integer field, which negative values are sign
+    /// extended to 64 bits for.
+    #[cfg(test)]
+    pub(crate) fn int32(self, field: u32, value: i32) -> Self {
+        self.varint(field, i64::from(value) as u64)
+    }
+
+    /// Add a bytes or string field.
+    pub(crate) fn bytes(mut self, field: u32, value: &[u8]) -> Self {
+        self.key(field, LENGTH_DELIMITED);
+        self.raw_varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    /// Add an embedded message field.
+    pub(crate) fn message(self, field: u32, message: Encoder) -> Self {
+        self.bytes(field, &message.buf)
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Value of a decoded field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed(u64),
+}
+
+/// Fields of a decoded message.
+///
+/// Missing fields read as their default values, as in proto3.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Fields<'a> {
+    fields: Vec<(u32, Value<'a>)>,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn decode(data: &'a [u8]) -> Option<Self> {
+        let mut fields = Vec::new();
+        let mut data = data;
+
+        while !data.is_empty() {
+            let key = read_varint(&mut data)?;
+            let field = (key >> 3) as u32;
+            let value = match key & 0x07 {
+                VARINT => Value::Varint(read_varint(&mut data)?),
+                FIXED64 => Value::Fixed(u64::from_le_bytes(take(&mut data, 8)?.try_into().ok()?)),
+                LENGTH_DELIMITED => {
+                    let len = read_varint(&mut data)? as usize;
+                    Value::Bytes(take(&mut data, len)?)
+                }
+                FIXED32 => Value::Fixed(u64::from(u32::from_le_bytes(
+                    take(&mut data, 4)?.try_into().ok()?,
+                ))),
+                _ => return None,
+            };
+
+            fields.push((field, value));
+        }
+
+        Some(Self { fields })
+    }
+
+    /// Last value of a field, which takes precedence in protobuf.
+    fn get(&self, field: u32) -> Option<Value<'a>> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(number, _)| *number == field)
+            .map(|(_, value)| *value)
+    }
+
+    pub(crate) fn varint(&self, field: u32) -> u64 {
+        match self.get(field) {
+            Some(Value::Varint(value)) => value,
+            _ => 0,
+        }
+    }
+
+    pub(crate) fn int32(&self, field: u32) -> i32 {
+        self.varint(field) as i32
+    }
+
+    pub(crate) fn bool(&self, field: u32) -> bool {
+        self.varint(field) != 0
+    }
+
+    pub(crate) fn bytes(&self, field: u32) -> &'a [u8] {
+        match self.get(field) {
+            Some(Value::Bytes(value)) => value,
+            _ => &[],
+        }
+    }
+
+    /// Embedded message, if present.
+    pub(crate) fn message(&self, field: u32) -> Option<Fields<'a>> {
+        match self.get(field)? {
+            Value::Bytes(value) => Fields::decode(value),
+            _ => None,
+        }
+    }
+
+    /// Every embedded message of a repeated field.
+    pub(crate) fn messages(&self, field: u32) -> Option<Vec<Fields<'a>>> {
+        self.fields
+            .iter()
+            .filter(|(number, _)| *number == field)
+            .map(|(_, value)| match value {
+                Value::Bytes(value) => Fields::decode(value),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = data.split_first()?;
+        *data = rest;
+
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        return None;
+    }
+
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encoder, Fields};
+
+    #[test]
+    fn test_round_trip() {
+        let data = Encoder::new()
+            .varint(1, 300)
+            .int32(2, -1)
+            .bytes(3, b"ssid")
+            .message(10, Encoder::new().varint(1, 1))
+            .message(11, Encoder::new().bytes(1, b"a"))
+            .message(11, Encoder::new().bytes(1, b"b"))
+            .finish();
+        assert_eq!(&data[..3], &[0x08, 0xAC, 0x02]);
+
+        let fields = Fields::decode(&data).unwrap();
+        assert_eq!(fields.varint(1), 300);
+        assert_eq!(fields.int32(2), -1);
+        assert_eq!(fields.bytes(3), b"ssid");
+        assert!(fields.message(10).unwrap().bool(1));
+        assert_eq!(fields.varint(4), 0);
+        assert_eq!(fields.bytes(4), b"");
+        assert!(fields.message(12).is_none());
+
+        let entries = fields.messages(11).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].bytes(1), b"b");
+
+        assert!(Fields::decode(&[0x1A, 0x05, 0x00]).is_none());
+        assert!(Fields::decode(&[0x08, 0x80]).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+//! Session establishment for the `prov-session` endpoint.
+
+use aes::cipher::generic_array::GenericArray;
+use aes::Aes256;
+use ctr::cipher::stream::{NewStreamCipher, SyncStreamCipher};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use super::proto::{Encoder, Fields};
+use super::ProvisioningError;
+use crate::{Error, Result};
+
+type Aes256Ctr = ctr::Ctr128<Aes256>;
+
+const SEC_VER: u32 = 2;
+const SEC0: u32 = 10;
+const SEC1: u32 = 11;
+
+const MSG: u32 = 1;
+const STATUS: u32 = 1;
+
+const SEC0_COMMAND: u32 = 20;
+const SEC0_RESPONSE: u32 = 21;
+
+const SEC1_COMMAND0: u32 = 20;
+const SEC1_RESPONSE0: u32 = 21;
+const SEC1_COMMAND1: u32 = 22;
+const SEC1_RESPONSE1: u32 = 23;
+
+const CLIENT_PUBKEY: u32 = 1;
+const DEVICE_PUBKEY: u32 = 2;
+const DEVICE_RANDOM: u32 = 3;
+const CLIENT_VERIFY_DATA: u32 = 2;
+const DEVICE_VERIFY_DATA: u32 = 3;
+
+/// Security scheme used to protect provisioning messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Security {
+    /// Messages are sent in plain text.
+    Security0,
+    /// Messages are encrypted with AES-CTR, using a key agreed through X25519
+    /// and optionally mixed with a proof of possession known to the device.
+    Security1 { proof_of_possession: Option<String> },
+}
+
+/// Established session, which encrypts and decrypts every message after the
+/// handshake with one continuous keystream.
+pub(crate) enum Session {
+    Plain,
+    Encrypted(Box<Aes256Ctr>),
+}
+
+impl Session {
+    /// Run the handshake, sending each request with `exchange` and receiving
+    /// its response.
+    pub(crate) fn establish(
+        security: &Security,
+        exchange: impl FnMut(&[u8]) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        Self::establish_with(security, StaticSecret::new(rand_core::OsRng), exchange)
+    }
+
+    /// Run the handshake with a given X25519 secret, which is only used by
+    /// Security1.
+    fn establish_with(
+        security: &Security,
+        secret: StaticSecret,
+        mut exchange: impl FnMut(&[u8]) -> Result<Vec<u8>>,
+    ) -> Result<Self> {
+        match security {
+            Security::Security0 => {
+                let request = Encoder::new()
+                    .varint(SEC_VER, 0)
+                    .message(
+                        SEC0,
+                        Encoder::new()
+                            .varint(MSG, 0)
+                            .message(SEC0_COMMAND, Encoder::new()),
+                    )
+                    .finish();
+                let response = exchange(&request)?;
+                let response = decode(&response)?
+                    .message(SEC0)
+                    .and_then(|sec0| sec0.message(SEC0_RESPONSE))
+                    .ok_or(Error::InvalidValue)?;
+                check_status(&response)?;
+
+                Ok(Session::Plain)
+            }
+            Security::Security1 {
+                proof_of_possession,
+            } => {
+                let client_pubkey = PublicKey::from(&secret);
+
+                let request = sec1_request(
+                    0,
+                    SEC1_COMMAND0,
+                    Encoder::new().bytes(CLIENT_PUBKEY, client_pubkey.as_bytes()),
+                );
+                let response = exchange(&request)?;
+                let fields = decode(&response)?;
+                let response = sec1_response(&fields, SEC1_RESPONSE0)?;
+                check_status(&response)?;
+
+                let device_pubkey = public_key(response.bytes(DEVICE_PUBKEY))?;
+                let device_random = response.bytes(DEVICE_RANDOM);
+                if device_random.len() != 16 {
+                    return Err(Error::InvalidValue);
+                }
+
+                let shared = secret.diffie_hellman(&device_pubkey);
+                let key = session_key(shared.as_bytes(), proof_of_possession.as_deref());
+                let mut cipher = Aes256Ctr::new(
+                    GenericArray::from_slice(&key),
+                    GenericArray::from_slice(device_random),
+                );
+
+                let mut client_verify = device_pubkey.as_bytes().to_vec();
+                cipher.apply_keystream(&mut client_verify);
+                let request = sec1_request(
+                    2,
+                    SEC1_COMMAND1,
+                    Encoder::new().bytes(CLIENT_VERIFY_DATA, &client_verify),
+                );
+                let response = exchange(&request)?;
+                let fields = decode(&response)?;
+                let response = sec1_response(&fields, SEC1_RESPONSE1)?;
+                check_status(&response)?;
+
+                let mut device_verify = response.bytes(DEVICE_VERIFY_DATA).to_vec();
+                cipher.apply_keystream(&mut device_verify);
+                if device_verify != client_pubkey.as_bytes() {
+                    return Err(ProvisioningError::VerificationFailed.into());
+                }
+
+                Ok(Session::Encrypted(Box::new(cipher)))
+            }
+        }
+    }
+
+    /// Encrypt a request or decrypt a response, which are the same operation
+    /// in CTR mode.
+    pub(crate) fn apply(&mut self, data: &mut [u8]) {
+        if let Session::Encrypted(cipher) = self {
+            cipher.apply_keystream(data);
+        }
+    }
+}
+
+fn decode(data: &[u8]) -> Result<Fields<'_>> {
+    Fields::decode(data).ok_or(Error::InvalidValue)
+}
+
+fn sec1_request(msg: u64, field: u32, payload: Encoder) -> Vec<u8> {
+    Encoder::new()
+        .varint(SEC_VER, 1)
+        .message(
+            SEC1,
+            Encoder::new().varint(MSG, msg).message(field, payload),
+        )
+        .finish()
+}
+
+fn sec1_response<'a>(fields: &Fields<'a>, field: u32) -> Result<Fields<'a>> {
+    fields
+        .message(SEC1)
+        .and_then(|sec1| sec1.message(field))
+        .ok_or(Error::InvalidValue)
+}
+
+fn check_status(response: &Fields<'_>) -> Result<()> {
+    match response.varint(STATUS) {
+        0 => Ok(()),
+        status => Err(ProvisioningError::Status(status as u32).into()),
+    }
+}
+
+fn public_key(data: &[u8]) -> Result<PublicKey> {
+    let mut key = [0; 32];
+    if data.len() != key.len() {
+        return Err(Error::InvalidValue);
+    }
+
+    key.copy_from_slice(data);
+    Ok(PublicKey::from(key))
+}
+
+/// Key for the session, which is the shared secret combined with the hash of
+/// the proof of possession.
+fn session_key(shared: &[u8; 32], proof_of_possession: Option<&str>) -> [u8; 32] {
+    let mut key = *shared;
+    if let Some(pop) = proof_of_possession {
+        for (byte, hash) in key.iter_mut().zip(Sha256::digest(pop.as_bytes())) {
+            *byte ^= hash;
+        }
+    }
+
+    key
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Reference implementation of a device's side of the handshake.
+    pub(crate) struct Peer {
+        security: Security,
+        secret: StaticSecret,
+        client_pubkey: Option<PublicKey>,
+        cipher: Option<Aes256Ctr>,
+        pub(crate) established: bool,
+    }
+
+    impl Peer {
+        const RANDOM: [u8; 16] = [0x5A; 16];
+
+        pub(crate) fn new(security: Security) -> Self {
+            Self {
+                security,
+                secret: StaticSecret::from([0x42; 32]),
+                client_pubkey: None,
+                cipher: None,
+                established: false,
+            }
+        }
+
+        /// Answer a message written to `prov-session`.
+        pub(crate) fn session(&mut self, request: &[u8]) -> Vec<u8> {
+            let request = Fields::decode(request).unwrap();
+            let pop = match &self.security {
+                Security::Security0 => {
+                    assert!(request.message(SEC0).is_some());
+                    self.established = true;
+                    let response = Encoder::new().varint(STATUS, 0);
+                    return Encoder::new()
+                        .varint(SEC_VER, 0)
+                        .message(
+                            SEC0,
+                            Encoder::new()
+                                .varint(MSG, 1)
+                                .message(SEC0_RESPONSE, response),
+                        )
+                        .finish();
+                }
+                Security::Security1 {
+                    proof_of_possession,
+                } => proof_of_possession.clone(),
+            };
+
+            let sec1 = request.message(SEC1).unwrap();
+            if let Some(command) = sec1.message(SEC1_COMMAND0) {
+                let client_pubkey = public_key(command.bytes(CLIENT_PUBKEY)).unwrap();
+                let shared = self.secret.diffie_hellman(&client_pubkey);
+                let key = session_key(shared.as_bytes(), pop.as_deref());
+                self.cipher = Some(Aes256Ctr::new(
+                    GenericArray::from_slice(&key),
+                    GenericArray::from_slice(&Self::RANDOM),
+                ));
+                self.client_pubkey = Some(client_pubkey);
+
+                let response = Encoder::new()
+                    .varint(STATUS, 0)
+                    .bytes(DEVICE_PUBKEY, PublicKey::from(&self.secret).as_bytes())
+                    .bytes(DEVICE_RANDOM, &Self::RANDOM);
+                return sec1_response_message(1, SEC1_RESPONSE0, response);
+            }
+
+            let command = sec1.message(SEC1_COMMAND1).unwrap();
+            let mut verify = command.bytes(CLIENT_VERIFY_DATA).to_vec();
+            self.apply(&mut verify);
+            if verify != PublicKey::from(&self.secret).as_bytes() {
+                // Crypto error, as reported by ESP-IDF.
+                let response = Encoder::new().varint(STATUS, 6);
+                return sec1_response_message(3, SEC1_RESPONSE1, response);
+            }
+
+            let mut device_verify = self.client_pubkey.unwrap().as_bytes().to_vec();
+            self.apply(&mut device_verify);
+            self.established = true;
+
+            let response = Encoder::new()
+                .varint(STATUS, 0)
+                .bytes(DEVICE_VERIFY_DATA, &device_verify);
+            sec1_response_message(3, SEC1_RESPONSE1, response)
+        }
+
+        /// Decrypt a request or encrypt a response.
+        pub(crate) fn apply(&mut self, data: &mut [u8]) {
+            if let Some(cipher) = &mut self.cipher {
+                cipher.apply_keystream(data);
+            }
+        }
+    }
+
+    fn sec1_response_message(msg: u64, field: u32, payload: Encoder) -> Vec<u8> {
+        Encoder::new()
+            .varint(SEC_VER, 1)
+            .message(
+                SEC1,
+                Encoder::new().varint(MSG, msg).message(field, payload),
+            )
+            .finish()
+    }
+
+    #[test]
+    fn test_security0() {
+        let mut peer = Peer::new(Security::Security0);
+        let mut session =
+            Session::establish(&Security::Security0, |request| Ok(peer.session(request))).unwrap();
+        assert!(peer.established);
+
+        let mut data = *b"plain";
+        session.apply(&mut data);
+        assert_eq!(&data, b"plain");
+    }
+
+    #[test]
+    fn test_security1() {
+        let security = Security::Security1 {
+            proof_of_possession: Some("abcd1234".to_string()),
+        };
+        let mut peer = Peer::new(security.clone());
+        let mut session =
+            Session::establish(&security, |request| Ok(peer.session(request))).unwrap();
+        assert!(peer.established);
+
+        let mut request = *b"get status";
+        session.apply(&mut request);
+        assert_ne!(&request, b"get status");
+        peer.apply(&mut request);
+        assert_eq!(&request, b"get status");
+
+        let mut response = *b"connected";
+        peer.apply(&mut response);
+        session.apply(&mut response);
+        assert_eq!(&response, b"connected");
+
+        let mut peer = Peer::new(security);
+        let wrong = Security::Security1 {
+            proof_of_possession: Some("wrong".to_string()),
+        };
+        assert!(matches!(
+            Session::establish(&wrong, |request| Ok(peer.session(request))),
+            Err(Error::Provisioning(ProvisioningError::Status(6)))
+        ));
+        assert!(!peer.established);
+    }
+
+    #[test]
+    fn test_verification_failed() {
+        let security = Security::Security1 {
+            proof_of_possession: None,
+        };
+        let mut peer = Peer::new(security.clone());
+        let result = Session::establish(&security, |request| {
+            let mut response = peer.session(request);
+            if peer.established {
+                // Corrupt the device's verify data, which is the last field.
+                *response.last_mut().unwrap() ^= 1;
+            }
+            Ok(response)
+        });
+        assert!(matches!(
+            result,
+            Err(Error::Provisioning(ProvisioningError::VerificationFailed))
+        ));
+    }
+
+    /// Check the handshake against values computed with the algorithm of
+    /// ESP-IDF's `esp_prov`, so a mistake shared with [Peer] is still caught.
+    #[test]
+    fn test_security1_known_answer() {
+        const CLIENT_PUBKEY_BYTES: [u8; 32] = [
+            0x7B, 0x4E, 0x90, 0x9B, 0xBE, 0x7F, 0xFE, 0x44, 0xC4, 0x65, 0xA2, 0x20, 0x03, 0x7D,
+            0x60, 0x8E, 0xE3, 0x58, 0x97, 0xD3, 0x1E, 0xF9, 0x72, 0xF0, 0x7F, 0x74, 0x89, 0x2C,
+            0xB0, 0xF7, 0x3F, 0x13,
+        ];
+        const DEVICE_PUBKEY_BYTES: [u8; 32] = [
+            0x0F, 0xAA, 0x68, 0x4E, 0xD2, 0x88, 0x67, 0xB9, 0x7F, 0x4A, 0x6A, 0x2D, 0xEE, 0x5D,
+            0xF8, 0xCE, 0x97, 0x4E, 0x76, 0xB7, 0x01, 0x8E, 0x3F, 0x22, 0xA1, 0xC4, 0xCF, 0x26,
+            0x78, 0x57, 0x0F, 0x20,
+        ];
+        const CLIENT_VERIFY: [u8; 32] = [
+            0x3C, 0xF3, 0x1A, 0x86, 0x0A, 0x82, 0x26, 0x62, 0x33, 0xBE, 0x9E, 0xFF, 0xBD, 0xF8,
+            0x5E, 0x60, 0x1A, 0x03, 0x6C, 0x04, 0xAE, 0xA4, 0x51, 0x34, 0xFB, 0xD2, 0x10, 0x1F,
+            0x6E, 0xA6, 0x03, 0x43,
+        ];
+        const DEVICE_VERIFY: [u8; 32] = [
+            0xA5, 0xDA, 0xD0, 0xDE, 0x01, 0x02, 0x79, 0x3C, 0xC7, 0x7D, 0xA7, 0x06, 0x66, 0x4A,
+            0x23, 0x95, 0xB2, 0x43, 0xE0, 0x5A, 0xE6, 0x13, 0x62, 0x43, 0xF6, 0xBB, 0x36, 0x52,
+            0x56, 0xE5, 0xD0, 0xA2,
+        ];
+        const DEVICE_RANDOM_BYTES: [u8; 16] = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D,
+            0x0E, 0x0F,
+        ];
+
+        let security = Security::Security1 {
+            proof_of_possession: Some("abcd1234".to_string()),
+        };
+        let mut client_verify = None;
+        let result =
+            Session::establish_with(&security, StaticSecret::from([0x11; 32]), |request| {
+                let request = Fields::decode(request).unwrap();
+                let sec1 = request.message(SEC1).unwrap();
+                if let Some(command) = sec1.message(SEC1_COMMAND0) {
+                    assert_eq!(command.bytes(CLIENT_PUBKEY), CLIENT_PUBKEY_BYTES);
+                    let response = Encoder::new()
+                        .varint(STATUS, 0)
+                        .bytes(DEVICE_PUBKEY, &DEVICE_PUBKEY_BYTES)
+                        .bytes(DEVICE_RANDOM, &DEVICE_RANDOM_BYTES);
+                    return Ok(sec1_response_message(1, SEC1_RESPONSE0, response));
+                }
+
+                let command = sec1.message(SEC1_COMMAND1).unwrap();
+                client_verify = Some(command.bytes(CLIENT_VERIFY_DATA).to_vec());
+                let response = Encoder::new()
+                    .varint(STATUS, 0)
+                    .bytes(DEVICE_VERIFY_DATA, &DEVICE_VERIFY);
+                Ok(sec1_response_message(3, SEC1_RESPONSE1, response))
+            });
+
+        assert!(result.is_ok());
+        assert_eq!(client_verify.unwrap(), CLIENT_VERIFY);
+    }
+}
@@ -0,0 +1,409 @@
+//! Battery Service, as provided by most battery powered devices.
+
+use super::{Notifications, Reader};
+use crate::gatt::types::SFloat;
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Battery service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x180F);
+/// UUID of the Battery Level characteristic.
+pub const BATTERY_LEVEL: Uuid = Uuid::from_u16(0x2A19);
+/// UUID of the Battery Information characteristic.
+pub const BATTERY_INFORMATION: Uuid = Uuid::from_u16(0x2BEC);
+/// UUID of the Battery Level Status characteristic.
+pub const BATTERY_LEVEL_STATUS: Uuid = Uuid::from_u16(0x2BED);
+
+bitflags::bitflags! {
+    struct LevelStatusFlags: u8 {
+        const IDENTIFIER = 1 << 0;
+        const BATTERY_LEVEL = 1 << 1;
+        const ADDITIONAL_STATUS = 1 << 2;
+    }
+}
+
+bitflags::bitflags! {
+    struct InformationFlags: u16 {
+        const MANUFACTURE_DATE = 1 << 0;
+        const EXPIRATION_DATE = 1 << 1;
+        const DESIGNED_CAPACITY = 1 << 2;
+        const LOW_ENERGY = 1 << 3;
+        const CRITICAL_ENERGY = 1 << 4;
+        const CHEMISTRY = 1 << 5;
+        const NOMINAL_VOLTAGE = 1 << 6;
+        const AGGREGATION_GROUP = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// Reasons a battery is not charging.
+    pub struct ChargingFault: u16 {
+        const BATTERY = 1 << 12;
+        const EXTERNAL_POWER_SOURCE = 1 << 13;
+        const OTHER = 1 << 14;
+    }
+}
+
+bitflags::bitflags! {
+    /// Features of a battery, from the Battery Information characteristic.
+    pub struct BatteryFeatures: u8 {
+        const REPLACEABLE = 1 << 0;
+        const RECHARGEABLE = 1 << 1;
+    }
+}
+
+/// Decode a two bit field where 0 is no, 1 is yes, and anything else is
+/// unknown.
+fn tristate(value: u16) -> Option<bool> {
+    match value & 0b11 {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+/// Whether a battery is charging.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargeState {
+    Unknown,
+    Charging,
+    /// Discharging while the device is in use.
+    DischargingActive,
+    /// Discharging while the device is idle.
+    DischargingInactive,
+}
+
+/// How much charge a battery has left, as judged by the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargeLevel {
+    Unknown,
+    Good,
+    Low,
+    Critical,
+}
+
+/// How a battery is being charged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChargingType {
+    Unknown,
+    ConstantCurrent,
+    ConstantVoltage,
+    Trickle,
+    Float,
+    /// Type not known by this library.
+    Reserved(u8),
+}
+
+/// Power state from the Battery Level Status characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PowerState {
+    pub battery_present: bool,
+    /// If wired external power is connected, or `None` if unknown.
+    pub wired_external_power: Option<bool>,
+    /// If wireless external power is connected, or `None` if unknown.
+    pub wireless_external_power: Option<bool>,
+    pub charge_state: ChargeState,
+    pub charge_level: ChargeLevel,
+    pub charging_type: ChargingType,
+    pub charging_fault: ChargingFault,
+}
+
+impl PowerState {
+    pub fn from_u16(value: u16) -> Self {
+        Self {
+            battery_present: value & 1 != 0,
+            wired_external_power: tristate(value >> 1),
+            wireless_external_power: tristate(value >> 3),
+            charge_state: match (value >> 5) & 0b11 {
+                1 => ChargeState::Charging,
+                2 => ChargeState::DischargingActive,
+                3 => ChargeState::DischargingInactive,
+                _ => ChargeState::Unknown,
+            },
+            charge_level: match (value >> 7) & 0b11 {
+                1 => ChargeLevel::Good,
+                2 => ChargeLevel::Low,
+                3 => ChargeLevel::Critical,
+                _ => ChargeLevel::Unknown,
+            },
+            charging_type: match (value >> 9) & 0b111 {
+                0 => ChargingType::Unknown,
+                1 => ChargingType::ConstantCurrent,
+                2 => ChargingType::ConstantVoltage,
+                3 => ChargingType::Trickle,
+                4 => ChargingType::Float,
+                other => ChargingType::Reserved(other as u8),
+            },
+            charging_fault: ChargingFault::from_bits_truncate(value),
+        }
+    }
+}
+
+/// Contents of the Battery Level Status characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BatteryLevelStatus {
+    pub power_state: PowerState,
+    /// Identifies the battery when a device has more than one.
+    pub identifier: Option<u16>,
+    /// Battery level in percent.
+    pub battery_level: Option<u8>,
+    /// If the battery needs service, or `None` if unknown. Only present if
+    /// the device sent additional status.
+    pub service_required: Option<bool>,
+    pub battery_fault: bool,
+}
+
+impl BatteryLevelStatus {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = LevelStatusFlags::from_bits_truncate(r.u8()?);
+
+        let power_state = PowerState::from_u16(r.u16()?);
+        let identifier = r.read_if(flags.contains(LevelStatusFlags::IDENTIFIER), Reader::u16)?;
+        let battery_level =
+            r.read_if(flags.contains(LevelStatusFlags::BATTERY_LEVEL), Reader::u8)?;
+        let additional_status = r.read_if(
+            flags.contains(LevelStatusFlags::ADDITIONAL_STATUS),
+            Reader::u8,
+        )?;
+
+        Some(Self {
+            power_state,
+            identifier,
+            battery_level,
+            service_required: additional_status.and_then(|status| tristate(u16::from(status))),
+            battery_fault: additional_status
+                .map(|status| status & 0b100 != 0)
+                .unwrap_or(false),
+        })
+    }
+}
+
+/// Contents of the Battery Information characteristic.
+///
+/// Dates are days since 1970-01-01 and energies are in kilowatt hours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BatteryInformation {
+    pub features: BatteryFeatures,
+    pub manufacture_date: Option<u32>,
+    pub expiration_date: Option<u32>,
+    pub designed_capacity: Option<SFloat>,
+    pub low_energy: Option<SFloat>,
+    pub critical_energy: Option<SFloat>,
+    /// Chemistry as assigned by the Bluetooth SIG, such as 5 for lithium
+    /// ion.
+    pub chemistry: Option<u8>,
+    /// Nominal voltage in volts.
+    pub nominal_voltage: Option<SFloat>,
+    /// Batteries in the same group are reported together.
+    pub aggregation_group: Option<u8>,
+}
+
+impl BatteryInformation {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = InformationFlags::from_bits_truncate(r.u16()?);
+        let sfloat = |r: &mut Reader| r.u16().map(SFloat);
+
+        Some(Self {
+            features: BatteryFeatures::from_bits_truncate(r.u8()?),
+            manufacture_date: r.read_if(
+                flags.contains(InformationFlags::MANUFACTURE_DATE),
+                Reader::u24,
+            )?,
+            expiration_date: r.read_if(
+                flags.contains(InformationFlags::EXPIRATION_DATE),
+                Reader::u24,
+            )?,
+            designed_capacity: r
+                .read_if(flags.contains(InformationFlags::DESIGNED_CAPACITY), sfloat)?,
+            low_energy: r.read_if(flags.contains(InformationFlags::LOW_ENERGY), sfloat)?,
+            critical_energy: r
+                .read_if(flags.contains(InformationFlags::CRITICAL_ENERGY), sfloat)?,
+            chemistry: r.read_if(flags.contains(InformationFlags::CHEMISTRY), Reader::u8)?,
+            nominal_voltage: r
+                .read_if(flags.contains(InformationFlags::NOMINAL_VOLTAGE), sfloat)?,
+            aggregation_group: r.read_if(
+                flags.contains(InformationFlags::AGGREGATION_GROUP),
+                Reader::u8,
+            )?,
+        })
+    }
+}
+
+/// Client for a device's Battery service.
+pub struct Battery<C: RemoteCharacteristic> {
+    battery_level: C,
+    battery_level_status: Option<C>,
+    battery_information: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> Battery<C> {
+    /// Find the characteristics of the Battery service.
+    ///
+    /// Fails if the service does not have the Battery Level characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let battery_level = service
+            .characteristic(BATTERY_LEVEL)?
+            .ok_or(Error::MissingAttribute(BATTERY_LEVEL))?;
+
+        Ok(Self {
+            battery_level,
+            battery_level_status: service.characteristic(BATTERY_LEVEL_STATUS)?,
+            battery_information: service.characteristic(BATTERY_INFORMATION)?,
+        })
+    }
+
+    /// Read the battery level in percent.
+    pub fn level(&self) -> Result<u8> {
+        match self.battery_level.read_value()?.as_slice() {
+            [level] => Ok(*level),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Subscribe to changes in the battery level.
+    pub fn levels(&self) -> Result<Notifications<'_, C, u8>> {
+        Notifications::new(&self.battery_level, |data| match *data {
+            [level] => Some(level),
+            _ => None,
+        })
+    }
+
+    /// Read the Battery Level Status, if the device has it.
+    pub fn level_status(&self) -> Result<Option<BatteryLevelStatus>> {
+        let characteristic = match &self.battery_level_status {
+            Some(characteristic) => characteristic,
+            None => return Ok(None),
+        };
+
+        BatteryLevelStatus::decode(&characteristic.read_value()?)
+            .map(Some)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Subscribe to changes in the Battery Level Status.
+    pub fn level_statuses(&self) -> Result<Notifications<'_, C, BatteryLevelStatus>> {
+        let characteristic = self
+            .battery_level_status
+            .as_ref()
+            .ok_or(Error::MissingAttribute(BATTERY_LEVEL_STATUS))?;
+
+        Notifications::new(characteristic, BatteryLevelStatus::decode)
+    }
+
+    /// Read the Battery Information, if the device has it.
+    pub fn information(&self) -> Result<Option<BatteryInformation>> {
+        let characteristic = match &self.battery_information {
+            Some(characteristic) => characteristic,
+            None => return Ok(None),
+        };
+
+        BatteryInformation::decode(&characteristic.read_value()?)
+            .map(Some)
+            .ok_or(Error::InvalidValue)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Battery<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Battery")
+            .field("battery_level", &self.battery_level)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        BatteryFeatures, BatteryInformation, BatteryLevelStatus, ChargeLevel, ChargeState,
+        ChargingFault, ChargingType, BATTERY_LEVEL, BATTERY_LEVEL_STATUS, SERVICE,
+    };
+    use crate::gatt::types::SFloat;
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error};
+
+    #[test]
+    fn test_decode_level_status() {
+        let status = BatteryLevelStatus::decode(&[0x06, 0xA3, 0x12, 0x4B, 0x05]).unwrap();
+        assert!(status.power_state.battery_present);
+        assert_eq!(status.power_state.wired_external_power, Some(true));
+        assert_eq!(status.power_state.wireless_external_power, Some(false));
+        assert_eq!(status.power_state.charge_state, ChargeState::Charging);
+        assert_eq!(status.power_state.charge_level, ChargeLevel::Good);
+        assert_eq!(
+            status.power_state.charging_type,
+            ChargingType::ConstantCurrent
+        );
+        assert_eq!(status.power_state.charging_fault, ChargingFault::BATTERY);
+        assert_eq!(status.identifier, None);
+        assert_eq!(status.battery_level, Some(75));
+        assert_eq!(status.service_required, Some(true));
+        assert!(status.battery_fault);
+
+        assert_eq!(BatteryLevelStatus::decode(&[0x02, 0x01, 0x00]), None);
+    }
+
+    #[test]
+    fn test_decode_information() {
+        let information =
+            BatteryInformation::decode(&[0x21, 0x00, 0x02, 0x7E, 0x48, 0x00, 0x05]).unwrap();
+        assert_eq!(information.features, BatteryFeatures::RECHARGEABLE);
+        assert_eq!(information.manufacture_date, Some(18558));
+        assert_eq!(information.expiration_date, None);
+        assert_eq!(information.chemistry, Some(5));
+
+        let information = BatteryInformation::decode(&[0x40, 0x00, 0x03, 0x25, 0xF0]).unwrap();
+        assert_eq!(information.nominal_voltage, SFloat::new(37, -1));
+    }
+
+    #[test]
+    fn test_simulated_battery() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        BATTERY_LEVEL,
+                        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+                    )
+                    .with_value(&[90]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(BATTERY_LEVEL_STATUS, CharacteristicProperties::READ)
+                        .with_value(&[0x00, 0x01, 0x00]),
+                ),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let battery = device.battery().unwrap();
+        assert_eq!(battery.level().unwrap(), 90);
+        assert!(
+            battery
+                .level_status()
+                .unwrap()
+                .unwrap()
+                .power_state
+                .battery_present
+        );
+        assert_eq!(battery.information().unwrap(), None);
+
+        let mut levels = battery.levels().unwrap();
+        server.notify(SERVICE, BATTERY_LEVEL, &[89]);
+        assert_eq!(levels.next_timeout(Duration::from_millis(50)), Some(89));
+
+        let sim = Simulator::new();
+        sim.add_peripheral(address, GattServer::new());
+        let device = sim.connect(address).unwrap();
+        assert!(matches!(
+            device.battery(),
+            Err(Error::MissingAttribute(SERVICE))
+        ));
+    }
+}
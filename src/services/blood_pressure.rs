@@ -0,0 +1,241 @@
+//! Blood Pressure Service, as provided by blood pressure monitors.
+//!
+//! Monitors usually indicate their stored measurements once subscribed to,
+//! which can be downloaded with
+//! [collect_until_quiet](super::Notifications::collect_until_quiet).
+
+use super::{Notifications, Reader};
+use crate::gatt::types::{DateTime, SFloat};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Blood Pressure service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1810);
+/// UUID of the Blood Pressure Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A35);
+/// UUID of the Intermediate Cuff Pressure characteristic.
+pub const INTERMEDIATE_CUFF_PRESSURE: Uuid = Uuid::from_u16(0x2A36);
+/// UUID of the Blood Pressure Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A49);
+
+bitflags::bitflags! {
+    /// Contents of the Blood Pressure Feature characteristic.
+    pub struct BloodPressureFeatures: u16 {
+        const BODY_MOVEMENT_DETECTION = 1 << 0;
+        const CUFF_FIT_DETECTION = 1 << 1;
+        const IRREGULAR_PULSE_DETECTION = 1 << 2;
+        const PULSE_RATE_RANGE_DETECTION = 1 << 3;
+        const MEASUREMENT_POSITION_DETECTION = 1 << 4;
+        const MULTIPLE_BOND = 1 << 5;
+    }
+}
+
+bitflags::bitflags! {
+    /// Problems the monitor found when taking a measurement.
+    pub struct MeasurementStatus: u16 {
+        const BODY_MOVEMENT = 1 << 0;
+        const CUFF_TOO_LOOSE = 1 << 1;
+        const IRREGULAR_PULSE = 1 << 2;
+        const PULSE_RATE_ABOVE_UPPER_LIMIT = 1 << 3;
+        const PULSE_RATE_BELOW_LOWER_LIMIT = 1 << 4;
+        const IMPROPER_MEASUREMENT_POSITION = 1 << 5;
+    }
+}
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const KILOPASCALS = 1 << 0;
+        const TIME_STAMP = 1 << 1;
+        const PULSE_RATE = 1 << 2;
+        const USER_ID = 1 << 3;
+        const MEASUREMENT_STATUS = 1 << 4;
+    }
+}
+
+/// Unit of a blood pressure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PressureUnit {
+    MillimetersOfMercury,
+    Kilopascals,
+}
+
+/// Contents of the Blood Pressure Measurement and Intermediate Cuff Pressure
+/// characteristics.
+///
+/// Intermediate cuff pressures only have a meaningful `systolic` value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloodPressureMeasurement {
+    pub unit: PressureUnit,
+    pub systolic: SFloat,
+    pub diastolic: SFloat,
+    pub mean_arterial_pressure: SFloat,
+    pub time_stamp: Option<DateTime>,
+    /// Pulse rate in beats per minute.
+    pub pulse_rate: Option<SFloat>,
+    /// User who took the measurement, with 0xFF meaning unknown.
+    pub user_id: Option<u8>,
+    pub status: Option<MeasurementStatus>,
+}
+
+impl BloodPressureMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let systolic = SFloat(r.u16()?);
+        let diastolic = SFloat(r.u16()?);
+        let mean_arterial_pressure = SFloat(r.u16()?);
+        let time_stamp = r.read_if(flags.contains(Flags::TIME_STAMP), |r| {
+            DateTime::decode(r.bytes(DateTime::LEN)?)
+        })?;
+        let pulse_rate = r.read_if(flags.contains(Flags::PULSE_RATE), |r| r.u16().map(SFloat))?;
+        let user_id = r.read_if(flags.contains(Flags::USER_ID), Reader::u8)?;
+        let status = r.read_if(flags.contains(Flags::MEASUREMENT_STATUS), |r| {
+            r.u16().map(MeasurementStatus::from_bits_truncate)
+        })?;
+
+        Some(Self {
+            unit: if flags.contains(Flags::KILOPASCALS) {
+                PressureUnit::Kilopascals
+            } else {
+                PressureUnit::MillimetersOfMercury
+            },
+            systolic,
+            diastolic,
+            mean_arterial_pressure,
+            time_stamp,
+            pulse_rate,
+            user_id,
+            status,
+        })
+    }
+}
+
+/// Client for a device's Blood Pressure service.
+pub struct BloodPressure<C: RemoteCharacteristic> {
+    measurement: C,
+    intermediate_cuff_pressure: Option<C>,
+    feature: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> BloodPressure<C> {
+    /// Find the characteristics of the Blood Pressure service.
+    ///
+    /// Fails if the service does not have the Blood Pressure Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            intermediate_cuff_pressure: service.characteristic(INTERMEDIATE_CUFF_PRESSURE)?,
+            feature: service.characteristic(FEATURE)?,
+        })
+    }
+
+    /// Subscribe to completed measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, BloodPressureMeasurement>> {
+        Notifications::new(&self.measurement, BloodPressureMeasurement::decode)
+    }
+
+    /// Subscribe to the cuff pressure while a measurement is being taken.
+    pub fn intermediate_cuff_pressure(
+        &self,
+    ) -> Result<Notifications<'_, C, BloodPressureMeasurement>> {
+        let characteristic = self
+            .intermediate_cuff_pressure
+            .as_ref()
+            .ok_or(Error::MissingAttribute(INTERMEDIATE_CUFF_PRESSURE))?;
+
+        Notifications::new(characteristic, BloodPressureMeasurement::decode)
+    }
+
+    /// Read which problems the monitor can detect.
+    pub fn features(&self) -> Result<BloodPressureFeatures> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Reader::new(&data)
+            .u16()
+            .map(BloodPressureFeatures::from_bits_truncate)
+            .ok_or(Error::InvalidValue)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for BloodPressure<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloodPressure")
+            .field("measurement", &self.measurement)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        BloodPressure, BloodPressureMeasurement, MeasurementStatus, PressureUnit, MEASUREMENT,
+        SERVICE,
+    };
+    use crate::gatt::types::{DateTime, SFloat};
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::CharacteristicProperties;
+
+    #[test]
+    fn test_download_measurements() {
+        let server = GattServer::new();
+        server.add_service(LocalService::new(SERVICE).with_characteristic(
+            LocalCharacteristic::new(MEASUREMENT, CharacteristicProperties::INDICATE),
+        ));
+
+        let service = connect(&server, SERVICE);
+        let monitor = BloodPressure::new(&service).unwrap();
+
+        let mut measurements = monitor.measurements().unwrap();
+        server.indicate(
+            SERVICE,
+            MEASUREMENT,
+            &[
+                0x1E, 0x79, 0x00, 0x51, 0x00, 0x5E, 0x00, 0xE4, 0x07, 0x06, 0x01, 0x08, 0x1E, 0x00,
+                0x48, 0x00, 0x01, 0x04, 0x00,
+            ],
+        );
+        server.indicate(SERVICE, MEASUREMENT, &[0x01, 0x79, 0x00]);
+        server.indicate(
+            SERVICE,
+            MEASUREMENT,
+            &[0x01, 0xA1, 0xF0, 0x6C, 0xF0, 0x7D, 0xF0],
+        );
+
+        let measurements = measurements.collect_until_quiet(Duration::from_millis(20));
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(
+            measurements[0],
+            BloodPressureMeasurement {
+                unit: PressureUnit::MillimetersOfMercury,
+                systolic: SFloat(121),
+                diastolic: SFloat(81),
+                mean_arterial_pressure: SFloat(94),
+                time_stamp: Some(DateTime {
+                    year: 2020,
+                    month: 6,
+                    day: 1,
+                    hours: 8,
+                    minutes: 30,
+                    seconds: 0,
+                }),
+                pulse_rate: Some(SFloat(72)),
+                user_id: Some(1),
+                status: Some(MeasurementStatus::IRREGULAR_PULSE),
+            }
+        );
+        assert_eq!(measurements[1].unit, PressureUnit::Kilopascals);
+        assert_eq!(measurements[1].systolic.to_f64(), 16.1);
+    }
+}
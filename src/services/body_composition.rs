@@ -0,0 +1,233 @@
+//! Body Composition Service, as provided by body composition scales.
+
+use super::weight_scale::{HeightResolution, MassResolution, MeasurementSystem};
+use super::{Notifications, Reader};
+use crate::gatt::types::DateTime;
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Body Composition service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x181B);
+/// UUID of the Body Composition Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A9B);
+/// UUID of the Body Composition Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A9C);
+
+bitflags::bitflags! {
+    struct Flags: u16 {
+        const IMPERIAL = 1 << 0;
+        const TIME_STAMP = 1 << 1;
+        const USER_ID = 1 << 2;
+        const BASAL_METABOLISM = 1 << 3;
+        const MUSCLE_PERCENTAGE = 1 << 4;
+        const MUSCLE_MASS = 1 << 5;
+        const FAT_FREE_MASS = 1 << 6;
+        const SOFT_LEAN_MASS = 1 << 7;
+        const BODY_WATER_MASS = 1 << 8;
+        const IMPEDANCE = 1 << 9;
+        const WEIGHT = 1 << 10;
+        const HEIGHT = 1 << 11;
+        const MULTIPLE_PACKET = 1 << 12;
+    }
+}
+
+bitflags::bitflags! {
+    /// Optional fields a scale can include in its measurements.
+    pub struct BodyCompositionFeatures: u32 {
+        const TIME_STAMP = 1 << 0;
+        const MULTIPLE_USERS = 1 << 1;
+        const BASAL_METABOLISM = 1 << 2;
+        const MUSCLE_PERCENTAGE = 1 << 3;
+        const MUSCLE_MASS = 1 << 4;
+        const FAT_FREE_MASS = 1 << 5;
+        const SOFT_LEAN_MASS = 1 << 6;
+        const BODY_WATER_MASS = 1 << 7;
+        const IMPEDANCE = 1 << 8;
+        const WEIGHT = 1 << 9;
+        const HEIGHT = 1 << 10;
+    }
+}
+
+/// Contents of the Body Composition Feature characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyCompositionFeature {
+    pub features: BodyCompositionFeatures,
+    pub mass_resolution: MassResolution,
+    pub height_resolution: HeightResolution,
+}
+
+impl BodyCompositionFeature {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let value = Reader::new(data).u32()?;
+
+        Some(Self {
+            features: BodyCompositionFeatures::from_bits_truncate(value),
+            mass_resolution: MassResolution(((value >> 11) & 0x0F) as u8),
+            height_resolution: HeightResolution(((value >> 15) & 0x07) as u8),
+        })
+    }
+}
+
+/// Contents of the Body Composition Measurement characteristic.
+///
+/// Masses and lengths are in the units given by `units`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BodyCompositionMeasurement {
+    pub units: MeasurementSystem,
+    /// Body fat in units of 0.1 percent, with 0xFFFF meaning the measurement
+    /// failed.
+    pub body_fat_percentage: u16,
+    pub time_stamp: Option<DateTime>,
+    /// User who took the measurement, with 0xFF meaning unknown.
+    pub user_id: Option<u8>,
+    /// Basal metabolism in kilojoules.
+    pub basal_metabolism: Option<u16>,
+    /// Muscle in units of 0.1 percent.
+    pub muscle_percentage: Option<u16>,
+    pub muscle_mass: Option<u16>,
+    pub fat_free_mass: Option<u16>,
+    pub soft_lean_mass: Option<u16>,
+    pub body_water_mass: Option<u16>,
+    /// Impedance in units of 0.1 ohms.
+    pub impedance: Option<u16>,
+    pub weight: Option<u16>,
+    pub height: Option<u16>,
+    /// If the rest of the measurement is sent in another value.
+    pub multiple_packet: bool,
+}
+
+impl BodyCompositionMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u16()?);
+
+        let body_fat_percentage = r.u16()?;
+        let time_stamp = r.read_if(flags.contains(Flags::TIME_STAMP), |r| {
+            DateTime::decode(r.bytes(DateTime::LEN)?)
+        })?;
+        let user_id = r.read_if(flags.contains(Flags::USER_ID), Reader::u8)?;
+        let basal_metabolism = r.read_if(flags.contains(Flags::BASAL_METABOLISM), Reader::u16)?;
+        let muscle_percentage = r.read_if(flags.contains(Flags::MUSCLE_PERCENTAGE), Reader::u16)?;
+        let muscle_mass = r.read_if(flags.contains(Flags::MUSCLE_MASS), Reader::u16)?;
+        let fat_free_mass = r.read_if(flags.contains(Flags::FAT_FREE_MASS), Reader::u16)?;
+        let soft_lean_mass = r.read_if(flags.contains(Flags::SOFT_LEAN_MASS), Reader::u16)?;
+        let body_water_mass = r.read_if(flags.contains(Flags::BODY_WATER_MASS), Reader::u16)?;
+        let impedance = r.read_if(flags.contains(Flags::IMPEDANCE), Reader::u16)?;
+        let weight = r.read_if(flags.contains(Flags::WEIGHT), Reader::u16)?;
+        let height = r.read_if(flags.contains(Flags::HEIGHT), Reader::u16)?;
+
+        Some(Self {
+            units: MeasurementSystem::from_imperial_flag(flags.contains(Flags::IMPERIAL)),
+            body_fat_percentage,
+            time_stamp,
+            user_id,
+            basal_metabolism,
+            muscle_percentage,
+            muscle_mass,
+            fat_free_mass,
+            soft_lean_mass,
+            body_water_mass,
+            impedance,
+            weight,
+            height,
+            multiple_packet: flags.contains(Flags::MULTIPLE_PACKET),
+        })
+    }
+}
+
+/// Client for a device's Body Composition service.
+pub struct BodyComposition<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> BodyComposition<C> {
+    /// Find the characteristics of the Body Composition service.
+    ///
+    /// Fails if the service does not have the Body Composition Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            feature: service.characteristic(FEATURE)?,
+        })
+    }
+
+    /// Subscribe to body composition measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, BodyCompositionMeasurement>> {
+        Notifications::new(&self.measurement, BodyCompositionMeasurement::decode)
+    }
+
+    /// Read which fields the scale supports and how precise it is.
+    pub fn feature(&self) -> Result<BodyCompositionFeature> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        BodyCompositionFeature::decode(&data).ok_or(Error::InvalidValue)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for BodyComposition<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BodyComposition")
+            .field("measurement", &self.measurement)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BodyCompositionFeature, BodyCompositionFeatures, BodyCompositionMeasurement};
+    use crate::services::weight_scale::{HeightResolution, MassResolution, MeasurementSystem};
+
+    #[test]
+    fn test_decode_measurement() {
+        let measurement = BodyCompositionMeasurement::decode(&[
+            0x04, 0x16, 0xC8, 0x00, 0x01, 0x8C, 0x14, 0x60, 0x3B,
+        ])
+        .unwrap();
+        assert_eq!(measurement.units, MeasurementSystem::Si);
+        assert_eq!(measurement.body_fat_percentage, 200);
+        assert_eq!(measurement.user_id, Some(1));
+        assert_eq!(measurement.impedance, Some(5260));
+        assert_eq!(measurement.weight, Some(15200));
+        assert!(measurement.multiple_packet);
+        assert_eq!(measurement.units.kilograms(15200), 76.0);
+
+        assert_eq!(
+            BodyCompositionMeasurement::decode(&[0x00, 0x04, 0xC8, 0x00]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_feature() {
+        let feature = BodyCompositionFeature::decode(&[0x02, 0xB6, 0x01, 0x00]).unwrap();
+        assert_eq!(
+            feature.features,
+            BodyCompositionFeatures::MULTIPLE_USERS
+                | BodyCompositionFeatures::WEIGHT
+                | BodyCompositionFeatures::HEIGHT
+        );
+        assert_eq!(feature.mass_resolution, MassResolution(6));
+        assert_eq!(feature.height_resolution, HeightResolution(3));
+        assert_eq!(
+            feature.mass_resolution.kilograms(MeasurementSystem::Si),
+            Some(0.01)
+        );
+        assert_eq!(
+            feature
+                .height_resolution
+                .meters(MeasurementSystem::Imperial),
+            Some(0.1 * 0.0254)
+        );
+
+        assert_eq!(BodyCompositionFeature::decode(&[0x02, 0xB6]), None);
+    }
+}
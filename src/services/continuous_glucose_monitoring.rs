@@ -0,0 +1,804 @@
+//! Continuous Glucose Monitoring Service, as provided by CGM sensors.
+//!
+//! Monitors may protect their values with an E2E-CRC, which is checked and
+//! added automatically if the monitor's features say it is supported.
+
+use std::time::{Duration, Instant};
+
+use super::glucose::{SampleLocation, SampleType};
+use super::{Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::gatt::types::{DateTime, SFloat};
+use crate::{
+    CharacteristicIO, ControlPointError, Error, RemoteCharacteristic, RemoteService, Result, Uuid,
+};
+
+/// UUID of the Continuous Glucose Monitoring service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x181F);
+/// UUID of the CGM Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2AA7);
+/// UUID of the CGM Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2AA8);
+/// UUID of the CGM Status characteristic.
+pub const STATUS: Uuid = Uuid::from_u16(0x2AA9);
+/// UUID of the CGM Session Start Time characteristic.
+pub const SESSION_START_TIME: Uuid = Uuid::from_u16(0x2AAA);
+/// UUID of the CGM Session Run Time characteristic.
+pub const SESSION_RUN_TIME: Uuid = Uuid::from_u16(0x2AAB);
+/// UUID of the CGM Specific Ops Control Point characteristic.
+pub const SPECIFIC_OPS_CONTROL_POINT: Uuid = Uuid::from_u16(0x2AAC);
+
+const SET_COMMUNICATION_INTERVAL: u8 = 0x01;
+const GET_COMMUNICATION_INTERVAL: u8 = 0x02;
+const COMMUNICATION_INTERVAL_RESPONSE: u8 = 0x03;
+const SET_GLUCOSE_CALIBRATION_VALUE: u8 = 0x04;
+const GET_GLUCOSE_CALIBRATION_VALUE: u8 = 0x05;
+const GLUCOSE_CALIBRATION_VALUE_RESPONSE: u8 = 0x06;
+const RESET_DEVICE_SPECIFIC_ALERT: u8 = 0x19;
+const START_SESSION: u8 = 0x1A;
+const STOP_SESSION: u8 = 0x1B;
+const RESPONSE_CODE: u8 = 0x1C;
+
+const SUCCESS: u8 = 0x01;
+
+/// Result code when a parameter was outside of the supported range.
+pub const PARAMETER_OUT_OF_RANGE: u8 = 0x05;
+
+bitflags::bitflags! {
+    /// Features supported by a monitor.
+    pub struct CgmFeatures: u32 {
+        const CALIBRATION = 1 << 0;
+        const PATIENT_HIGH_LOW_ALERTS = 1 << 1;
+        const HYPO_ALERTS = 1 << 2;
+        const HYPER_ALERTS = 1 << 3;
+        const RATE_OF_INCREASE_DECREASE_ALERTS = 1 << 4;
+        const DEVICE_SPECIFIC_ALERT = 1 << 5;
+        const SENSOR_MALFUNCTION_DETECTION = 1 << 6;
+        const SENSOR_TEMPERATURE_HIGH_LOW_DETECTION = 1 << 7;
+        const SENSOR_RESULT_HIGH_LOW_DETECTION = 1 << 8;
+        const LOW_BATTERY_DETECTION = 1 << 9;
+        const SENSOR_TYPE_ERROR_DETECTION = 1 << 10;
+        const GENERAL_DEVICE_FAULT = 1 << 11;
+        const E2E_CRC = 1 << 12;
+        const MULTIPLE_BOND = 1 << 13;
+        const MULTIPLE_SESSIONS = 1 << 14;
+        const TREND_INFORMATION = 1 << 15;
+        const QUALITY = 1 << 16;
+    }
+}
+
+bitflags::bitflags! {
+    /// Sensor Status Annunciation, made of the status, calibration and
+    /// temperature, and warning octets.
+    pub struct SensorStatus: u32 {
+        const SESSION_STOPPED = 1 << 0;
+        const DEVICE_BATTERY_LOW = 1 << 1;
+        const SENSOR_TYPE_INCORRECT = 1 << 2;
+        const SENSOR_MALFUNCTION = 1 << 3;
+        const DEVICE_SPECIFIC_ALERT = 1 << 4;
+        const GENERAL_DEVICE_FAULT = 1 << 5;
+
+        const TIME_SYNCHRONIZATION_REQUIRED = 1 << 8;
+        const CALIBRATION_NOT_ALLOWED = 1 << 9;
+        const CALIBRATION_RECOMMENDED = 1 << 10;
+        const CALIBRATION_REQUIRED = 1 << 11;
+        const SENSOR_TEMPERATURE_TOO_HIGH = 1 << 12;
+        const SENSOR_TEMPERATURE_TOO_LOW = 1 << 13;
+
+        const RESULT_LOWER_THAN_PATIENT_LOW_LEVEL = 1 << 16;
+        const RESULT_HIGHER_THAN_PATIENT_HIGH_LEVEL = 1 << 17;
+        const RESULT_LOWER_THAN_HYPO_LEVEL = 1 << 18;
+        const RESULT_HIGHER_THAN_HYPER_LEVEL = 1 << 19;
+        const RATE_OF_DECREASE_EXCEEDED = 1 << 20;
+        const RATE_OF_INCREASE_EXCEEDED = 1 << 21;
+        const RESULT_LOWER_THAN_DEVICE_CAN_PROCESS = 1 << 22;
+        const RESULT_HIGHER_THAN_DEVICE_CAN_PROCESS = 1 << 23;
+    }
+}
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const TREND = 1 << 0;
+        const QUALITY = 1 << 1;
+        const WARNING_OCTET = 1 << 5;
+        const CAL_TEMP_OCTET = 1 << 6;
+        const STATUS_OCTET = 1 << 7;
+    }
+}
+
+/// Calculate the E2E-CRC of a value.
+fn e2e_crc(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0x8408
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Check and remove the E2E-CRC from the end of a value, if it has one.
+fn strip_crc(data: &[u8], crc: bool) -> Option<&[u8]> {
+    if !crc {
+        return Some(data);
+    }
+
+    if data.len() < 2 {
+        return None;
+    }
+
+    let (value, expected) = data.split_at(data.len() - 2);
+    if e2e_crc(value) != u16::from_le_bytes([expected[0], expected[1]]) {
+        log::warn!("Value had invalid E2E-CRC: {:?}", data);
+        return None;
+    }
+
+    Some(value)
+}
+
+/// Add an E2E-CRC to the end of a value, if needed.
+fn append_crc(mut data: Vec<u8>, crc: bool) -> Vec<u8> {
+    if crc {
+        let crc = e2e_crc(&data);
+        data.extend_from_slice(&crc.to_le_bytes());
+    }
+
+    data
+}
+
+/// Contents of the CGM Feature characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CgmFeature {
+    pub features: CgmFeatures,
+    pub sample_type: SampleType,
+    pub sample_location: SampleLocation,
+}
+
+impl CgmFeature {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+
+        let features = CgmFeatures::from_bits_truncate(r.u24()?);
+        let type_location = r.u8()?;
+        let crc = r.u16()?;
+
+        // The CRC is always present, but only valid if it is supported.
+        if features.contains(CgmFeatures::E2E_CRC) && e2e_crc(&data[..4]) != crc {
+            log::warn!("CGM Feature had invalid E2E-CRC: {:?}", data);
+            return None;
+        }
+
+        Some(Self {
+            features,
+            sample_type: SampleType::from_u8(type_location & 0x0F),
+            sample_location: SampleLocation::from_u8(type_location >> 4),
+        })
+    }
+}
+
+/// A single measurement from the CGM Measurement characteristic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CgmMeasurement {
+    /// Glucose concentration in mg/dL.
+    pub glucose: SFloat,
+    /// Minutes since the session started.
+    pub time_offset: u16,
+    /// Status octets sent with the measurement, with missing octets empty.
+    pub status: SensorStatus,
+    /// Trend in mg/dL/min.
+    pub trend: Option<SFloat>,
+    /// Quality in percent.
+    pub quality: Option<SFloat>,
+}
+
+impl CgmMeasurement {
+    /// Decode every measurement in a value, checking the E2E-CRC of each if
+    /// `crc` is set.
+    ///
+    /// Fails if any measurement is invalid, or has a missing or incorrect
+    /// E2E-CRC.
+    pub fn decode_all(data: &[u8], crc: bool) -> Option<Vec<Self>> {
+        let mut measurements = Vec::new();
+
+        let mut rest = data;
+        while let Some(size) = rest.first() {
+            let size = usize::from(*size);
+            if size == 0 || size > rest.len() {
+                return None;
+            }
+
+            let (record, next) = rest.split_at(size);
+            measurements.push(Self::decode(record, crc)?);
+            rest = next;
+        }
+
+        Some(measurements)
+    }
+
+    /// Decode a single measurement, starting with its size.
+    fn decode(record: &[u8], crc: bool) -> Option<Self> {
+        let mut r = Reader::new(strip_crc(record, crc)?);
+        r.u8()?;
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let glucose = SFloat(r.u16()?);
+        let time_offset = r.u16()?;
+
+        let mut status = 0;
+        for (flag, shift) in &[
+            (Flags::WARNING_OCTET, 16),
+            (Flags::CAL_TEMP_OCTET, 8),
+            (Flags::STATUS_OCTET, 0),
+        ] {
+            if let Some(octet) = r.read_if(flags.contains(*flag), Reader::u8)? {
+                status |= u32::from(octet) << shift;
+            }
+        }
+
+        let trend = r.read_if(flags.contains(Flags::TREND), |r| r.u16().map(SFloat))?;
+        let quality = r.read_if(flags.contains(Flags::QUALITY), |r| r.u16().map(SFloat))?;
+
+        if !r.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glucose,
+            time_offset,
+            status: SensorStatus::from_bits_truncate(status),
+            trend,
+            quality,
+        })
+    }
+}
+
+/// Contents of the CGM Status characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CgmStatus {
+    /// Minutes since the session started.
+    pub time_offset: u16,
+    pub status: SensorStatus,
+}
+
+impl CgmStatus {
+    /// Decode the value, checking the E2E-CRC if `crc` is set.
+    pub fn decode(data: &[u8], crc: bool) -> Option<Self> {
+        let mut r = Reader::new(strip_crc(data, crc)?);
+
+        Some(Self {
+            time_offset: r.u16()?,
+            status: SensorStatus::from_bits_truncate(r.u24()?),
+        })
+    }
+}
+
+/// Contents of the CGM Session Start Time characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionStartTime {
+    pub time: DateTime,
+    /// Offset from UTC in units of 15 minutes, with -128 meaning unknown.
+    pub time_zone: i8,
+    /// Daylight saving time offset in units of 15 minutes, with 255 meaning
+    /// unknown.
+    pub dst_offset: u8,
+}
+
+impl SessionStartTime {
+    /// Decode the value, checking the E2E-CRC if `crc` is set.
+    pub fn decode(data: &[u8], crc: bool) -> Option<Self> {
+        let mut r = Reader::new(strip_crc(data, crc)?);
+
+        let time = DateTime::decode(r.bytes(DateTime::LEN)?)?;
+        let time_zone = r.u8()? as i8;
+        let dst_offset = r.u8()?;
+
+        Some(Self {
+            time,
+            time_zone,
+            dst_offset,
+        })
+    }
+
+    /// Encode the value, adding an E2E-CRC if `crc` is set.
+    pub fn encode(&self, crc: bool) -> Vec<u8> {
+        let mut data = self.time.encode().to_vec();
+        data.push(self.time_zone as u8);
+        data.push(self.dst_offset);
+
+        append_crc(data, crc)
+    }
+}
+
+/// A calibration value, as used by the CGM Specific Ops Control Point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Calibration {
+    /// Glucose concentration in mg/dL.
+    pub glucose: SFloat,
+    /// Minutes since the session started.
+    pub time: u16,
+    pub sample_type: SampleType,
+    pub sample_location: SampleLocation,
+    /// Minutes since the session started when the next calibration is
+    /// required.
+    pub next_calibration_time: u16,
+    pub record_number: u16,
+    pub status: u8,
+}
+
+impl Calibration {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+
+        let glucose = SFloat(r.u16()?);
+        let time = r.u16()?;
+        let type_location = r.u8()?;
+        let next_calibration_time = r.u16()?;
+        let record_number = r.u16()?;
+        let status = r.u8()?;
+
+        if !r.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            glucose,
+            time,
+            sample_type: SampleType::from_u8(type_location & 0x0F),
+            sample_location: SampleLocation::from_u8(type_location >> 4),
+            next_calibration_time,
+            record_number,
+            status,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(10);
+        data.extend_from_slice(&self.glucose.to_le_bytes());
+        data.extend_from_slice(&self.time.to_le_bytes());
+        data.push((self.sample_type.to_u8() & 0x0F) | (self.sample_location.to_u8() << 4));
+        data.extend_from_slice(&self.next_calibration_time.to_le_bytes());
+        data.extend_from_slice(&self.record_number.to_le_bytes());
+        data.push(self.status);
+        data
+    }
+}
+
+/// Glucose levels the monitor can alert on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Alert {
+    /// Level in mg/dL.
+    PatientHigh,
+    /// Level in mg/dL.
+    PatientLow,
+    /// Level in mg/dL.
+    Hypo,
+    /// Level in mg/dL.
+    Hyper,
+    /// Rate in mg/dL/min.
+    RateOfDecrease,
+    /// Rate in mg/dL/min.
+    RateOfIncrease,
+}
+
+impl Alert {
+    /// Op code to set the level. The next two op codes get the level and
+    /// respond with it.
+    fn set_op_code(self) -> u8 {
+        match self {
+            Alert::PatientHigh => 0x07,
+            Alert::PatientLow => 0x0A,
+            Alert::Hypo => 0x0D,
+            Alert::Hyper => 0x10,
+            Alert::RateOfDecrease => 0x13,
+            Alert::RateOfIncrease => 0x16,
+        }
+    }
+}
+
+/// Client for a device's Continuous Glucose Monitoring service.
+pub struct ContinuousGlucoseMonitor<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: CgmFeature,
+    status: Option<C>,
+    session_start_time: Option<C>,
+    session_run_time: Option<C>,
+    specific_ops_control_point: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> ContinuousGlucoseMonitor<C> {
+    /// Find the characteristics of the Continuous Glucose Monitoring service,
+    /// and read the monitor's features to know if it uses E2E-CRCs.
+    ///
+    /// Fails if the service does not have the CGM Measurement or CGM Feature
+    /// characteristics.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+        let feature = service
+            .characteristic(FEATURE)?
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Ok(Self {
+            measurement,
+            feature: CgmFeature::decode(&feature).ok_or(Error::InvalidValue)?,
+            status: service.characteristic(STATUS)?,
+            session_start_time: service.characteristic(SESSION_START_TIME)?,
+            session_run_time: service.characteristic(SESSION_RUN_TIME)?,
+            specific_ops_control_point: service.characteristic(SPECIFIC_OPS_CONTROL_POINT)?,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the monitor to respond to control point
+    /// requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Get the monitor's features, as read when this client was created.
+    pub fn feature(&self) -> CgmFeature {
+        self.feature
+    }
+
+    fn crc(&self) -> bool {
+        self.feature.features.contains(CgmFeatures::E2E_CRC)
+    }
+
+    /// Subscribe to measurements, which may arrive a few at a time.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, Vec<CgmMeasurement>>> {
+        let crc = self.crc();
+
+        Notifications::new(&self.measurement, move |data| {
+            CgmMeasurement::decode_all(data, crc)
+        })
+    }
+
+    /// Read the current status of the monitor.
+    pub fn status(&self) -> Result<CgmStatus> {
+        let data = self
+            .status
+            .as_ref()
+            .ok_or(Error::MissingAttribute(STATUS))?
+            .read_value()?;
+
+        CgmStatus::decode(&data, self.crc()).ok_or(Error::InvalidValue)
+    }
+
+    /// Read when the current session started.
+    pub fn session_start_time(&self) -> Result<SessionStartTime> {
+        let data = self
+            .session_start_time
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SESSION_START_TIME))?
+            .read_value()?;
+
+        SessionStartTime::decode(&data, self.crc()).ok_or(Error::InvalidValue)
+    }
+
+    /// Tell the monitor when the current session started, so it can convert
+    /// time offsets to the user's time.
+    pub fn set_session_start_time(&self, start_time: &SessionStartTime) -> Result<()> {
+        self.session_start_time
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SESSION_START_TIME))?
+            .write_value(&start_time.encode(self.crc()))
+    }
+
+    /// Read how many hours the current session is expected to last.
+    pub fn session_run_time(&self) -> Result<u16> {
+        let data = self
+            .session_run_time
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SESSION_RUN_TIME))?
+            .read_value()?;
+
+        match strip_crc(&data, self.crc()) {
+            Some(&[low, high]) => Ok(u16::from_le_bytes([low, high])),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Subscribe to control point responses so the monitor can be
+    /// configured.
+    pub fn control(&self) -> Result<SpecificOpsControlPoint<'_, C>> {
+        let characteristic = self
+            .specific_ops_control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SPECIFIC_OPS_CONTROL_POINT))?;
+
+        Ok(SpecificOpsControlPoint {
+            characteristic,
+            io: CharacteristicIO::new(characteristic)?,
+            timeout: self.timeout,
+            crc: self.crc(),
+        })
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for ContinuousGlucoseMonitor<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContinuousGlucoseMonitor")
+            .field("measurement", &self.measurement)
+            .field("feature", &self.feature)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Procedures on the CGM Specific Ops Control Point.
+///
+/// Each request waits for the monitor to indicate its response, failing with
+/// [Error::Timeout] if it does not respond in time, or
+/// [Error::ControlPoint] if it rejects the request.
+pub struct SpecificOpsControlPoint<'a, C: RemoteCharacteristic> {
+    characteristic: &'a C,
+    io: CharacteristicIO<'a, C>,
+    timeout: Duration,
+    crc: bool,
+}
+
+impl<C: RemoteCharacteristic> SpecificOpsControlPoint<'_, C> {
+    /// Send a request, returning the operand of the response.
+    ///
+    /// Requests which get a value are answered with `response_op_code`,
+    /// while others are answered with only a result code.
+    fn execute(
+        &mut self,
+        op_code: u8,
+        operand: &[u8],
+        response_op_code: Option<u8>,
+    ) -> Result<Vec<u8>> {
+        let mut request = vec![op_code];
+        request.extend_from_slice(operand);
+        let request = append_crc(request, self.crc);
+
+        log::debug!("Sending CGM control point request {:?}", request);
+        self.characteristic.write_value(&request)?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(Error::Timeout)?;
+            let data = self
+                .io
+                .next_notification_timeout(remaining)
+                .ok_or(Error::Timeout)?;
+
+            let response = match strip_crc(&data, self.crc) {
+                Some(response) => response,
+                None => continue,
+            };
+
+            match *response {
+                [RESPONSE_CODE, request, SUCCESS] if request == op_code => return Ok(vec![]),
+                [RESPONSE_CODE, request, result] if request == op_code => {
+                    return Err(ControlPointError { op_code, result }.into())
+                }
+                [code, ref operand @ ..] if Some(code) == response_op_code => {
+                    return Ok(operand.to_vec())
+                }
+                _ => log::debug!("Ignoring unexpected response {:?}", response),
+            }
+        }
+    }
+
+    /// Set how often the monitor sends measurements, in minutes.
+    ///
+    /// Zero disables measurements and 255 uses the fastest interval.
+    pub fn set_communication_interval(&mut self, minutes: u8) -> Result<()> {
+        self.execute(SET_COMMUNICATION_INTERVAL, &[minutes], None)
+            .map(|_operand| ())
+    }
+
+    /// Get how often the monitor sends measurements, in minutes.
+    pub fn communication_interval(&mut self) -> Result<u8> {
+        match *self
+            .execute(
+                GET_COMMUNICATION_INTERVAL,
+                &[],
+                Some(COMMUNICATION_INTERVAL_RESPONSE),
+            )?
+            .as_slice()
+        {
+            [minutes] => Ok(minutes),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Calibrate the monitor with a reference measurement.
+    pub fn set_calibration(&mut self, calibration: &Calibration) -> Result<()> {
+        self.execute(SET_GLUCOSE_CALIBRATION_VALUE, &calibration.encode(), None)
+            .map(|_operand| ())
+    }
+
+    /// Get a stored calibration by its record number, or the most recent
+    /// with 0xFFFF.
+    pub fn calibration(&mut self, record_number: u16) -> Result<Calibration> {
+        let operand = self.execute(
+            GET_GLUCOSE_CALIBRATION_VALUE,
+            &record_number.to_le_bytes(),
+            Some(GLUCOSE_CALIBRATION_VALUE_RESPONSE),
+        )?;
+
+        Calibration::decode(&operand).ok_or(Error::InvalidValue)
+    }
+
+    /// Set the level of an alert.
+    pub fn set_alert_level(&mut self, alert: Alert, level: SFloat) -> Result<()> {
+        self.execute(alert.set_op_code(), &level.to_le_bytes(), None)
+            .map(|_operand| ())
+    }
+
+    /// Get the level of an alert.
+    pub fn alert_level(&mut self, alert: Alert) -> Result<SFloat> {
+        let op_code = alert.set_op_code();
+
+        match *self
+            .execute(op_code + 1, &[], Some(op_code + 2))?
+            .as_slice()
+        {
+            [low, high] => Ok(SFloat::from_le_bytes([low, high])),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Clear the device specific alert.
+    pub fn reset_device_specific_alert(&mut self) -> Result<()> {
+        self.execute(RESET_DEVICE_SPECIFIC_ALERT, &[], None)
+            .map(|_operand| ())
+    }
+
+    /// Start a new session.
+    pub fn start_session(&mut self) -> Result<()> {
+        self.execute(START_SESSION, &[], None).map(|_operand| ())
+    }
+
+    /// Stop the current session.
+    pub fn stop_session(&mut self) -> Result<()> {
+        self.execute(STOP_SESSION, &[], None).map(|_operand| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        append_crc, e2e_crc, Alert, CgmFeatures, CgmMeasurement, ContinuousGlucoseMonitor,
+        SensorStatus, FEATURE, MEASUREMENT, SERVICE, SPECIFIC_OPS_CONTROL_POINT, STATUS,
+    };
+    use crate::gatt::types::SFloat;
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::services::glucose::SampleLocation;
+    use crate::{CharacteristicProperties, ControlPointError, Error};
+
+    #[test]
+    fn test_e2e_crc() {
+        assert_eq!(e2e_crc(b"123456789"), 0x6F91);
+    }
+
+    #[test]
+    fn test_decode_measurements() {
+        let mut data = append_crc(vec![0x0A, 0xC0, 0x64, 0x00, 0x05, 0x00, 0x02, 0x02], true);
+        data.extend(append_crc(
+            vec![0x0A, 0x01, 0x6E, 0x00, 0x0A, 0x00, 0xFE, 0x0F],
+            true,
+        ));
+
+        let measurements = CgmMeasurement::decode_all(&data, true).unwrap();
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements[0].glucose, SFloat(100));
+        assert_eq!(measurements[0].time_offset, 5);
+        assert_eq!(
+            measurements[0].status,
+            SensorStatus::CALIBRATION_NOT_ALLOWED | SensorStatus::DEVICE_BATTERY_LOW
+        );
+        assert_eq!(measurements[0].trend, None);
+        assert_eq!(measurements[1].trend.unwrap().to_f64(), -2.0);
+
+        data[4] ^= 0xFF;
+        assert_eq!(CgmMeasurement::decode_all(&data, true), None);
+        assert_eq!(
+            CgmMeasurement::decode_all(&[0x06, 0x00, 0x64, 0x00], false),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_measurements_crc_feature() {
+        let record = [0x06, 0x00, 0x64, 0x00, 0x05, 0x00];
+        assert!(CgmMeasurement::decode_all(&record, false).is_some());
+
+        // A monitor with E2E-CRC support must send one with every record.
+        assert_eq!(CgmMeasurement::decode_all(&record, true), None);
+
+        // Without support, nothing may follow the fields.
+        let with_crc = append_crc(vec![0x08, 0x00, 0x64, 0x00, 0x05, 0x00], true);
+        assert!(CgmMeasurement::decode_all(&with_crc, true).is_some());
+        assert_eq!(CgmMeasurement::decode_all(&with_crc, false), None);
+    }
+
+    #[test]
+    fn test_simulated_monitor() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(FEATURE, CharacteristicProperties::READ)
+                        .with_value(&append_crc(vec![0x00, 0x10, 0x00, 0x59], true)),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(STATUS, CharacteristicProperties::READ)
+                        .with_value(&append_crc(vec![0x3C, 0x00, 0x01, 0x00, 0x00], true)),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        SPECIFIC_OPS_CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(|server, value| {
+                        let (request, crc) = value.split_at(value.len() - 2);
+                        assert_eq!(e2e_crc(request).to_le_bytes(), crc);
+
+                        let response = match *request {
+                            [0x01, _] => vec![0x1C, 0x01, 0x01],
+                            [0x02] => vec![0x03, 0x05],
+                            [0x08] => vec![0x09, 0xFA, 0x00],
+                            [op_code, ..] => vec![0x1C, op_code, 0x02],
+                            [] => return Ok(()),
+                        };
+                        server.indicate(
+                            SERVICE,
+                            SPECIFIC_OPS_CONTROL_POINT,
+                            &append_crc(response, true),
+                        );
+                        Ok(())
+                    }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let monitor = ContinuousGlucoseMonitor::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        let feature = monitor.feature();
+        assert_eq!(feature.features, CgmFeatures::E2E_CRC);
+        assert_eq!(feature.sample_location, SampleLocation::SubcutaneousTissue);
+
+        let status = monitor.status().unwrap();
+        assert_eq!(status.time_offset, 60);
+        assert_eq!(status.status, SensorStatus::SESSION_STOPPED);
+
+        let mut control = monitor.control().unwrap();
+        control.set_communication_interval(5).unwrap();
+        assert_eq!(control.communication_interval().unwrap(), 5);
+        assert_eq!(
+            control.alert_level(Alert::PatientHigh).unwrap(),
+            SFloat(250)
+        );
+        match control.start_session() {
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x1A,
+                result: ControlPointError::OP_CODE_NOT_SUPPORTED,
+            })) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! Current Time Service, as provided by watches and data loggers.
+//!
+//! The device keeps local time, described by its Local Time Information.
+
+use std::time::SystemTime;
+
+use super::Notifications;
+use crate::gatt::types::ExactTime256;
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Current Time service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1805);
+/// UUID of the Local Time Information characteristic.
+pub const LOCAL_TIME_INFORMATION: Uuid = Uuid::from_u16(0x2A0F);
+/// UUID of the Current Time characteristic.
+pub const CURRENT_TIME: Uuid = Uuid::from_u16(0x2A2B);
+
+bitflags::bitflags! {
+    /// Why the time was last changed.
+    pub struct AdjustReason: u8 {
+        const MANUAL_TIME_UPDATE = 1 << 0;
+        const EXTERNAL_REFERENCE_TIME_UPDATE = 1 << 1;
+        const CHANGE_OF_TIME_ZONE = 1 << 2;
+        const CHANGE_OF_DST = 1 << 3;
+    }
+}
+
+/// Contents of the Current Time characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CurrentTime {
+    pub exact_time: ExactTime256,
+    pub adjust_reason: AdjustReason,
+}
+
+impl CurrentTime {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != ExactTime256::LEN + 1 {
+            return None;
+        }
+
+        Some(Self {
+            exact_time: ExactTime256::decode(&data[..ExactTime256::LEN])?,
+            adjust_reason: AdjustReason::from_bits_truncate(data[ExactTime256::LEN]),
+        })
+    }
+
+    pub fn encode(&self) -> [u8; 10] {
+        let mut data = [0u8; 10];
+        data[..ExactTime256::LEN].copy_from_slice(&self.exact_time.encode());
+        data[ExactTime256::LEN] = self.adjust_reason.bits();
+
+        data
+    }
+}
+
+/// Daylight saving time offset from the Local Time Information
+/// characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DstOffset {
+    StandardTime,
+    HalfAnHourDaylightTime,
+    DaylightTime,
+    DoubleDaylightTime,
+    Unknown,
+    /// Offset not known by this library.
+    Reserved(u8),
+}
+
+impl DstOffset {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => DstOffset::StandardTime,
+            2 => DstOffset::HalfAnHourDaylightTime,
+            4 => DstOffset::DaylightTime,
+            8 => DstOffset::DoubleDaylightTime,
+            255 => DstOffset::Unknown,
+            other => DstOffset::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            DstOffset::StandardTime => 0,
+            DstOffset::HalfAnHourDaylightTime => 2,
+            DstOffset::DaylightTime => 4,
+            DstOffset::DoubleDaylightTime => 8,
+            DstOffset::Unknown => 255,
+            DstOffset::Reserved(other) => other,
+        }
+    }
+
+    /// Offset in seconds, if known.
+    pub fn seconds(self) -> Option<i32> {
+        match self {
+            DstOffset::StandardTime => Some(0),
+            DstOffset::HalfAnHourDaylightTime => Some(1800),
+            DstOffset::DaylightTime => Some(3600),
+            DstOffset::DoubleDaylightTime => Some(7200),
+            DstOffset::Unknown | DstOffset::Reserved(_) => None,
+        }
+    }
+}
+
+/// Contents of the Local Time Information characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LocalTimeInformation {
+    /// Offset of standard time from UTC in units of 15 minutes, with -128
+    /// meaning unknown.
+    pub time_zone: i8,
+    pub dst_offset: DstOffset,
+}
+
+impl LocalTimeInformation {
+    /// Value of `time_zone` when it is not known.
+    pub const TIME_ZONE_UNKNOWN: i8 = -128;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [time_zone, dst_offset] => Some(Self {
+                time_zone: time_zone as i8,
+                dst_offset: DstOffset::from_u8(dst_offset),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; 2] {
+        [self.time_zone as u8, self.dst_offset.to_u8()]
+    }
+
+    /// Offset of local time from UTC in seconds, if known.
+    pub fn utc_offset(&self) -> Option<i32> {
+        if self.time_zone == Self::TIME_ZONE_UNKNOWN {
+            return None;
+        }
+
+        Some(i32::from(self.time_zone) * 900 + self.dst_offset.seconds()?)
+    }
+}
+
+/// Client for a device's Current Time service.
+pub struct CurrentTimeService<C: RemoteCharacteristic> {
+    current_time: C,
+    local_time_information: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> CurrentTimeService<C> {
+    /// Find the characteristics of the Current Time service.
+    ///
+    /// Fails if the service does not have the Current Time characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let current_time = service
+            .characteristic(CURRENT_TIME)?
+            .ok_or(Error::MissingAttribute(CURRENT_TIME))?;
+
+        Ok(Self {
+            current_time,
+            local_time_information: service.characteristic(LOCAL_TIME_INFORMATION)?,
+        })
+    }
+
+    /// Read the device's local time.
+    pub fn current_time(&self) -> Result<CurrentTime> {
+        CurrentTime::decode(&self.current_time.read_value()?).ok_or(Error::InvalidValue)
+    }
+
+    /// Replace the device's local time.
+    pub fn set_current_time(&self, current_time: &CurrentTime) -> Result<()> {
+        self.current_time.write_value(&current_time.encode())
+    }
+
+    /// Set the device's clock from a system time, converted to local time
+    /// using the device's Local Time Information.
+    ///
+    /// Devices without Local Time Information, or which do not know their
+    /// time zone, are set to UTC.
+    pub fn set_time(&self, time: SystemTime) -> Result<()> {
+        let utc_offset = self
+            .local_time_information()?
+            .and_then(|local| local.utc_offset())
+            .unwrap_or(0);
+        let exact_time =
+            ExactTime256::from_system_time(time, utc_offset).ok_or(Error::InvalidValue)?;
+
+        self.set_current_time(&CurrentTime {
+            exact_time,
+            adjust_reason: AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE,
+        })
+    }
+
+    /// Subscribe to changes of the device's time.
+    pub fn changes(&self) -> Result<Notifications<'_, C, CurrentTime>> {
+        Notifications::new(&self.current_time, CurrentTime::decode)
+    }
+
+    /// Read the device's time zone and daylight saving time offset, if the
+    /// device has them.
+    pub fn local_time_information(&self) -> Result<Option<LocalTimeInformation>> {
+        let characteristic = match &self.local_time_information {
+            Some(characteristic) => characteristic,
+            None => return Ok(None),
+        };
+
+        LocalTimeInformation::decode(&characteristic.read_value()?)
+            .map(Some)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Replace the device's time zone and daylight saving time offset.
+    pub fn set_local_time_information(&self, local: &LocalTimeInformation) -> Result<()> {
+        let characteristic = self
+            .local_time_information
+            .as_ref()
+            .ok_or(Error::MissingAttribute(LOCAL_TIME_INFORMATION))?;
+
+        characteristic.write_value(&local.encode())
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for CurrentTimeService<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurrentTimeService")
+            .field("current_time", &self.current_time)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{
+        AdjustReason, CurrentTime, CurrentTimeService, DstOffset, LocalTimeInformation,
+        CURRENT_TIME, LOCAL_TIME_INFORMATION, SERVICE,
+    };
+    use crate::gatt::types::ExactTime256;
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::CharacteristicProperties;
+
+    #[test]
+    fn test_round_trip() {
+        let data = [0xE8, 0x07, 0x03, 0x0F, 0x0C, 0x22, 0x38, 0x05, 0x80, 0x03];
+        let current_time = CurrentTime::decode(&data).unwrap();
+        assert_eq!(current_time.exact_time.day_date_time.date_time.year, 2024);
+        assert_eq!(
+            current_time.adjust_reason,
+            AdjustReason::MANUAL_TIME_UPDATE | AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE
+        );
+        assert_eq!(current_time.encode(), data);
+        assert_eq!(CurrentTime::decode(&data[..9]), None);
+
+        let local = LocalTimeInformation::decode(&[0xEC, 0x04]).unwrap();
+        assert_eq!(local.time_zone, -20);
+        assert_eq!(local.dst_offset, DstOffset::DaylightTime);
+        assert_eq!(local.utc_offset(), Some(-4 * 3600));
+        assert_eq!(local.encode(), [0xEC, 0x04]);
+
+        let unknown = LocalTimeInformation::decode(&[0x80, 0x00]).unwrap();
+        assert_eq!(unknown.utc_offset(), None);
+        assert_eq!(unknown.encode(), [0x80, 0x00]);
+    }
+
+    #[test]
+    fn test_simulated_clock() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        CURRENT_TIME,
+                        CharacteristicProperties::READ
+                            | CharacteristicProperties::WRITE
+                            | CharacteristicProperties::NOTIFY,
+                    )
+                    .with_value(&ExactTime256::default().encode()),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        LOCAL_TIME_INFORMATION,
+                        CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+                    )
+                    .with_value(&[0x00, 0x00]),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let clock = CurrentTimeService::new(&service).unwrap();
+
+        let local = LocalTimeInformation {
+            time_zone: 4,
+            dst_offset: DstOffset::StandardTime,
+        };
+        clock.set_local_time_information(&local).unwrap();
+        assert_eq!(clock.local_time_information().unwrap(), Some(local));
+
+        let time = UNIX_EPOCH + Duration::from_secs(1_710_506_096);
+        clock.set_time(time).unwrap();
+        let current_time = clock.current_time().unwrap();
+        assert_eq!(
+            current_time.adjust_reason,
+            AdjustReason::EXTERNAL_REFERENCE_TIME_UPDATE
+        );
+        assert_eq!(current_time.exact_time.day_date_time.date_time.hours, 13);
+        assert_eq!(current_time.exact_time.to_system_time(3600), Some(time));
+
+        let mut changes = clock.changes().unwrap();
+        let changed = CurrentTime {
+            adjust_reason: AdjustReason::CHANGE_OF_TIME_ZONE,
+            ..current_time
+        };
+        server.notify(SERVICE, CURRENT_TIME, &changed.encode());
+        assert_eq!(
+            changes.next_timeout(Duration::from_millis(50)),
+            Some(changed)
+        );
+    }
+}
@@ -0,0 +1,408 @@
+//! Cycling Power Service, as provided by power meters and smart trainers.
+
+use std::time::Duration;
+
+use super::speed_cadence::{
+    read_sensor_location, CrankRevolutions, SensorLocation, WheelRevolutions, SENSOR_LOCATION,
+};
+use super::{ControlPointClient, Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Cycling Power service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1818);
+/// UUID of the Cycling Power Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A63);
+/// UUID of the Cycling Power Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A65);
+/// UUID of the Cycling Power Control Point characteristic.
+pub const CONTROL_POINT: Uuid = Uuid::from_u16(0x2A66);
+
+const SET_CUMULATIVE_VALUE: u8 = 0x01;
+const UPDATE_SENSOR_LOCATION: u8 = 0x02;
+const REQUEST_SUPPORTED_SENSOR_LOCATIONS: u8 = 0x03;
+const START_OFFSET_COMPENSATION: u8 = 0x0C;
+const RESPONSE_CODE: u8 = 0x20;
+
+bitflags::bitflags! {
+    /// Contents of the Cycling Power Feature characteristic.
+    pub struct CyclingPowerFeatures: u32 {
+        const PEDAL_POWER_BALANCE = 1 << 0;
+        const ACCUMULATED_TORQUE = 1 << 1;
+        const WHEEL_REVOLUTION_DATA = 1 << 2;
+        const CRANK_REVOLUTION_DATA = 1 << 3;
+        const EXTREME_MAGNITUDES = 1 << 4;
+        const EXTREME_ANGLES = 1 << 5;
+        const DEAD_SPOT_ANGLES = 1 << 6;
+        const ACCUMULATED_ENERGY = 1 << 7;
+        const OFFSET_COMPENSATION_INDICATOR = 1 << 8;
+        const OFFSET_COMPENSATION = 1 << 9;
+        const MEASUREMENT_CONTENT_MASKING = 1 << 10;
+        const MULTIPLE_SENSOR_LOCATIONS = 1 << 11;
+        const CRANK_LENGTH_ADJUSTMENT = 1 << 12;
+        const CHAIN_LENGTH_ADJUSTMENT = 1 << 13;
+        const CHAIN_WEIGHT_ADJUSTMENT = 1 << 14;
+        const SPAN_LENGTH_ADJUSTMENT = 1 << 15;
+        const TORQUE_BASED = 1 << 16;
+        const INSTANTANEOUS_MEASUREMENT_DIRECTION = 1 << 17;
+        const FACTORY_CALIBRATION_DATE = 1 << 18;
+        const ENHANCED_OFFSET_COMPENSATION = 1 << 19;
+    }
+}
+
+bitflags::bitflags! {
+    struct Flags: u16 {
+        const PEDAL_POWER_BALANCE = 1 << 0;
+        const PEDAL_POWER_BALANCE_LEFT = 1 << 1;
+        const ACCUMULATED_TORQUE = 1 << 2;
+        const ACCUMULATED_TORQUE_CRANK = 1 << 3;
+        const WHEEL_REVOLUTION_DATA = 1 << 4;
+        const CRANK_REVOLUTION_DATA = 1 << 5;
+        const EXTREME_FORCE_MAGNITUDES = 1 << 6;
+        const EXTREME_TORQUE_MAGNITUDES = 1 << 7;
+        const EXTREME_ANGLES = 1 << 8;
+        const TOP_DEAD_SPOT_ANGLE = 1 << 9;
+        const BOTTOM_DEAD_SPOT_ANGLE = 1 << 10;
+        const ACCUMULATED_ENERGY = 1 << 11;
+        const OFFSET_COMPENSATION_INDICATOR = 1 << 12;
+    }
+}
+
+/// Contents of the Cycling Power Measurement characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CyclingPowerMeasurement {
+    /// Power in watts.
+    pub instantaneous_power: i16,
+    /// Share of the power from one pedal in units of 0.5 percent.
+    pub pedal_power_balance: Option<u8>,
+    /// If the pedal power balance is for the left pedal, otherwise which pedal
+    /// is not known.
+    pub pedal_power_balance_left: bool,
+    /// Torque in units of 1/32 newton meters.
+    pub accumulated_torque: Option<u16>,
+    /// If the accumulated torque is measured at the crank, otherwise it is
+    /// measured at the wheel.
+    pub accumulated_torque_crank: bool,
+    /// Wheel revolutions, with event times in units of 1/2048 seconds.
+    pub wheel_revolutions: Option<WheelRevolutions>,
+    pub crank_revolutions: Option<CrankRevolutions>,
+    /// Force in newtons.
+    pub maximum_force: Option<i16>,
+    /// Force in newtons.
+    pub minimum_force: Option<i16>,
+    /// Torque in units of 1/32 newton meters.
+    pub maximum_torque: Option<i16>,
+    /// Torque in units of 1/32 newton meters.
+    pub minimum_torque: Option<i16>,
+    /// Crank angle of the maximum force or torque in degrees.
+    pub maximum_angle: Option<u16>,
+    /// Crank angle of the minimum force or torque in degrees.
+    pub minimum_angle: Option<u16>,
+    /// Angle in degrees.
+    pub top_dead_spot_angle: Option<u16>,
+    /// Angle in degrees.
+    pub bottom_dead_spot_angle: Option<u16>,
+    /// Energy in kilojoules.
+    pub accumulated_energy: Option<u16>,
+    /// If the sensor is compensating for its offset, and values should not be
+    /// trusted.
+    pub offset_compensation: bool,
+}
+
+impl CyclingPowerMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        type F = Flags;
+
+        let mut r = Reader::new(data);
+        let flags = F::from_bits_truncate(r.u16()?);
+
+        let instantaneous_power = r.i16()?;
+        let pedal_power_balance = r.read_if(flags.contains(F::PEDAL_POWER_BALANCE), Reader::u8)?;
+        let accumulated_torque = r.read_if(flags.contains(F::ACCUMULATED_TORQUE), Reader::u16)?;
+        let wheel_revolutions = r.read_if(flags.contains(F::WHEEL_REVOLUTION_DATA), |r| {
+            Some(WheelRevolutions {
+                cumulative: r.u32()?,
+                last_event_time: r.u16()?,
+                ticks_per_second: 2048,
+            })
+        })?;
+        let crank_revolutions = r.read_if(flags.contains(F::CRANK_REVOLUTION_DATA), |r| {
+            Some(CrankRevolutions {
+                cumulative: r.u16()?,
+                last_event_time: r.u16()?,
+            })
+        })?;
+        let maximum_force = r.read_if(flags.contains(F::EXTREME_FORCE_MAGNITUDES), Reader::i16)?;
+        let minimum_force = r.read_if(flags.contains(F::EXTREME_FORCE_MAGNITUDES), Reader::i16)?;
+        let maximum_torque =
+            r.read_if(flags.contains(F::EXTREME_TORQUE_MAGNITUDES), Reader::i16)?;
+        let minimum_torque =
+            r.read_if(flags.contains(F::EXTREME_TORQUE_MAGNITUDES), Reader::i16)?;
+        let angles = r.read_if(flags.contains(F::EXTREME_ANGLES), Reader::u24)?;
+        let top_dead_spot_angle = r.read_if(flags.contains(F::TOP_DEAD_SPOT_ANGLE), Reader::u16)?;
+        let bottom_dead_spot_angle =
+            r.read_if(flags.contains(F::BOTTOM_DEAD_SPOT_ANGLE), Reader::u16)?;
+        let accumulated_energy = r.read_if(flags.contains(F::ACCUMULATED_ENERGY), Reader::u16)?;
+
+        Some(Self {
+            instantaneous_power,
+            pedal_power_balance,
+            pedal_power_balance_left: flags.contains(F::PEDAL_POWER_BALANCE_LEFT),
+            accumulated_torque,
+            accumulated_torque_crank: flags.contains(F::ACCUMULATED_TORQUE_CRANK),
+            wheel_revolutions,
+            crank_revolutions,
+            maximum_force,
+            minimum_force,
+            maximum_torque,
+            minimum_torque,
+            maximum_angle: angles.map(|angles| angles as u16 & 0x0FFF),
+            minimum_angle: angles.map(|angles| (angles >> 12) as u16),
+            top_dead_spot_angle,
+            bottom_dead_spot_angle,
+            accumulated_energy,
+            offset_compensation: flags.contains(F::OFFSET_COMPENSATION_INDICATOR),
+        })
+    }
+}
+
+/// Client for a device's Cycling Power service.
+pub struct CyclingPower<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: Option<C>,
+    sensor_location: Option<C>,
+    control_point: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> CyclingPower<C> {
+    /// Find the characteristics of the Cycling Power service.
+    ///
+    /// Fails if the service does not have the Cycling Power Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            feature: service.characteristic(FEATURE)?,
+            sensor_location: service.characteristic(SENSOR_LOCATION)?,
+            control_point: service.characteristic(CONTROL_POINT)?,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the sensor to respond to control point
+    /// requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Subscribe to power measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, CyclingPowerMeasurement>> {
+        Notifications::new(&self.measurement, CyclingPowerMeasurement::decode)
+    }
+
+    /// Read which measurements and procedures the sensor supports.
+    pub fn features(&self) -> Result<CyclingPowerFeatures> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Reader::new(&data)
+            .u32()
+            .map(CyclingPowerFeatures::from_bits_truncate)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Read where the sensor is mounted, if the device says.
+    pub fn sensor_location(&self) -> Result<Option<SensorLocation>> {
+        read_sensor_location(self.sensor_location.as_ref())
+    }
+
+    /// Subscribe to control point responses so the sensor can be configured.
+    pub fn control(&self) -> Result<CyclingPowerControlPoint<'_, C>> {
+        let characteristic = self
+            .control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(CONTROL_POINT))?;
+
+        Ok(CyclingPowerControlPoint {
+            client: ControlPointClient::new(characteristic, RESPONSE_CODE, self.timeout)?,
+        })
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for CyclingPower<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CyclingPower")
+            .field("measurement", &self.measurement)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Procedures on the Cycling Power Control Point.
+///
+/// Each request waits for the sensor to indicate its response, failing with
+/// [Error::Timeout] if it does not respond in time, or
+/// [Error::ControlPoint] if it rejects the request.
+pub struct CyclingPowerControlPoint<'a, C: RemoteCharacteristic> {
+    client: ControlPointClient<'a, C>,
+}
+
+impl<C: RemoteCharacteristic> CyclingPowerControlPoint<'_, C> {
+    /// Set the cumulative wheel revolutions.
+    pub fn set_cumulative_value(&mut self, value: u32) -> Result<()> {
+        self.client
+            .execute(SET_CUMULATIVE_VALUE, &value.to_le_bytes())
+            .map(|_parameter| ())
+    }
+
+    /// Tell the sensor where it is mounted.
+    pub fn set_sensor_location(&mut self, location: SensorLocation) -> Result<()> {
+        self.client
+            .execute(UPDATE_SENSOR_LOCATION, &[location.to_u8()])
+            .map(|_parameter| ())
+    }
+
+    /// Ask the sensor where it can be mounted.
+    pub fn supported_sensor_locations(&mut self) -> Result<Vec<SensorLocation>> {
+        let locations = self
+            .client
+            .execute(REQUEST_SUPPORTED_SENSOR_LOCATIONS, &[])?;
+
+        Ok(locations.into_iter().map(SensorLocation::from_u8).collect())
+    }
+
+    /// Calibrate the sensor's zero offset, returning the new offset in the
+    /// sensor's units.
+    pub fn start_offset_compensation(&mut self) -> Result<i16> {
+        let offset = self.client.execute(START_OFFSET_COMPENSATION, &[])?;
+
+        Reader::new(&offset).i16().ok_or(Error::InvalidValue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        CyclingPower, CyclingPowerMeasurement, CONTROL_POINT, FEATURE, MEASUREMENT, SERVICE,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::services::speed_cadence::{
+        CrankRevolutions, SensorLocation, WheelRevolutions, SENSOR_LOCATION,
+    };
+    use crate::{CharacteristicProperties, ControlPointError, Error};
+
+    #[test]
+    fn test_decode_measurement() {
+        // 250 W with a 48% balance from the left pedal, crank revolutions,
+        // and extreme angles of 90 and 270 degrees.
+        let data = [
+            0x23, 0x01, 0xFA, 0x00, 0x60, 0x2A, 0x00, 0x00, 0x04, 0x5A, 0xE0, 0x10,
+        ];
+        assert_eq!(
+            CyclingPowerMeasurement::decode(&data),
+            Some(CyclingPowerMeasurement {
+                instantaneous_power: 250,
+                pedal_power_balance: Some(96),
+                pedal_power_balance_left: true,
+                crank_revolutions: Some(CrankRevolutions {
+                    cumulative: 42,
+                    last_event_time: 0x0400,
+                }),
+                maximum_angle: Some(90),
+                minimum_angle: Some(270),
+                ..Default::default()
+            })
+        );
+        assert_eq!(CyclingPowerMeasurement::decode(&data[..10]), None);
+
+        let data = [
+            0x14, 0x00, 0x64, 0x00, 0x40, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x08,
+        ];
+        let measurement = CyclingPowerMeasurement::decode(&data).unwrap();
+        assert_eq!(measurement.accumulated_torque, Some(64));
+        assert!(!measurement.accumulated_torque_crank);
+        assert_eq!(
+            measurement.wheel_revolutions,
+            Some(WheelRevolutions {
+                cumulative: 10,
+                last_event_time: 0x0800,
+                ticks_per_second: 2048,
+            })
+        );
+    }
+
+    #[test]
+    fn test_control_point() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(FEATURE, CharacteristicProperties::READ)
+                        .with_value(&[0x08, 0x02, 0x00, 0x00]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(SENSOR_LOCATION, CharacteristicProperties::READ)
+                        .with_value(&[0x05]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(|server, value| {
+                        let response: &[u8] = match value {
+                            [0x01, ..] => &[0x20, 0x01, 0x01],
+                            [0x03] => &[0x20, 0x03, 0x01, 0x05, 0x06],
+                            [0x0C] => &[0x20, 0x0C, 0x01, 0xF6, 0xFF],
+                            [op_code, ..] => &[0x20, *op_code, 0x02],
+                            [] => return Ok(()),
+                        };
+
+                        server.indicate(SERVICE, CONTROL_POINT, response);
+                        Ok(())
+                    }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let power = CyclingPower::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        assert_eq!(power.features().unwrap().bits(), 0x0208);
+        assert_eq!(
+            power.sensor_location().unwrap(),
+            Some(SensorLocation::LeftCrank)
+        );
+
+        let mut control = power.control().unwrap();
+        control.set_cumulative_value(0).unwrap();
+        assert_eq!(
+            control.supported_sensor_locations().unwrap(),
+            vec![SensorLocation::LeftCrank, SensorLocation::RightCrank]
+        );
+        assert_eq!(control.start_offset_compensation().unwrap(), -10);
+        assert!(matches!(
+            control.set_sensor_location(SensorLocation::RightCrank),
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x02,
+                result: ControlPointError::OP_CODE_NOT_SUPPORTED,
+            }))
+        ));
+    }
+}
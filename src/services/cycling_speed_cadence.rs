@@ -0,0 +1,238 @@
+//! Cycling Speed and Cadence Service, as provided by wheel and crank sensors.
+
+use std::time::Duration;
+
+use super::speed_cadence::{
+    read_sensor_location, CrankRevolutions, ScControlPoint, SensorLocation, WheelRevolutions,
+    SC_CONTROL_POINT, SENSOR_LOCATION,
+};
+use super::{Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Cycling Speed and Cadence service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1816);
+/// UUID of the CSC Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A5B);
+/// UUID of the CSC Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A5C);
+
+bitflags::bitflags! {
+    /// Contents of the CSC Feature characteristic.
+    pub struct CscFeatures: u16 {
+        const WHEEL_REVOLUTION_DATA = 1 << 0;
+        const CRANK_REVOLUTION_DATA = 1 << 1;
+        const MULTIPLE_SENSOR_LOCATIONS = 1 << 2;
+    }
+}
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const WHEEL_REVOLUTION_DATA = 1 << 0;
+        const CRANK_REVOLUTION_DATA = 1 << 1;
+    }
+}
+
+/// Contents of the CSC Measurement characteristic.
+///
+/// Speed and cadence are found by comparing two measurements, with
+/// [WheelRevolutions::speed_since] and [CrankRevolutions::cadence_since].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CscMeasurement {
+    /// Wheel revolutions, with event times in units of 1/1024 seconds.
+    pub wheel_revolutions: Option<WheelRevolutions>,
+    pub crank_revolutions: Option<CrankRevolutions>,
+}
+
+impl CscMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let wheel_revolutions = r.read_if(flags.contains(Flags::WHEEL_REVOLUTION_DATA), |r| {
+            Some(WheelRevolutions {
+                cumulative: r.u32()?,
+                last_event_time: r.u16()?,
+                ticks_per_second: 1024,
+            })
+        })?;
+        let crank_revolutions = r.read_if(flags.contains(Flags::CRANK_REVOLUTION_DATA), |r| {
+            Some(CrankRevolutions {
+                cumulative: r.u16()?,
+                last_event_time: r.u16()?,
+            })
+        })?;
+
+        Some(Self {
+            wheel_revolutions,
+            crank_revolutions,
+        })
+    }
+}
+
+/// Client for a device's Cycling Speed and Cadence service.
+pub struct CyclingSpeedCadence<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: Option<C>,
+    sensor_location: Option<C>,
+    control_point: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> CyclingSpeedCadence<C> {
+    /// Find the characteristics of the Cycling Speed and Cadence service.
+    ///
+    /// Fails if the service does not have the CSC Measurement characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            feature: service.characteristic(FEATURE)?,
+            sensor_location: service.characteristic(SENSOR_LOCATION)?,
+            control_point: service.characteristic(SC_CONTROL_POINT)?,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the sensor to respond to control point
+    /// requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Subscribe to speed and cadence measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, CscMeasurement>> {
+        Notifications::new(&self.measurement, CscMeasurement::decode)
+    }
+
+    /// Read which measurements the sensor supports.
+    pub fn features(&self) -> Result<CscFeatures> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Reader::new(&data)
+            .u16()
+            .map(CscFeatures::from_bits_truncate)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Read where the sensor is mounted, if the device says.
+    pub fn sensor_location(&self) -> Result<Option<SensorLocation>> {
+        read_sensor_location(self.sensor_location.as_ref())
+    }
+
+    /// Subscribe to control point responses so the sensor can be configured.
+    ///
+    /// The cumulative value is the number of wheel revolutions.
+    pub fn control(&self) -> Result<ScControlPoint<'_, C>> {
+        let characteristic = self
+            .control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SC_CONTROL_POINT))?;
+
+        ScControlPoint::new(characteristic, self.timeout)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for CyclingSpeedCadence<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CyclingSpeedCadence")
+            .field("measurement", &self.measurement)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{CscFeatures, CscMeasurement, CyclingSpeedCadence, FEATURE, MEASUREMENT, SERVICE};
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::services::speed_cadence::{
+        CrankRevolutions, SensorLocation, WheelRevolutions, SC_CONTROL_POINT,
+    };
+    use crate::{CharacteristicProperties, Error};
+
+    #[test]
+    fn test_decode_measurement() {
+        let data = [
+            0x03, 0x10, 0x00, 0x00, 0x00, 0x00, 0x04, 0x05, 0x00, 0x00, 0x08,
+        ];
+        assert_eq!(
+            CscMeasurement::decode(&data),
+            Some(CscMeasurement {
+                wheel_revolutions: Some(WheelRevolutions {
+                    cumulative: 16,
+                    last_event_time: 0x0400,
+                    ticks_per_second: 1024,
+                }),
+                crank_revolutions: Some(CrankRevolutions {
+                    cumulative: 5,
+                    last_event_time: 0x0800,
+                }),
+            })
+        );
+        assert_eq!(CscMeasurement::decode(&data[..9]), None);
+    }
+
+    #[test]
+    fn test_speed_cadence_sensor() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(FEATURE, CharacteristicProperties::READ)
+                        .with_value(&[0x03, 0x00]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        SC_CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(|server, value| {
+                        if let [0x01, ..] = value {
+                            server.indicate(SERVICE, SC_CONTROL_POINT, &[0x10, 0x01, 0x01]);
+                        }
+                        Ok(())
+                    }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let sensor = CyclingSpeedCadence::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        assert_eq!(
+            sensor.features().unwrap(),
+            CscFeatures::WHEEL_REVOLUTION_DATA | CscFeatures::CRANK_REVOLUTION_DATA
+        );
+        assert_eq!(sensor.sensor_location().unwrap(), None);
+
+        let mut measurements = sensor.measurements().unwrap();
+        server.notify(SERVICE, MEASUREMENT, &[0x02, 0xFF, 0xFF, 0x00, 0xFC]);
+        server.notify(SERVICE, MEASUREMENT, &[0x02, 0x01, 0x00, 0x00, 0x01]);
+        let first = measurements.next().unwrap().crank_revolutions.unwrap();
+        let second = measurements.next().unwrap().crank_revolutions.unwrap();
+        assert_eq!(second.cadence_since(&first), Some(96.0));
+
+        let mut control = sensor.control().unwrap();
+        control.set_cumulative_value(0).unwrap();
+        assert!(matches!(
+            control.set_sensor_location(SensorLocation::RearWheel),
+            Err(Error::Timeout)
+        ));
+    }
+}
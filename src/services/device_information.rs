@@ -0,0 +1,261 @@
+//! Device Information Service, provided by most devices to identify
+//! themselves.
+
+use crate::{RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Device Information service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x180A);
+/// UUID of the System ID characteristic.
+pub const SYSTEM_ID: Uuid = Uuid::from_u16(0x2A23);
+/// UUID of the Model Number String characteristic.
+pub const MODEL_NUMBER: Uuid = Uuid::from_u16(0x2A24);
+/// UUID of the Serial Number String characteristic.
+pub const SERIAL_NUMBER: Uuid = Uuid::from_u16(0x2A25);
+/// UUID of the Firmware Revision String characteristic.
+pub const FIRMWARE_REVISION: Uuid = Uuid::from_u16(0x2A26);
+/// UUID of the Hardware Revision String characteristic.
+pub const HARDWARE_REVISION: Uuid = Uuid::from_u16(0x2A27);
+/// UUID of the Software Revision String characteristic.
+pub const SOFTWARE_REVISION: Uuid = Uuid::from_u16(0x2A28);
+/// UUID of the Manufacturer Name String characteristic.
+pub const MANUFACTURER_NAME: Uuid = Uuid::from_u16(0x2A29);
+/// UUID of the IEEE 11073-20601 Regulatory Certification Data List
+/// characteristic.
+pub const REGULATORY_CERTIFICATION: Uuid = Uuid::from_u16(0x2A2A);
+/// UUID of the PnP ID characteristic.
+pub const PNP_ID: Uuid = Uuid::from_u16(0x2A50);
+
+/// Contents of the System ID characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SystemId {
+    /// 40-bit identifier assigned by the manufacturer.
+    pub manufacturer_identifier: u64,
+    /// 24-bit IEEE Organizationally Unique Identifier.
+    pub organizationally_unique_identifier: u32,
+}
+
+impl SystemId {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [m0, m1, m2, m3, m4, o0, o1, o2] => Some(Self {
+                manufacturer_identifier: u64::from_le_bytes([m0, m1, m2, m3, m4, 0, 0, 0]),
+                organizationally_unique_identifier: u32::from_le_bytes([o0, o1, o2, 0]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Organization which assigned a vendor ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VendorIdSource {
+    /// Company identifier assigned by the Bluetooth SIG.
+    Bluetooth,
+    /// Vendor ID assigned by the USB Implementer's Forum.
+    Usb,
+    /// Source not known by this library.
+    Reserved(u8),
+}
+
+impl VendorIdSource {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => VendorIdSource::Bluetooth,
+            2 => VendorIdSource::Usb,
+            other => VendorIdSource::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            VendorIdSource::Bluetooth => 1,
+            VendorIdSource::Usb => 2,
+            VendorIdSource::Reserved(other) => other,
+        }
+    }
+}
+
+/// Contents of the PnP ID characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PnpId {
+    pub vendor_id_source: VendorIdSource,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Product version, as 0xJJMN for version JJ.M.N.
+    pub product_version: u16,
+}
+
+impl PnpId {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [source, v0, v1, p0, p1, r0, r1] => Some(Self {
+                vendor_id_source: VendorIdSource::from_u8(source),
+                vendor_id: u16::from_le_bytes([v0, v1]),
+                product_id: u16::from_le_bytes([p0, p1]),
+                product_version: u16::from_le_bytes([r0, r1]),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Values read from a device's Device Information service.
+///
+/// Characteristics the device does not have are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeviceInformation {
+    pub manufacturer_name: Option<String>,
+    pub model_number: Option<String>,
+    pub serial_number: Option<String>,
+    pub hardware_revision: Option<String>,
+    pub firmware_revision: Option<String>,
+    pub software_revision: Option<String>,
+    pub system_id: Option<SystemId>,
+    pub pnp_id: Option<PnpId>,
+    /// IEEE 11073-20601 Regulatory Certification Data List, left encoded.
+    pub regulatory_certification: Option<Vec<u8>>,
+}
+
+impl DeviceInformation {
+    /// Read every characteristic the service has.
+    ///
+    /// Values which can't be decoded are logged and left out, so one bad
+    /// characteristic doesn't hide the others.
+    pub fn read<S: RemoteService>(service: &S) -> Result<Self> {
+        let read = |uuid| -> Result<Option<Vec<u8>>> {
+            match service.characteristic(uuid)? {
+                Some(characteristic) => characteristic.read_value().map(Some),
+                None => Ok(None),
+            }
+        };
+        let string = |uuid| read(uuid).map(|value| value.map(|value| decode_string(&value)));
+
+        Ok(Self {
+            manufacturer_name: string(MANUFACTURER_NAME)?,
+            model_number: string(MODEL_NUMBER)?,
+            serial_number: string(SERIAL_NUMBER)?,
+            hardware_revision: string(HARDWARE_REVISION)?,
+            firmware_revision: string(FIRMWARE_REVISION)?,
+            software_revision: string(SOFTWARE_REVISION)?,
+            system_id: read(SYSTEM_ID)?
+                .and_then(|value| decode(SYSTEM_ID, &value, SystemId::decode)),
+            pnp_id: read(PNP_ID)?.and_then(|value| decode(PNP_ID, &value, PnpId::decode)),
+            regulatory_certification: read(REGULATORY_CERTIFICATION)?,
+        })
+    }
+}
+
+/// Decode an optional characteristic, logging values which are invalid.
+fn decode<T>(uuid: Uuid, value: &[u8], decode: impl FnOnce(&[u8]) -> Option<T>) -> Option<T> {
+    let decoded = decode(value);
+    if decoded.is_none() {
+        log::warn!("Ignoring invalid value {:?} of {}", value, uuid);
+    }
+
+    decoded
+}
+
+/// Decode a UTF-8 string characteristic, dropping the trailing NULs some
+/// devices pad values with.
+fn decode_string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        PnpId, SystemId, VendorIdSource, MANUFACTURER_NAME, MODEL_NUMBER, PNP_ID, SERVICE,
+        SYSTEM_ID,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error};
+
+    #[test]
+    fn test_decode_ids() {
+        assert_eq!(
+            SystemId::decode(&[0x05, 0x04, 0x03, 0x02, 0x01, 0xC0, 0xB0, 0xA0]),
+            Some(SystemId {
+                manufacturer_identifier: 0x01_0203_0405,
+                organizationally_unique_identifier: 0xA0_B0C0,
+            })
+        );
+        assert_eq!(SystemId::decode(&[0x05, 0x04]), None);
+
+        assert_eq!(
+            PnpId::decode(&[0x02, 0x5E, 0x04, 0x28, 0x07, 0x10, 0x01]),
+            Some(PnpId {
+                vendor_id_source: VendorIdSource::Usb,
+                vendor_id: 0x045E,
+                product_id: 0x0728,
+                product_version: 0x0110,
+            })
+        );
+    }
+
+    #[test]
+    fn test_simulated_device_info() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(MANUFACTURER_NAME, CharacteristicProperties::READ)
+                        .with_value(b"Acme\0\0"),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(MODEL_NUMBER, CharacteristicProperties::READ)
+                        .with_value(b"HR-1"),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(PNP_ID, CharacteristicProperties::READ)
+                        .with_value(&[0x01, 0x59, 0x00, 0x01, 0x00, 0x00, 0x02]),
+                ),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server);
+
+        let device = sim.connect(address).unwrap();
+        let info = device.device_info().unwrap();
+        assert_eq!(info.manufacturer_name.as_deref(), Some("Acme"));
+        assert_eq!(info.model_number.as_deref(), Some("HR-1"));
+        assert_eq!(info.serial_number, None);
+        assert_eq!(info.system_id, None);
+        assert_eq!(
+            info.pnp_id.unwrap().vendor_id_source,
+            VendorIdSource::Bluetooth
+        );
+        assert_eq!(info.pnp_id.unwrap().product_version, 0x0200);
+
+        // A malformed value doesn't hide the others.
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(MANUFACTURER_NAME, CharacteristicProperties::READ)
+                        .with_value(b"Acme"),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(SYSTEM_ID, CharacteristicProperties::READ)
+                        .with_value(&[0]),
+                ),
+        );
+        sim.add_peripheral(BluetoothAddress(1), server);
+
+        let device = sim.connect(BluetoothAddress(1)).unwrap();
+        let info = device.device_info().unwrap();
+        assert_eq!(info.manufacturer_name.as_deref(), Some("Acme"));
+        assert_eq!(info.system_id, None);
+
+        let sim = Simulator::new();
+        sim.add_peripheral(address, GattServer::new());
+        let device = sim.connect(address).unwrap();
+        assert!(matches!(
+            device.device_info(),
+            Err(Error::MissingAttribute(SERVICE))
+        ));
+    }
+}
@@ -0,0 +1,764 @@
+//! Environmental Sensing Service, as provided by weather stations and
+//! building sensors.
+//!
+//! A device may have several characteristics for the same quantity, such as
+//! indoor and outdoor temperatures, each described by its own descriptors.
+
+use super::Notifications;
+use crate::gatt::types::{scale, SFloat};
+use crate::gatt::{DescriptorValue, Format, Value, USER_DESCRIPTION};
+use crate::{Error, RemoteCharacteristic, RemoteDescriptor, RemoteService, Result, Uuid};
+
+/// UUID of the Environmental Sensing service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x181A);
+/// UUID of the Environmental Sensing Configuration descriptor.
+pub const ES_CONFIGURATION: Uuid = Uuid::from_u16(0x290B);
+/// UUID of the Environmental Sensing Measurement descriptor.
+pub const ES_MEASUREMENT: Uuid = Uuid::from_u16(0x290C);
+/// UUID of the Environmental Sensing Trigger Setting descriptor.
+pub const ES_TRIGGER_SETTING: Uuid = Uuid::from_u16(0x290D);
+
+/// Quantity measured by an Environmental Sensing characteristic.
+///
+/// Values are in the units given by each variant, after scaling.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Quantity {
+    /// Degrees.
+    ApparentWindDirection,
+    /// Meters per second.
+    ApparentWindSpeed,
+    /// Degrees Celsius.
+    DewPoint,
+    /// Meters.
+    Elevation,
+    GustFactor,
+    /// Degrees Celsius.
+    HeatIndex,
+    /// Percent.
+    Humidity,
+    /// Watts per square meter.
+    Irradiance,
+    /// Degrees.
+    MagneticDeclination,
+    /// Count per cubic meter.
+    PollenConcentration,
+    /// Pascals.
+    Pressure,
+    /// Meters.
+    Rainfall,
+    /// Degrees Celsius.
+    Temperature,
+    /// Degrees.
+    TrueWindDirection,
+    /// Meters per second.
+    TrueWindSpeed,
+    UvIndex,
+    /// Degrees Celsius.
+    WindChill,
+    /// Kilograms per cubic meter.
+    AmmoniaConcentration,
+    /// Kilograms per cubic meter.
+    CarbonMonoxideConcentration,
+    /// Parts per million.
+    Co2Concentration,
+    /// Kilograms per cubic meter.
+    MethaneConcentration,
+    /// Kilograms per cubic meter.
+    NitrogenDioxideConcentration,
+    /// Kilograms per cubic meter.
+    NonMethaneVocConcentration,
+    /// Kilograms per cubic meter.
+    OzoneConcentration,
+    /// Kilograms per cubic meter.
+    Pm1Concentration,
+    /// Kilograms per cubic meter.
+    Pm25Concentration,
+    /// Kilograms per cubic meter.
+    Pm10Concentration,
+    /// Kilograms per cubic meter.
+    SulfurDioxideConcentration,
+    /// Parts per billion.
+    VocConcentration,
+}
+
+impl Quantity {
+    /// Every quantity known by this library.
+    pub const ALL: [Quantity; 29] = [
+        Quantity::ApparentWindDirection,
+        Quantity::ApparentWindSpeed,
+        Quantity::DewPoint,
+        Quantity::Elevation,
+        Quantity::GustFactor,
+        Quantity::HeatIndex,
+        Quantity::Humidity,
+        Quantity::Irradiance,
+        Quantity::MagneticDeclination,
+        Quantity::PollenConcentration,
+        Quantity::Pressure,
+        Quantity::Rainfall,
+        Quantity::Temperature,
+        Quantity::TrueWindDirection,
+        Quantity::TrueWindSpeed,
+        Quantity::UvIndex,
+        Quantity::WindChill,
+        Quantity::AmmoniaConcentration,
+        Quantity::CarbonMonoxideConcentration,
+        Quantity::Co2Concentration,
+        Quantity::MethaneConcentration,
+        Quantity::NitrogenDioxideConcentration,
+        Quantity::NonMethaneVocConcentration,
+        Quantity::OzoneConcentration,
+        Quantity::Pm1Concentration,
+        Quantity::Pm25Concentration,
+        Quantity::Pm10Concentration,
+        Quantity::SulfurDioxideConcentration,
+        Quantity::VocConcentration,
+    ];
+
+    /// Find the quantity measured by a characteristic.
+    pub fn from_uuid(uuid: Uuid) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|quantity| quantity.uuid() == uuid)
+    }
+
+    /// UUID of the characteristic measuring this quantity.
+    pub fn uuid(self) -> Uuid {
+        let uuid = match self {
+            Quantity::ApparentWindDirection => 0x2A73,
+            Quantity::ApparentWindSpeed => 0x2A72,
+            Quantity::DewPoint => 0x2A7B,
+            Quantity::Elevation => 0x2A6C,
+            Quantity::GustFactor => 0x2A74,
+            Quantity::HeatIndex => 0x2A7A,
+            Quantity::Humidity => 0x2A6F,
+            Quantity::Irradiance => 0x2A77,
+            Quantity::MagneticDeclination => 0x2A2C,
+            Quantity::PollenConcentration => 0x2A75,
+            Quantity::Pressure => 0x2A6D,
+            Quantity::Rainfall => 0x2A78,
+            Quantity::Temperature => 0x2A6E,
+            Quantity::TrueWindDirection => 0x2A71,
+            Quantity::TrueWindSpeed => 0x2A70,
+            Quantity::UvIndex => 0x2A76,
+            Quantity::WindChill => 0x2A79,
+            Quantity::AmmoniaConcentration => 0x2BCF,
+            Quantity::CarbonMonoxideConcentration => 0x2BD0,
+            Quantity::Co2Concentration => 0x2B8C,
+            Quantity::MethaneConcentration => 0x2BD1,
+            Quantity::NitrogenDioxideConcentration => 0x2BD2,
+            Quantity::NonMethaneVocConcentration => 0x2BD3,
+            Quantity::OzoneConcentration => 0x2BD4,
+            Quantity::Pm1Concentration => 0x2BD5,
+            Quantity::Pm25Concentration => 0x2BD6,
+            Quantity::Pm10Concentration => 0x2BD7,
+            Quantity::SulfurDioxideConcentration => 0x2BD8,
+            Quantity::VocConcentration => 0x2BE7,
+        };
+
+        Uuid::from_u16(uuid)
+    }
+
+    /// Format of the characteristic value and the base 10 exponent applied
+    /// to integer formats.
+    fn format(self) -> (Format, i32) {
+        match self {
+            Quantity::ApparentWindDirection
+            | Quantity::ApparentWindSpeed
+            | Quantity::Humidity
+            | Quantity::MagneticDeclination
+            | Quantity::TrueWindDirection
+            | Quantity::TrueWindSpeed => (Format::UInt16, -2),
+            Quantity::DewPoint | Quantity::HeatIndex | Quantity::WindChill => (Format::SInt8, 0),
+            Quantity::Elevation => (Format::SInt24, -2),
+            Quantity::GustFactor => (Format::UInt8, -1),
+            Quantity::Irradiance => (Format::UInt16, -1),
+            Quantity::PollenConcentration => (Format::UInt24, 0),
+            Quantity::Pressure => (Format::UInt32, -1),
+            Quantity::Rainfall => (Format::UInt16, -3),
+            Quantity::Temperature => (Format::SInt16, -2),
+            Quantity::UvIndex => (Format::UInt8, 0),
+            Quantity::Co2Concentration | Quantity::VocConcentration => (Format::UInt16, 0),
+            Quantity::AmmoniaConcentration
+            | Quantity::CarbonMonoxideConcentration
+            | Quantity::MethaneConcentration
+            | Quantity::NitrogenDioxideConcentration
+            | Quantity::NonMethaneVocConcentration
+            | Quantity::OzoneConcentration
+            | Quantity::Pm1Concentration
+            | Quantity::Pm25Concentration
+            | Quantity::Pm10Concentration
+            | Quantity::SulfurDioxideConcentration => (Format::SFloat, 0),
+        }
+    }
+
+    /// Decode a characteristic value, applying its scaling.
+    pub fn decode(self, data: &[u8]) -> Option<f64> {
+        let (format, exponent) = self.format();
+
+        match Value::decode(format, data)? {
+            Value::SFloat(value) => Some(value.to_f64()),
+            value => Some(scale(value.as_f64()?, exponent)),
+        }
+    }
+
+    /// Encode a value, rounding it to the characteristic's resolution.
+    ///
+    /// Returns `None` if the value does not fit.
+    pub fn encode(self, value: f64) -> Option<Vec<u8>> {
+        let (format, exponent) = self.format();
+
+        let value = if format == Format::SFloat {
+            Value::SFloat(SFloat::from_f64(value)?)
+        } else {
+            let raw = scale(value, -exponent).round();
+            if !raw.is_finite() {
+                return None;
+            }
+
+            let signed = matches!(format, Format::SInt8 | Format::SInt16 | Format::SInt24);
+            if signed || raw < 0.0 {
+                Value::Signed(raw as i128)
+            } else {
+                Value::Unsigned(raw as u128)
+            }
+        };
+
+        value.encode(format)
+    }
+}
+
+/// How a measurement is derived from its samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SamplingFunction {
+    Unspecified,
+    Instantaneous,
+    ArithmeticMean,
+    Rms,
+    Maximum,
+    Minimum,
+    Accumulated,
+    Count,
+    /// Function not known by this library.
+    Reserved(u8),
+}
+
+impl SamplingFunction {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => SamplingFunction::Unspecified,
+            1 => SamplingFunction::Instantaneous,
+            2 => SamplingFunction::ArithmeticMean,
+            3 => SamplingFunction::Rms,
+            4 => SamplingFunction::Maximum,
+            5 => SamplingFunction::Minimum,
+            6 => SamplingFunction::Accumulated,
+            7 => SamplingFunction::Count,
+            other => SamplingFunction::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SamplingFunction::Unspecified => 0,
+            SamplingFunction::Instantaneous => 1,
+            SamplingFunction::ArithmeticMean => 2,
+            SamplingFunction::Rms => 3,
+            SamplingFunction::Maximum => 4,
+            SamplingFunction::Minimum => 5,
+            SamplingFunction::Accumulated => 6,
+            SamplingFunction::Count => 7,
+            SamplingFunction::Reserved(other) => other,
+        }
+    }
+}
+
+/// Contents of the Environmental Sensing Measurement descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EsMeasurement {
+    pub sampling_function: SamplingFunction,
+    /// Period the samples are taken over in seconds, with 0 meaning not in
+    /// use.
+    pub measurement_period: u32,
+    /// Seconds between updates of the value, with 0 meaning not in use.
+    pub update_interval: u32,
+    /// Where the measurement applies, such as 0x13 for outdoors or 0x14 for
+    /// indoors.
+    pub application: u8,
+    /// Uncertainty in units of 0.5 percent, with 0xFF meaning unknown.
+    pub uncertainty: u8,
+}
+
+impl EsMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [_, _, sampling_function, p0, p1, p2, i0, i1, i2, application, uncertainty] => {
+                Some(Self {
+                    sampling_function: SamplingFunction::from_u8(sampling_function),
+                    measurement_period: u32::from_le_bytes([p0, p1, p2, 0]),
+                    update_interval: u32::from_le_bytes([i0, i1, i2, 0]),
+                    application,
+                    uncertainty,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Contents of an Environmental Sensing Trigger Setting descriptor, telling
+/// the device when to notify the value.
+///
+/// Intervals are in seconds. Compared values are in the units of the
+/// characteristic's [Quantity].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerSetting {
+    Inactive,
+    /// Notify at a fixed interval.
+    FixedInterval(u32),
+    /// Notify when the value changes, but no more often than the interval.
+    MinimumInterval(u32),
+    ValueChanged,
+    LessThan(f64),
+    LessThanOrEqual(f64),
+    GreaterThan(f64),
+    GreaterThanOrEqual(f64),
+    Equal(f64),
+    NotEqual(f64),
+    /// Condition not known by this library.
+    Reserved(u8),
+}
+
+impl TriggerSetting {
+    pub fn decode(quantity: Quantity, data: &[u8]) -> Option<Self> {
+        let (&condition, operand) = data.split_first()?;
+
+        let interval = || match *operand {
+            [i0, i1, i2] => Some(u32::from_le_bytes([i0, i1, i2, 0])),
+            _ => None,
+        };
+        let value = || quantity.decode(operand);
+
+        let setting = match condition {
+            0x00 => TriggerSetting::Inactive,
+            0x01 => TriggerSetting::FixedInterval(interval()?),
+            0x02 => TriggerSetting::MinimumInterval(interval()?),
+            0x03 => TriggerSetting::ValueChanged,
+            0x04 => TriggerSetting::LessThan(value()?),
+            0x05 => TriggerSetting::LessThanOrEqual(value()?),
+            0x06 => TriggerSetting::GreaterThan(value()?),
+            0x07 => TriggerSetting::GreaterThanOrEqual(value()?),
+            0x08 => TriggerSetting::Equal(value()?),
+            0x09 => TriggerSetting::NotEqual(value()?),
+            other => TriggerSetting::Reserved(other),
+        };
+
+        Some(setting)
+    }
+
+    /// Encode the descriptor value.
+    ///
+    /// Returns `None` if an interval is longer than 24 bits or a value does
+    /// not fit the characteristic's format.
+    pub fn encode(&self, quantity: Quantity) -> Option<Vec<u8>> {
+        let interval = |condition: u8, seconds: u32| {
+            if seconds > 0x00FF_FFFF {
+                return None;
+            }
+
+            let mut data = vec![condition];
+            data.extend_from_slice(&seconds.to_le_bytes()[..3]);
+            Some(data)
+        };
+        let value = |condition: u8, value: f64| {
+            let mut data = vec![condition];
+            data.extend(quantity.encode(value)?);
+            Some(data)
+        };
+
+        match *self {
+            TriggerSetting::Inactive => Some(vec![0x00]),
+            TriggerSetting::FixedInterval(seconds) => interval(0x01, seconds),
+            TriggerSetting::MinimumInterval(seconds) => interval(0x02, seconds),
+            TriggerSetting::ValueChanged => Some(vec![0x03]),
+            TriggerSetting::LessThan(operand) => value(0x04, operand),
+            TriggerSetting::LessThanOrEqual(operand) => value(0x05, operand),
+            TriggerSetting::GreaterThan(operand) => value(0x06, operand),
+            TriggerSetting::GreaterThanOrEqual(operand) => value(0x07, operand),
+            TriggerSetting::Equal(operand) => value(0x08, operand),
+            TriggerSetting::NotEqual(operand) => value(0x09, operand),
+            TriggerSetting::Reserved(condition) => Some(vec![condition]),
+        }
+    }
+}
+
+/// Contents of the Environmental Sensing Configuration descriptor, telling
+/// how multiple trigger settings are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TriggerLogic {
+    And,
+    Or,
+    /// Logic not known by this library.
+    Reserved(u8),
+}
+
+impl TriggerLogic {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => TriggerLogic::And,
+            1 => TriggerLogic::Or,
+            other => TriggerLogic::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            TriggerLogic::And => 0,
+            TriggerLogic::Or => 1,
+            TriggerLogic::Reserved(other) => other,
+        }
+    }
+}
+
+/// One Environmental Sensing characteristic and its descriptors.
+pub struct Sensor<C: RemoteCharacteristic> {
+    quantity: Quantity,
+    characteristic: C,
+}
+
+impl<C: RemoteCharacteristic> Sensor<C> {
+    /// Quantity measured by this sensor.
+    pub fn quantity(&self) -> Quantity {
+        self.quantity
+    }
+
+    /// Characteristic providing the value.
+    pub fn characteristic(&self) -> &C {
+        &self.characteristic
+    }
+
+    /// Read the current value.
+    pub fn read(&self) -> Result<f64> {
+        self.quantity
+            .decode(&self.characteristic.read_value()?)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Subscribe to values, sent as configured by the trigger settings.
+    pub fn values(&self) -> Result<Notifications<'_, C, f64>> {
+        let quantity = self.quantity;
+
+        Notifications::new(&self.characteristic, move |data| quantity.decode(data))
+    }
+
+    /// Read the Characteristic User Description, which often tells apart
+    /// sensors for the same quantity.
+    pub fn description(&self) -> Result<Option<String>> {
+        let descriptor = match self.characteristic.descriptor(USER_DESCRIPTION)? {
+            Some(descriptor) => descriptor,
+            None => return Ok(None),
+        };
+
+        match descriptor.read_decoded()? {
+            DescriptorValue::UserDescription(description) => Ok(Some(description)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Read the Environmental Sensing Measurement descriptor, if present.
+    pub fn measurement(&self) -> Result<Option<EsMeasurement>> {
+        let descriptor = match self.characteristic.descriptor(ES_MEASUREMENT)? {
+            Some(descriptor) => descriptor,
+            None => return Ok(None),
+        };
+
+        EsMeasurement::decode(&descriptor.read_value()?)
+            .map(Some)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Read every trigger setting, in order.
+    pub fn trigger_settings(&self) -> Result<Vec<TriggerSetting>> {
+        self.trigger_setting_descriptors()?
+            .iter()
+            .map(|descriptor| {
+                TriggerSetting::decode(self.quantity, &descriptor.read_value()?)
+                    .ok_or(Error::InvalidValue)
+            })
+            .collect()
+    }
+
+    /// Replace the trigger setting at the given index.
+    ///
+    /// Fails with [Error::MissingAttribute] if the sensor has fewer trigger
+    /// settings.
+    pub fn set_trigger_setting(&self, index: usize, setting: &TriggerSetting) -> Result<()> {
+        let descriptor = self
+            .trigger_setting_descriptors()?
+            .into_iter()
+            .nth(index)
+            .ok_or(Error::MissingAttribute(ES_TRIGGER_SETTING))?;
+        let data = setting.encode(self.quantity).ok_or(Error::InvalidValue)?;
+
+        descriptor.write_value(&data)
+    }
+
+    /// Read how trigger settings are combined, if there are more than one.
+    pub fn trigger_logic(&self) -> Result<Option<TriggerLogic>> {
+        let descriptor = match self.characteristic.descriptor(ES_CONFIGURATION)? {
+            Some(descriptor) => descriptor,
+            None => return Ok(None),
+        };
+
+        match descriptor.read_value()?.as_slice() {
+            [logic] => Ok(Some(TriggerLogic::from_u8(*logic))),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Change how trigger settings are combined.
+    pub fn set_trigger_logic(&self, logic: TriggerLogic) -> Result<()> {
+        let descriptor = self
+            .characteristic
+            .descriptor(ES_CONFIGURATION)?
+            .ok_or(Error::MissingAttribute(ES_CONFIGURATION))?;
+
+        descriptor.write_value(&[logic.to_u8()])
+    }
+
+    fn trigger_setting_descriptors(&self) -> Result<Vec<C::Descriptor>> {
+        let mut descriptors = Vec::new();
+        for descriptor in self.characteristic.descriptors()? {
+            if descriptor.uuid()? == ES_TRIGGER_SETTING {
+                descriptors.push(descriptor);
+            }
+        }
+
+        Ok(descriptors)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Sensor<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sensor")
+            .field("quantity", &self.quantity)
+            .field("characteristic", &self.characteristic)
+            .finish()
+    }
+}
+
+/// Client for a device's Environmental Sensing service.
+pub struct EnvironmentalSensing<C: RemoteCharacteristic> {
+    sensors: Vec<Sensor<C>>,
+}
+
+impl<C: RemoteCharacteristic> EnvironmentalSensing<C> {
+    /// Find every characteristic of the Environmental Sensing service.
+    ///
+    /// Characteristics for quantities not known by this library are skipped.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let mut sensors = Vec::new();
+        for characteristic in service.characteristics()? {
+            match Quantity::from_uuid(characteristic.uuid()?) {
+                Some(quantity) => sensors.push(Sensor {
+                    quantity,
+                    characteristic,
+                }),
+                None => log::debug!(
+                    "Skipping unknown characteristic {:?}",
+                    characteristic.uuid()
+                ),
+            }
+        }
+
+        Ok(Self { sensors })
+    }
+
+    /// Every sensor on the device, in the order they were discovered.
+    pub fn sensors(&self) -> &[Sensor<C>] {
+        &self.sensors
+    }
+
+    /// Find the first sensor for the given quantity.
+    pub fn sensor(&self, quantity: Quantity) -> Option<&Sensor<C>> {
+        self.sensors
+            .iter()
+            .find(|sensor| sensor.quantity == quantity)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for EnvironmentalSensing<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EnvironmentalSensing")
+            .field("sensors", &self.sensors)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        EnvironmentalSensing, EsMeasurement, Quantity, SamplingFunction, TriggerLogic,
+        TriggerSetting, ES_CONFIGURATION, ES_MEASUREMENT, ES_TRIGGER_SETTING, SERVICE,
+    };
+    use crate::gatt::{AttError, GattServer, LocalCharacteristic, LocalDescriptor, LocalService};
+    use crate::services::connect;
+    use crate::{CharacteristicProperties, Error, Uuid};
+
+    #[test]
+    fn test_decode_values() {
+        assert_eq!(Quantity::Temperature.decode(&[0x4C, 0xF9]), Some(-17.16));
+        assert_eq!(Quantity::Humidity.decode(&[0x9A, 0x10]), Some(42.5));
+        assert_eq!(
+            Quantity::Pressure.decode(&[0x06, 0x76, 0x0F, 0x00]),
+            Some(101_325.4)
+        );
+        assert_eq!(Quantity::Elevation.decode(&[0x9C, 0xFF, 0xFF]), Some(-1.0));
+        assert_eq!(
+            Quantity::Co2Concentration.decode(&[0x20, 0x03]),
+            Some(800.0)
+        );
+        assert_eq!(
+            Quantity::Pm25Concentration.decode(&[0x0C, 0x80]),
+            Some(1.2e-7)
+        );
+        assert_eq!(Quantity::Temperature.decode(&[0x4C]), None);
+
+        assert_eq!(Quantity::Temperature.encode(-17.16), Some(vec![0x4C, 0xF9]));
+        assert_eq!(Quantity::Humidity.encode(-1.0), None);
+        assert_eq!(Quantity::UvIndex.encode(256.0), None);
+        assert_eq!(
+            Quantity::from_uuid(Uuid::from_u16(0x2A6E)),
+            Some(Quantity::Temperature)
+        );
+    }
+
+    #[test]
+    fn test_trigger_settings() {
+        let setting = TriggerSetting::decode(Quantity::Temperature, &[0x06, 0xC4, 0x09]).unwrap();
+        assert_eq!(setting, TriggerSetting::GreaterThan(25.0));
+        assert_eq!(
+            setting.encode(Quantity::Temperature),
+            Some(vec![0x06, 0xC4, 0x09])
+        );
+
+        let setting = TriggerSetting::FixedInterval(600);
+        assert_eq!(
+            setting.encode(Quantity::Humidity),
+            Some(vec![0x01, 0x58, 0x02, 0x00])
+        );
+        assert_eq!(
+            TriggerSetting::decode(Quantity::Humidity, &[0x01, 0x58, 0x02, 0x00]),
+            Some(setting)
+        );
+
+        assert_eq!(
+            TriggerSetting::decode(Quantity::Humidity, &[0x03]),
+            Some(TriggerSetting::ValueChanged)
+        );
+        assert_eq!(
+            TriggerSetting::decode(Quantity::Humidity, &[0x01, 0x58]),
+            None
+        );
+        assert_eq!(
+            TriggerSetting::MinimumInterval(0x0100_0000).encode(Quantity::Humidity),
+            None
+        );
+    }
+
+    #[test]
+    fn test_simulated_sensor() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        Quantity::Temperature.uuid(),
+                        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+                    )
+                    .with_value(&[0xD4, 0x08])
+                    .with_descriptor(LocalDescriptor::new(
+                        ES_MEASUREMENT,
+                        &[
+                            0x00, 0x00, 0x02, 0x3C, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x14, 0x02,
+                        ],
+                    ))
+                    .with_descriptor(LocalDescriptor::new(ES_TRIGGER_SETTING, &[0x03]).writable())
+                    .with_descriptor(LocalDescriptor::new(ES_TRIGGER_SETTING, &[0x00]).writable())
+                    .with_descriptor(LocalDescriptor::new(ES_CONFIGURATION, &[0x00]).writable()),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        Quantity::Humidity.uuid(),
+                        CharacteristicProperties::READ,
+                    )
+                    .with_value(&[0x9A, 0x10])
+                    .with_descriptor(LocalDescriptor::new(ES_TRIGGER_SETTING, &[0x00])),
+                )
+                .with_characteristic(LocalCharacteristic::new(
+                    Uuid::from_u16(0x2A7D),
+                    CharacteristicProperties::INDICATE,
+                )),
+        );
+
+        let service = connect(&server, SERVICE);
+        let ess = EnvironmentalSensing::new(&service).unwrap();
+
+        let quantities: Vec<_> = ess
+            .sensors()
+            .iter()
+            .map(|sensor| sensor.quantity())
+            .collect();
+        assert_eq!(quantities, vec![Quantity::Temperature, Quantity::Humidity]);
+
+        let temperature = ess.sensor(Quantity::Temperature).unwrap();
+        assert_eq!(temperature.read().unwrap(), 22.6);
+        assert_eq!(
+            temperature.measurement().unwrap(),
+            Some(EsMeasurement {
+                sampling_function: SamplingFunction::ArithmeticMean,
+                measurement_period: 60,
+                update_interval: 10,
+                application: 0x14,
+                uncertainty: 2,
+            })
+        );
+        assert_eq!(
+            temperature.trigger_settings().unwrap(),
+            vec![TriggerSetting::ValueChanged, TriggerSetting::Inactive]
+        );
+
+        temperature
+            .set_trigger_setting(1, &TriggerSetting::LessThan(18.0))
+            .unwrap();
+        temperature.set_trigger_logic(TriggerLogic::Or).unwrap();
+        assert_eq!(
+            temperature.trigger_settings().unwrap(),
+            vec![TriggerSetting::ValueChanged, TriggerSetting::LessThan(18.0)]
+        );
+        assert_eq!(temperature.trigger_logic().unwrap(), Some(TriggerLogic::Or));
+        assert_eq!(
+            server.descriptor_value(SERVICE, Quantity::Temperature.uuid(), ES_CONFIGURATION),
+            Some(vec![0x01])
+        );
+        assert!(matches!(
+            temperature.set_trigger_setting(2, &TriggerSetting::Inactive),
+            Err(Error::MissingAttribute(ES_TRIGGER_SETTING))
+        ));
+
+        let mut values = temperature.values().unwrap();
+        server.notify(SERVICE, Quantity::Temperature.uuid(), &[0x07, 0x07]);
+        assert_eq!(values.next_timeout(Duration::from_millis(50)), Some(17.99));
+
+        let humidity = ess.sensor(Quantity::Humidity).unwrap();
+        assert_eq!(humidity.read().unwrap(), 42.5);
+        assert_eq!(humidity.trigger_logic().unwrap(), None);
+        assert!(matches!(
+            humidity.set_trigger_setting(0, &TriggerSetting::ValueChanged),
+            Err(Error::Att(AttError::WRITE_NOT_PERMITTED))
+        ));
+    }
+}
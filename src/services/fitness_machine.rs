@@ -0,0 +1,1055 @@
+//! Fitness Machine Service, as provided by smart trainers, treadmills, and
+//! rowers.
+//!
+//! Values are kept in the units sent by the machine, which are noted on each
+//! field.
+
+use std::time::Duration;
+
+use super::{ControlPointClient, Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::{ControlPointError, Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Fitness Machine service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1826);
+/// UUID of the Fitness Machine Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2ACC);
+/// UUID of the Treadmill Data characteristic.
+pub const TREADMILL_DATA: Uuid = Uuid::from_u16(0x2ACD);
+/// UUID of the Cross Trainer Data characteristic.
+pub const CROSS_TRAINER_DATA: Uuid = Uuid::from_u16(0x2ACE);
+/// UUID of the Rower Data characteristic.
+pub const ROWER_DATA: Uuid = Uuid::from_u16(0x2AD1);
+/// UUID of the Indoor Bike Data characteristic.
+pub const INDOOR_BIKE_DATA: Uuid = Uuid::from_u16(0x2AD2);
+/// UUID of the Supported Speed Range characteristic.
+pub const SUPPORTED_SPEED_RANGE: Uuid = Uuid::from_u16(0x2AD4);
+/// UUID of the Supported Inclination Range characteristic.
+pub const SUPPORTED_INCLINATION_RANGE: Uuid = Uuid::from_u16(0x2AD5);
+/// UUID of the Supported Resistance Level Range characteristic.
+pub const SUPPORTED_RESISTANCE_LEVEL_RANGE: Uuid = Uuid::from_u16(0x2AD6);
+/// UUID of the Supported Heart Rate Range characteristic.
+pub const SUPPORTED_HEART_RATE_RANGE: Uuid = Uuid::from_u16(0x2AD7);
+/// UUID of the Supported Power Range characteristic.
+pub const SUPPORTED_POWER_RANGE: Uuid = Uuid::from_u16(0x2AD8);
+/// UUID of the Fitness Machine Control Point characteristic.
+pub const CONTROL_POINT: Uuid = Uuid::from_u16(0x2AD9);
+
+/// Result code for requests which need control to be requested first.
+pub const CONTROL_NOT_PERMITTED: u8 = 0x05;
+
+const REQUEST_CONTROL: u8 = 0x00;
+const RESET: u8 = 0x01;
+const SET_TARGET_SPEED: u8 = 0x02;
+const SET_TARGET_INCLINATION: u8 = 0x03;
+const SET_TARGET_RESISTANCE_LEVEL: u8 = 0x04;
+const SET_TARGET_POWER: u8 = 0x05;
+const START_OR_RESUME: u8 = 0x07;
+const STOP_OR_PAUSE: u8 = 0x08;
+const SET_INDOOR_BIKE_SIMULATION: u8 = 0x11;
+const RESPONSE_CODE: u8 = 0x80;
+
+bitflags::bitflags! {
+    /// Measurements supported by a fitness machine.
+    pub struct MachineFeatures: u32 {
+        const AVERAGE_SPEED = 1 << 0;
+        const CADENCE = 1 << 1;
+        const TOTAL_DISTANCE = 1 << 2;
+        const INCLINATION = 1 << 3;
+        const ELEVATION_GAIN = 1 << 4;
+        const PACE = 1 << 5;
+        const STEP_COUNT = 1 << 6;
+        const RESISTANCE_LEVEL = 1 << 7;
+        const STRIDE_COUNT = 1 << 8;
+        const EXPENDED_ENERGY = 1 << 9;
+        const HEART_RATE = 1 << 10;
+        const METABOLIC_EQUIVALENT = 1 << 11;
+        const ELAPSED_TIME = 1 << 12;
+        const REMAINING_TIME = 1 << 13;
+        const POWER = 1 << 14;
+        const FORCE_ON_BELT_AND_POWER_OUTPUT = 1 << 15;
+        const USER_DATA_RETENTION = 1 << 16;
+    }
+}
+
+bitflags::bitflags! {
+    /// Targets which can be set on a fitness machine.
+    pub struct TargetSettingFeatures: u32 {
+        const SPEED = 1 << 0;
+        const INCLINATION = 1 << 1;
+        const RESISTANCE_LEVEL = 1 << 2;
+        const POWER = 1 << 3;
+        const HEART_RATE = 1 << 4;
+        const EXPENDED_ENERGY = 1 << 5;
+        const STEP_NUMBER = 1 << 6;
+        const STRIDE_NUMBER = 1 << 7;
+        const DISTANCE = 1 << 8;
+        const TRAINING_TIME = 1 << 9;
+        const TIME_IN_TWO_HEART_RATE_ZONES = 1 << 10;
+        const TIME_IN_THREE_HEART_RATE_ZONES = 1 << 11;
+        const TIME_IN_FIVE_HEART_RATE_ZONES = 1 << 12;
+        const INDOOR_BIKE_SIMULATION = 1 << 13;
+        const WHEEL_CIRCUMFERENCE = 1 << 14;
+        const SPIN_DOWN = 1 << 15;
+        const CADENCE = 1 << 16;
+    }
+}
+
+/// Contents of the Fitness Machine Feature characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Features {
+    pub machine: MachineFeatures,
+    pub target_setting: TargetSettingFeatures,
+}
+
+impl Features {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(data);
+        let machine = MachineFeatures::from_bits_truncate(reader.u32()?);
+        let target_setting = TargetSettingFeatures::from_bits_truncate(reader.u32()?);
+
+        Some(Self {
+            machine,
+            target_setting,
+        })
+    }
+}
+
+/// Contents of one of the Supported Range characteristics, in the units of
+/// the matching target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SupportedRange {
+    pub minimum: i32,
+    pub maximum: i32,
+    pub increment: u32,
+}
+
+fn decode_range(uuid: Uuid, data: &[u8]) -> Option<SupportedRange> {
+    let mut r = Reader::new(data);
+
+    let range = match uuid {
+        SUPPORTED_SPEED_RANGE => SupportedRange {
+            minimum: i32::from(r.u16()?),
+            maximum: i32::from(r.u16()?),
+            increment: u32::from(r.u16()?),
+        },
+        SUPPORTED_HEART_RATE_RANGE => SupportedRange {
+            minimum: i32::from(r.u8()?),
+            maximum: i32::from(r.u8()?),
+            increment: u32::from(r.u8()?),
+        },
+        _ => SupportedRange {
+            minimum: i32::from(r.i16()?),
+            maximum: i32::from(r.i16()?),
+            increment: u32::from(r.u16()?),
+        },
+    };
+
+    Some(range)
+}
+
+/// Energy used during a session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpendedEnergy {
+    /// Total energy in kilocalories.
+    pub total: u16,
+    /// Energy per hour in kilocalories.
+    pub per_hour: u16,
+    /// Energy per minute in kilocalories.
+    pub per_minute: u8,
+}
+
+fn expended_energy(reader: &mut Reader) -> Option<ExpendedEnergy> {
+    Some(ExpendedEnergy {
+        total: reader.u16()?,
+        per_hour: reader.u16()?,
+        per_minute: reader.u8()?,
+    })
+}
+
+bitflags::bitflags! {
+    struct IndoorBikeFlags: u16 {
+        const MORE_DATA = 1 << 0;
+        const AVERAGE_SPEED = 1 << 1;
+        const INSTANTANEOUS_CADENCE = 1 << 2;
+        const AVERAGE_CADENCE = 1 << 3;
+        const TOTAL_DISTANCE = 1 << 4;
+        const RESISTANCE_LEVEL = 1 << 5;
+        const INSTANTANEOUS_POWER = 1 << 6;
+        const AVERAGE_POWER = 1 << 7;
+        const EXPENDED_ENERGY = 1 << 8;
+        const HEART_RATE = 1 << 9;
+        const METABOLIC_EQUIVALENT = 1 << 10;
+        const ELAPSED_TIME = 1 << 11;
+        const REMAINING_TIME = 1 << 12;
+    }
+}
+
+/// Contents of the Indoor Bike Data characteristic.
+///
+/// Machines may split their data over several notifications, so any field
+/// may be missing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndoorBikeData {
+    /// Speed in units of 0.01 km/h.
+    pub instantaneous_speed: Option<u16>,
+    /// Speed in units of 0.01 km/h.
+    pub average_speed: Option<u16>,
+    /// Cadence in units of 0.5 rpm.
+    pub instantaneous_cadence: Option<u16>,
+    /// Cadence in units of 0.5 rpm.
+    pub average_cadence: Option<u16>,
+    /// Distance in meters.
+    pub total_distance: Option<u32>,
+    pub resistance_level: Option<i16>,
+    /// Power in watts.
+    pub instantaneous_power: Option<i16>,
+    /// Power in watts.
+    pub average_power: Option<i16>,
+    pub expended_energy: Option<ExpendedEnergy>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u8>,
+    /// Metabolic equivalent in units of 0.1.
+    pub metabolic_equivalent: Option<u8>,
+    /// Elapsed time in seconds.
+    pub elapsed_time: Option<u16>,
+    /// Remaining time in seconds.
+    pub remaining_time: Option<u16>,
+}
+
+impl IndoorBikeData {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        type F = IndoorBikeFlags;
+
+        let mut r = Reader::new(data);
+        let flags = F::from_bits_truncate(r.u16()?);
+
+        Some(Self {
+            instantaneous_speed: r.read_if(!flags.contains(F::MORE_DATA), Reader::u16)?,
+            average_speed: r.read_if(flags.contains(F::AVERAGE_SPEED), Reader::u16)?,
+            instantaneous_cadence: r
+                .read_if(flags.contains(F::INSTANTANEOUS_CADENCE), Reader::u16)?,
+            average_cadence: r.read_if(flags.contains(F::AVERAGE_CADENCE), Reader::u16)?,
+            total_distance: r.read_if(flags.contains(F::TOTAL_DISTANCE), Reader::u24)?,
+            resistance_level: r.read_if(flags.contains(F::RESISTANCE_LEVEL), Reader::i16)?,
+            instantaneous_power: r.read_if(flags.contains(F::INSTANTANEOUS_POWER), Reader::i16)?,
+            average_power: r.read_if(flags.contains(F::AVERAGE_POWER), Reader::i16)?,
+            expended_energy: r.read_if(flags.contains(F::EXPENDED_ENERGY), expended_energy)?,
+            heart_rate: r.read_if(flags.contains(F::HEART_RATE), Reader::u8)?,
+            metabolic_equivalent: r.read_if(flags.contains(F::METABOLIC_EQUIVALENT), Reader::u8)?,
+            elapsed_time: r.read_if(flags.contains(F::ELAPSED_TIME), Reader::u16)?,
+            remaining_time: r.read_if(flags.contains(F::REMAINING_TIME), Reader::u16)?,
+        })
+    }
+}
+
+bitflags::bitflags! {
+    struct TreadmillFlags: u16 {
+        const MORE_DATA = 1 << 0;
+        const AVERAGE_SPEED = 1 << 1;
+        const TOTAL_DISTANCE = 1 << 2;
+        const INCLINATION = 1 << 3;
+        const ELEVATION_GAIN = 1 << 4;
+        const INSTANTANEOUS_PACE = 1 << 5;
+        const AVERAGE_PACE = 1 << 6;
+        const EXPENDED_ENERGY = 1 << 7;
+        const HEART_RATE = 1 << 8;
+        const METABOLIC_EQUIVALENT = 1 << 9;
+        const ELAPSED_TIME = 1 << 10;
+        const REMAINING_TIME = 1 << 11;
+        const FORCE_ON_BELT = 1 << 12;
+    }
+}
+
+/// Contents of the Treadmill Data characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TreadmillData {
+    /// Speed in units of 0.01 km/h.
+    pub instantaneous_speed: Option<u16>,
+    /// Speed in units of 0.01 km/h.
+    pub average_speed: Option<u16>,
+    /// Distance in meters.
+    pub total_distance: Option<u32>,
+    /// Inclination in units of 0.1 percent.
+    pub inclination: Option<i16>,
+    /// Ramp angle in units of 0.1 degrees.
+    pub ramp_angle: Option<i16>,
+    /// Elevation gained in units of 0.1 meters.
+    pub positive_elevation_gain: Option<u16>,
+    /// Elevation lost in units of 0.1 meters.
+    pub negative_elevation_gain: Option<u16>,
+    /// Pace in units of 0.1 km/min.
+    pub instantaneous_pace: Option<u8>,
+    /// Pace in units of 0.1 km/min.
+    pub average_pace: Option<u8>,
+    pub expended_energy: Option<ExpendedEnergy>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u8>,
+    /// Metabolic equivalent in units of 0.1.
+    pub metabolic_equivalent: Option<u8>,
+    /// Elapsed time in seconds.
+    pub elapsed_time: Option<u16>,
+    /// Remaining time in seconds.
+    pub remaining_time: Option<u16>,
+    /// Force on the belt in newtons.
+    pub force_on_belt: Option<i16>,
+    /// Power in watts.
+    pub power_output: Option<i16>,
+}
+
+impl TreadmillData {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        type F = TreadmillFlags;
+
+        let mut r = Reader::new(data);
+        let flags = F::from_bits_truncate(r.u16()?);
+
+        let instantaneous_speed = r.read_if(!flags.contains(F::MORE_DATA), Reader::u16)?;
+        let average_speed = r.read_if(flags.contains(F::AVERAGE_SPEED), Reader::u16)?;
+        let total_distance = r.read_if(flags.contains(F::TOTAL_DISTANCE), Reader::u24)?;
+        let inclination = r.read_if(flags.contains(F::INCLINATION), Reader::i16)?;
+        let ramp_angle = r.read_if(flags.contains(F::INCLINATION), Reader::i16)?;
+        let positive_elevation_gain = r.read_if(flags.contains(F::ELEVATION_GAIN), Reader::u16)?;
+        let negative_elevation_gain = r.read_if(flags.contains(F::ELEVATION_GAIN), Reader::u16)?;
+        let instantaneous_pace = r.read_if(flags.contains(F::INSTANTANEOUS_PACE), Reader::u8)?;
+        let average_pace = r.read_if(flags.contains(F::AVERAGE_PACE), Reader::u8)?;
+        let expended_energy = r.read_if(flags.contains(F::EXPENDED_ENERGY), expended_energy)?;
+        let heart_rate = r.read_if(flags.contains(F::HEART_RATE), Reader::u8)?;
+        let metabolic_equivalent =
+            r.read_if(flags.contains(F::METABOLIC_EQUIVALENT), Reader::u8)?;
+        let elapsed_time = r.read_if(flags.contains(F::ELAPSED_TIME), Reader::u16)?;
+        let remaining_time = r.read_if(flags.contains(F::REMAINING_TIME), Reader::u16)?;
+        let force_on_belt = r.read_if(flags.contains(F::FORCE_ON_BELT), Reader::i16)?;
+        let power_output = r.read_if(flags.contains(F::FORCE_ON_BELT), Reader::i16)?;
+
+        Some(Self {
+            instantaneous_speed,
+            average_speed,
+            total_distance,
+            inclination,
+            ramp_angle,
+            positive_elevation_gain,
+            negative_elevation_gain,
+            instantaneous_pace,
+            average_pace,
+            expended_energy,
+            heart_rate,
+            metabolic_equivalent,
+            elapsed_time,
+            remaining_time,
+            force_on_belt,
+            power_output,
+        })
+    }
+}
+
+bitflags::bitflags! {
+    struct RowerFlags: u16 {
+        const MORE_DATA = 1 << 0;
+        const AVERAGE_STROKE_RATE = 1 << 1;
+        const TOTAL_DISTANCE = 1 << 2;
+        const INSTANTANEOUS_PACE = 1 << 3;
+        const AVERAGE_PACE = 1 << 4;
+        const INSTANTANEOUS_POWER = 1 << 5;
+        const AVERAGE_POWER = 1 << 6;
+        const RESISTANCE_LEVEL = 1 << 7;
+        const EXPENDED_ENERGY = 1 << 8;
+        const HEART_RATE = 1 << 9;
+        const METABOLIC_EQUIVALENT = 1 << 10;
+        const ELAPSED_TIME = 1 << 11;
+        const REMAINING_TIME = 1 << 12;
+    }
+}
+
+/// Contents of the Rower Data characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RowerData {
+    /// Stroke rate in units of 0.5 strokes per minute.
+    pub stroke_rate: Option<u8>,
+    pub stroke_count: Option<u16>,
+    /// Stroke rate in units of 0.5 strokes per minute.
+    pub average_stroke_rate: Option<u8>,
+    /// Distance in meters.
+    pub total_distance: Option<u32>,
+    /// Time to row 500 meters in seconds.
+    pub instantaneous_pace: Option<u16>,
+    /// Time to row 500 meters in seconds.
+    pub average_pace: Option<u16>,
+    /// Power in watts.
+    pub instantaneous_power: Option<i16>,
+    /// Power in watts.
+    pub average_power: Option<i16>,
+    pub resistance_level: Option<i16>,
+    pub expended_energy: Option<ExpendedEnergy>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u8>,
+    /// Metabolic equivalent in units of 0.1.
+    pub metabolic_equivalent: Option<u8>,
+    /// Elapsed time in seconds.
+    pub elapsed_time: Option<u16>,
+    /// Remaining time in seconds.
+    pub remaining_time: Option<u16>,
+}
+
+impl RowerData {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        type F = RowerFlags;
+
+        let mut r = Reader::new(data);
+        let flags = F::from_bits_truncate(r.u16()?);
+
+        Some(Self {
+            stroke_rate: r.read_if(!flags.contains(F::MORE_DATA), Reader::u8)?,
+            stroke_count: r.read_if(!flags.contains(F::MORE_DATA), Reader::u16)?,
+            average_stroke_rate: r.read_if(flags.contains(F::AVERAGE_STROKE_RATE), Reader::u8)?,
+            total_distance: r.read_if(flags.contains(F::TOTAL_DISTANCE), Reader::u24)?,
+            instantaneous_pace: r.read_if(flags.contains(F::INSTANTANEOUS_PACE), Reader::u16)?,
+            average_pace: r.read_if(flags.contains(F::AVERAGE_PACE), Reader::u16)?,
+            instantaneous_power: r.read_if(flags.contains(F::INSTANTANEOUS_POWER), Reader::i16)?,
+            average_power: r.read_if(flags.contains(F::AVERAGE_POWER), Reader::i16)?,
+            resistance_level: r.read_if(flags.contains(F::RESISTANCE_LEVEL), Reader::i16)?,
+            expended_energy: r.read_if(flags.contains(F::EXPENDED_ENERGY), expended_energy)?,
+            heart_rate: r.read_if(flags.contains(F::HEART_RATE), Reader::u8)?,
+            metabolic_equivalent: r.read_if(flags.contains(F::METABOLIC_EQUIVALENT), Reader::u8)?,
+            elapsed_time: r.read_if(flags.contains(F::ELAPSED_TIME), Reader::u16)?,
+            remaining_time: r.read_if(flags.contains(F::REMAINING_TIME), Reader::u16)?,
+        })
+    }
+}
+
+bitflags::bitflags! {
+    struct CrossTrainerFlags: u32 {
+        const MORE_DATA = 1 << 0;
+        const AVERAGE_SPEED = 1 << 1;
+        const TOTAL_DISTANCE = 1 << 2;
+        const STEP_COUNT = 1 << 3;
+        const STRIDE_COUNT = 1 << 4;
+        const ELEVATION_GAIN = 1 << 5;
+        const INCLINATION = 1 << 6;
+        const RESISTANCE_LEVEL = 1 << 7;
+        const INSTANTANEOUS_POWER = 1 << 8;
+        const AVERAGE_POWER = 1 << 9;
+        const EXPENDED_ENERGY = 1 << 10;
+        const HEART_RATE = 1 << 11;
+        const METABOLIC_EQUIVALENT = 1 << 12;
+        const ELAPSED_TIME = 1 << 13;
+        const REMAINING_TIME = 1 << 14;
+        const BACKWARD = 1 << 15;
+    }
+}
+
+/// Contents of the Cross Trainer Data characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CrossTrainerData {
+    /// Speed in units of 0.01 km/h.
+    pub instantaneous_speed: Option<u16>,
+    /// Speed in units of 0.01 km/h.
+    pub average_speed: Option<u16>,
+    /// Distance in meters.
+    pub total_distance: Option<u32>,
+    pub steps_per_minute: Option<u16>,
+    /// Average steps per minute.
+    pub average_step_rate: Option<u16>,
+    /// Strides in units of 0.1.
+    pub stride_count: Option<u16>,
+    /// Elevation gained in meters.
+    pub positive_elevation_gain: Option<u16>,
+    /// Elevation lost in meters.
+    pub negative_elevation_gain: Option<u16>,
+    /// Inclination in units of 0.1 percent.
+    pub inclination: Option<i16>,
+    /// Ramp angle in units of 0.1 degrees.
+    pub ramp_angle: Option<i16>,
+    pub resistance_level: Option<i16>,
+    /// Power in watts.
+    pub instantaneous_power: Option<i16>,
+    /// Power in watts.
+    pub average_power: Option<i16>,
+    pub expended_energy: Option<ExpendedEnergy>,
+    /// Heart rate in beats per minute.
+    pub heart_rate: Option<u8>,
+    /// Metabolic equivalent in units of 0.1.
+    pub metabolic_equivalent: Option<u8>,
+    /// Elapsed time in seconds.
+    pub elapsed_time: Option<u16>,
+    /// Remaining time in seconds.
+    pub remaining_time: Option<u16>,
+    /// If the user is moving backwards.
+    pub backward: bool,
+}
+
+impl CrossTrainerData {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        type F = CrossTrainerFlags;
+
+        let mut r = Reader::new(data);
+        let flags = F::from_bits_truncate(r.u24()?);
+
+        let instantaneous_speed = r.read_if(!flags.contains(F::MORE_DATA), Reader::u16)?;
+        let average_speed = r.read_if(flags.contains(F::AVERAGE_SPEED), Reader::u16)?;
+        let total_distance = r.read_if(flags.contains(F::TOTAL_DISTANCE), Reader::u24)?;
+        let steps_per_minute = r.read_if(flags.contains(F::STEP_COUNT), Reader::u16)?;
+        let average_step_rate = r.read_if(flags.contains(F::STEP_COUNT), Reader::u16)?;
+        let stride_count = r.read_if(flags.contains(F::STRIDE_COUNT), Reader::u16)?;
+        let positive_elevation_gain = r.read_if(flags.contains(F::ELEVATION_GAIN), Reader::u16)?;
+        let negative_elevation_gain = r.read_if(flags.contains(F::ELEVATION_GAIN), Reader::u16)?;
+        let inclination = r.read_if(flags.contains(F::INCLINATION), Reader::i16)?;
+        let ramp_angle = r.read_if(flags.contains(F::INCLINATION), Reader::i16)?;
+        let resistance_level = r.read_if(flags.contains(F::RESISTANCE_LEVEL), Reader::i16)?;
+        let instantaneous_power = r.read_if(flags.contains(F::INSTANTANEOUS_POWER), Reader::i16)?;
+        let average_power = r.read_if(flags.contains(F::AVERAGE_POWER), Reader::i16)?;
+        let expended_energy = r.read_if(flags.contains(F::EXPENDED_ENERGY), expended_energy)?;
+        let heart_rate = r.read_if(flags.contains(F::HEART_RATE), Reader::u8)?;
+        let metabolic_equivalent =
+            r.read_if(flags.contains(F::METABOLIC_EQUIVALENT), Reader::u8)?;
+        let elapsed_time = r.read_if(flags.contains(F::ELAPSED_TIME), Reader::u16)?;
+        let remaining_time = r.read_if(flags.contains(F::REMAINING_TIME), Reader::u16)?;
+
+        Some(Self {
+            instantaneous_speed,
+            average_speed,
+            total_distance,
+            steps_per_minute,
+            average_step_rate,
+            stride_count,
+            positive_elevation_gain,
+            negative_elevation_gain,
+            inclination,
+            ramp_angle,
+            resistance_level,
+            instantaneous_power,
+            average_power,
+            expended_energy,
+            heart_rate,
+            metabolic_equivalent,
+            elapsed_time,
+            remaining_time,
+            backward: flags.contains(F::BACKWARD),
+        })
+    }
+}
+
+/// Parameters used by an indoor bike to simulate riding outside.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimulationParameters {
+    /// Wind speed in units of 0.001 m/s, positive for a headwind.
+    pub wind_speed: i16,
+    /// Grade in units of 0.01 percent.
+    pub grade: i16,
+    /// Coefficient of rolling resistance in units of 0.0001.
+    pub crr: u8,
+    /// Wind resistance coefficient in units of 0.01 kg/m.
+    pub cw: u8,
+}
+
+impl SimulationParameters {
+    pub fn encode(&self) -> [u8; 6] {
+        let wind_speed = self.wind_speed.to_le_bytes();
+        let grade = self.grade.to_le_bytes();
+
+        [
+            wind_speed[0],
+            wind_speed[1],
+            grade[0],
+            grade[1],
+            self.crr,
+            self.cw,
+        ]
+    }
+}
+
+/// Client for a device's Fitness Machine service.
+pub struct FitnessMachine<C: RemoteCharacteristic> {
+    feature: C,
+    indoor_bike_data: Option<C>,
+    treadmill_data: Option<C>,
+    rower_data: Option<C>,
+    cross_trainer_data: Option<C>,
+    supported_ranges: Vec<C>,
+    control_point: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> FitnessMachine<C> {
+    /// Find the characteristics of the Fitness Machine service.
+    ///
+    /// Fails if the service does not have the Fitness Machine Feature
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let mut feature = None;
+        let mut indoor_bike_data = None;
+        let mut treadmill_data = None;
+        let mut rower_data = None;
+        let mut cross_trainer_data = None;
+        let mut supported_ranges = Vec::new();
+        let mut control_point = None;
+
+        for characteristic in service.characteristics()? {
+            match characteristic.uuid()? {
+                FEATURE => feature = Some(characteristic),
+                INDOOR_BIKE_DATA => indoor_bike_data = Some(characteristic),
+                TREADMILL_DATA => treadmill_data = Some(characteristic),
+                ROWER_DATA => rower_data = Some(characteristic),
+                CROSS_TRAINER_DATA => cross_trainer_data = Some(characteristic),
+                SUPPORTED_SPEED_RANGE
+                | SUPPORTED_INCLINATION_RANGE
+                | SUPPORTED_RESISTANCE_LEVEL_RANGE
+                | SUPPORTED_HEART_RATE_RANGE
+                | SUPPORTED_POWER_RANGE => supported_ranges.push(characteristic),
+                CONTROL_POINT => control_point = Some(characteristic),
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            feature: feature.ok_or(Error::MissingAttribute(FEATURE))?,
+            indoor_bike_data,
+            treadmill_data,
+            rower_data,
+            cross_trainer_data,
+            supported_ranges,
+            control_point,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the machine to respond to control point
+    /// requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Read which measurements and targets the machine supports.
+    pub fn features(&self) -> Result<Features> {
+        Features::decode(&self.feature.read_value()?).ok_or(Error::InvalidValue)
+    }
+
+    /// Read the range supported for a target, given the UUID of one of the
+    /// Supported Range characteristics.
+    ///
+    /// Returns `None` if the machine does not provide the range.
+    pub fn supported_range(&self, uuid: Uuid) -> Result<Option<SupportedRange>> {
+        let mut characteristic = None;
+        for range in &self.supported_ranges {
+            if range.uuid()? == uuid {
+                characteristic = Some(range);
+                break;
+            }
+        }
+
+        let data = match characteristic {
+            Some(characteristic) => characteristic.read_value()?,
+            None => return Ok(None),
+        };
+
+        decode_range(uuid, &data)
+            .map(Some)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Subscribe to data from an indoor bike.
+    pub fn indoor_bike_data(&self) -> Result<Notifications<'_, C, IndoorBikeData>> {
+        let characteristic = self
+            .indoor_bike_data
+            .as_ref()
+            .ok_or(Error::MissingAttribute(INDOOR_BIKE_DATA))?;
+
+        Notifications::new(characteristic, IndoorBikeData::decode)
+    }
+
+    /// Subscribe to data from a treadmill.
+    pub fn treadmill_data(&self) -> Result<Notifications<'_, C, TreadmillData>> {
+        let characteristic = self
+            .treadmill_data
+            .as_ref()
+            .ok_or(Error::MissingAttribute(TREADMILL_DATA))?;
+
+        Notifications::new(characteristic, TreadmillData::decode)
+    }
+
+    /// Subscribe to data from a rower.
+    pub fn rower_data(&self) -> Result<Notifications<'_, C, RowerData>> {
+        let characteristic = self
+            .rower_data
+            .as_ref()
+            .ok_or(Error::MissingAttribute(ROWER_DATA))?;
+
+        Notifications::new(characteristic, RowerData::decode)
+    }
+
+    /// Subscribe to data from a cross trainer.
+    pub fn cross_trainer_data(&self) -> Result<Notifications<'_, C, CrossTrainerData>> {
+        let characteristic = self
+            .cross_trainer_data
+            .as_ref()
+            .ok_or(Error::MissingAttribute(CROSS_TRAINER_DATA))?;
+
+        Notifications::new(characteristic, CrossTrainerData::decode)
+    }
+
+    /// Subscribe to control point responses so the machine can be
+    /// controlled.
+    ///
+    /// Control must be requested before any other request is sent.
+    pub fn control(&self) -> Result<ControlPoint<'_, C>> {
+        let characteristic = self
+            .control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(CONTROL_POINT))?;
+
+        Ok(ControlPoint {
+            client: ControlPointClient::new(characteristic, RESPONSE_CODE, self.timeout)?,
+            state: ControlState::Released,
+        })
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for FitnessMachine<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FitnessMachine")
+            .field("feature", &self.feature)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// What a [ControlPoint] is allowed to do with the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlState {
+    /// Control has not been requested, or was given up with a reset.
+    Released,
+    /// Control was granted and the machine is stopped.
+    Idle,
+    /// Machine was started or resumed.
+    Running,
+    /// Machine was paused.
+    Paused,
+}
+
+/// Procedures on the Fitness Machine Control Point.
+///
+/// Each request waits for the machine to indicate its response, failing with
+/// [Error::Timeout] if it does not respond in time, or
+/// [Error::ControlPoint] if it rejects the request.
+pub struct ControlPoint<'a, C: RemoteCharacteristic> {
+    client: ControlPointClient<'a, C>,
+    state: ControlState,
+}
+
+impl<C: RemoteCharacteristic> ControlPoint<'_, C> {
+    /// Current state of control over the machine.
+    pub fn state(&self) -> ControlState {
+        self.state
+    }
+
+    /// Ask for permission to control the machine.
+    pub fn request_control(&mut self) -> Result<()> {
+        self.execute(REQUEST_CONTROL, &[])?;
+        self.state = ControlState::Idle;
+
+        Ok(())
+    }
+
+    /// Reset the machine, which also gives up control.
+    pub fn reset(&mut self) -> Result<()> {
+        self.require_control(RESET)?;
+        self.execute(RESET, &[])?;
+        self.state = ControlState::Released;
+
+        Ok(())
+    }
+
+    /// Set the target speed in units of 0.01 km/h.
+    pub fn set_target_speed(&mut self, speed: u16) -> Result<()> {
+        self.require_control(SET_TARGET_SPEED)?;
+        self.execute(SET_TARGET_SPEED, &speed.to_le_bytes())
+    }
+
+    /// Set the target inclination in units of 0.1 percent.
+    pub fn set_target_inclination(&mut self, inclination: i16) -> Result<()> {
+        self.require_control(SET_TARGET_INCLINATION)?;
+        self.execute(SET_TARGET_INCLINATION, &inclination.to_le_bytes())
+    }
+
+    /// Set the target resistance level in units of 0.1.
+    pub fn set_target_resistance_level(&mut self, level: u8) -> Result<()> {
+        self.require_control(SET_TARGET_RESISTANCE_LEVEL)?;
+        self.execute(SET_TARGET_RESISTANCE_LEVEL, &[level])
+    }
+
+    /// Set the target power in watts.
+    pub fn set_target_power(&mut self, power: i16) -> Result<()> {
+        self.require_control(SET_TARGET_POWER)?;
+        self.execute(SET_TARGET_POWER, &power.to_le_bytes())
+    }
+
+    /// Set the parameters an indoor bike uses to simulate riding outside.
+    pub fn set_simulation_parameters(&mut self, parameters: SimulationParameters) -> Result<()> {
+        self.require_control(SET_INDOOR_BIKE_SIMULATION)?;
+        self.execute(SET_INDOOR_BIKE_SIMULATION, &parameters.encode())
+    }
+
+    /// Start or resume the session.
+    pub fn start(&mut self) -> Result<()> {
+        self.require_control(START_OR_RESUME)?;
+        self.execute(START_OR_RESUME, &[])?;
+        self.state = ControlState::Running;
+
+        Ok(())
+    }
+
+    /// Stop the session.
+    pub fn stop(&mut self) -> Result<()> {
+        self.require_control(STOP_OR_PAUSE)?;
+        self.execute(STOP_OR_PAUSE, &[0x01])?;
+        self.state = ControlState::Idle;
+
+        Ok(())
+    }
+
+    /// Pause the session.
+    pub fn pause(&mut self) -> Result<()> {
+        self.require_control(STOP_OR_PAUSE)?;
+        self.execute(STOP_OR_PAUSE, &[0x02])?;
+        self.state = ControlState::Paused;
+
+        Ok(())
+    }
+
+    /// Fail without sending the request if control was not granted, as the
+    /// machine would reject it anyway.
+    fn require_control(&self, op_code: u8) -> Result<()> {
+        if self.state == ControlState::Released {
+            return Err(ControlPointError {
+                op_code,
+                result: CONTROL_NOT_PERMITTED,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, op_code: u8, parameter: &[u8]) -> Result<()> {
+        self.client.execute(op_code, parameter).map(|_parameter| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{
+        ControlState, CrossTrainerData, ExpendedEnergy, FitnessMachine, IndoorBikeData,
+        MachineFeatures, RowerData, SimulationParameters, SupportedRange, TargetSettingFeatures,
+        TreadmillData, CONTROL_NOT_PERMITTED, CONTROL_POINT, FEATURE, INDOOR_BIKE_DATA, SERVICE,
+        SUPPORTED_POWER_RANGE, SUPPORTED_SPEED_RANGE,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::{CharacteristicProperties, ControlPointError, Error};
+
+    #[test]
+    fn test_decode_data() {
+        // Speed 25.00 km/h, cadence 90 rpm, power 200 W, heart rate 140.
+        let data = [0x44, 0x02, 0xC4, 0x09, 0xB4, 0x00, 0xC8, 0x00, 0x8C];
+        assert_eq!(
+            IndoorBikeData::decode(&data),
+            Some(IndoorBikeData {
+                instantaneous_speed: Some(2500),
+                instantaneous_cadence: Some(180),
+                instantaneous_power: Some(200),
+                heart_rate: Some(140),
+                ..Default::default()
+            })
+        );
+        assert_eq!(IndoorBikeData::decode(&data[..8]), None);
+
+        // Speed 10.00 km/h, 1% incline, 5 kcal.
+        let data = [
+            0x88, 0x00, 0xE8, 0x03, 0x0A, 0x00, 0x00, 0x00, 0x05, 0x00, 0x78, 0x00, 0x02,
+        ];
+        assert_eq!(
+            TreadmillData::decode(&data),
+            Some(TreadmillData {
+                instantaneous_speed: Some(1000),
+                inclination: Some(10),
+                ramp_angle: Some(0),
+                expended_energy: Some(ExpendedEnergy {
+                    total: 5,
+                    per_hour: 120,
+                    per_minute: 2,
+                }),
+                ..Default::default()
+            })
+        );
+
+        // 24 strokes per minute after 10 strokes, 2:05 per 500 meters.
+        let data = [0x08, 0x00, 0x30, 0x0A, 0x00, 0x7D, 0x00];
+        assert_eq!(
+            RowerData::decode(&data),
+            Some(RowerData {
+                stroke_rate: Some(48),
+                stroke_count: Some(10),
+                instantaneous_pace: Some(125),
+                ..Default::default()
+            })
+        );
+
+        let data = [0x81, 0x80, 0x00, 0x96, 0x00];
+        assert_eq!(
+            CrossTrainerData::decode(&data),
+            Some(CrossTrainerData {
+                resistance_level: Some(150),
+                backward: true,
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_control_simulated_trainer() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler_requests = requests.clone();
+        let controlled = Arc::new(Mutex::new(false));
+
+        let features = (MachineFeatures::CADENCE | MachineFeatures::POWER).bits();
+        let targets =
+            (TargetSettingFeatures::POWER | TargetSettingFeatures::INDOOR_BIKE_SIMULATION).bits();
+        let mut feature = features.to_le_bytes().to_vec();
+        feature.extend_from_slice(&targets.to_le_bytes());
+
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(FEATURE, CharacteristicProperties::READ)
+                        .with_value(&feature),
+                )
+                .with_characteristic(LocalCharacteristic::new(
+                    INDOOR_BIKE_DATA,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(SUPPORTED_POWER_RANGE, CharacteristicProperties::READ)
+                        .with_value(&[0x00, 0x00, 0xE8, 0x03, 0x05, 0x00]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(move |server, value| {
+                        handler_requests.lock().unwrap().push(value.to_vec());
+
+                        let mut controlled = controlled.lock().unwrap();
+                        let result = match value[0] {
+                            // Ignore start requests to test timeouts.
+                            0x07 => return Ok(()),
+                            0x00 => {
+                                *controlled = true;
+                                0x01
+                            }
+                            0x01 => {
+                                *controlled = false;
+                                0x01
+                            }
+                            0x05 | 0x08 | 0x11 if *controlled => 0x01,
+                            0x05 | 0x08 | 0x11 => CONTROL_NOT_PERMITTED,
+                            _ => ControlPointError::OP_CODE_NOT_SUPPORTED,
+                        };
+
+                        server.indicate(SERVICE, CONTROL_POINT, &[0x80, value[0], result]);
+                        Ok(())
+                    }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let machine = FitnessMachine::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        let features = machine.features().unwrap();
+        assert!(features.machine.contains(MachineFeatures::POWER));
+        assert!(features
+            .target_setting
+            .contains(TargetSettingFeatures::INDOOR_BIKE_SIMULATION));
+        assert_eq!(
+            machine.supported_range(SUPPORTED_POWER_RANGE).unwrap(),
+            Some(SupportedRange {
+                minimum: 0,
+                maximum: 1000,
+                increment: 5,
+            })
+        );
+        assert_eq!(
+            machine.supported_range(SUPPORTED_SPEED_RANGE).unwrap(),
+            None
+        );
+
+        let mut control = machine.control().unwrap();
+        assert!(matches!(
+            control.set_target_power(150),
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x05,
+                result: CONTROL_NOT_PERMITTED,
+            }))
+        ));
+        assert!(requests.lock().unwrap().is_empty());
+
+        control.request_control().unwrap();
+        assert_eq!(control.state(), ControlState::Idle);
+        control.set_target_power(150).unwrap();
+        control
+            .set_simulation_parameters(SimulationParameters {
+                wind_speed: 0,
+                grade: 250,
+                crr: 40,
+                cw: 51,
+            })
+            .unwrap();
+        assert!(matches!(
+            control.set_target_speed(2500),
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x02,
+                result: ControlPointError::OP_CODE_NOT_SUPPORTED,
+            }))
+        ));
+        assert!(matches!(control.start(), Err(Error::Timeout)));
+        assert_eq!(control.state(), ControlState::Idle);
+        control.pause().unwrap();
+        assert_eq!(control.state(), ControlState::Paused);
+        control.reset().unwrap();
+        assert_eq!(control.state(), ControlState::Released);
+
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                vec![0x00],
+                vec![0x05, 0x96, 0x00],
+                vec![0x11, 0x00, 0x00, 0xFA, 0x00, 0x28, 0x33],
+                vec![0x02, 0xC4, 0x09],
+                vec![0x07],
+                vec![0x08, 0x02],
+                vec![0x01],
+            ]
+        );
+
+        let mut data = machine.indoor_bike_data().unwrap();
+        server.notify(
+            SERVICE,
+            INDOOR_BIKE_DATA,
+            &[0x40, 0x00, 0xC4, 0x09, 0x96, 0x00],
+        );
+        assert_eq!(
+            data.next_timeout(Duration::from_millis(50)),
+            Some(IndoorBikeData {
+                instantaneous_speed: Some(2500),
+                instantaneous_power: Some(150),
+                ..Default::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,667 @@
+//! Glucose Service, as provided by blood glucose meters.
+//!
+//! Meters keep their readings until asked for them with the
+//! [record access](super::record_access) procedures.
+
+use std::time::Duration;
+
+use super::record_access::{RecordAccessClient, RecordFilter, RECORD_ACCESS_CONTROL_POINT};
+use super::{Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::gatt::types::{DateTime, SFloat};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Glucose service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1808);
+/// UUID of the Glucose Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A18);
+/// UUID of the Glucose Measurement Context characteristic.
+pub const MEASUREMENT_CONTEXT: Uuid = Uuid::from_u16(0x2A34);
+/// UUID of the Glucose Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A51);
+
+bitflags::bitflags! {
+    /// Contents of the Glucose Feature characteristic.
+    pub struct GlucoseFeatures: u16 {
+        const LOW_BATTERY_DETECTION = 1 << 0;
+        const SENSOR_MALFUNCTION_DETECTION = 1 << 1;
+        const SENSOR_SAMPLE_SIZE = 1 << 2;
+        const SENSOR_STRIP_INSERTION_ERROR_DETECTION = 1 << 3;
+        const SENSOR_STRIP_TYPE_ERROR_DETECTION = 1 << 4;
+        const SENSOR_RESULT_HIGH_LOW_DETECTION = 1 << 5;
+        const SENSOR_TEMPERATURE_HIGH_LOW_DETECTION = 1 << 6;
+        const SENSOR_READ_INTERRUPT_DETECTION = 1 << 7;
+        const GENERAL_DEVICE_FAULT = 1 << 8;
+        const TIME_FAULT = 1 << 9;
+        const MULTIPLE_BOND = 1 << 10;
+    }
+}
+
+bitflags::bitflags! {
+    /// Problems the meter found when taking a measurement.
+    pub struct SensorStatus: u16 {
+        const DEVICE_BATTERY_LOW = 1 << 0;
+        const SENSOR_MALFUNCTION = 1 << 1;
+        const SAMPLE_SIZE_INSUFFICIENT = 1 << 2;
+        const STRIP_INSERTION_ERROR = 1 << 3;
+        const STRIP_TYPE_INCORRECT = 1 << 4;
+        const RESULT_TOO_HIGH = 1 << 5;
+        const RESULT_TOO_LOW = 1 << 6;
+        const TEMPERATURE_TOO_HIGH = 1 << 7;
+        const TEMPERATURE_TOO_LOW = 1 << 8;
+        const READ_INTERRUPTED = 1 << 9;
+        const GENERAL_DEVICE_FAULT = 1 << 10;
+        const TIME_FAULT = 1 << 11;
+    }
+}
+
+bitflags::bitflags! {
+    struct MeasurementFlags: u8 {
+        const TIME_OFFSET = 1 << 0;
+        const CONCENTRATION = 1 << 1;
+        const MOLES_PER_LITER = 1 << 2;
+        const SENSOR_STATUS = 1 << 3;
+        const CONTEXT_FOLLOWS = 1 << 4;
+    }
+}
+
+bitflags::bitflags! {
+    struct ContextFlags: u8 {
+        const CARBOHYDRATE = 1 << 0;
+        const MEAL = 1 << 1;
+        const TESTER_HEALTH = 1 << 2;
+        const EXERCISE = 1 << 3;
+        const MEDICATION = 1 << 4;
+        const MEDICATION_LITERS = 1 << 5;
+        const HBA1C = 1 << 6;
+        const EXTENDED_FLAGS = 1 << 7;
+    }
+}
+
+/// Unit of a glucose concentration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ConcentrationUnit {
+    KilogramsPerLiter,
+    MolesPerLiter,
+}
+
+/// What kind of sample was measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SampleType {
+    CapillaryWholeBlood,
+    CapillaryPlasma,
+    VenousWholeBlood,
+    VenousPlasma,
+    ArterialWholeBlood,
+    ArterialPlasma,
+    UndeterminedWholeBlood,
+    UndeterminedPlasma,
+    InterstitialFluid,
+    ControlSolution,
+    /// Type not known by this library.
+    Reserved(u8),
+}
+
+impl SampleType {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => SampleType::CapillaryWholeBlood,
+            2 => SampleType::CapillaryPlasma,
+            3 => SampleType::VenousWholeBlood,
+            4 => SampleType::VenousPlasma,
+            5 => SampleType::ArterialWholeBlood,
+            6 => SampleType::ArterialPlasma,
+            7 => SampleType::UndeterminedWholeBlood,
+            8 => SampleType::UndeterminedPlasma,
+            9 => SampleType::InterstitialFluid,
+            10 => SampleType::ControlSolution,
+            other => SampleType::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SampleType::CapillaryWholeBlood => 1,
+            SampleType::CapillaryPlasma => 2,
+            SampleType::VenousWholeBlood => 3,
+            SampleType::VenousPlasma => 4,
+            SampleType::ArterialWholeBlood => 5,
+            SampleType::ArterialPlasma => 6,
+            SampleType::UndeterminedWholeBlood => 7,
+            SampleType::UndeterminedPlasma => 8,
+            SampleType::InterstitialFluid => 9,
+            SampleType::ControlSolution => 10,
+            SampleType::Reserved(other) => other,
+        }
+    }
+}
+
+/// Where a sample was taken from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SampleLocation {
+    Finger,
+    AlternateSiteTest,
+    Earlobe,
+    ControlSolution,
+    /// Only used by continuous glucose monitors.
+    SubcutaneousTissue,
+    NotAvailable,
+    /// Location not known by this library.
+    Reserved(u8),
+}
+
+impl SampleLocation {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => SampleLocation::Finger,
+            2 => SampleLocation::AlternateSiteTest,
+            3 => SampleLocation::Earlobe,
+            4 => SampleLocation::ControlSolution,
+            5 => SampleLocation::SubcutaneousTissue,
+            15 => SampleLocation::NotAvailable,
+            other => SampleLocation::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SampleLocation::Finger => 1,
+            SampleLocation::AlternateSiteTest => 2,
+            SampleLocation::Earlobe => 3,
+            SampleLocation::ControlSolution => 4,
+            SampleLocation::SubcutaneousTissue => 5,
+            SampleLocation::NotAvailable => 15,
+            SampleLocation::Reserved(other) => other,
+        }
+    }
+}
+
+/// Measured glucose concentration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Concentration {
+    pub value: SFloat,
+    pub unit: ConcentrationUnit,
+    pub sample_type: SampleType,
+    pub sample_location: SampleLocation,
+}
+
+/// Contents of the Glucose Measurement characteristic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlucoseMeasurement {
+    pub sequence_number: u16,
+    /// Time of the measurement, before applying the time offset.
+    pub base_time: DateTime,
+    /// Minutes to add to the base time to get the user facing time.
+    pub time_offset: Option<i16>,
+    pub concentration: Option<Concentration>,
+    pub sensor_status: Option<SensorStatus>,
+    /// If a Glucose Measurement Context with the same sequence number
+    /// follows this measurement.
+    pub context_follows: bool,
+}
+
+impl GlucoseMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = MeasurementFlags::from_bits_truncate(r.u8()?);
+
+        let sequence_number = r.u16()?;
+        let base_time = DateTime::decode(r.bytes(DateTime::LEN)?)?;
+        let time_offset = r.read_if(flags.contains(MeasurementFlags::TIME_OFFSET), Reader::i16)?;
+        let concentration = r.read_if(flags.contains(MeasurementFlags::CONCENTRATION), |r| {
+            let value = SFloat(r.u16()?);
+            let type_location = r.u8()?;
+
+            Some(Concentration {
+                value,
+                unit: if flags.contains(MeasurementFlags::MOLES_PER_LITER) {
+                    ConcentrationUnit::MolesPerLiter
+                } else {
+                    ConcentrationUnit::KilogramsPerLiter
+                },
+                sample_type: SampleType::from_u8(type_location & 0x0F),
+                sample_location: SampleLocation::from_u8(type_location >> 4),
+            })
+        })?;
+        let sensor_status = r.read_if(flags.contains(MeasurementFlags::SENSOR_STATUS), |r| {
+            r.u16().map(SensorStatus::from_bits_truncate)
+        })?;
+
+        Some(Self {
+            sequence_number,
+            base_time,
+            time_offset,
+            concentration,
+            sensor_status,
+            context_follows: flags.contains(MeasurementFlags::CONTEXT_FOLLOWS),
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = MeasurementFlags::empty();
+        let mut data = vec![0];
+
+        data.extend_from_slice(&self.sequence_number.to_le_bytes());
+        data.extend_from_slice(&self.base_time.encode());
+
+        if let Some(time_offset) = self.time_offset {
+            flags |= MeasurementFlags::TIME_OFFSET;
+            data.extend_from_slice(&time_offset.to_le_bytes());
+        }
+
+        if let Some(concentration) = &self.concentration {
+            flags |= MeasurementFlags::CONCENTRATION;
+            flags.set(
+                MeasurementFlags::MOLES_PER_LITER,
+                concentration.unit == ConcentrationUnit::MolesPerLiter,
+            );
+            data.extend_from_slice(&concentration.value.to_le_bytes());
+            data.push(
+                (concentration.sample_type.to_u8() & 0x0F)
+                    | (concentration.sample_location.to_u8() << 4),
+            );
+        }
+
+        if let Some(sensor_status) = self.sensor_status {
+            flags |= MeasurementFlags::SENSOR_STATUS;
+            data.extend_from_slice(&sensor_status.bits().to_le_bytes());
+        }
+
+        flags.set(MeasurementFlags::CONTEXT_FOLLOWS, self.context_follows);
+
+        data[0] = flags.bits();
+        data
+    }
+}
+
+/// Contents of the Glucose Measurement Context characteristic.
+///
+/// Codes for meals, testers, health and medication are left as sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlucoseMeasurementContext {
+    /// Sequence number of the measurement this belongs to.
+    pub sequence_number: u16,
+    pub extended_flags: Option<u8>,
+    /// Carbohydrate ID and amount in kilograms.
+    pub carbohydrate: Option<(u8, SFloat)>,
+    pub meal: Option<u8>,
+    /// Tester in the low nibble and health in the high nibble.
+    pub tester_health: Option<u8>,
+    /// Exercise duration in seconds and intensity in percent.
+    pub exercise: Option<(u16, u8)>,
+    /// Medication ID and amount, in liters if `medication_in_liters` is set
+    /// or kilograms otherwise.
+    pub medication: Option<(u8, SFloat)>,
+    pub medication_in_liters: bool,
+    /// HbA1c in percent.
+    pub hba1c: Option<SFloat>,
+}
+
+impl GlucoseMeasurementContext {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = ContextFlags::from_bits_truncate(r.u8()?);
+
+        let sequence_number = r.u16()?;
+        let extended_flags = r.read_if(flags.contains(ContextFlags::EXTENDED_FLAGS), Reader::u8)?;
+        let carbohydrate = r.read_if(flags.contains(ContextFlags::CARBOHYDRATE), |r| {
+            Some((r.u8()?, SFloat(r.u16()?)))
+        })?;
+        let meal = r.read_if(flags.contains(ContextFlags::MEAL), Reader::u8)?;
+        let tester_health = r.read_if(flags.contains(ContextFlags::TESTER_HEALTH), Reader::u8)?;
+        let exercise = r.read_if(flags.contains(ContextFlags::EXERCISE), |r| {
+            Some((r.u16()?, r.u8()?))
+        })?;
+        let medication = r.read_if(flags.contains(ContextFlags::MEDICATION), |r| {
+            Some((r.u8()?, SFloat(r.u16()?)))
+        })?;
+        let hba1c = r.read_if(flags.contains(ContextFlags::HBA1C), |r| r.u16().map(SFloat))?;
+
+        Some(Self {
+            sequence_number,
+            extended_flags,
+            carbohydrate,
+            meal,
+            tester_health,
+            exercise,
+            medication,
+            medication_in_liters: flags.contains(ContextFlags::MEDICATION_LITERS),
+            hba1c,
+        })
+    }
+}
+
+/// A stored measurement, along with its context if the meter sent one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlucoseRecord {
+    pub measurement: GlucoseMeasurement,
+    pub context: Option<GlucoseMeasurementContext>,
+}
+
+/// Client for a device's Glucose service.
+pub struct Glucose<C: RemoteCharacteristic> {
+    measurement: C,
+    measurement_context: Option<C>,
+    feature: Option<C>,
+    record_access_control_point: C,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> Glucose<C> {
+    /// Find the characteristics of the Glucose service.
+    ///
+    /// Fails if the service does not have the Glucose Measurement or Record
+    /// Access Control Point characteristics.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+        let record_access_control_point = service
+            .characteristic(RECORD_ACCESS_CONTROL_POINT)?
+            .ok_or(Error::MissingAttribute(RECORD_ACCESS_CONTROL_POINT))?;
+
+        Ok(Self {
+            measurement,
+            measurement_context: service.characteristic(MEASUREMENT_CONTEXT)?,
+            feature: service.characteristic(FEATURE)?,
+            record_access_control_point,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the meter to respond to requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Read which problems the meter can detect.
+    pub fn features(&self) -> Result<GlucoseFeatures> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Reader::new(&data)
+            .u16()
+            .map(GlucoseFeatures::from_bits_truncate)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Download stored records.
+    ///
+    /// Contexts are matched to measurements by sequence number. If the meter
+    /// stops sending records before finishing, the procedure is aborted and
+    /// this fails with [Error::Timeout].
+    pub fn records(&self, filter: &RecordFilter) -> Result<Vec<GlucoseRecord>> {
+        let mut measurements = Notifications::new(&self.measurement, GlucoseMeasurement::decode)?;
+        let mut contexts = match &self.measurement_context {
+            Some(characteristic) => Some(Notifications::new(
+                characteristic,
+                GlucoseMeasurementContext::decode,
+            )?),
+            None => None,
+        };
+
+        let mut records: Vec<GlucoseRecord> = Vec::new();
+        let mut orphans = Vec::new();
+
+        let mut client = RecordAccessClient::new(&self.record_access_control_point, self.timeout)?;
+        client.report(filter, || {
+            let mut received = false;
+
+            while let Some(measurement) = measurements.next_timeout(Duration::from_millis(0)) {
+                received = true;
+                records.push(GlucoseRecord {
+                    measurement,
+                    context: None,
+                });
+            }
+
+            if let Some(contexts) = &mut contexts {
+                while let Some(context) = contexts.next_timeout(Duration::from_millis(0)) {
+                    received = true;
+                    orphans.push(context);
+                }
+            }
+
+            received
+        })?;
+
+        for context in orphans {
+            match records
+                .iter_mut()
+                .rev()
+                .find(|record| record.measurement.sequence_number == context.sequence_number)
+            {
+                Some(record) => record.context = Some(context),
+                None => log::warn!(
+                    "Got context for unknown measurement {}",
+                    context.sequence_number
+                ),
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Get how many stored records match a filter.
+    pub fn record_count(&self, filter: &RecordFilter) -> Result<u16> {
+        RecordAccessClient::new(&self.record_access_control_point, self.timeout)?.count(filter)
+    }
+
+    /// Delete stored records matching a filter.
+    pub fn delete_records(&self, filter: &RecordFilter) -> Result<()> {
+        RecordAccessClient::new(&self.record_access_control_point, self.timeout)?.delete(filter)
+    }
+
+    /// Abort a record access procedure left running on the meter.
+    pub fn abort(&self) -> Result<()> {
+        RecordAccessClient::new(&self.record_access_control_point, self.timeout)?.abort()
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Glucose<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Glucose")
+            .field("measurement", &self.measurement)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{
+        Concentration, ConcentrationUnit, Glucose, GlucoseMeasurement, SampleLocation, SampleType,
+        SensorStatus, MEASUREMENT, MEASUREMENT_CONTEXT, SERVICE,
+    };
+    use crate::gatt::types::{DateTime, SFloat};
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::services::record_access::{
+        RecordFilter, OPERATOR_NOT_SUPPORTED, RECORD_ACCESS_CONTROL_POINT,
+    };
+    use crate::{CharacteristicProperties, ControlPointError, Error};
+
+    fn measurement(sequence_number: u16, context_follows: bool) -> GlucoseMeasurement {
+        GlucoseMeasurement {
+            sequence_number,
+            base_time: DateTime {
+                year: 2020,
+                month: 6,
+                day: 1,
+                hours: 8,
+                minutes: sequence_number as u8,
+                seconds: 0,
+            },
+            time_offset: Some(-60),
+            concentration: Some(Concentration {
+                value: SFloat::new(95, -5).unwrap(),
+                unit: ConcentrationUnit::KilogramsPerLiter,
+                sample_type: SampleType::CapillaryPlasma,
+                sample_location: SampleLocation::Finger,
+            }),
+            sensor_status: None,
+            context_follows,
+        }
+    }
+
+    #[test]
+    fn test_decode_measurement() {
+        let data = [
+            0x0B, 0x01, 0x00, 0xE4, 0x07, 0x06, 0x01, 0x08, 0x01, 0x00, 0xC4, 0xFF, 0x5F, 0xB0,
+            0x12, 0x20, 0x00,
+        ];
+        let mut expected = measurement(1, false);
+        expected.sensor_status = Some(SensorStatus::RESULT_TOO_HIGH);
+        assert_eq!(GlucoseMeasurement::decode(&data), Some(expected.clone()));
+        assert_eq!(expected.encode(), data);
+        assert_eq!(GlucoseMeasurement::decode(&data[..16]), None);
+    }
+
+    /// Start a meter with records 1 to 3, where record 2 has a context.
+    ///
+    /// Requests to report records with sequence numbers after 10 are never
+    /// answered.
+    fn simulated_meter() -> (GattServer, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler_requests = requests.clone();
+
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT_CONTEXT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        RECORD_ACCESS_CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(move |server, value| {
+                        handler_requests.lock().unwrap().push(value.to_vec());
+
+                        let first = match value {
+                            [0x01, 0x01] | [0x04, 0x01] => 1,
+                            [0x01, 0x03, 0x01, low, high] => u16::from_le_bytes([*low, *high]),
+                            [0x03, 0x00] => {
+                                server.indicate(
+                                    SERVICE,
+                                    RECORD_ACCESS_CONTROL_POINT,
+                                    &[0x06, 0x00, 0x03, 0x01],
+                                );
+                                return Ok(());
+                            }
+                            _ => {
+                                server.indicate(
+                                    SERVICE,
+                                    RECORD_ACCESS_CONTROL_POINT,
+                                    &[0x06, 0x00, value[0], OPERATOR_NOT_SUPPORTED],
+                                );
+                                return Ok(());
+                            }
+                        };
+
+                        if value[0] == 0x04 {
+                            server.indicate(
+                                SERVICE,
+                                RECORD_ACCESS_CONTROL_POINT,
+                                &[0x05, 0x00, 0x03, 0x00],
+                            );
+                            return Ok(());
+                        }
+
+                        if first > 10 {
+                            return Ok(());
+                        }
+
+                        for sequence_number in first..=3 {
+                            let context_follows = sequence_number == 2;
+                            server.notify(
+                                SERVICE,
+                                MEASUREMENT,
+                                &measurement(sequence_number, context_follows).encode(),
+                            );
+                            if context_follows {
+                                server.notify(
+                                    SERVICE,
+                                    MEASUREMENT_CONTEXT,
+                                    &[0x42, 0x02, 0x00, 0x01, 0x05, 0x00],
+                                );
+                            }
+                        }
+
+                        let result = if first > 3 { 0x06 } else { 0x01 };
+                        server.indicate(
+                            SERVICE,
+                            RECORD_ACCESS_CONTROL_POINT,
+                            &[0x06, 0x00, 0x01, result],
+                        );
+                        Ok(())
+                    }),
+                ),
+        );
+
+        (server, requests)
+    }
+
+    #[test]
+    fn test_simulated_meter() {
+        let (server, requests) = simulated_meter();
+
+        let service = connect(&server, SERVICE);
+        let meter = Glucose::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        assert_eq!(meter.record_count(&RecordFilter::All).unwrap(), 3);
+
+        let records = meter.records(&RecordFilter::All).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].measurement, measurement(1, false));
+        assert_eq!(records[0].context, None);
+        let context = records[1].context.as_ref().unwrap();
+        assert_eq!(context.sequence_number, 2);
+        assert_eq!(context.meal, Some(0x01));
+        assert_eq!(context.hba1c.map(SFloat::to_f64), Some(5.0));
+        assert_eq!(records[2].context, None);
+
+        let records = meter
+            .records(&RecordFilter::SequenceNumberAtLeast(3))
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].measurement.sequence_number, 3);
+
+        let records = meter
+            .records(&RecordFilter::SequenceNumberAtLeast(4))
+            .unwrap();
+        assert!(records.is_empty());
+
+        match meter.records(&RecordFilter::Last) {
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x01,
+                result: OPERATOR_NOT_SUPPORTED,
+            })) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match meter.records(&RecordFilter::SequenceNumberAtLeast(11)) {
+            Err(Error::Timeout) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(
+            requests.lock().unwrap().last(),
+            Some(&vec![0x03, 0x00]),
+            "stalled procedure should be aborted"
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Health Thermometer Service, as provided by medical thermometers.
+
+use super::{Notifications, Reader};
+use crate::gatt::types::{DateTime, Float};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Health Thermometer service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1809);
+/// UUID of the Temperature Measurement characteristic.
+pub const TEMPERATURE_MEASUREMENT: Uuid = Uuid::from_u16(0x2A1C);
+/// UUID of the Temperature Type characteristic.
+pub const TEMPERATURE_TYPE: Uuid = Uuid::from_u16(0x2A1D);
+/// UUID of the Intermediate Temperature characteristic.
+pub const INTERMEDIATE_TEMPERATURE: Uuid = Uuid::from_u16(0x2A1E);
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const FAHRENHEIT = 1 << 0;
+        const TIME_STAMP = 1 << 1;
+        const TEMPERATURE_TYPE = 1 << 2;
+    }
+}
+
+/// Unit of a temperature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+/// Where a temperature was measured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TemperatureType {
+    Armpit,
+    Body,
+    Ear,
+    Finger,
+    GastrointestinalTract,
+    Mouth,
+    Rectum,
+    Toe,
+    Tympanum,
+    /// Type not known by this library.
+    Reserved(u8),
+}
+
+impl TemperatureType {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => TemperatureType::Armpit,
+            2 => TemperatureType::Body,
+            3 => TemperatureType::Ear,
+            4 => TemperatureType::Finger,
+            5 => TemperatureType::GastrointestinalTract,
+            6 => TemperatureType::Mouth,
+            7 => TemperatureType::Rectum,
+            8 => TemperatureType::Toe,
+            9 => TemperatureType::Tympanum,
+            other => TemperatureType::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            TemperatureType::Armpit => 1,
+            TemperatureType::Body => 2,
+            TemperatureType::Ear => 3,
+            TemperatureType::Finger => 4,
+            TemperatureType::GastrointestinalTract => 5,
+            TemperatureType::Mouth => 6,
+            TemperatureType::Rectum => 7,
+            TemperatureType::Toe => 8,
+            TemperatureType::Tympanum => 9,
+            TemperatureType::Reserved(other) => other,
+        }
+    }
+}
+
+/// Contents of the Temperature Measurement and Intermediate Temperature
+/// characteristics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemperatureMeasurement {
+    pub unit: TemperatureUnit,
+    pub temperature: Float,
+    pub time_stamp: Option<DateTime>,
+    /// Where the temperature was measured, if it can change between
+    /// measurements.
+    pub temperature_type: Option<TemperatureType>,
+}
+
+impl TemperatureMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let temperature = Float(r.u32()?);
+        let time_stamp = r.read_if(flags.contains(Flags::TIME_STAMP), |r| {
+            DateTime::decode(r.bytes(DateTime::LEN)?)
+        })?;
+        let temperature_type = r.read_if(flags.contains(Flags::TEMPERATURE_TYPE), |r| {
+            r.u8().map(TemperatureType::from_u8)
+        })?;
+
+        Some(Self {
+            unit: if flags.contains(Flags::FAHRENHEIT) {
+                TemperatureUnit::Fahrenheit
+            } else {
+                TemperatureUnit::Celsius
+            },
+            temperature,
+            time_stamp,
+            temperature_type,
+        })
+    }
+}
+
+/// Client for a device's Health Thermometer service.
+pub struct HealthThermometer<C: RemoteCharacteristic> {
+    temperature_measurement: C,
+    temperature_type: Option<C>,
+    intermediate_temperature: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> HealthThermometer<C> {
+    /// Find the characteristics of the Health Thermometer service.
+    ///
+    /// Fails if the service does not have the Temperature Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let temperature_measurement = service
+            .characteristic(TEMPERATURE_MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(TEMPERATURE_MEASUREMENT))?;
+
+        Ok(Self {
+            temperature_measurement,
+            temperature_type: service.characteristic(TEMPERATURE_TYPE)?,
+            intermediate_temperature: service.characteristic(INTERMEDIATE_TEMPERATURE)?,
+        })
+    }
+
+    /// Subscribe to completed measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, TemperatureMeasurement>> {
+        Notifications::new(
+            &self.temperature_measurement,
+            TemperatureMeasurement::decode,
+        )
+    }
+
+    /// Subscribe to temperatures while a measurement is being taken.
+    pub fn intermediate_temperatures(
+        &self,
+    ) -> Result<Notifications<'_, C, TemperatureMeasurement>> {
+        let characteristic = self
+            .intermediate_temperature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(INTERMEDIATE_TEMPERATURE))?;
+
+        Notifications::new(characteristic, TemperatureMeasurement::decode)
+    }
+
+    /// Read where temperatures are measured, if it does not change between
+    /// measurements.
+    pub fn temperature_type(&self) -> Result<Option<TemperatureType>> {
+        let characteristic = match &self.temperature_type {
+            Some(characteristic) => characteristic,
+            None => return Ok(None),
+        };
+
+        match characteristic.read_value()?.as_slice() {
+            [temperature_type] => Ok(Some(TemperatureType::from_u8(*temperature_type))),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for HealthThermometer<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HealthThermometer")
+            .field("temperature_measurement", &self.temperature_measurement)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TemperatureMeasurement, TemperatureType, TemperatureUnit};
+    use crate::gatt::types::Float;
+
+    #[test]
+    fn test_decode_measurement() {
+        let measurement =
+            TemperatureMeasurement::decode(&[0x04, 0x72, 0x01, 0x00, 0xFF, 0x06]).unwrap();
+        assert_eq!(measurement.unit, TemperatureUnit::Celsius);
+        assert_eq!(measurement.temperature, Float::new(370, -1).unwrap());
+        assert_eq!(measurement.temperature.to_f64(), 37.0);
+        assert_eq!(measurement.time_stamp, None);
+        assert_eq!(measurement.temperature_type, Some(TemperatureType::Mouth));
+
+        let measurement = TemperatureMeasurement::decode(&[
+            0x03, 0xCC, 0x03, 0x00, 0xFF, 0xE4, 0x07, 0x06, 0x01, 0x08, 0x1E, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(measurement.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(measurement.temperature.to_f64(), 97.2);
+        assert_eq!(measurement.time_stamp.unwrap().minutes, 30);
+
+        assert_eq!(
+            TemperatureMeasurement::decode(&[0x02, 0x72, 0x01, 0x00, 0xFF]),
+            None
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Heart Rate Service, as provided by chest straps and watches.
+
+use std::time::Duration;
+
+use super::{Notifications, Reader};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Heart Rate service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x180D);
+/// UUID of the Heart Rate Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A37);
+/// UUID of the Body Sensor Location characteristic.
+pub const BODY_SENSOR_LOCATION: Uuid = Uuid::from_u16(0x2A38);
+/// UUID of the Heart Rate Control Point characteristic.
+pub const CONTROL_POINT: Uuid = Uuid::from_u16(0x2A39);
+
+/// Control point command to reset the Energy Expended value to zero.
+const RESET_ENERGY_EXPENDED: u8 = 0x01;
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const VALUE_UINT16 = 0x01;
+        const SENSOR_CONTACT_DETECTED = 0x02;
+        const SENSOR_CONTACT_SUPPORTED = 0x04;
+        const ENERGY_EXPENDED = 0x08;
+        const RR_INTERVALS = 0x10;
+    }
+}
+
+/// Contents of the Heart Rate Measurement characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeartRateMeasurement {
+    /// Heart rate in beats per minute.
+    pub heart_rate: u16,
+    /// If the sensor is in contact with the skin, or `None` if the sensor
+    /// cannot tell.
+    pub sensor_contact: Option<bool>,
+    /// Energy expended since the last reset in kilojoules.
+    pub energy_expended: Option<u16>,
+    /// Time between beats in units of 1/1024 seconds, oldest first.
+    pub rr_intervals: Vec<u16>,
+}
+
+impl HeartRateMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(data);
+        let flags = Flags::from_bits_truncate(reader.u8()?);
+
+        let heart_rate = if flags.contains(Flags::VALUE_UINT16) {
+            reader.u16()?
+        } else {
+            u16::from(reader.u8()?)
+        };
+
+        let sensor_contact = if flags.contains(Flags::SENSOR_CONTACT_SUPPORTED) {
+            Some(flags.contains(Flags::SENSOR_CONTACT_DETECTED))
+        } else {
+            None
+        };
+
+        let energy_expended =
+            reader.read_if(flags.contains(Flags::ENERGY_EXPENDED), Reader::u16)?;
+
+        let mut rr_intervals = Vec::new();
+        if flags.contains(Flags::RR_INTERVALS) {
+            while !reader.is_empty() {
+                rr_intervals.push(reader.u16()?);
+            }
+        }
+
+        Some(Self {
+            heart_rate,
+            sensor_contact,
+            energy_expended,
+            rr_intervals,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut flags = Flags::empty();
+        let mut data = vec![0];
+
+        if self.heart_rate > u16::from(u8::MAX) {
+            flags |= Flags::VALUE_UINT16;
+            data.extend_from_slice(&self.heart_rate.to_le_bytes());
+        } else {
+            data.push(self.heart_rate as u8);
+        }
+
+        if let Some(detected) = self.sensor_contact {
+            flags |= Flags::SENSOR_CONTACT_SUPPORTED;
+            flags.set(Flags::SENSOR_CONTACT_DETECTED, detected);
+        }
+
+        if let Some(energy_expended) = self.energy_expended {
+            flags |= Flags::ENERGY_EXPENDED;
+            data.extend_from_slice(&energy_expended.to_le_bytes());
+        }
+
+        if !self.rr_intervals.is_empty() {
+            flags |= Flags::RR_INTERVALS;
+            for interval in &self.rr_intervals {
+                data.extend_from_slice(&interval.to_le_bytes());
+            }
+        }
+
+        data[0] = flags.bits();
+        data
+    }
+
+    /// Get the RR-intervals as durations.
+    pub fn rr_interval_durations(&self) -> Vec<Duration> {
+        self.rr_intervals
+            .iter()
+            .map(|interval| Duration::from_micros(u64::from(*interval) * 1_000_000 / 1024))
+            .collect()
+    }
+}
+
+/// Where a heart rate sensor is worn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BodySensorLocation {
+    Other,
+    Chest,
+    Wrist,
+    Finger,
+    Hand,
+    EarLobe,
+    Foot,
+    /// Location not known by this library.
+    Reserved(u8),
+}
+
+impl BodySensorLocation {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => BodySensorLocation::Other,
+            1 => BodySensorLocation::Chest,
+            2 => BodySensorLocation::Wrist,
+            3 => BodySensorLocation::Finger,
+            4 => BodySensorLocation::Hand,
+            5 => BodySensorLocation::EarLobe,
+            6 => BodySensorLocation::Foot,
+            other => BodySensorLocation::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BodySensorLocation::Other => 0,
+            BodySensorLocation::Chest => 1,
+            BodySensorLocation::Wrist => 2,
+            BodySensorLocation::Finger => 3,
+            BodySensorLocation::Hand => 4,
+            BodySensorLocation::EarLobe => 5,
+            BodySensorLocation::Foot => 6,
+            BodySensorLocation::Reserved(other) => other,
+        }
+    }
+}
+
+/// Client for a device's Heart Rate service.
+pub struct HeartRate<C: RemoteCharacteristic> {
+    measurement: C,
+    body_sensor_location: Option<C>,
+    control_point: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> HeartRate<C> {
+    /// Find the characteristics of the Heart Rate service.
+    ///
+    /// Fails if the service does not have the Heart Rate Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            body_sensor_location: service.characteristic(BODY_SENSOR_LOCATION)?,
+            control_point: service.characteristic(CONTROL_POINT)?,
+        })
+    }
+
+    /// Subscribe to heart rate measurements.
+    ///
+    /// Notifications stop when the returned value is dropped.
+    pub fn measurements(&self) -> Result<Measurements<'_, C>> {
+        Notifications::new(&self.measurement, HeartRateMeasurement::decode)
+    }
+
+    /// Read where the sensor is worn, if the device says.
+    pub fn body_sensor_location(&self) -> Result<Option<BodySensorLocation>> {
+        let characteristic = match &self.body_sensor_location {
+            Some(characteristic) => characteristic,
+            None => return Ok(None),
+        };
+
+        match characteristic.read_value()?.as_slice() {
+            [location] => Ok(Some(BodySensorLocation::from_u8(*location))),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Reset the energy expended in measurements to zero.
+    pub fn reset_energy_expended(&self) -> Result<()> {
+        self.control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(CONTROL_POINT))?
+            .write_value(&[RESET_ENERGY_EXPENDED])
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for HeartRate<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeartRate")
+            .field("measurement", &self.measurement)
+            .finish()
+    }
+}
+
+/// Heart rate measurements sent by a device.
+pub type Measurements<'a, C> = Notifications<'a, C, HeartRateMeasurement>;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{
+        BodySensorLocation, HeartRate, HeartRateMeasurement, BODY_SENSOR_LOCATION, CONTROL_POINT,
+        MEASUREMENT, SERVICE,
+    };
+    use crate::gatt::{AttError, GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::CharacteristicProperties;
+
+    #[test]
+    fn test_decode_measurement() {
+        let measurement = HeartRateMeasurement::decode(&[0x16, 0x48, 0x00, 0x04, 0x10, 0x03])
+            .expect("measurement should decode");
+        assert_eq!(
+            measurement,
+            HeartRateMeasurement {
+                heart_rate: 72,
+                sensor_contact: Some(true),
+                energy_expended: None,
+                rr_intervals: vec![0x0400, 0x0310],
+            }
+        );
+        assert_eq!(
+            measurement.rr_interval_durations()[0],
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            measurement.encode(),
+            vec![0x16, 0x48, 0x00, 0x04, 0x10, 0x03]
+        );
+
+        let data = [0x09, 0x2C, 0x01, 0xE8, 0x03];
+        let measurement = HeartRateMeasurement::decode(&data).unwrap();
+        assert_eq!(measurement.heart_rate, 300);
+        assert_eq!(measurement.sensor_contact, None);
+        assert_eq!(measurement.energy_expended, Some(1000));
+        assert_eq!(measurement.encode(), data);
+
+        assert_eq!(HeartRateMeasurement::decode(&[0x01, 0x48]), None);
+        assert_eq!(HeartRateMeasurement::decode(&[0x10, 0x48, 0x00]), None);
+    }
+
+    #[test]
+    fn test_heart_rate_client() {
+        let resets = Arc::new(Mutex::new(0));
+        let handler_resets = resets.clone();
+
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(BODY_SENSOR_LOCATION, CharacteristicProperties::READ)
+                        .with_value(&[BodySensorLocation::Chest.to_u8()]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(CONTROL_POINT, CharacteristicProperties::WRITE)
+                        .on_write(move |_server, value| match value {
+                            [0x01] => {
+                                *handler_resets.lock().unwrap() += 1;
+                                Ok(())
+                            }
+                            _ => Err(AttError(0x80)),
+                        }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let heart_rate = HeartRate::new(&service).unwrap();
+
+        assert_eq!(
+            heart_rate.body_sensor_location().unwrap(),
+            Some(BodySensorLocation::Chest)
+        );
+
+        let mut measurements = heart_rate.measurements().unwrap();
+        let sent = HeartRateMeasurement {
+            heart_rate: 64,
+            sensor_contact: Some(true),
+            energy_expended: Some(12),
+            rr_intervals: vec![960],
+        };
+        server.notify(SERVICE, MEASUREMENT, &[0x10]);
+        server.notify(SERVICE, MEASUREMENT, &sent.encode());
+        assert_eq!(measurements.next(), Some(sent));
+        assert_eq!(measurements.next_timeout(Duration::from_millis(10)), None);
+
+        heart_rate.reset_energy_expended().unwrap();
+        assert_eq!(*resets.lock().unwrap(), 1);
+    }
+}
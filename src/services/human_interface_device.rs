@@ -0,0 +1,888 @@
+//! HID over GATT, used by keyboards, mice, and game controllers.
+//!
+//! Reports are described by the device's report map, which is parsed into a
+//! [ReportMap] so that reports can be decoded into the values of each usage.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::{Notifications, Reader};
+use crate::{Error, RemoteCharacteristic, RemoteDescriptor, RemoteService, Result, Uuid};
+
+/// UUID of the Human Interface Device service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1812);
+/// UUID of the HID Information characteristic.
+pub const HID_INFORMATION: Uuid = Uuid::from_u16(0x2A4A);
+/// UUID of the Report Map characteristic.
+pub const REPORT_MAP: Uuid = Uuid::from_u16(0x2A4B);
+/// UUID of the HID Control Point characteristic.
+pub const HID_CONTROL_POINT: Uuid = Uuid::from_u16(0x2A4C);
+/// UUID of the Report characteristic.
+pub const REPORT: Uuid = Uuid::from_u16(0x2A4D);
+/// UUID of the Protocol Mode characteristic.
+pub const PROTOCOL_MODE: Uuid = Uuid::from_u16(0x2A4E);
+/// UUID of the Report Reference descriptor.
+pub const REPORT_REFERENCE: Uuid = Uuid::from_u16(0x2908);
+
+/// Direction of a report.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReportType {
+    /// Sent by the device.
+    Input,
+    /// Sent to the device.
+    Output,
+    /// Read and written by the host.
+    Feature,
+    /// Type not known by this library.
+    Reserved(u8),
+}
+
+impl ReportType {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ReportType::Input,
+            2 => ReportType::Output,
+            3 => ReportType::Feature,
+            other => ReportType::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ReportType::Input => 1,
+            ReportType::Output => 2,
+            ReportType::Feature => 3,
+            ReportType::Reserved(other) => other,
+        }
+    }
+}
+
+/// Contents of the Report Reference descriptor, identifying the report
+/// carried by a Report characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ReportReference {
+    /// Report ID from the report map, or 0 if the map does not use IDs.
+    pub report_id: u8,
+    pub report_type: ReportType,
+}
+
+impl ReportReference {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [report_id, report_type] => Some(Self {
+                report_id,
+                report_type: ReportType::from_u8(report_type),
+            }),
+            _ => None,
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags from the HID Information characteristic.
+    pub struct HidFlags: u8 {
+        const REMOTE_WAKE = 1 << 0;
+        const NORMALLY_CONNECTABLE = 1 << 1;
+    }
+}
+
+/// Contents of the HID Information characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HidInformation {
+    /// Version of the HID specification, as 0xJJMN for version JJ.M.N.
+    pub version: u16,
+    /// Country code of localized hardware, or 0 if not localized.
+    pub country_code: u8,
+    pub flags: HidFlags,
+}
+
+impl HidInformation {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [v0, v1, country_code, flags] => Some(Self {
+                version: u16::from_le_bytes([v0, v1]),
+                country_code,
+                flags: HidFlags::from_bits_truncate(flags),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Protocol used by the device's reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProtocolMode {
+    /// Fixed reports of the boot keyboard and mouse characteristics.
+    Boot,
+    /// Reports described by the report map.
+    Report,
+    /// Mode not known by this library.
+    Reserved(u8),
+}
+
+impl ProtocolMode {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ProtocolMode::Boot,
+            1 => ProtocolMode::Report,
+            other => ProtocolMode::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ProtocolMode::Boot => 0,
+            ProtocolMode::Report => 1,
+            ProtocolMode::Reserved(other) => other,
+        }
+    }
+}
+
+/// What a value means, such as a key or an axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Usage {
+    pub page: u16,
+    pub id: u16,
+}
+
+impl Usage {
+    pub const fn new(page: u16, id: u16) -> Self {
+        Self { page, id }
+    }
+
+    /// Resolve a usage item, which only includes its page when it is four
+    /// bytes long.
+    fn from_item(value: u32, size: usize, usage_page: u16) -> Self {
+        if size == 4 {
+            Self::new((value >> 16) as u16, value as u16)
+        } else {
+            Self::new(usage_page, value as u16)
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Flags of an Input, Output, or Feature item.
+    pub struct FieldFlags: u32 {
+        /// The field is padding or otherwise unchanging.
+        const CONSTANT = 1 << 0;
+        /// Each value has its own usage, rather than values being an array
+        /// of the usages which are active.
+        const VARIABLE = 1 << 1;
+        /// Values are changes since the last report.
+        const RELATIVE = 1 << 2;
+        const WRAP = 1 << 3;
+        const NONLINEAR = 1 << 4;
+        const NO_PREFERRED_STATE = 1 << 5;
+        const NULL_STATE = 1 << 6;
+        const VOLATILE = 1 << 7;
+        const BUFFERED_BYTES = 1 << 8;
+    }
+}
+
+/// Consecutive values within a report, all described by the same item.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReportField {
+    /// Position of the field's first bit, not counting any report ID.
+    pub bit_offset: usize,
+    /// Size of each value in bits.
+    pub bit_size: usize,
+    /// Number of values.
+    pub count: usize,
+    pub flags: FieldFlags,
+    /// Usage of each value for variable fields, with the last usage
+    /// repeated for any remaining values, or the usages an array field may
+    /// contain.
+    pub usages: Vec<Usage>,
+    pub logical_minimum: i32,
+    pub logical_maximum: i32,
+}
+
+impl ReportField {
+    fn bit_end(&self) -> usize {
+        self.bit_offset + self.bit_size * self.count
+    }
+
+    /// Extract a value, which must be within the data.
+    fn value(&self, data: &[u8], index: usize) -> i32 {
+        let offset = self.bit_offset + self.bit_size * index;
+
+        let mut value = 0u32;
+        for bit in 0..self.bit_size {
+            let position = offset + bit;
+            if data[position / 8] >> (position % 8) & 1 == 1 {
+                value |= 1 << bit;
+            }
+        }
+
+        let sign = 1 << (self.bit_size - 1);
+        if self.logical_minimum < 0 && self.bit_size < 32 && value & sign != 0 {
+            value |= !0 << self.bit_size;
+        }
+
+        value as i32
+    }
+}
+
+/// Layout of one report, from the report map.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub report_type: ReportType,
+    /// Report ID, or 0 if the map does not use IDs.
+    pub report_id: u8,
+    pub fields: Vec<ReportField>,
+}
+
+impl Report {
+    /// Size of the report in bytes, not counting any report ID.
+    pub fn size(&self) -> usize {
+        let bits = self.fields.last().map(ReportField::bit_end).unwrap_or(0);
+
+        bits / 8 + usize::from(bits % 8 != 0)
+    }
+
+    /// Decode a report, as sent over GATT without its report ID, into the
+    /// value of each usage.
+    ///
+    /// Usages listed by array fields have the value 1, while usage ID 0
+    /// means no usage and is skipped. Constant fields and values larger
+    /// than 32 bits are skipped.
+    pub fn decode(&self, data: &[u8]) -> Option<HashMap<Usage, i32>> {
+        if data.len() < self.size() {
+            return None;
+        }
+
+        let mut values = HashMap::new();
+        for field in &self.fields {
+            if field.flags.contains(FieldFlags::CONSTANT)
+                || field.bit_size == 0
+                || field.bit_size > 32
+            {
+                continue;
+            }
+
+            for index in 0..field.count {
+                let value = field.value(data, index);
+
+                if field.flags.contains(FieldFlags::VARIABLE) {
+                    if let Some(usage) = field.usages.get(index).or_else(|| field.usages.last()) {
+                        values.insert(*usage, value);
+                    }
+                } else if value >= field.logical_minimum && value <= field.logical_maximum {
+                    // The range of a 32-bit field may not fit in an i32.
+                    let index = i64::from(value) - i64::from(field.logical_minimum);
+                    let usage = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| field.usages.get(index))
+                        .filter(|usage| usage.id != 0);
+                    if let Some(usage) = usage {
+                        values.insert(*usage, 1);
+                    }
+                }
+            }
+        }
+
+        Some(values)
+    }
+}
+
+/// Global items, which apply to every following main item.
+#[derive(Clone, Default)]
+struct GlobalState {
+    usage_page: u16,
+    logical_minimum: i32,
+    logical_maximum: i32,
+    /// Logical maximum read as unsigned, as many devices encode it.
+    logical_maximum_unsigned: u32,
+    report_size: usize,
+    report_count: usize,
+    report_id: u8,
+}
+
+/// Local items, which apply only to the next main item.
+#[derive(Default)]
+struct LocalState {
+    usages: Vec<(u32, usize)>,
+    usage_minimum: Option<(u32, usize)>,
+    usage_maximum: Option<(u32, usize)>,
+}
+
+impl LocalState {
+    fn usages(&self, usage_page: u16) -> Vec<Usage> {
+        let mut usages: Vec<_> = self
+            .usages
+            .iter()
+            .map(|&(value, size)| Usage::from_item(value, size, usage_page))
+            .collect();
+
+        if let (Some((minimum, min_size)), Some((maximum, max_size))) =
+            (self.usage_minimum, self.usage_maximum)
+        {
+            let minimum = Usage::from_item(minimum, min_size, usage_page);
+            let maximum = Usage::from_item(maximum, max_size, usage_page);
+            usages.extend((minimum.id..=maximum.id).map(|id| Usage::new(minimum.page, id)));
+        }
+
+        usages
+    }
+}
+
+/// Parsed contents of the Report Map characteristic, a HID report
+/// descriptor.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReportMap {
+    pub reports: Vec<Report>,
+}
+
+impl ReportMap {
+    const LONG_ITEM: u8 = 0xFE;
+
+    /// Parse a report descriptor, failing if it is truncated or its
+    /// collections or pushed globals are unbalanced.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let mut map = Self::default();
+        let mut global = GlobalState::default();
+        let mut globals = Vec::new();
+        let mut local = LocalState::default();
+        let mut depth = 0usize;
+
+        let mut reader = Reader::new(data);
+        while !reader.is_empty() {
+            let prefix = reader.u8()?;
+            if prefix == Self::LONG_ITEM {
+                let size = reader.u8()?;
+                reader.u8()?;
+                reader.bytes(size as usize)?;
+                continue;
+            }
+
+            let size = match prefix & 0x03 {
+                3 => 4,
+                size => size as usize,
+            };
+            let data = reader.bytes(size)?;
+            let unsigned = data
+                .iter()
+                .rev()
+                .fold(0u32, |value, byte| value << 8 | u32::from(*byte));
+            let signed = match *data {
+                [b0] => i32::from(b0 as i8),
+                [b0, b1] => i32::from(i16::from_le_bytes([b0, b1])),
+                _ => unsigned as i32,
+            };
+
+            match prefix & 0xFC {
+                // Input, Output, and Feature.
+                tag @ 0x80 | tag @ 0x90 | tag @ 0xB0 => {
+                    let report_type = match tag {
+                        0x80 => ReportType::Input,
+                        0x90 => ReportType::Output,
+                        _ => ReportType::Feature,
+                    };
+                    let logical_maximum = if global.logical_minimum >= 0
+                        && global.logical_maximum < global.logical_minimum
+                    {
+                        global.logical_maximum_unsigned as i32
+                    } else {
+                        global.logical_maximum
+                    };
+
+                    let report = map.report_mut(report_type, global.report_id);
+                    let bit_offset = report.fields.last().map(ReportField::bit_end).unwrap_or(0);
+                    global
+                        .report_size
+                        .checked_mul(global.report_count)
+                        .and_then(|bits| bits.checked_add(bit_offset))?;
+
+                    report.fields.push(ReportField {
+                        bit_offset,
+                        bit_size: global.report_size,
+                        count: global.report_count,
+                        flags: FieldFlags::from_bits_truncate(unsigned),
+                        usages: local.usages(global.usage_page),
+                        logical_minimum: global.logical_minimum,
+                        logical_maximum,
+                    });
+                    local = LocalState::default();
+                }
+                // Collection.
+                0xA0 => {
+                    depth += 1;
+                    local = LocalState::default();
+                }
+                // End Collection.
+                0xC0 => {
+                    depth = depth.checked_sub(1)?;
+                    local = LocalState::default();
+                }
+                0x04 => global.usage_page = unsigned as u16,
+                0x14 => global.logical_minimum = signed,
+                0x24 => {
+                    global.logical_maximum = signed;
+                    global.logical_maximum_unsigned = unsigned;
+                }
+                0x74 => global.report_size = unsigned as usize,
+                0x84 => global.report_id = unsigned as u8,
+                0x94 => global.report_count = unsigned as usize,
+                // Push and Pop.
+                0xA4 => globals.push(global.clone()),
+                0xB4 => global = globals.pop()?,
+                0x08 => local.usages.push((unsigned, size)),
+                0x18 => local.usage_minimum = Some((unsigned, size)),
+                0x28 => local.usage_maximum = Some((unsigned, size)),
+                other => log::trace!("Ignoring report descriptor item {:02X}", other),
+            }
+        }
+
+        if depth != 0 || !globals.is_empty() {
+            return None;
+        }
+
+        Some(map)
+    }
+
+    /// Find a report by its type and ID.
+    pub fn report(&self, report_type: ReportType, report_id: u8) -> Option<&Report> {
+        self.reports
+            .iter()
+            .find(|report| report.report_type == report_type && report.report_id == report_id)
+    }
+
+    fn report_mut(&mut self, report_type: ReportType, report_id: u8) -> &mut Report {
+        let index =
+            match self.reports.iter().position(|report| {
+                report.report_type == report_type && report.report_id == report_id
+            }) {
+                Some(index) => index,
+                None => {
+                    self.reports.push(Report {
+                        report_type,
+                        report_id,
+                        fields: Vec::new(),
+                    });
+                    self.reports.len() - 1
+                }
+            };
+
+        &mut self.reports[index]
+    }
+}
+
+/// A Report characteristic and the report it carries.
+pub struct ReportCharacteristic<C: RemoteCharacteristic> {
+    reference: ReportReference,
+    characteristic: C,
+}
+
+impl<C: RemoteCharacteristic> ReportCharacteristic<C> {
+    /// The report carried by this characteristic.
+    pub fn reference(&self) -> ReportReference {
+        self.reference
+    }
+
+    /// The underlying characteristic.
+    pub fn characteristic(&self) -> &C {
+        &self.characteristic
+    }
+
+    /// Read the current report.
+    pub fn read(&self) -> Result<Vec<u8>> {
+        self.characteristic.read_value()
+    }
+
+    /// Write an output or feature report.
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        self.characteristic.write_value(data)
+    }
+
+    /// Subscribe to input reports, decoded using their layout from the
+    /// report map.
+    pub fn inputs(&self, report: &Report) -> Result<Notifications<'_, C, HashMap<Usage, i32>>> {
+        let report = report.clone();
+
+        Notifications::new(&self.characteristic, move |data| report.decode(data))
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for ReportCharacteristic<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReportCharacteristic")
+            .field("reference", &self.reference)
+            .field("characteristic", &self.characteristic)
+            .finish()
+    }
+}
+
+/// Client for a device's Human Interface Device service.
+pub struct HumanInterfaceDevice<C: RemoteCharacteristic> {
+    report_map: C,
+    information: Option<C>,
+    control_point: Option<C>,
+    protocol_mode: Option<C>,
+    reports: Vec<ReportCharacteristic<C>>,
+}
+
+impl<C: RemoteCharacteristic> HumanInterfaceDevice<C> {
+    /// Find the characteristics of the Human Interface Device service,
+    /// reading the Report Reference of each Report characteristic.
+    ///
+    /// Fails if the service does not have the Report Map characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let mut report_map = None;
+        let mut information = None;
+        let mut control_point = None;
+        let mut protocol_mode = None;
+        let mut reports = Vec::new();
+
+        for characteristic in service.characteristics()? {
+            match characteristic.uuid()? {
+                REPORT_MAP => report_map = Some(characteristic),
+                HID_INFORMATION => information = Some(characteristic),
+                HID_CONTROL_POINT => control_point = Some(characteristic),
+                PROTOCOL_MODE => protocol_mode = Some(characteristic),
+                REPORT => {
+                    let descriptor = characteristic
+                        .descriptor(REPORT_REFERENCE)?
+                        .ok_or(Error::MissingAttribute(REPORT_REFERENCE))?;
+                    let reference = ReportReference::decode(&descriptor.read_value()?)
+                        .ok_or(Error::InvalidValue)?;
+
+                    reports.push(ReportCharacteristic {
+                        reference,
+                        characteristic,
+                    });
+                }
+                other => log::debug!("Skipping unknown characteristic {:?}", other),
+            }
+        }
+
+        Ok(Self {
+            report_map: report_map.ok_or(Error::MissingAttribute(REPORT_MAP))?,
+            information,
+            control_point,
+            protocol_mode,
+            reports,
+        })
+    }
+
+    /// Read and parse the report map.
+    pub fn report_map(&self) -> Result<ReportMap> {
+        ReportMap::parse(&self.report_map.read_value()?).ok_or(Error::InvalidValue)
+    }
+
+    /// Read the HID Information characteristic.
+    pub fn information(&self) -> Result<HidInformation> {
+        let characteristic = self
+            .information
+            .as_ref()
+            .ok_or(Error::MissingAttribute(HID_INFORMATION))?;
+
+        HidInformation::decode(&characteristic.read_value()?).ok_or(Error::InvalidValue)
+    }
+
+    /// Read which protocol the device is using.
+    pub fn protocol_mode(&self) -> Result<ProtocolMode> {
+        match self
+            .protocol_mode_characteristic()?
+            .read_value()?
+            .as_slice()
+        {
+            [mode] => Ok(ProtocolMode::from_u8(*mode)),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Switch the device between boot and report protocols.
+    pub fn set_protocol_mode(&self, mode: ProtocolMode) -> Result<()> {
+        self.protocol_mode_characteristic()?
+            .write_value(&[mode.to_u8()])
+    }
+
+    /// Tell the device the host is entering suspend.
+    pub fn suspend(&self) -> Result<()> {
+        self.control_point_characteristic()?.write_value(&[0x00])
+    }
+
+    /// Tell the device the host is leaving suspend.
+    pub fn exit_suspend(&self) -> Result<()> {
+        self.control_point_characteristic()?.write_value(&[0x01])
+    }
+
+    /// Every Report characteristic, in the order they were discovered.
+    pub fn reports(&self) -> &[ReportCharacteristic<C>] {
+        &self.reports
+    }
+
+    /// Find the Report characteristic carrying a report.
+    pub fn report(
+        &self,
+        report_type: ReportType,
+        report_id: u8,
+    ) -> Option<&ReportCharacteristic<C>> {
+        self.reports.iter().find(|report| {
+            report.reference.report_type == report_type && report.reference.report_id == report_id
+        })
+    }
+
+    /// Subscribe to an input report, decoded using the report map.
+    ///
+    /// Fails with [Error::InvalidValue] if the report map does not describe
+    /// the report.
+    pub fn inputs(
+        &self,
+        map: &ReportMap,
+        report_id: u8,
+    ) -> Result<Notifications<'_, C, HashMap<Usage, i32>>> {
+        let layout = map
+            .report(ReportType::Input, report_id)
+            .ok_or(Error::InvalidValue)?;
+        let report = self
+            .report(ReportType::Input, report_id)
+            .ok_or(Error::MissingAttribute(REPORT))?;
+
+        report.inputs(layout)
+    }
+
+    fn protocol_mode_characteristic(&self) -> Result<&C> {
+        self.protocol_mode
+            .as_ref()
+            .ok_or(Error::MissingAttribute(PROTOCOL_MODE))
+    }
+
+    fn control_point_characteristic(&self) -> Result<&C> {
+        self.control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(HID_CONTROL_POINT))
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for HumanInterfaceDevice<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HumanInterfaceDevice")
+            .field("report_map", &self.report_map)
+            .field("reports", &self.reports)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        FieldFlags, HidFlags, HidInformation, HumanInterfaceDevice, ProtocolMode, Report,
+        ReportField, ReportMap, ReportReference, ReportType, Usage, HID_CONTROL_POINT,
+        HID_INFORMATION, PROTOCOL_MODE, REPORT, REPORT_MAP, REPORT_REFERENCE, SERVICE,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalDescriptor, LocalService};
+    use crate::services::connect;
+    use crate::CharacteristicProperties;
+
+    /// A mouse with report ID 1 and a keyboard with report ID 2.
+    const REPORT_DESCRIPTOR: &[u8] = &[
+        0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x85, 0x01, 0x09, 0x01, 0xA1, 0x00, // Mouse
+        0x05, 0x09, 0x19, 0x01, 0x29, 0x03, 0x15, 0x00, 0x25, 0x01, 0x95, 0x03, 0x75, 0x01, 0x81,
+        0x02, // Buttons
+        0x95, 0x01, 0x75, 0x05, 0x81, 0x01, // Padding
+        0x05, 0x01, 0x09, 0x30, 0x09, 0x31, 0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95, 0x02, 0x81,
+        0x06, // X and Y
+        0xC0, 0xC0, //
+        0x05, 0x01, 0x09, 0x06, 0xA1, 0x01, 0x85, 0x02, 0x05, 0x07, // Keyboard
+        0x19, 0xE0, 0x29, 0xE7, 0x15, 0x00, 0x25, 0x01, 0x75, 0x01, 0x95, 0x08, 0x81,
+        0x02, // Modifiers
+        0x95, 0x01, 0x75, 0x08, 0x81, 0x01, // Reserved
+        0x95, 0x05, 0x75, 0x01, 0x05, 0x08, 0x19, 0x01, 0x29, 0x05, 0x91, 0x02, // LEDs
+        0x95, 0x01, 0x75, 0x03, 0x91, 0x01, // Padding
+        0x95, 0x06, 0x75, 0x08, 0x15, 0x00, 0x25, 0x65, 0x05, 0x07, 0x19, 0x00, 0x29, 0x65, 0x81,
+        0x00, // Keys
+        0xC0,
+    ];
+
+    #[test]
+    fn test_parse_report_map() {
+        let map = ReportMap::parse(REPORT_DESCRIPTOR).unwrap();
+        assert_eq!(map.reports.len(), 3);
+
+        let mouse = map.report(ReportType::Input, 1).unwrap();
+        assert_eq!(mouse.size(), 3);
+        assert_eq!(mouse.fields.len(), 3);
+        assert_eq!(mouse.fields[2].bit_offset, 8);
+        assert_eq!(mouse.fields[2].logical_minimum, -127);
+        assert_eq!(
+            mouse.fields[2].flags,
+            FieldFlags::VARIABLE | FieldFlags::RELATIVE
+        );
+        assert_eq!(
+            mouse.fields[2].usages,
+            vec![Usage::new(0x01, 0x30), Usage::new(0x01, 0x31)]
+        );
+
+        let leds = map.report(ReportType::Output, 2).unwrap();
+        assert_eq!(leds.size(), 1);
+        assert_eq!(leds.fields[0].usages.len(), 5);
+        assert_eq!(leds.fields[0].usages[0], Usage::new(0x08, 0x01));
+
+        let keyboard = map.report(ReportType::Input, 2).unwrap();
+        assert_eq!(keyboard.size(), 8);
+        assert_eq!(keyboard.fields[2].usages.len(), 0x66);
+        assert!(map.report(ReportType::Feature, 1).is_none());
+
+        // Unsigned logical maximum, extended usage, and push and pop.
+        let map = ReportMap::parse(&[
+            0x05, 0x01, 0x0B, 0x38, 0x02, 0x0C, 0x00, 0xA4, 0x15, 0x00, 0x25, 0xFF, 0x75, 0x08,
+            0x95, 0x01, 0x81, 0x02, 0xB4, 0x81, 0x02,
+        ])
+        .unwrap();
+        let fields = &map.report(ReportType::Input, 0).unwrap().fields;
+        assert_eq!(fields[0].logical_maximum, 255);
+        assert_eq!(fields[0].usages, vec![Usage::new(0x0C, 0x0238)]);
+        assert_eq!(fields[1].bit_size, 0);
+
+        assert_eq!(ReportMap::parse(&[0x05]), None);
+        assert_eq!(ReportMap::parse(&[0xA1, 0x01]), None);
+        assert_eq!(ReportMap::parse(&[0xC0]), None);
+        assert_eq!(ReportMap::parse(&[0xB4]), None);
+    }
+
+    #[test]
+    fn test_decode_reports() {
+        let map = ReportMap::parse(REPORT_DESCRIPTOR).unwrap();
+
+        let mouse = map.report(ReportType::Input, 1).unwrap();
+        let values = mouse.decode(&[0x05, 0xFE, 0x03]).unwrap();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[&Usage::new(0x09, 0x01)], 1);
+        assert_eq!(values[&Usage::new(0x09, 0x02)], 0);
+        assert_eq!(values[&Usage::new(0x09, 0x03)], 1);
+        assert_eq!(values[&Usage::new(0x01, 0x30)], -2);
+        assert_eq!(values[&Usage::new(0x01, 0x31)], 3);
+        assert_eq!(mouse.decode(&[0x05, 0xFE]), None);
+
+        let keyboard = map.report(ReportType::Input, 2).unwrap();
+        let values = keyboard
+            .decode(&[0x02, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(values.len(), 10);
+        assert_eq!(values[&Usage::new(0x07, 0xE0)], 0);
+        assert_eq!(values[&Usage::new(0x07, 0xE1)], 1);
+        assert_eq!(values[&Usage::new(0x07, 0x04)], 1);
+        assert_eq!(values[&Usage::new(0x07, 0x05)], 1);
+        assert!(!values.contains_key(&Usage::new(0x07, 0x00)));
+    }
+
+    #[test]
+    fn test_decode_full_range_array() {
+        let report = Report {
+            report_type: ReportType::Input,
+            report_id: 0,
+            fields: vec![ReportField {
+                bit_offset: 0,
+                bit_size: 32,
+                count: 1,
+                flags: FieldFlags::empty(),
+                usages: vec![Usage::new(0x07, 0x04)],
+                logical_minimum: i32::MIN,
+                logical_maximum: i32::MAX,
+            }],
+        };
+
+        let values = report.decode(&[0x00, 0x00, 0x00, 0x80]).unwrap();
+        assert_eq!(values[&Usage::new(0x07, 0x04)], 1);
+
+        let values = report.decode(&[0xFF, 0xFF, 0xFF, 0x7F]).unwrap();
+        assert!(values.is_empty());
+    }
+
+    #[test]
+    fn test_simulated_keyboard() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(HID_INFORMATION, CharacteristicProperties::READ)
+                        .with_value(&[0x11, 0x01, 0x00, 0x02]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(REPORT_MAP, CharacteristicProperties::READ)
+                        .with_value(REPORT_DESCRIPTOR),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        REPORT,
+                        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+                    )
+                    .with_value(&[0; 8])
+                    .with_descriptor(LocalDescriptor::new(REPORT_REFERENCE, &[0x02, 0x01])),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        REPORT,
+                        CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+                    )
+                    .with_value(&[0])
+                    .with_descriptor(LocalDescriptor::new(REPORT_REFERENCE, &[0x02, 0x02])),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        PROTOCOL_MODE,
+                        CharacteristicProperties::READ
+                            | CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+                    )
+                    .with_value(&[0x01]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        HID_CONTROL_POINT,
+                        CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+                    )
+                    .with_value(&[0x01]),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let hid = HumanInterfaceDevice::new(&service).unwrap();
+
+        assert_eq!(
+            hid.information().unwrap(),
+            HidInformation {
+                version: 0x0111,
+                country_code: 0,
+                flags: HidFlags::NORMALLY_CONNECTABLE,
+            }
+        );
+        assert_eq!(hid.reports().len(), 2);
+        assert_eq!(
+            hid.reports()[1].reference(),
+            ReportReference {
+                report_id: 2,
+                report_type: ReportType::Output,
+            }
+        );
+
+        hid.set_protocol_mode(ProtocolMode::Boot).unwrap();
+        assert_eq!(hid.protocol_mode().unwrap(), ProtocolMode::Boot);
+        hid.suspend().unwrap();
+        assert_eq!(
+            server.value(SERVICE, HID_CONTROL_POINT).as_deref(),
+            Some(&[0x00][..])
+        );
+
+        let leds = hid.report(ReportType::Output, 2).unwrap();
+        leds.write(&[0x02]).unwrap();
+        assert_eq!(leds.read().unwrap(), vec![0x02]);
+
+        let map = hid.report_map().unwrap();
+        assert!(hid.inputs(&map, 1).is_err());
+
+        let mut inputs = hid.inputs(&map, 2).unwrap();
+        server.notify(SERVICE, REPORT, &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00]);
+        server.notify(
+            SERVICE,
+            REPORT,
+            &[0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00],
+        );
+        assert_eq!(inputs.next_timeout(Duration::from_millis(50)), None);
+        let values = inputs.next_timeout(Duration::from_millis(50)).unwrap();
+        assert_eq!(values[&Usage::new(0x07, 0x04)], 1);
+    }
+}
@@ -0,0 +1,247 @@
+//! Clients for standard services.
+//!
+//! Each client is built from a [RemoteService](crate::RemoteService), so it
+//! works with both real devices and the devices provided by the
+//! [sim](crate::sim) module.
+
+use std::time::{Duration, Instant};
+
+use crate::{CharacteristicIO, ControlPointError, Error, RemoteCharacteristic, Result};
+
+pub mod battery;
+pub mod blood_pressure;
+pub mod body_composition;
+pub mod continuous_glucose_monitoring;
+pub mod current_time;
+pub mod cycling_power;
+pub mod cycling_speed_cadence;
+pub mod device_information;
+pub mod environmental_sensing;
+pub mod fitness_machine;
+pub mod glucose;
+pub mod health_thermometer;
+pub mod heart_rate;
+pub mod human_interface_device;
+pub mod pulse_oximeter;
+pub mod record_access;
+pub mod running_speed_cadence;
+pub mod speed_cadence;
+pub mod weight_scale;
+
+type Decoder<'a, T> = Box<dyn Fn(&[u8]) -> Option<T> + 'a>;
+
+/// Values sent by a device, decoded as they arrive.
+///
+/// Values which cannot be decoded are skipped. Notifications stop when this
+/// is dropped.
+pub struct Notifications<'a, C: RemoteCharacteristic, T> {
+    io: CharacteristicIO<'a, C>,
+    decode: Decoder<'a, T>,
+}
+
+impl<'a, C: RemoteCharacteristic, T> Notifications<'a, C, T> {
+    pub(crate) fn new(
+        characteristic: &'a C,
+        decode: impl Fn(&[u8]) -> Option<T> + 'a,
+    ) -> Result<Self> {
+        Ok(Self {
+            io: CharacteristicIO::new(characteristic)?,
+            decode: Box::new(decode),
+        })
+    }
+
+    /// Wait up to the given duration for the next value.
+    pub fn next_timeout(&mut self, timeout: Duration) -> Option<T> {
+        let data = self.io.next_notification_timeout(timeout)?;
+
+        self.decode(&data)
+    }
+
+    /// Collect values until none arrive for the given duration.
+    ///
+    /// Useful for devices which send their stored measurements as soon as
+    /// they are subscribed to.
+    pub fn collect_until_quiet(&mut self, quiet: Duration) -> Vec<T> {
+        let mut values = Vec::new();
+
+        while let Some(data) = self.io.next_notification_timeout(quiet) {
+            if let Some(value) = self.decode(&data) {
+                values.push(value);
+            }
+        }
+
+        values
+    }
+
+    fn decode(&self, data: &[u8]) -> Option<T> {
+        let value = (self.decode)(data);
+        if value.is_none() {
+            log::warn!(
+                "Unable to decode {}: {:?}",
+                std::any::type_name::<T>(),
+                data
+            );
+        }
+
+        value
+    }
+}
+
+impl<C: RemoteCharacteristic, T> Iterator for Notifications<'_, C, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let data = self.io.next_notification()?;
+
+            if let Some(value) = self.decode(&data) {
+                return Some(value);
+            }
+        }
+    }
+}
+
+/// Default time to wait for a device to respond to a control point request.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Result code for a successful control point request, shared by every
+/// service.
+const SUCCESS: u8 = 0x01;
+
+/// Sends requests to a control point and waits for the indicated responses.
+///
+/// Responses start with the service's response code, the op code of the
+/// request, and a result code, followed by any response parameters.
+pub(crate) struct ControlPointClient<'a, C: RemoteCharacteristic> {
+    characteristic: &'a C,
+    io: CharacteristicIO<'a, C>,
+    response_code: u8,
+    timeout: Duration,
+}
+
+impl<'a, C: RemoteCharacteristic> ControlPointClient<'a, C> {
+    pub(crate) fn new(characteristic: &'a C, response_code: u8, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            characteristic,
+            io: CharacteristicIO::new(characteristic)?,
+            response_code,
+            timeout,
+        })
+    }
+
+    /// Send a request, returning the response parameters if it succeeded.
+    pub(crate) fn execute(&mut self, op_code: u8, parameter: &[u8]) -> Result<Vec<u8>> {
+        let mut request = vec![op_code];
+        request.extend_from_slice(parameter);
+
+        log::debug!("Sending control point request {:?}", request);
+        self.characteristic.write_value(&request)?;
+
+        self.wait(op_code)
+    }
+
+    /// Wait for the response to a request, including responses the device
+    /// sends without being asked.
+    pub(crate) fn wait(&mut self, op_code: u8) -> Result<Vec<u8>> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(Error::Timeout)?;
+            let response = self
+                .io
+                .next_notification_timeout(remaining)
+                .ok_or(Error::Timeout)?;
+
+            match *response.as_slice() {
+                [code, request, SUCCESS, ..]
+                    if code == self.response_code && request == op_code =>
+                {
+                    return Ok(response[3..].to_vec())
+                }
+                [code, request, result, ..] if code == self.response_code && request == op_code => {
+                    return Err(ControlPointError { op_code, result }.into())
+                }
+                _ => log::debug!("Ignoring unexpected response {:?}", response),
+            }
+        }
+    }
+}
+
+/// Cursor over a little-endian characteristic value.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Some(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn i16(&mut self) -> Option<i16> {
+        self.u16().map(|value| value as i16)
+    }
+
+    pub(crate) fn u24(&mut self) -> Option<u32> {
+        self.bytes(3)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    /// Read a field only if it is present, as told by a flag.
+    pub(crate) fn read_if<T>(
+        &mut self,
+        present: bool,
+        read: impl FnOnce(&mut Self) -> Option<T>,
+    ) -> Option<Option<T>> {
+        if present {
+            read(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Connect to a simulated device running the server, and return one of its
+/// services.
+#[cfg(test)]
+pub(crate) fn connect(server: &crate::gatt::GattServer, uuid: crate::Uuid) -> crate::sim::Service {
+    let sim = crate::sim::Simulator::new();
+    let address = crate::BluetoothAddress(0x0102_0304_0506);
+    sim.add_peripheral(address, server.clone());
+
+    let device = sim.connect(address).unwrap();
+    device.service(uuid).unwrap().unwrap()
+}
@@ -0,0 +1,359 @@
+//! Pulse Oximeter Service, as provided by finger clip oximeters.
+
+use super::{Notifications, Reader};
+use crate::gatt::types::{DateTime, SFloat};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Pulse Oximeter service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1822);
+/// UUID of the PLX Spot-check Measurement characteristic.
+pub const SPOT_CHECK_MEASUREMENT: Uuid = Uuid::from_u16(0x2A5E);
+/// UUID of the PLX Continuous Measurement characteristic.
+pub const CONTINUOUS_MEASUREMENT: Uuid = Uuid::from_u16(0x2A5F);
+/// UUID of the PLX Features characteristic.
+pub const FEATURES: Uuid = Uuid::from_u16(0x2A60);
+
+bitflags::bitflags! {
+    /// Features supported by an oximeter.
+    pub struct SupportedFeatures: u16 {
+        const MEASUREMENT_STATUS = 1 << 0;
+        const DEVICE_AND_SENSOR_STATUS = 1 << 1;
+        const SPOT_CHECK_MEASUREMENT_STORAGE = 1 << 2;
+        const SPOT_CHECK_TIME_STAMP = 1 << 3;
+        const SPO2PR_FAST = 1 << 4;
+        const SPO2PR_SLOW = 1 << 5;
+        const PULSE_AMPLITUDE_INDEX = 1 << 6;
+        const MULTIPLE_BONDS = 1 << 7;
+    }
+}
+
+bitflags::bitflags! {
+    /// State of the measurement.
+    pub struct MeasurementStatus: u16 {
+        const MEASUREMENT_ONGOING = 1 << 5;
+        const EARLY_ESTIMATED_DATA = 1 << 6;
+        const VALIDATED_DATA = 1 << 7;
+        const FULLY_QUALIFIED_DATA = 1 << 8;
+        const DATA_FROM_MEASUREMENT_STORAGE = 1 << 9;
+        const DATA_FOR_DEMONSTRATION = 1 << 10;
+        const DATA_FOR_TESTING = 1 << 11;
+        const CALIBRATION_ONGOING = 1 << 12;
+        const MEASUREMENT_UNAVAILABLE = 1 << 13;
+        const QUESTIONABLE_MEASUREMENT_DETECTED = 1 << 14;
+        const INVALID_MEASUREMENT_DETECTED = 1 << 15;
+    }
+}
+
+bitflags::bitflags! {
+    /// Problems with the oximeter or its sensor.
+    pub struct DeviceAndSensorStatus: u32 {
+        const EXTENDED_DISPLAY_UPDATE_ONGOING = 1 << 0;
+        const EQUIPMENT_MALFUNCTION = 1 << 1;
+        const SIGNAL_PROCESSING_IRREGULARITY = 1 << 2;
+        const INADEQUATE_SIGNAL = 1 << 3;
+        const POOR_SIGNAL = 1 << 4;
+        const LOW_PERFUSION = 1 << 5;
+        const ERRATIC_SIGNAL = 1 << 6;
+        const NONPULSATILE_SIGNAL = 1 << 7;
+        const QUESTIONABLE_PULSE = 1 << 8;
+        const SIGNAL_ANALYSIS_ONGOING = 1 << 9;
+        const SENSOR_INTERFERENCE = 1 << 10;
+        const SENSOR_UNCONNECTED_TO_USER = 1 << 11;
+        const UNKNOWN_SENSOR_CONNECTED = 1 << 12;
+        const SENSOR_DISPLACED = 1 << 13;
+        const SENSOR_MALFUNCTIONING = 1 << 14;
+        const SENSOR_DISCONNECTED = 1 << 15;
+    }
+}
+
+bitflags::bitflags! {
+    struct SpotCheckFlags: u8 {
+        const TIME_STAMP = 1 << 0;
+        const MEASUREMENT_STATUS = 1 << 1;
+        const DEVICE_AND_SENSOR_STATUS = 1 << 2;
+        const PULSE_AMPLITUDE_INDEX = 1 << 3;
+        const DEVICE_CLOCK_NOT_SET = 1 << 4;
+    }
+}
+
+bitflags::bitflags! {
+    struct ContinuousFlags: u8 {
+        const SPO2PR_FAST = 1 << 0;
+        const SPO2PR_SLOW = 1 << 1;
+        const MEASUREMENT_STATUS = 1 << 2;
+        const DEVICE_AND_SENSOR_STATUS = 1 << 3;
+        const PULSE_AMPLITUDE_INDEX = 1 << 4;
+    }
+}
+
+/// Oxygen saturation and pulse rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Spo2Pr {
+    /// Oxygen saturation in percent.
+    pub spo2: SFloat,
+    /// Pulse rate in beats per minute.
+    pub pulse_rate: SFloat,
+}
+
+impl Spo2Pr {
+    fn read(r: &mut Reader) -> Option<Self> {
+        Some(Self {
+            spo2: SFloat(r.u16()?),
+            pulse_rate: SFloat(r.u16()?),
+        })
+    }
+}
+
+/// Contents of the PLX Spot-check Measurement characteristic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpotCheckMeasurement {
+    pub spo2_pr: Spo2Pr,
+    pub time_stamp: Option<DateTime>,
+    pub measurement_status: Option<MeasurementStatus>,
+    pub device_and_sensor_status: Option<DeviceAndSensorStatus>,
+    /// Pulse amplitude index in percent.
+    pub pulse_amplitude_index: Option<SFloat>,
+    /// If the time stamp is not from a set clock.
+    pub device_clock_not_set: bool,
+}
+
+impl SpotCheckMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = SpotCheckFlags::from_bits_truncate(r.u8()?);
+
+        let spo2_pr = Spo2Pr::read(&mut r)?;
+        let time_stamp = r.read_if(flags.contains(SpotCheckFlags::TIME_STAMP), |r| {
+            DateTime::decode(r.bytes(DateTime::LEN)?)
+        })?;
+        let measurement_status = r.read_if(
+            flags.contains(SpotCheckFlags::MEASUREMENT_STATUS),
+            read_measurement_status,
+        )?;
+        let device_and_sensor_status = r.read_if(
+            flags.contains(SpotCheckFlags::DEVICE_AND_SENSOR_STATUS),
+            read_device_and_sensor_status,
+        )?;
+        let pulse_amplitude_index = r
+            .read_if(flags.contains(SpotCheckFlags::PULSE_AMPLITUDE_INDEX), |r| {
+                r.u16().map(SFloat)
+            })?;
+
+        Some(Self {
+            spo2_pr,
+            time_stamp,
+            measurement_status,
+            device_and_sensor_status,
+            pulse_amplitude_index,
+            device_clock_not_set: flags.contains(SpotCheckFlags::DEVICE_CLOCK_NOT_SET),
+        })
+    }
+}
+
+/// Contents of the PLX Continuous Measurement characteristic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContinuousMeasurement {
+    /// Values with normal averaging.
+    pub spo2_pr_normal: Spo2Pr,
+    /// Values with less averaging.
+    pub spo2_pr_fast: Option<Spo2Pr>,
+    /// Values with more averaging.
+    pub spo2_pr_slow: Option<Spo2Pr>,
+    pub measurement_status: Option<MeasurementStatus>,
+    pub device_and_sensor_status: Option<DeviceAndSensorStatus>,
+    /// Pulse amplitude index in percent.
+    pub pulse_amplitude_index: Option<SFloat>,
+}
+
+impl ContinuousMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = ContinuousFlags::from_bits_truncate(r.u8()?);
+
+        let spo2_pr_normal = Spo2Pr::read(&mut r)?;
+        let spo2_pr_fast = r.read_if(flags.contains(ContinuousFlags::SPO2PR_FAST), Spo2Pr::read)?;
+        let spo2_pr_slow = r.read_if(flags.contains(ContinuousFlags::SPO2PR_SLOW), Spo2Pr::read)?;
+        let measurement_status = r.read_if(
+            flags.contains(ContinuousFlags::MEASUREMENT_STATUS),
+            read_measurement_status,
+        )?;
+        let device_and_sensor_status = r.read_if(
+            flags.contains(ContinuousFlags::DEVICE_AND_SENSOR_STATUS),
+            read_device_and_sensor_status,
+        )?;
+        let pulse_amplitude_index = r.read_if(
+            flags.contains(ContinuousFlags::PULSE_AMPLITUDE_INDEX),
+            |r| r.u16().map(SFloat),
+        )?;
+
+        Some(Self {
+            spo2_pr_normal,
+            spo2_pr_fast,
+            spo2_pr_slow,
+            measurement_status,
+            device_and_sensor_status,
+            pulse_amplitude_index,
+        })
+    }
+}
+
+fn read_measurement_status(r: &mut Reader) -> Option<MeasurementStatus> {
+    r.u16().map(MeasurementStatus::from_bits_truncate)
+}
+
+fn read_device_and_sensor_status(r: &mut Reader) -> Option<DeviceAndSensorStatus> {
+    r.u24().map(DeviceAndSensorStatus::from_bits_truncate)
+}
+
+/// Contents of the PLX Features characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Features {
+    pub supported: SupportedFeatures,
+    /// Which measurement status bits the oximeter can set.
+    pub measurement_status: Option<MeasurementStatus>,
+    /// Which device and sensor status bits the oximeter can set.
+    pub device_and_sensor_status: Option<DeviceAndSensorStatus>,
+}
+
+impl Features {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let supported = SupportedFeatures::from_bits_truncate(r.u16()?);
+
+        let measurement_status = r.read_if(
+            supported.contains(SupportedFeatures::MEASUREMENT_STATUS),
+            read_measurement_status,
+        )?;
+        let device_and_sensor_status = r.read_if(
+            supported.contains(SupportedFeatures::DEVICE_AND_SENSOR_STATUS),
+            read_device_and_sensor_status,
+        )?;
+
+        Some(Self {
+            supported,
+            measurement_status,
+            device_and_sensor_status,
+        })
+    }
+}
+
+/// Client for a device's Pulse Oximeter service.
+pub struct PulseOximeter<C: RemoteCharacteristic> {
+    spot_check_measurement: Option<C>,
+    continuous_measurement: Option<C>,
+    features: C,
+}
+
+impl<C: RemoteCharacteristic> PulseOximeter<C> {
+    /// Find the characteristics of the Pulse Oximeter service.
+    ///
+    /// Fails if the service does not have the PLX Features characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let features = service
+            .characteristic(FEATURES)?
+            .ok_or(Error::MissingAttribute(FEATURES))?;
+
+        Ok(Self {
+            spot_check_measurement: service.characteristic(SPOT_CHECK_MEASUREMENT)?,
+            continuous_measurement: service.characteristic(CONTINUOUS_MEASUREMENT)?,
+            features,
+        })
+    }
+
+    /// Read which features the oximeter supports.
+    pub fn features(&self) -> Result<Features> {
+        Features::decode(&self.features.read_value()?).ok_or(Error::InvalidValue)
+    }
+
+    /// Subscribe to spot-check measurements.
+    pub fn spot_check_measurements(&self) -> Result<Notifications<'_, C, SpotCheckMeasurement>> {
+        let characteristic = self
+            .spot_check_measurement
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SPOT_CHECK_MEASUREMENT))?;
+
+        Notifications::new(characteristic, SpotCheckMeasurement::decode)
+    }
+
+    /// Subscribe to continuous measurements.
+    pub fn continuous_measurements(&self) -> Result<Notifications<'_, C, ContinuousMeasurement>> {
+        let characteristic = self
+            .continuous_measurement
+            .as_ref()
+            .ok_or(Error::MissingAttribute(CONTINUOUS_MEASUREMENT))?;
+
+        Notifications::new(characteristic, ContinuousMeasurement::decode)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for PulseOximeter<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PulseOximeter")
+            .field("features", &self.features)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ContinuousMeasurement, DeviceAndSensorStatus, Features, MeasurementStatus, Spo2Pr,
+        SpotCheckMeasurement, SupportedFeatures,
+    };
+    use crate::gatt::types::SFloat;
+
+    #[test]
+    fn test_decode_measurements() {
+        let measurement = SpotCheckMeasurement::decode(&[
+            0x16, 0x62, 0x00, 0x48, 0x00, 0x80, 0x00, 0x20, 0x00, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(
+            measurement.spo2_pr,
+            Spo2Pr {
+                spo2: SFloat(98),
+                pulse_rate: SFloat(72),
+            }
+        );
+        assert_eq!(
+            measurement.measurement_status,
+            Some(MeasurementStatus::VALIDATED_DATA)
+        );
+        assert_eq!(
+            measurement.device_and_sensor_status,
+            Some(DeviceAndSensorStatus::LOW_PERFUSION)
+        );
+        assert!(measurement.device_clock_not_set);
+        assert_eq!(measurement.time_stamp, None);
+
+        let measurement = ContinuousMeasurement::decode(&[
+            0x11, 0x62, 0x00, 0x48, 0x00, 0x61, 0x00, 0x4A, 0x00, 0x2C, 0xF0,
+        ])
+        .unwrap();
+        assert_eq!(measurement.spo2_pr_fast.unwrap().spo2, SFloat(97));
+        assert_eq!(measurement.spo2_pr_slow, None);
+        assert_eq!(measurement.pulse_amplitude_index.unwrap().to_f64(), 4.4);
+        assert_eq!(
+            ContinuousMeasurement::decode(&[0x02, 0x62, 0x00, 0x48, 0x00]),
+            None
+        );
+
+        let features = Features::decode(&[0x45, 0x00, 0xE0, 0x01]).unwrap();
+        assert_eq!(
+            features.supported,
+            SupportedFeatures::MEASUREMENT_STATUS
+                | SupportedFeatures::SPOT_CHECK_MEASUREMENT_STORAGE
+                | SupportedFeatures::PULSE_AMPLITUDE_INDEX
+        );
+        assert_eq!(
+            features.measurement_status,
+            Some(
+                MeasurementStatus::MEASUREMENT_ONGOING
+                    | MeasurementStatus::EARLY_ESTIMATED_DATA
+                    | MeasurementStatus::VALIDATED_DATA
+                    | MeasurementStatus::FULLY_QUALIFIED_DATA
+            )
+        );
+        assert_eq!(features.device_and_sensor_status, None);
+    }
+}
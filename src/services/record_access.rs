@@ -0,0 +1,275 @@
+//! Record Access Control Point, used to download stored records from
+//! medical devices.
+//!
+//! Records are sent as notifications on the service's measurement
+//! characteristics, followed by an indication on the control point once the
+//! device is done.
+
+use std::time::{Duration, Instant};
+
+use crate::gatt::types::DateTime;
+use crate::{CharacteristicIO, ControlPointError, Error, RemoteCharacteristic, Result, Uuid};
+
+/// UUID of the Record Access Control Point characteristic.
+pub const RECORD_ACCESS_CONTROL_POINT: Uuid = Uuid::from_u16(0x2A52);
+
+const REPORT_STORED_RECORDS: u8 = 0x01;
+const DELETE_STORED_RECORDS: u8 = 0x02;
+const ABORT_OPERATION: u8 = 0x03;
+const REPORT_NUMBER_OF_STORED_RECORDS: u8 = 0x04;
+const NUMBER_OF_STORED_RECORDS_RESPONSE: u8 = 0x05;
+const RESPONSE_CODE: u8 = 0x06;
+
+const NULL: u8 = 0x00;
+const ALL_RECORDS: u8 = 0x01;
+const GREATER_THAN_OR_EQUAL_TO: u8 = 0x03;
+const WITHIN_RANGE_OF: u8 = 0x04;
+const FIRST_RECORD: u8 = 0x05;
+const LAST_RECORD: u8 = 0x06;
+
+const SEQUENCE_NUMBER: u8 = 0x01;
+const USER_FACING_TIME: u8 = 0x02;
+
+const SUCCESS: u8 = 0x01;
+
+/// Result code when the device does not support a request's op code.
+pub const OP_CODE_NOT_SUPPORTED: u8 = 0x02;
+/// Result code when a request's operator was not valid for its op code.
+pub const INVALID_OPERATOR: u8 = 0x03;
+/// Result code when the device does not support a request's operator.
+pub const OPERATOR_NOT_SUPPORTED: u8 = 0x04;
+/// Result code when a request's operand was not valid.
+pub const INVALID_OPERAND: u8 = 0x05;
+/// Result code when a procedure was valid but no records matched.
+pub const NO_RECORDS_FOUND: u8 = 0x06;
+/// Result code when a procedure could not be aborted.
+pub const ABORT_UNSUCCESSFUL: u8 = 0x07;
+/// Result code when a procedure was stopped before it finished.
+pub const PROCEDURE_NOT_COMPLETED: u8 = 0x08;
+/// Result code when the device does not support a request's filter type.
+pub const OPERAND_NOT_SUPPORTED: u8 = 0x09;
+
+/// Time to wait between checking for records and for the procedure to
+/// finish.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Which stored records a procedure applies to.
+///
+/// Ranges include both ends. Continuous glucose monitors use time offsets in
+/// place of sequence numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordFilter {
+    All,
+    First,
+    Last,
+    SequenceNumberAtLeast(u16),
+    SequenceNumberRange(u16, u16),
+    TimeAtLeast(DateTime),
+    TimeRange(DateTime, DateTime),
+}
+
+impl RecordFilter {
+    /// Encode the operator and operand of a request.
+    fn encode(&self) -> Vec<u8> {
+        match self {
+            RecordFilter::All => vec![ALL_RECORDS],
+            RecordFilter::First => vec![FIRST_RECORD],
+            RecordFilter::Last => vec![LAST_RECORD],
+            RecordFilter::SequenceNumberAtLeast(minimum) => {
+                let mut data = vec![GREATER_THAN_OR_EQUAL_TO, SEQUENCE_NUMBER];
+                data.extend_from_slice(&minimum.to_le_bytes());
+                data
+            }
+            RecordFilter::SequenceNumberRange(minimum, maximum) => {
+                let mut data = vec![WITHIN_RANGE_OF, SEQUENCE_NUMBER];
+                data.extend_from_slice(&minimum.to_le_bytes());
+                data.extend_from_slice(&maximum.to_le_bytes());
+                data
+            }
+            RecordFilter::TimeAtLeast(minimum) => {
+                let mut data = vec![GREATER_THAN_OR_EQUAL_TO, USER_FACING_TIME];
+                data.extend_from_slice(&minimum.encode());
+                data
+            }
+            RecordFilter::TimeRange(minimum, maximum) => {
+                let mut data = vec![WITHIN_RANGE_OF, USER_FACING_TIME];
+                data.extend_from_slice(&minimum.encode());
+                data.extend_from_slice(&maximum.encode());
+                data
+            }
+        }
+    }
+}
+
+/// Response indicated by the control point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Response {
+    NumberOfRecords(u16),
+    Result { op_code: u8, result: u8 },
+}
+
+/// Sends procedures to a Record Access Control Point and waits for their
+/// responses.
+pub(crate) struct RecordAccessClient<'a, C: RemoteCharacteristic> {
+    characteristic: &'a C,
+    io: CharacteristicIO<'a, C>,
+    timeout: Duration,
+}
+
+impl<'a, C: RemoteCharacteristic> RecordAccessClient<'a, C> {
+    pub(crate) fn new(characteristic: &'a C, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            characteristic,
+            io: CharacteristicIO::new(characteristic)?,
+            timeout,
+        })
+    }
+
+    /// Start a procedure without waiting for its response.
+    fn send(&mut self, op_code: u8, filter: Option<&RecordFilter>) -> Result<()> {
+        let mut request = vec![op_code];
+        match filter {
+            Some(filter) => request.extend(filter.encode()),
+            None => request.push(NULL),
+        }
+
+        log::debug!("Sending record access request {:?}", request);
+        self.characteristic.write_value(&request)
+    }
+
+    /// Wait up to the given duration for a response, skipping any values
+    /// that are not valid responses.
+    fn poll(&mut self, timeout: Duration) -> Option<Response> {
+        let response = self.io.next_notification_timeout(timeout)?;
+
+        match *response.as_slice() {
+            [NUMBER_OF_STORED_RECORDS_RESPONSE, NULL, low, high] => {
+                Some(Response::NumberOfRecords(u16::from_le_bytes([low, high])))
+            }
+            [RESPONSE_CODE, NULL, op_code, result] => Some(Response::Result { op_code, result }),
+            _ => {
+                log::debug!("Ignoring unexpected response {:?}", response);
+                None
+            }
+        }
+    }
+
+    /// Run a procedure which is answered only by the control point,
+    /// returning the number of records if the device sent it.
+    fn execute(&mut self, op_code: u8, filter: Option<&RecordFilter>) -> Result<Option<u16>> {
+        self.send(op_code, filter)?;
+
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(Error::Timeout)?;
+
+            match self.poll(remaining) {
+                Some(Response::NumberOfRecords(count))
+                    if op_code == REPORT_NUMBER_OF_STORED_RECORDS =>
+                {
+                    return Ok(Some(count))
+                }
+                Some(Response::Result {
+                    op_code: request,
+                    result,
+                }) if request == op_code => {
+                    return match result {
+                        SUCCESS | NO_RECORDS_FOUND => Ok(None),
+                        result => Err(ControlPointError { op_code, result }.into()),
+                    }
+                }
+                Some(response) => log::debug!("Ignoring unexpected response {:?}", response),
+                None => (),
+            }
+        }
+    }
+
+    /// Run a procedure which reports records through other characteristics.
+    ///
+    /// `collect` is called while waiting to gather any records that arrived,
+    /// returning if there were any. The procedure times out only if nothing
+    /// arrives for the client's timeout, and is aborted if it does.
+    pub(crate) fn report(
+        &mut self,
+        filter: &RecordFilter,
+        mut collect: impl FnMut() -> bool,
+    ) -> Result<()> {
+        self.send(REPORT_STORED_RECORDS, Some(filter))?;
+
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            if collect() {
+                deadline = Instant::now() + self.timeout;
+            }
+
+            match self.poll(POLL_INTERVAL) {
+                Some(Response::Result { op_code, result }) if op_code == REPORT_STORED_RECORDS => {
+                    // Devices send every record before the response.
+                    collect();
+
+                    return match result {
+                        SUCCESS | NO_RECORDS_FOUND => Ok(()),
+                        result => Err(ControlPointError { op_code, result }.into()),
+                    };
+                }
+                Some(response) => log::debug!("Ignoring unexpected response {:?}", response),
+                None if Instant::now() > deadline => {
+                    log::warn!("Timed out reporting records, aborting");
+                    if let Err(err) = self.abort() {
+                        log::warn!("Unable to abort reporting records: {}", err);
+                    }
+
+                    return Err(Error::Timeout);
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// Abort the procedure in progress.
+    pub(crate) fn abort(&mut self) -> Result<()> {
+        self.execute(ABORT_OPERATION, None).map(|_count| ())
+    }
+
+    /// Get how many records match a filter.
+    pub(crate) fn count(&mut self, filter: &RecordFilter) -> Result<u16> {
+        self.execute(REPORT_NUMBER_OF_STORED_RECORDS, Some(filter))?
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Delete records matching a filter.
+    pub(crate) fn delete(&mut self, filter: &RecordFilter) -> Result<()> {
+        self.execute(DELETE_STORED_RECORDS, Some(filter))
+            .map(|_count| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordFilter;
+    use crate::gatt::types::DateTime;
+
+    #[test]
+    fn test_encode_filter() {
+        assert_eq!(RecordFilter::All.encode(), vec![0x01]);
+        assert_eq!(
+            RecordFilter::SequenceNumberRange(5, 0x0102).encode(),
+            vec![0x04, 0x01, 0x05, 0x00, 0x02, 0x01]
+        );
+
+        let time = DateTime {
+            year: 2020,
+            month: 6,
+            day: 1,
+            hours: 12,
+            minutes: 0,
+            seconds: 0,
+        };
+        assert_eq!(
+            RecordFilter::TimeAtLeast(time).encode(),
+            vec![0x03, 0x02, 0xE4, 0x07, 0x06, 0x01, 0x0C, 0x00, 0x00]
+        );
+    }
+}
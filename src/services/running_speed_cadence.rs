@@ -0,0 +1,251 @@
+//! Running Speed and Cadence Service, as provided by footpods.
+
+use std::time::Duration;
+
+use super::speed_cadence::{
+    read_sensor_location, ScControlPoint, SensorLocation, SC_CONTROL_POINT, SENSOR_LOCATION,
+};
+use super::{Notifications, Reader, DEFAULT_TIMEOUT};
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Running Speed and Cadence service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x1814);
+/// UUID of the RSC Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A53);
+/// UUID of the RSC Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A54);
+
+bitflags::bitflags! {
+    /// Contents of the RSC Feature characteristic.
+    pub struct RscFeatures: u16 {
+        const STRIDE_LENGTH_MEASUREMENT = 1 << 0;
+        const TOTAL_DISTANCE_MEASUREMENT = 1 << 1;
+        const WALKING_OR_RUNNING_STATUS = 1 << 2;
+        const CALIBRATION_PROCEDURE = 1 << 3;
+        const MULTIPLE_SENSOR_LOCATIONS = 1 << 4;
+    }
+}
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const STRIDE_LENGTH = 1 << 0;
+        const TOTAL_DISTANCE = 1 << 1;
+        const RUNNING = 1 << 2;
+    }
+}
+
+/// Contents of the RSC Measurement characteristic.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RscMeasurement {
+    /// Speed in units of 1/256 meters per second.
+    pub speed: u16,
+    /// Cadence in steps per minute.
+    pub cadence: u8,
+    /// Stride length in centimeters.
+    pub stride_length: Option<u16>,
+    /// Total distance in units of 1/10 meters.
+    pub total_distance: Option<u32>,
+    /// If the user is running rather than walking. Only meaningful if the
+    /// sensor supports [RscFeatures::WALKING_OR_RUNNING_STATUS].
+    pub running: bool,
+}
+
+impl RscMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let speed = r.u16()?;
+        let cadence = r.u8()?;
+        let stride_length = r.read_if(flags.contains(Flags::STRIDE_LENGTH), Reader::u16)?;
+        let total_distance = r.read_if(flags.contains(Flags::TOTAL_DISTANCE), Reader::u32)?;
+
+        Some(Self {
+            speed,
+            cadence,
+            stride_length,
+            total_distance,
+            running: flags.contains(Flags::RUNNING),
+        })
+    }
+
+    /// Get the speed in meters per second.
+    pub fn speed_mps(&self) -> f64 {
+        f64::from(self.speed) / 256.0
+    }
+}
+
+/// Client for a device's Running Speed and Cadence service.
+pub struct RunningSpeedCadence<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: Option<C>,
+    sensor_location: Option<C>,
+    control_point: Option<C>,
+    timeout: Duration,
+}
+
+impl<C: RemoteCharacteristic> RunningSpeedCadence<C> {
+    /// Find the characteristics of the Running Speed and Cadence service.
+    ///
+    /// Fails if the service does not have the RSC Measurement characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            feature: service.characteristic(FEATURE)?,
+            sensor_location: service.characteristic(SENSOR_LOCATION)?,
+            control_point: service.characteristic(SC_CONTROL_POINT)?,
+            timeout: DEFAULT_TIMEOUT,
+        })
+    }
+
+    /// Set how long to wait for the sensor to respond to control point
+    /// requests.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Subscribe to speed and cadence measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, RscMeasurement>> {
+        Notifications::new(&self.measurement, RscMeasurement::decode)
+    }
+
+    /// Read which measurements and procedures the sensor supports.
+    pub fn features(&self) -> Result<RscFeatures> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        Reader::new(&data)
+            .u16()
+            .map(RscFeatures::from_bits_truncate)
+            .ok_or(Error::InvalidValue)
+    }
+
+    /// Read where the sensor is mounted, if the device says.
+    pub fn sensor_location(&self) -> Result<Option<SensorLocation>> {
+        read_sensor_location(self.sensor_location.as_ref())
+    }
+
+    /// Subscribe to control point responses so the sensor can be configured.
+    ///
+    /// The cumulative value is the total distance in units of 1/10 meters.
+    pub fn control(&self) -> Result<ScControlPoint<'_, C>> {
+        let characteristic = self
+            .control_point
+            .as_ref()
+            .ok_or(Error::MissingAttribute(SC_CONTROL_POINT))?;
+
+        ScControlPoint::new(characteristic, self.timeout)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for RunningSpeedCadence<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunningSpeedCadence")
+            .field("measurement", &self.measurement)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::{RscFeatures, RscMeasurement, RunningSpeedCadence, FEATURE, MEASUREMENT, SERVICE};
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::services::connect;
+    use crate::services::speed_cadence::SC_CONTROL_POINT;
+    use crate::{CharacteristicProperties, ControlPointError, Error};
+
+    #[test]
+    fn test_footpod() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler_requests = requests.clone();
+
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    MEASUREMENT,
+                    CharacteristicProperties::NOTIFY,
+                ))
+                .with_characteristic(
+                    LocalCharacteristic::new(FEATURE, CharacteristicProperties::READ)
+                        .with_value(&[0x0F, 0x00]),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        SC_CONTROL_POINT,
+                        CharacteristicProperties::WRITE | CharacteristicProperties::INDICATE,
+                    )
+                    .on_write(move |server, value| {
+                        handler_requests.lock().unwrap().push(value.to_vec());
+                        let result = if value[0] == 0x02 { 0x04 } else { 0x01 };
+                        server.indicate(SERVICE, SC_CONTROL_POINT, &[0x10, value[0], result]);
+                        Ok(())
+                    }),
+                ),
+        );
+
+        let service = connect(&server, SERVICE);
+        let footpod = RunningSpeedCadence::new(&service)
+            .unwrap()
+            .with_timeout(Duration::from_millis(50));
+
+        assert_eq!(
+            footpod.features().unwrap(),
+            RscFeatures::STRIDE_LENGTH_MEASUREMENT
+                | RscFeatures::TOTAL_DISTANCE_MEASUREMENT
+                | RscFeatures::WALKING_OR_RUNNING_STATUS
+                | RscFeatures::CALIBRATION_PROCEDURE
+        );
+
+        let mut measurements = footpod.measurements().unwrap();
+        server.notify(
+            SERVICE,
+            MEASUREMENT,
+            &[0x07, 0x00, 0x03, 0xAA, 0x78, 0x00, 0x10, 0x27, 0x00, 0x00],
+        );
+        server.notify(SERVICE, MEASUREMENT, &[0x00, 0x80, 0x01, 0x50]);
+        let measurement = measurements.next().unwrap();
+        assert_eq!(
+            measurement,
+            RscMeasurement {
+                speed: 0x0300,
+                cadence: 170,
+                stride_length: Some(120),
+                total_distance: Some(10_000),
+                running: true,
+            }
+        );
+        assert_eq!(measurement.speed_mps(), 3.0);
+        let measurement = measurements.next().unwrap();
+        assert_eq!(measurement.speed_mps(), 1.5);
+        assert!(!measurement.running);
+        assert_eq!(measurement.total_distance, None);
+        assert_eq!(RscMeasurement::decode(&[0x01, 0x00, 0x03, 0xAA]), None);
+
+        let mut control = footpod.control().unwrap();
+        control.set_cumulative_value(42_195 * 10).unwrap();
+        match control.start_calibration() {
+            Err(Error::ControlPoint(ControlPointError {
+                op_code: 0x02,
+                result: ControlPointError::OPERATION_FAILED,
+            })) => (),
+            other => panic!("unexpected calibration result: {:?}", other),
+        }
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![vec![0x01, 0x3E, 0x70, 0x06, 0x00], vec![0x02]]
+        );
+    }
+}
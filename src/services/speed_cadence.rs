@@ -0,0 +1,240 @@
+//! Pieces shared by the cycling and running sensor services.
+
+use std::time::Duration;
+
+use super::ControlPointClient;
+use crate::{Error, RemoteCharacteristic, Result, Uuid};
+
+/// UUID of the Sensor Location characteristic.
+pub const SENSOR_LOCATION: Uuid = Uuid::from_u16(0x2A5D);
+/// UUID of the SC Control Point characteristic.
+pub const SC_CONTROL_POINT: Uuid = Uuid::from_u16(0x2A55);
+
+const SET_CUMULATIVE_VALUE: u8 = 0x01;
+const START_SENSOR_CALIBRATION: u8 = 0x02;
+const UPDATE_SENSOR_LOCATION: u8 = 0x03;
+const REQUEST_SUPPORTED_SENSOR_LOCATIONS: u8 = 0x04;
+const RESPONSE_CODE: u8 = 0x10;
+
+/// Where a sensor is mounted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SensorLocation {
+    Other,
+    TopOfShoe,
+    InShoe,
+    Hip,
+    FrontWheel,
+    LeftCrank,
+    RightCrank,
+    LeftPedal,
+    RightPedal,
+    FrontHub,
+    RearDropout,
+    Chainstay,
+    RearWheel,
+    RearHub,
+    Chest,
+    Spider,
+    ChainRing,
+    /// Location not known by this library.
+    Reserved(u8),
+}
+
+impl SensorLocation {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => SensorLocation::Other,
+            1 => SensorLocation::TopOfShoe,
+            2 => SensorLocation::InShoe,
+            3 => SensorLocation::Hip,
+            4 => SensorLocation::FrontWheel,
+            5 => SensorLocation::LeftCrank,
+            6 => SensorLocation::RightCrank,
+            7 => SensorLocation::LeftPedal,
+            8 => SensorLocation::RightPedal,
+            9 => SensorLocation::FrontHub,
+            10 => SensorLocation::RearDropout,
+            11 => SensorLocation::Chainstay,
+            12 => SensorLocation::RearWheel,
+            13 => SensorLocation::RearHub,
+            14 => SensorLocation::Chest,
+            15 => SensorLocation::Spider,
+            16 => SensorLocation::ChainRing,
+            other => SensorLocation::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            SensorLocation::Other => 0,
+            SensorLocation::TopOfShoe => 1,
+            SensorLocation::InShoe => 2,
+            SensorLocation::Hip => 3,
+            SensorLocation::FrontWheel => 4,
+            SensorLocation::LeftCrank => 5,
+            SensorLocation::RightCrank => 6,
+            SensorLocation::LeftPedal => 7,
+            SensorLocation::RightPedal => 8,
+            SensorLocation::FrontHub => 9,
+            SensorLocation::RearDropout => 10,
+            SensorLocation::Chainstay => 11,
+            SensorLocation::RearWheel => 12,
+            SensorLocation::RearHub => 13,
+            SensorLocation::Chest => 14,
+            SensorLocation::Spider => 15,
+            SensorLocation::ChainRing => 16,
+            SensorLocation::Reserved(other) => other,
+        }
+    }
+}
+
+/// Read a Sensor Location characteristic, if the device has one.
+pub(crate) fn read_sensor_location<C: RemoteCharacteristic>(
+    characteristic: Option<&C>,
+) -> Result<Option<SensorLocation>> {
+    let characteristic = match characteristic {
+        Some(characteristic) => characteristic,
+        None => return Ok(None),
+    };
+
+    match characteristic.read_value()?.as_slice() {
+        [location] => Ok(Some(SensorLocation::from_u8(*location))),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+/// Revolutions per second between two event times, handling the counters
+/// rolling over.
+fn rate(revolutions: u32, ticks: u16, ticks_per_second: u16) -> Option<f64> {
+    if ticks == 0 {
+        return None;
+    }
+
+    Some(f64::from(revolutions) * f64::from(ticks_per_second) / f64::from(ticks))
+}
+
+/// Wheel revolutions counted by a sensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WheelRevolutions {
+    /// Total revolutions, wrapping around.
+    pub cumulative: u32,
+    /// Time of the last revolution, wrapping around.
+    pub last_event_time: u16,
+    /// Units of `last_event_time` per second, which depends on the service.
+    pub ticks_per_second: u16,
+}
+
+impl WheelRevolutions {
+    /// Get the speed in meters per second since a previous count, given the
+    /// wheel circumference in meters.
+    ///
+    /// Returns `None` if no time passed between the counts.
+    pub fn speed_since(&self, previous: &Self, circumference: f64) -> Option<f64> {
+        let revolutions = self.cumulative.wrapping_sub(previous.cumulative);
+        let ticks = self.last_event_time.wrapping_sub(previous.last_event_time);
+
+        rate(revolutions, ticks, self.ticks_per_second).map(|rate| rate * circumference)
+    }
+}
+
+/// Crank revolutions counted by a sensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CrankRevolutions {
+    /// Total revolutions, wrapping around.
+    pub cumulative: u16,
+    /// Time of the last revolution in units of 1/1024 seconds, wrapping
+    /// around.
+    pub last_event_time: u16,
+}
+
+impl CrankRevolutions {
+    /// Get the cadence in revolutions per minute since a previous count.
+    ///
+    /// Returns `None` if no time passed between the counts.
+    pub fn cadence_since(&self, previous: &Self) -> Option<f64> {
+        let revolutions = self.cumulative.wrapping_sub(previous.cumulative);
+        let ticks = self.last_event_time.wrapping_sub(previous.last_event_time);
+
+        rate(u32::from(revolutions), ticks, 1024).map(|rate| rate * 60.0)
+    }
+}
+
+/// Procedures on the SC Control Point, used by speed and cadence sensors.
+///
+/// Each request waits for the sensor to indicate its response, failing with
+/// [Error::Timeout] if it does not respond in time, or
+/// [Error::ControlPoint] if it rejects the request.
+pub struct ScControlPoint<'a, C: RemoteCharacteristic> {
+    client: ControlPointClient<'a, C>,
+}
+
+impl<'a, C: RemoteCharacteristic> ScControlPoint<'a, C> {
+    pub(crate) fn new(characteristic: &'a C, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            client: ControlPointClient::new(characteristic, RESPONSE_CODE, timeout)?,
+        })
+    }
+
+    /// Set the cumulative value, such as wheel revolutions or total distance.
+    pub fn set_cumulative_value(&mut self, value: u32) -> Result<()> {
+        self.client
+            .execute(SET_CUMULATIVE_VALUE, &value.to_le_bytes())
+            .map(|_parameter| ())
+    }
+
+    /// Start calibrating the sensor.
+    pub fn start_calibration(&mut self) -> Result<()> {
+        self.client
+            .execute(START_SENSOR_CALIBRATION, &[])
+            .map(|_parameter| ())
+    }
+
+    /// Tell the sensor where it is mounted.
+    pub fn set_sensor_location(&mut self, location: SensorLocation) -> Result<()> {
+        self.client
+            .execute(UPDATE_SENSOR_LOCATION, &[location.to_u8()])
+            .map(|_parameter| ())
+    }
+
+    /// Ask the sensor where it can be mounted.
+    pub fn supported_sensor_locations(&mut self) -> Result<Vec<SensorLocation>> {
+        let locations = self
+            .client
+            .execute(REQUEST_SUPPORTED_SENSOR_LOCATIONS, &[])?;
+
+        Ok(locations.into_iter().map(SensorLocation::from_u8).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrankRevolutions, WheelRevolutions};
+
+    #[test]
+    fn test_rollover() {
+        let previous = CrankRevolutions {
+            cumulative: 0xFFFF,
+            last_event_time: 0xFC00,
+        };
+        let current = CrankRevolutions {
+            cumulative: 0x0001,
+            last_event_time: 0x0100,
+        };
+        // Two revolutions in 1280/1024 seconds.
+        assert_eq!(current.cadence_since(&previous), Some(96.0));
+        assert_eq!(current.cadence_since(&current), None);
+
+        let previous = WheelRevolutions {
+            cumulative: 0xFFFF_FFFF,
+            last_event_time: 0xFFFF,
+            ticks_per_second: 2048,
+        };
+        let current = WheelRevolutions {
+            cumulative: 0x0000_0003,
+            last_event_time: 0x07FF,
+            ticks_per_second: 2048,
+        };
+        // Four revolutions of a 2 meter wheel in one second.
+        assert_eq!(current.speed_since(&previous, 2.0), Some(8.0));
+    }
+}
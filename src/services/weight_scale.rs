@@ -0,0 +1,300 @@
+//! Weight Scale Service, as provided by bathroom scales.
+
+use super::{Notifications, Reader};
+use crate::gatt::types::DateTime;
+use crate::{Error, RemoteCharacteristic, RemoteService, Result, Uuid};
+
+/// UUID of the Weight Scale service.
+pub const SERVICE: Uuid = Uuid::from_u16(0x181D);
+/// UUID of the Weight Measurement characteristic.
+pub const MEASUREMENT: Uuid = Uuid::from_u16(0x2A9D);
+/// UUID of the Weight Scale Feature characteristic.
+pub const FEATURE: Uuid = Uuid::from_u16(0x2A9E);
+
+bitflags::bitflags! {
+    struct Flags: u8 {
+        const IMPERIAL = 1 << 0;
+        const TIME_STAMP = 1 << 1;
+        const USER_ID = 1 << 2;
+        const BMI_AND_HEIGHT = 1 << 3;
+    }
+}
+
+bitflags::bitflags! {
+    /// Optional fields a scale can include in its measurements.
+    pub struct WeightScaleFeatures: u32 {
+        const TIME_STAMP = 1 << 0;
+        const MULTIPLE_USERS = 1 << 1;
+        const BMI = 1 << 2;
+    }
+}
+
+/// Units and resolutions of masses and lengths.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeasurementSystem {
+    /// Masses in units of 0.005 kilograms and lengths in units of 0.001
+    /// meters.
+    Si,
+    /// Masses in units of 0.01 pounds and lengths in units of 0.1 inches.
+    Imperial,
+}
+
+impl MeasurementSystem {
+    pub(crate) fn from_imperial_flag(imperial: bool) -> Self {
+        if imperial {
+            MeasurementSystem::Imperial
+        } else {
+            MeasurementSystem::Si
+        }
+    }
+
+    /// Convert a mass to kilograms.
+    pub fn kilograms(self, mass: u16) -> f64 {
+        match self {
+            MeasurementSystem::Si => f64::from(mass) / 200.0,
+            MeasurementSystem::Imperial => f64::from(mass) / 100.0 * 0.453_592_37,
+        }
+    }
+
+    /// Convert a length to meters.
+    pub fn meters(self, length: u16) -> f64 {
+        match self {
+            MeasurementSystem::Si => f64::from(length) / 1000.0,
+            MeasurementSystem::Imperial => f64::from(length) / 10.0 * 0.0254,
+        }
+    }
+}
+
+/// Resolution a scale measures masses with, as given in its feature
+/// characteristic.
+///
+/// This does not change the units of masses, only how precise they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MassResolution(pub u8);
+
+impl MassResolution {
+    /// Get the resolution in kilograms, or `None` if it is not specified.
+    pub fn kilograms(self, units: MeasurementSystem) -> Option<f64> {
+        let (kilograms, pounds) = match self.0 {
+            1 => (0.5, 1.0),
+            2 => (0.2, 0.5),
+            3 => (0.1, 0.2),
+            4 => (0.05, 0.1),
+            5 => (0.02, 0.05),
+            6 => (0.01, 0.02),
+            7 => (0.005, 0.01),
+            _ => return None,
+        };
+
+        Some(match units {
+            MeasurementSystem::Si => kilograms,
+            MeasurementSystem::Imperial => pounds * 0.453_592_37,
+        })
+    }
+}
+
+/// Resolution a scale measures heights with, as given in its feature
+/// characteristic.
+///
+/// This does not change the units of heights, only how precise they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HeightResolution(pub u8);
+
+impl HeightResolution {
+    /// Get the resolution in meters, or `None` if it is not specified.
+    pub fn meters(self, units: MeasurementSystem) -> Option<f64> {
+        let (meters, inches) = match self.0 {
+            1 => (0.01, 1.0),
+            2 => (0.005, 0.5),
+            3 => (0.001, 0.1),
+            _ => return None,
+        };
+
+        Some(match units {
+            MeasurementSystem::Si => meters,
+            MeasurementSystem::Imperial => inches * 0.0254,
+        })
+    }
+}
+
+/// Contents of the Weight Scale Feature characteristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WeightScaleFeature {
+    pub features: WeightScaleFeatures,
+    pub mass_resolution: MassResolution,
+    pub height_resolution: HeightResolution,
+}
+
+impl WeightScaleFeature {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let value = Reader::new(data).u32()?;
+
+        Some(Self {
+            features: WeightScaleFeatures::from_bits_truncate(value),
+            mass_resolution: MassResolution(((value >> 3) & 0x0F) as u8),
+            height_resolution: HeightResolution(((value >> 7) & 0x07) as u8),
+        })
+    }
+}
+
+/// Contents of the Weight Measurement characteristic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WeightMeasurement {
+    pub units: MeasurementSystem,
+    /// Weight in mass units, with 0xFFFF meaning the measurement failed.
+    pub weight: u16,
+    pub time_stamp: Option<DateTime>,
+    /// User who took the measurement, with 0xFF meaning unknown.
+    pub user_id: Option<u8>,
+    /// Body mass index in units of 0.1 kg/m².
+    pub bmi: Option<u16>,
+    /// Height in length units.
+    pub height: Option<u16>,
+}
+
+impl WeightMeasurement {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut r = Reader::new(data);
+        let flags = Flags::from_bits_truncate(r.u8()?);
+
+        let weight = r.u16()?;
+        let time_stamp = r.read_if(flags.contains(Flags::TIME_STAMP), |r| {
+            DateTime::decode(r.bytes(DateTime::LEN)?)
+        })?;
+        let user_id = r.read_if(flags.contains(Flags::USER_ID), Reader::u8)?;
+        let bmi_and_height = r.read_if(flags.contains(Flags::BMI_AND_HEIGHT), |r| {
+            Some((r.u16()?, r.u16()?))
+        })?;
+
+        Some(Self {
+            units: MeasurementSystem::from_imperial_flag(flags.contains(Flags::IMPERIAL)),
+            weight,
+            time_stamp,
+            user_id,
+            bmi: bmi_and_height.map(|(bmi, _height)| bmi),
+            height: bmi_and_height.map(|(_bmi, height)| height),
+        })
+    }
+
+    /// Get the weight in kilograms, if the measurement succeeded.
+    pub fn weight_kilograms(&self) -> Option<f64> {
+        if self.weight == u16::MAX {
+            return None;
+        }
+
+        Some(self.units.kilograms(self.weight))
+    }
+}
+
+/// Client for a device's Weight Scale service.
+pub struct WeightScale<C: RemoteCharacteristic> {
+    measurement: C,
+    feature: Option<C>,
+}
+
+impl<C: RemoteCharacteristic> WeightScale<C> {
+    /// Find the characteristics of the Weight Scale service.
+    ///
+    /// Fails if the service does not have the Weight Measurement
+    /// characteristic.
+    pub fn new<S: RemoteService<Characteristic = C>>(service: &S) -> Result<Self> {
+        let measurement = service
+            .characteristic(MEASUREMENT)?
+            .ok_or(Error::MissingAttribute(MEASUREMENT))?;
+
+        Ok(Self {
+            measurement,
+            feature: service.characteristic(FEATURE)?,
+        })
+    }
+
+    /// Subscribe to weight measurements.
+    pub fn measurements(&self) -> Result<Notifications<'_, C, WeightMeasurement>> {
+        Notifications::new(&self.measurement, WeightMeasurement::decode)
+    }
+
+    /// Read which fields the scale supports and how precise it is.
+    pub fn feature(&self) -> Result<WeightScaleFeature> {
+        let data = self
+            .feature
+            .as_ref()
+            .ok_or(Error::MissingAttribute(FEATURE))?
+            .read_value()?;
+
+        WeightScaleFeature::decode(&data).ok_or(Error::InvalidValue)
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for WeightScale<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeightScale")
+            .field("measurement", &self.measurement)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HeightResolution, MassResolution, MeasurementSystem, WeightMeasurement, WeightScaleFeature,
+        WeightScaleFeatures,
+    };
+
+    #[test]
+    fn test_decode_measurement() {
+        let measurement =
+            WeightMeasurement::decode(&[0x0C, 0x60, 0x3B, 0x02, 0xE6, 0x00, 0x5A, 0x06]).unwrap();
+        assert_eq!(measurement.units, MeasurementSystem::Si);
+        assert_eq!(measurement.weight_kilograms(), Some(76.0));
+        assert_eq!(measurement.user_id, Some(2));
+        assert_eq!(measurement.bmi, Some(230));
+        assert_eq!(measurement.height, Some(1626));
+
+        let measurement = WeightMeasurement::decode(&[0x01, 0x10, 0x27]).unwrap();
+        assert_eq!(measurement.units, MeasurementSystem::Imperial);
+        assert_eq!(measurement.weight, 10_000);
+        assert_eq!(measurement.bmi, None);
+
+        let measurement = WeightMeasurement::decode(&[0x00, 0xFF, 0xFF]).unwrap();
+        assert_eq!(measurement.weight_kilograms(), None);
+
+        assert_eq!(
+            WeightMeasurement::decode(&[0x08, 0x60, 0x3B, 0xE6, 0x00]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_decode_feature() {
+        let feature = WeightScaleFeature::decode(&[0xBE, 0x01, 0x00, 0x00]).unwrap();
+        assert_eq!(
+            feature.features,
+            WeightScaleFeatures::MULTIPLE_USERS | WeightScaleFeatures::BMI
+        );
+        assert_eq!(feature.mass_resolution, MassResolution(7));
+        assert_eq!(feature.height_resolution, HeightResolution(3));
+        assert_eq!(
+            feature.mass_resolution.kilograms(MeasurementSystem::Si),
+            Some(0.005)
+        );
+        assert_eq!(
+            feature.height_resolution.meters(MeasurementSystem::Si),
+            Some(0.001)
+        );
+
+        let feature = WeightScaleFeature::decode(&[0x01, 0x00, 0x00, 0x00]).unwrap();
+        assert_eq!(feature.features, WeightScaleFeatures::TIME_STAMP);
+        assert_eq!(
+            feature
+                .mass_resolution
+                .kilograms(MeasurementSystem::Imperial),
+            None
+        );
+        assert_eq!(
+            feature.height_resolution.meters(MeasurementSystem::Si),
+            None
+        );
+
+        assert_eq!(WeightScaleFeature::decode(&[0x01, 0x00, 0x00]), None);
+    }
+}
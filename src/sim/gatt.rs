@@ -0,0 +1,693 @@
+//! Central role connections to simulated peripherals.
+
+use std::sync::{mpsc, Arc, Mutex};
+
+use super::{pairing, Simulator};
+use crate::gatt::{
+    GattServer, NotifyMode, RemoteCharacteristic, RemoteDescriptor, RemoteService, Subscription,
+    Subscriptions,
+};
+use crate::pairing::{PairingHandler, ProtectionLevel};
+use crate::services::battery::{self, Battery};
+use crate::services::device_information::{self, DeviceInformation};
+use crate::{BluetoothAddress, CharacteristicIO, CharacteristicProperties, Error, Result, Uuid};
+
+/// Connection to a simulated peripheral.
+#[derive(Clone)]
+pub struct Device {
+    sim: Simulator,
+    address: BluetoothAddress,
+    server: GattServer,
+    client: usize,
+    subscriptions: Arc<Subscriptions<(usize, usize)>>,
+}
+
+impl Device {
+    pub(crate) fn new(
+        sim: Simulator,
+        address: BluetoothAddress,
+        server: GattServer,
+        client: usize,
+    ) -> Self {
+        Self {
+            sim,
+            address,
+            server,
+            client,
+            subscriptions: Arc::new(Subscriptions::new()),
+        }
+    }
+
+    /// Get the address of the peripheral.
+    pub fn address(&self) -> BluetoothAddress {
+        self.address
+    }
+
+    /// Get a list of services provided by this device.
+    pub fn services(&self) -> Result<Vec<Service>> {
+        let services = self
+            .server
+            .service_uuids()
+            .into_iter()
+            .enumerate()
+            .map(|(index, uuid)| Service {
+                device: self.clone(),
+                index,
+                uuid,
+            })
+            .collect();
+
+        Ok(services)
+    }
+
+    /// Find the first service with the given UUID.
+    pub fn service(&self, uuid: Uuid) -> Result<Option<Service>> {
+        Ok(self
+            .services()?
+            .into_iter()
+            .find(|service| service.uuid == uuid))
+    }
+
+    /// Read the Device Information service.
+    pub fn device_info(&self) -> Result<DeviceInformation> {
+        let service = self
+            .service(device_information::SERVICE)?
+            .ok_or(Error::MissingAttribute(device_information::SERVICE))?;
+
+        DeviceInformation::read(&service)
+    }
+
+    /// Get a client for the Battery service.
+    pub fn battery(&self) -> Result<Battery<Characteristic>> {
+        let service = self
+            .service(battery::SERVICE)?
+            .ok_or(Error::MissingAttribute(battery::SERVICE))?;
+
+        Battery::new(&service)
+    }
+
+    /// Pair with the peripheral, using the handler to answer any prompts.
+    ///
+    /// Returns the protection level of the bond, which is kept until
+    /// [unpair](Device::unpair) is called. Pairing again succeeds immediately
+    /// if the existing bond already meets the requested protection level.
+    pub fn pair<H: PairingHandler>(
+        &self,
+        protection_level: ProtectionLevel,
+        mut handler: H,
+    ) -> Result<ProtectionLevel> {
+        let config = {
+            let radio = self.sim.radio.lock().unwrap();
+
+            if let Some(bond) = radio.bonds.get(&self.address) {
+                if *bond >= protection_level {
+                    return Ok(*bond);
+                }
+            }
+
+            radio
+                .pairing
+                .get(&self.address)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let bond = pairing::negotiate(&config, protection_level, &mut handler)?;
+        self.sim
+            .radio
+            .lock()
+            .unwrap()
+            .bonds
+            .insert(self.address, bond);
+
+        Ok(bond)
+    }
+
+    /// Remove the bond with the peripheral, if there is one.
+    pub fn unpair(&self) -> Result<()> {
+        self.sim.radio.lock().unwrap().bonds.remove(&self.address);
+
+        Ok(())
+    }
+
+    /// Check if there is a bond with the peripheral.
+    pub fn is_paired(&self) -> Result<bool> {
+        Ok(self.link() != ProtectionLevel::None)
+    }
+
+    fn link(&self) -> ProtectionLevel {
+        self.sim
+            .radio
+            .lock()
+            .unwrap()
+            .bonds
+            .get(&self.address)
+            .copied()
+            .unwrap_or(ProtectionLevel::None)
+    }
+}
+
+impl std::fmt::Debug for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Device")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+/// Service provided by a simulated peripheral.
+#[derive(Clone)]
+pub struct Service {
+    device: Device,
+    index: usize,
+    uuid: Uuid,
+}
+
+impl RemoteService for Service {
+    type Characteristic = Characteristic;
+
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(self.uuid)
+    }
+
+    fn characteristics(&self) -> Result<Vec<Characteristic>> {
+        let characteristics = self
+            .device
+            .server
+            .characteristics(self.index)
+            .into_iter()
+            .enumerate()
+            .map(|(index, (uuid, properties))| Characteristic {
+                service: self.clone(),
+                index,
+                uuid,
+                properties,
+            })
+            .collect();
+
+        Ok(characteristics)
+    }
+}
+
+impl std::fmt::Debug for Service {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Service").field("uuid", &self.uuid).finish()
+    }
+}
+
+/// Characteristic provided by a simulated peripheral.
+#[derive(Clone)]
+pub struct Characteristic {
+    service: Service,
+    index: usize,
+    uuid: Uuid,
+    properties: CharacteristicProperties,
+}
+
+impl Characteristic {
+    /// Get a [CharacteristicIO] instance for this characteristic which
+    /// provides the [Read](std::io::Read) and [Write](std::io::Write) traits.
+    pub fn io(&self) -> Result<CharacteristicIO<'_, Self>> {
+        CharacteristicIO::new(self)
+    }
+
+    fn server(&self) -> &GattServer {
+        &self.service.device.server
+    }
+
+    fn link(&self) -> ProtectionLevel {
+        self.service.device.link()
+    }
+}
+
+impl RemoteCharacteristic for Characteristic {
+    type Descriptor = Descriptor;
+
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(self.uuid)
+    }
+
+    fn properties(&self) -> Option<CharacteristicProperties> {
+        Some(self.properties)
+    }
+
+    fn read_value(&self) -> Result<Vec<u8>> {
+        log::trace!("Reading data from {:?}", &self);
+
+        self.server()
+            .read(self.link(), self.service.index, self.index)
+            .map_err(Error::Att)
+    }
+
+    fn write_value(&self, data: &[u8]) -> Result<()> {
+        log::trace!("Writing data to {:?}", &self);
+
+        self.server()
+            .write(self.link(), self.service.index, self.index, data)
+            .map_err(Error::Att)
+    }
+
+    fn subscribe(
+        &self,
+        _subscription: &mut Subscription,
+        mode: NotifyMode,
+    ) -> Result<mpsc::Receiver<Vec<u8>>> {
+        let (tx, rx) = mpsc::channel();
+
+        self.server()
+            .subscribe(
+                self.service.device.client,
+                self.link(),
+                self.service.index,
+                self.index,
+                mode,
+                Some(tx),
+            )
+            .map_err(Error::Att)?;
+
+        Ok(rx)
+    }
+
+    fn unsubscribe(&self, subscription: &mut Subscription) -> Result<()> {
+        // The server drops channels whose receivers are gone by itself.
+        if subscription.explicit {
+            return Ok(());
+        }
+
+        self.server()
+            .subscribe(
+                self.service.device.client,
+                self.link(),
+                self.service.index,
+                self.index,
+                NotifyMode::None,
+                None,
+            )
+            .map_err(Error::Att)
+    }
+
+    fn subscription(&self) -> Result<Arc<Mutex<Subscription>>> {
+        Ok(self
+            .service
+            .device
+            .subscriptions
+            .get((self.service.index, self.index)))
+    }
+
+    fn descriptors(&self) -> Result<Vec<Descriptor>> {
+        let descriptors = self
+            .server()
+            .descriptor_uuids(self.service.index, self.index)
+            .into_iter()
+            .enumerate()
+            .map(|(index, uuid)| Descriptor {
+                characteristic: self.clone(),
+                index,
+                uuid,
+            })
+            .collect();
+
+        Ok(descriptors)
+    }
+}
+
+impl std::fmt::Debug for Characteristic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Characteristic")
+            .field("uuid", &self.uuid)
+            .field("properties", &self.properties)
+            .finish()
+    }
+}
+
+/// Descriptor provided by a simulated peripheral.
+#[derive(Clone)]
+pub struct Descriptor {
+    characteristic: Characteristic,
+    index: usize,
+    uuid: Uuid,
+}
+
+impl RemoteDescriptor for Descriptor {
+    fn uuid(&self) -> Result<Uuid> {
+        Ok(self.uuid)
+    }
+
+    fn read_value(&self) -> Result<Vec<u8>> {
+        let characteristic = &self.characteristic;
+
+        characteristic
+            .server()
+            .read_descriptor(
+                characteristic.service.device.client,
+                characteristic.link(),
+                characteristic.service.index,
+                characteristic.index,
+                self.index,
+            )
+            .map_err(Error::Att)
+    }
+
+    fn write_value(&self, data: &[u8]) -> Result<()> {
+        log::trace!("Writing data to {:?}", &self);
+
+        let characteristic = &self.characteristic;
+
+        characteristic
+            .server()
+            .write_descriptor(
+                characteristic.service.device.client,
+                characteristic.link(),
+                characteristic.service.index,
+                characteristic.index,
+                self.index,
+                data,
+            )
+            .map_err(Error::Att)
+    }
+}
+
+impl std::fmt::Debug for Descriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Descriptor")
+            .field("uuid", &self.uuid)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+
+    use crate::gatt::{
+        AttError, DescriptorValue, ExtendedProperties, Format, GattServer, LocalCharacteristic,
+        LocalDescriptor, LocalService, PresentationFormat, Value, PRESENTATION_FORMAT,
+    };
+    use crate::sim::Simulator;
+    use crate::{
+        BluetoothAddress, CharacteristicProperties, Error, NotifyMode, RemoteCharacteristic,
+        RemoteDescriptor, RemoteService, Uuid,
+    };
+
+    const SERVICE: Uuid = Uuid::from_u16(0xFFF0);
+    const COUNTER: Uuid = Uuid::from_u16(0xFFF1);
+    const CONTROL: Uuid = Uuid::from_u16(0xFFF2);
+
+    #[test]
+    fn test_central_talks_to_server() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        COUNTER,
+                        CharacteristicProperties::READ | CharacteristicProperties::NOTIFY,
+                    )
+                    .with_value(&[0])
+                    .with_descriptor(LocalDescriptor::new(Uuid::from_u16(0x2901), b"Counter")),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(CONTROL, CharacteristicProperties::WRITE).on_write(
+                        |server, value| match value {
+                            [count] => {
+                                server.notify(SERVICE, COUNTER, &[*count]);
+                                Ok(())
+                            }
+                            _ => Err(AttError::INVALID_ATTRIBUTE_VALUE_LENGTH),
+                        },
+                    ),
+                ),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        assert!(matches!(
+            sim.connect(BluetoothAddress(0)),
+            Err(Error::Unreachable)
+        ));
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let counter = service.characteristic(COUNTER).unwrap().unwrap();
+        let control = service.characteristic(CONTROL).unwrap().unwrap();
+
+        assert_eq!(counter.read_value().unwrap(), vec![0]);
+        assert!(matches!(
+            control.read_value(),
+            Err(Error::Att(AttError::READ_NOT_PERMITTED))
+        ));
+        assert!(matches!(
+            control.write_value(&[1, 2]),
+            Err(Error::Att(AttError::INVALID_ATTRIBUTE_VALUE_LENGTH))
+        ));
+
+        let descriptors = counter.descriptors().unwrap();
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(descriptors[0].read_value().unwrap(), b"Counter");
+
+        let mut io = counter.io().unwrap();
+        assert_eq!(
+            descriptors[1].read_value().unwrap(),
+            NotifyMode::Notify.cccd_value()
+        );
+
+        control.write_value(&[5]).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(io.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 5);
+        assert_eq!(server.value(SERVICE, COUNTER), Some(vec![5]));
+
+        let control_chr = control.clone();
+        let mut control_io = control_chr.io().unwrap();
+        control_io.write_all(&[6]).unwrap();
+        assert_eq!(io.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 6);
+
+        drop(io);
+        assert_eq!(
+            descriptors[1].read_value().unwrap(),
+            NotifyMode::None.cccd_value()
+        );
+        assert_eq!(server.notify(SERVICE, COUNTER, &[7]), 0);
+    }
+
+    #[test]
+    fn test_explicit_notify() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(LocalCharacteristic::new(
+                    COUNTER,
+                    CharacteristicProperties::NOTIFY | CharacteristicProperties::INDICATE,
+                ))
+                .with_characteristic(LocalCharacteristic::new(
+                    CONTROL,
+                    CharacteristicProperties::WRITE,
+                )),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let counter = service.characteristic(COUNTER).unwrap().unwrap();
+        let control = service.characteristic(CONTROL).unwrap().unwrap();
+
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::None);
+        counter.set_notify(NotifyMode::Indicate).unwrap();
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::Indicate);
+
+        let mut io = counter.io().unwrap();
+        assert_eq!(server.indicate(SERVICE, COUNTER, &[1]), 1);
+        assert_eq!(io.next_notification(), Some(vec![1]));
+        drop(io);
+
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::Indicate);
+        assert_eq!(server.indicate(SERVICE, COUNTER, &[2]), 0);
+
+        let cccd = &counter.descriptors().unwrap()[0];
+        cccd.write_value(&NotifyMode::None.cccd_value()).unwrap();
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::None);
+        assert!(matches!(
+            cccd.write_value(&[0x01]),
+            Err(Error::Att(AttError::INVALID_ATTRIBUTE_VALUE_LENGTH))
+        ));
+
+        assert!(matches!(
+            control.set_notify(NotifyMode::Notify),
+            Err(Error::MissingAttribute(_))
+        ));
+    }
+
+    #[test]
+    fn test_io_falls_back_to_indications() {
+        let server = GattServer::new();
+        server.add_service(LocalService::new(SERVICE).with_characteristic(
+            LocalCharacteristic::new(COUNTER, CharacteristicProperties::INDICATE),
+        ));
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let counter = service.characteristic(COUNTER).unwrap().unwrap();
+
+        let mut io = counter.io().unwrap();
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::Indicate);
+        assert_eq!(server.notify(SERVICE, COUNTER, &[1]), 0);
+        assert_eq!(server.indicate(SERVICE, COUNTER, &[2]), 1);
+        assert_eq!(io.next_notification(), Some(vec![2]));
+
+        drop(io);
+        assert_eq!(counter.get_notify().unwrap(), NotifyMode::None);
+    }
+
+    #[test]
+    fn test_overlapping_io() {
+        let server = GattServer::new();
+        server.add_service(LocalService::new(SERVICE).with_characteristic(
+            LocalCharacteristic::new(COUNTER, CharacteristicProperties::NOTIFY),
+        ));
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let first = service.characteristic(COUNTER).unwrap().unwrap();
+        let second = service.characteristic(COUNTER).unwrap().unwrap();
+
+        // Left behind by a process which never cleaned up.
+        first.descriptors().unwrap()[0]
+            .write_value(&NotifyMode::Notify.cccd_value())
+            .unwrap();
+
+        let mut first_io = first.io().unwrap();
+        let mut second_io = second.io().unwrap();
+        assert_eq!(server.notify(SERVICE, COUNTER, &[1]), 1);
+        assert_eq!(first_io.next_notification(), Some(vec![1]));
+        assert_eq!(second_io.next_notification(), Some(vec![1]));
+
+        drop(first_io);
+        assert_eq!(second.get_notify().unwrap(), NotifyMode::Notify);
+        assert_eq!(server.notify(SERVICE, COUNTER, &[2]), 1);
+        assert_eq!(second_io.next_notification(), Some(vec![2]));
+
+        let subscription = Arc::downgrade(&second.subscription().unwrap());
+        drop(second_io);
+        assert_eq!(second.get_notify().unwrap(), NotifyMode::None);
+        assert_eq!(server.notify(SERVICE, COUNTER, &[3]), 0);
+
+        // Unused subscriptions are forgotten on the next lookup.
+        assert_eq!(second.subscription().unwrap().lock().unwrap().users, 0);
+        assert!(subscription.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_decode_descriptors() {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE).with_characteristic(
+                LocalCharacteristic::new(
+                    CONTROL,
+                    CharacteristicProperties::WRITE | CharacteristicProperties::RELIABLE_WRITES,
+                )
+                .with_descriptor(LocalDescriptor::new(Uuid::from_u16(0x2901), b"Control")),
+            ),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server);
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let control = service.characteristic(CONTROL).unwrap().unwrap();
+
+        assert!(control
+            .properties()
+            .unwrap()
+            .contains(CharacteristicProperties::EXTENDED_PROPERTIES));
+        assert_eq!(
+            control.extended_properties().unwrap(),
+            ExtendedProperties::RELIABLE_WRITE
+        );
+
+        let decoded: Vec<_> = control
+            .descriptors()
+            .unwrap()
+            .iter()
+            .map(|descriptor| descriptor.read_decoded().unwrap())
+            .collect();
+        assert_eq!(
+            decoded,
+            vec![
+                DescriptorValue::ExtendedProperties(ExtendedProperties::RELIABLE_WRITE),
+                DescriptorValue::UserDescription("Control".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_typed_values() {
+        // Temperature in Celsius with two decimal places.
+        let format = PresentationFormat {
+            format: Format::SInt16,
+            exponent: -2,
+            unit: 0x272F,
+            namespace: 0x01,
+            description: 0x0000,
+        };
+
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE)
+                .with_characteristic(
+                    LocalCharacteristic::new(
+                        COUNTER,
+                        CharacteristicProperties::READ | CharacteristicProperties::WRITE,
+                    )
+                    .with_value(&[0x66, 0x08])
+                    .with_descriptor(LocalDescriptor::new(PRESENTATION_FORMAT, &format.encode())),
+                )
+                .with_characteristic(
+                    LocalCharacteristic::new(CONTROL, CharacteristicProperties::READ)
+                        .with_value(&[0x01]),
+                ),
+        );
+
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, server.clone());
+
+        let device = sim.connect(address).unwrap();
+        let service = device.service(SERVICE).unwrap().unwrap();
+        let temperature = service.characteristic(COUNTER).unwrap().unwrap();
+
+        let typed = temperature.read_typed().unwrap();
+        assert_eq!(typed.value, Value::Signed(2150));
+        assert_eq!(typed.scaled(), Some(21.5));
+        assert_eq!(typed.unit(), 0x272F);
+
+        temperature.write_typed(&Value::Signed(-40)).unwrap();
+        assert_eq!(server.value(SERVICE, COUNTER), Some(vec![0xD8, 0xFF]));
+        assert!(matches!(
+            temperature.write_typed(&Value::Float32(1.0)),
+            Err(Error::InvalidValue)
+        ));
+
+        let control = service.characteristic(CONTROL).unwrap().unwrap();
+        assert!(matches!(
+            control.read_typed(),
+            Err(Error::NoPresentationFormat)
+        ));
+    }
+}
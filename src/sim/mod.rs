@@ -0,0 +1,338 @@
+//! An in-process simulated Bluetooth environment.
+//!
+//! Everything created from the same [Simulator] shares a simulated radio, so
+//! advertisements published through it are delivered to its watchers and
+//! [GattServer]s added to it can be connected to and paired with, all without
+//! any Bluetooth hardware. This is mostly useful for tests.
+
+use std::collections::HashMap;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::gatt::GattServer;
+use crate::pairing::ProtectionLevel;
+use crate::privacy::IrkStore;
+use crate::{
+    AdvertisementData, AdvertisementParseError, BluetoothAddress, Error, PublisherBackend, Result,
+};
+
+mod gatt;
+mod pairing;
+
+pub use gatt::{Characteristic, Descriptor, Device, Service};
+pub use pairing::PairingConfig;
+
+/// Signal strength reported for every simulated advertisement.
+const SIMULATED_SIGNAL_STRENGTH: i16 = -50;
+
+#[derive(Default)]
+struct Radio {
+    next_id: usize,
+    broadcasts: HashMap<usize, (BluetoothAddress, Vec<u8>)>,
+    watchers: Vec<mpsc::Sender<Advertisement>>,
+    peripherals: HashMap<BluetoothAddress, GattServer>,
+    pairing: HashMap<BluetoothAddress, PairingConfig>,
+    bonds: HashMap<BluetoothAddress, ProtectionLevel>,
+}
+
+impl Radio {
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        id
+    }
+
+    fn deliver(&mut self, address: BluetoothAddress, payload: &[u8]) {
+        self.watchers.retain(|tx| {
+            tx.send(Advertisement::new(address, payload.to_vec()))
+                .is_ok()
+        });
+    }
+}
+
+/// Shared state for a simulated Bluetooth environment.
+///
+/// Cloning a simulator gives another handle to the same environment.
+#[derive(Clone, Default)]
+pub struct Simulator {
+    radio: Arc<Mutex<Radio>>,
+}
+
+impl Simulator {
+    /// Create a new, empty environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a backend for an [AdvertisementPublisher](crate::AdvertisementPublisher)
+    /// which broadcasts from the given address.
+    pub fn publisher(&self, address: BluetoothAddress) -> SimulatedPublisher {
+        let id = self.radio.lock().unwrap().next_id();
+
+        SimulatedPublisher {
+            radio: self.radio.clone(),
+            id,
+            address,
+        }
+    }
+
+    /// Host a GATT server at the given address, replacing any server already
+    /// there.
+    pub fn add_peripheral(&self, address: BluetoothAddress, server: GattServer) {
+        self.radio
+            .lock()
+            .unwrap()
+            .peripherals
+            .insert(address, server);
+    }
+
+    /// Set how the peripheral at the given address responds to pairing
+    /// requests.
+    pub fn set_pairing(&self, address: BluetoothAddress, config: PairingConfig) {
+        self.radio.lock().unwrap().pairing.insert(address, config);
+    }
+
+    /// Remove the GATT server at the given address.
+    ///
+    /// Existing connections are not affected.
+    pub fn remove_peripheral(&self, address: BluetoothAddress) {
+        self.radio.lock().unwrap().peripherals.remove(&address);
+    }
+
+    /// Connect to the GATT server at the given address.
+    pub fn connect(&self, address: BluetoothAddress) -> Result<Device> {
+        let mut radio = self.radio.lock().unwrap();
+        let server = radio
+            .peripherals
+            .get(&address)
+            .cloned()
+            .ok_or(Error::Unreachable)?;
+
+        Ok(Device::new(self.clone(), address, server, radio.next_id()))
+    }
+
+    /// Start listening for advertisements.
+    ///
+    /// Advertisements that are already being broadcast are delivered
+    /// immediately.
+    pub fn watcher(&self) -> AdvertisementWatcher {
+        self.watcher_with_irk_store(IrkStore::new())
+    }
+
+    /// Start listening for advertisements, resolving the identity of devices
+    /// which send from Resolvable Private Addresses using the given keys.
+    pub fn watcher_with_irk_store(&self, irks: IrkStore) -> AdvertisementWatcher {
+        let (tx, rx) = mpsc::channel();
+
+        let mut radio = self.radio.lock().unwrap();
+        for (address, payload) in radio.broadcasts.values() {
+            let _ = tx.send(Advertisement::new(*address, payload.clone()));
+        }
+        radio.watchers.push(tx);
+
+        AdvertisementWatcher { rx, irks }
+    }
+}
+
+impl std::fmt::Debug for Simulator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Simulator").finish()
+    }
+}
+
+/// Publisher backend which broadcasts on a simulated radio.
+pub struct SimulatedPublisher {
+    radio: Arc<Mutex<Radio>>,
+    id: usize,
+    address: BluetoothAddress,
+}
+
+impl PublisherBackend for SimulatedPublisher {
+    fn start(&mut self, data: &AdvertisementData) -> Result<()> {
+        let payload = data.encode()?;
+
+        let mut radio = self.radio.lock().unwrap();
+        radio
+            .broadcasts
+            .insert(self.id, (self.address, payload.clone()));
+        radio.deliver(self.address, &payload);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.radio.lock().unwrap().broadcasts.remove(&self.id);
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for SimulatedPublisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimulatedPublisher")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+/// Advertisement received on a simulated radio.
+#[derive(Clone, Debug)]
+pub struct Advertisement {
+    address: BluetoothAddress,
+    identity: Option<BluetoothAddress>,
+    payload: Vec<u8>,
+}
+
+impl Advertisement {
+    fn new(address: BluetoothAddress, payload: Vec<u8>) -> Self {
+        Self {
+            address,
+            identity: None,
+            payload,
+        }
+    }
+
+    /// Get the address of the publisher which sent this advertisement.
+    pub fn address(&self) -> BluetoothAddress {
+        self.address
+    }
+
+    /// Get the identity address of the publisher, if its address was resolved
+    /// by the watcher's [IrkStore].
+    pub fn identity(&self) -> Option<BluetoothAddress> {
+        self.identity
+    }
+
+    /// Signal strength as dBm, always the same for simulated advertisements.
+    pub fn signal_strength(&self) -> i16 {
+        SIMULATED_SIGNAL_STRENGTH
+    }
+
+    /// The raw advertising payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Parse the AD structures contained within this advertisement.
+    pub fn data(&self) -> std::result::Result<AdvertisementData, AdvertisementParseError> {
+        AdvertisementData::parse(&self.payload)
+    }
+}
+
+/// Utility to allow iteration through simulated advertisements.
+pub struct AdvertisementWatcher {
+    rx: mpsc::Receiver<Advertisement>,
+    irks: IrkStore,
+}
+
+impl AdvertisementWatcher {
+    /// Wait up to the given duration for the next advertisement.
+    pub fn next_timeout(&self, timeout: std::time::Duration) -> Option<Advertisement> {
+        self.rx
+            .recv_timeout(timeout)
+            .ok()
+            .map(|ad| self.resolve(ad))
+    }
+
+    /// Get the next advertisement if one has already been received.
+    pub fn try_next(&self) -> Option<Advertisement> {
+        self.rx.try_recv().ok().map(|ad| self.resolve(ad))
+    }
+
+    fn resolve(&self, mut advertisement: Advertisement) -> Advertisement {
+        advertisement.identity = self.irks.resolve(advertisement.address);
+        advertisement
+    }
+}
+
+impl Iterator for &AdvertisementWatcher {
+    type Item = Advertisement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok().map(|ad| self.resolve(ad))
+    }
+}
+
+impl std::fmt::Debug for AdvertisementWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdvertisementWatcher").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Simulator;
+    use crate::privacy::{Irk, IrkStore};
+    use crate::{AdvertisementData, AdvertisementPublisher, BluetoothAddress, IBeacon, Uuid};
+
+    #[test]
+    fn test_publish_to_watchers() {
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0xC8FD_1912_7FCD);
+
+        let early = sim.watcher();
+
+        let beacon = IBeacon {
+            proximity_uuid: Uuid::from_u16(0x1234),
+            major: 10,
+            minor: 20,
+            measured_power: -60,
+        };
+        let mut publisher = AdvertisementPublisher::with_backend(
+            beacon.to_advertisement_data(),
+            sim.publisher(address),
+        );
+        assert!(early.try_next().is_none());
+        publisher.start().unwrap();
+
+        let late = sim.watcher();
+
+        for watcher in &[early, late] {
+            let advertisement = watcher.try_next().expect("advertisement was not delivered");
+            assert_eq!(advertisement.address(), address);
+
+            let data = advertisement.data().unwrap();
+            assert_eq!(IBeacon::from_advertisement_data(&data), Some(beacon));
+        }
+
+        publisher.stop().unwrap();
+        assert!(sim.watcher().try_next().is_none());
+
+        let too_large = AdvertisementData::new().with_local_name(&"x".repeat(30));
+        assert!(publisher.set_data(too_large).is_ok());
+        assert!(publisher.start().is_err());
+    }
+
+    #[test]
+    fn test_resolve_rotating_address() {
+        let sim = Simulator::new();
+        let identity = BluetoothAddress(0xC8FD_1912_7FCD);
+        let irk = Irk([0x5A; 16]);
+
+        let irks = IrkStore::new();
+        let watcher = sim.watcher_with_irk_store(irks.clone());
+        irks.insert(identity, irk);
+
+        for prand in &[0x12_3456, 0x0A_BCDE] {
+            let mut publisher = AdvertisementPublisher::with_backend(
+                AdvertisementData::new().with_local_name("phone"),
+                sim.publisher(irk.resolvable_address(*prand)),
+            );
+            publisher.start().unwrap();
+            publisher.stop().unwrap();
+        }
+
+        let first = watcher.try_next().unwrap();
+        let second = watcher.try_next().unwrap();
+        assert_ne!(first.address(), second.address());
+        assert_eq!(first.identity(), Some(identity));
+        assert_eq!(second.identity(), Some(identity));
+
+        let mut publisher = AdvertisementPublisher::with_backend(
+            AdvertisementData::new(),
+            sim.publisher(Irk([0xA5; 16]).resolvable_address(0x12_3456)),
+        );
+        publisher.start().unwrap();
+        assert_eq!(watcher.try_next().unwrap().identity(), None);
+    }
+}
@@ -0,0 +1,257 @@
+//! Pairing with simulated peripherals.
+
+use crate::pairing::{IoCapability, PairingError, PairingHandler, PairingMethod, ProtectionLevel};
+
+/// How a simulated peripheral responds to pairing requests.
+///
+/// The default configuration has no input or output and accepts every
+/// request, so pairing always uses Just Works.
+#[derive(Clone, Debug)]
+pub struct PairingConfig {
+    /// Input and output available on the peripheral.
+    pub io_capability: IoCapability,
+    /// Passkey shown by the peripheral, or entered on it when the central
+    /// shows one. Also used as the value for numeric comparison.
+    pub passkey: u32,
+    /// If the user of the peripheral accepts pairing.
+    pub accept: bool,
+}
+
+impl Default for PairingConfig {
+    fn default() -> Self {
+        Self {
+            io_capability: IoCapability::NoInputNoOutput,
+            passkey: 0,
+            accept: true,
+        }
+    }
+}
+
+/// Run pairing as the initiator, returning the protection level of the
+/// resulting bond.
+pub(super) fn negotiate<H: PairingHandler>(
+    config: &PairingConfig,
+    protection_level: ProtectionLevel,
+    handler: &mut H,
+) -> Result<ProtectionLevel, PairingError> {
+    let method = PairingMethod::select(handler.io_capability(), config.io_capability);
+    log::debug!("Pairing with {:?} using {:?}", config, method);
+
+    if method.protection_level() < protection_level {
+        return Err(PairingError::ProtectionLevelNotMet);
+    }
+
+    let accepted = match method {
+        PairingMethod::JustWorks => handler.confirm_pairing(),
+        PairingMethod::NumericComparison => handler.confirm_numeric_comparison(config.passkey),
+        PairingMethod::PasskeyDisplayed => handler.display_passkey(config.passkey),
+        PairingMethod::PasskeyEntered | PairingMethod::PasskeyEnteredOnBoth => {
+            match handler.request_passkey() {
+                Some(passkey) if passkey == config.passkey => true,
+                Some(_) => return Err(PairingError::PasskeyMismatch),
+                None => false,
+            }
+        }
+    };
+
+    if !accepted || !config.accept {
+        return Err(PairingError::Rejected);
+    }
+
+    Ok(method.protection_level())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PairingConfig;
+    use crate::gatt::{
+        AttError, GattServer, LocalCharacteristic, LocalDescriptor, LocalService, USER_DESCRIPTION,
+    };
+    use crate::pairing::{IoCapability, PairingError, PairingHandler, ProtectionLevel};
+    use crate::sim::Simulator;
+    use crate::{
+        BluetoothAddress, CharacteristicProperties, Error, JustWorks, RemoteCharacteristic,
+        RemoteDescriptor, RemoteService, Uuid,
+    };
+
+    const SERVICE: Uuid = Uuid::from_u16(0xFFF0);
+    const SECRET: Uuid = Uuid::from_u16(0xFFF1);
+
+    #[derive(Default)]
+    struct Keyboard {
+        passkey: Option<u32>,
+        prompts: Vec<&'static str>,
+    }
+
+    impl PairingHandler for Keyboard {
+        fn io_capability(&self) -> IoCapability {
+            IoCapability::KeyboardDisplay
+        }
+
+        fn confirm_pairing(&mut self) -> bool {
+            self.prompts.push("confirm");
+            true
+        }
+
+        fn request_passkey(&mut self) -> Option<u32> {
+            self.prompts.push("request");
+            self.passkey
+        }
+
+        fn display_passkey(&mut self, _passkey: u32) -> bool {
+            self.prompts.push("display");
+            true
+        }
+
+        fn confirm_numeric_comparison(&mut self, value: u32) -> bool {
+            self.prompts.push("compare");
+            Some(value) == self.passkey
+        }
+    }
+
+    fn peripheral(sim: &Simulator, address: BluetoothAddress, config: PairingConfig) {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE).with_characteristic(
+                LocalCharacteristic::new(SECRET, CharacteristicProperties::READ)
+                    .with_value(b"secret")
+                    .with_descriptor(LocalDescriptor::new(USER_DESCRIPTION, b"Secret"))
+                    .with_security(ProtectionLevel::EncryptionAndAuthentication),
+            ),
+        );
+
+        sim.add_peripheral(address, server);
+        sim.set_pairing(address, config);
+    }
+
+    #[test]
+    fn test_pairing_methods() {
+        let sim = Simulator::new();
+
+        let display = BluetoothAddress(1);
+        peripheral(
+            &sim,
+            display,
+            PairingConfig {
+                io_capability: IoCapability::DisplayOnly,
+                passkey: 123_456,
+                ..Default::default()
+            },
+        );
+
+        let device = sim.connect(display).unwrap();
+        let secret = device
+            .service(SERVICE)
+            .unwrap()
+            .unwrap()
+            .characteristic(SECRET)
+            .unwrap()
+            .unwrap();
+
+        let description = secret.descriptor(USER_DESCRIPTION).unwrap().unwrap();
+
+        assert!(!device.is_paired().unwrap());
+        assert!(matches!(
+            secret.read_value(),
+            Err(Error::Att(AttError::INSUFFICIENT_AUTHENTICATION))
+        ));
+        assert!(matches!(
+            description.read_value(),
+            Err(Error::Att(AttError::INSUFFICIENT_AUTHENTICATION))
+        ));
+
+        let mut handler = Keyboard {
+            passkey: Some(654_321),
+            ..Default::default()
+        };
+        assert!(matches!(
+            device.pair(ProtectionLevel::EncryptionAndAuthentication, &mut handler),
+            Err(Error::Pairing(PairingError::PasskeyMismatch))
+        ));
+        assert!(!device.is_paired().unwrap());
+
+        handler.passkey = Some(123_456);
+        assert_eq!(
+            device
+                .pair(ProtectionLevel::EncryptionAndAuthentication, &mut handler)
+                .unwrap(),
+            ProtectionLevel::EncryptionAndAuthentication
+        );
+        assert_eq!(handler.prompts, vec!["request", "request"]);
+        assert!(device.is_paired().unwrap());
+        assert_eq!(secret.read_value().unwrap(), b"secret");
+        assert_eq!(description.read_value().unwrap(), b"Secret");
+
+        let reconnected = sim.connect(display).unwrap();
+        assert!(reconnected.is_paired().unwrap());
+        reconnected.unpair().unwrap();
+        assert!(!device.is_paired().unwrap());
+
+        let yes_no = BluetoothAddress(2);
+        peripheral(
+            &sim,
+            yes_no,
+            PairingConfig {
+                io_capability: IoCapability::DisplayYesNo,
+                passkey: 111_111,
+                ..Default::default()
+            },
+        );
+
+        let mut handler = Keyboard {
+            passkey: Some(111_111),
+            ..Default::default()
+        };
+        let device = sim.connect(yes_no).unwrap();
+        device
+            .pair(ProtectionLevel::Encryption, &mut handler)
+            .unwrap();
+        assert_eq!(handler.prompts, vec!["compare"]);
+    }
+
+    #[test]
+    fn test_pairing_protection_level() {
+        let sim = Simulator::new();
+        let address = BluetoothAddress(1);
+        peripheral(&sim, address, PairingConfig::default());
+
+        let device = sim.connect(address).unwrap();
+        assert!(matches!(
+            device.pair(ProtectionLevel::EncryptionAndAuthentication, JustWorks),
+            Err(Error::Pairing(PairingError::ProtectionLevelNotMet))
+        ));
+
+        assert_eq!(
+            device.pair(ProtectionLevel::Encryption, JustWorks).unwrap(),
+            ProtectionLevel::Encryption
+        );
+
+        let secret = device
+            .service(SERVICE)
+            .unwrap()
+            .unwrap()
+            .characteristic(SECRET)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            secret.read_value(),
+            Err(Error::Att(AttError::INSUFFICIENT_AUTHENTICATION))
+        ));
+
+        let rejected = BluetoothAddress(2);
+        peripheral(
+            &sim,
+            rejected,
+            PairingConfig {
+                accept: false,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            sim.connect(rejected)
+                .unwrap()
+                .pair(ProtectionLevel::None, JustWorks),
+            Err(Error::Pairing(PairingError::Rejected))
+        ));
+    }
+}
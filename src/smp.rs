@@ -0,0 +1,982 @@
+//! Managing Zephyr devices with the Simple Management Protocol used by
+//! MCUmgr.
+//!
+//! Requests and responses are a header followed by a CBOR map, sent over a
+//! single characteristic. Messages longer than the MTU are split across
+//! several writes and notifications.
+//!
+//! ```no_run
+//! use wible::RemoteService;
+//! use wible::smp::{Smp, CHARACTERISTIC, SERVICE};
+//! # fn manage(device: &wible::Device) -> Result<(), Box<dyn std::error::Error>> {
+//! let service = device.service(SERVICE)?.expect("no SMP service");
+//! let characteristic = service.characteristic(CHARACTERISTIC)?.expect("no SMP characteristic");
+//!
+//! let mut smp = Smp::new(&characteristic)?;
+//! for slot in smp.image_state()? {
+//!     println!("{:?}", slot);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+pub use serde_cbor::Value;
+
+use crate::{CharacteristicIO, Error, RemoteCharacteristic, Result, Uuid};
+
+/// UUID of the SMP service.
+pub const SERVICE: Uuid = Uuid(0x8D53_DC1D_1DB7_4CD3_868B_8A52_7460_AA84);
+/// UUID of the SMP characteristic, which requests are written to and
+/// responses are notified from.
+pub const CHARACTERISTIC: Uuid = Uuid(0xDA2E_7828_FBCE_4E01_AE9E_2611_7499_7C48);
+
+/// Group of OS management commands.
+pub const GROUP_OS: u16 = 0;
+/// Group of image management commands.
+pub const GROUP_IMAGE: u16 = 1;
+/// Group of file system management commands.
+pub const GROUP_FS: u16 = 8;
+/// Group of shell management commands.
+pub const GROUP_SHELL: u16 = 9;
+
+/// Replies in a row which may leave the offset of an upload where it was
+/// before it is abandoned.
+const MAX_STALLED_REPLIES: usize = 3;
+
+/// Payload of a message, a CBOR map keyed by strings.
+pub type Map = BTreeMap<Value, Value>;
+
+/// Kind of SMP message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Op {
+    Read,
+    ReadResponse,
+    Write,
+    WriteResponse,
+    /// Operation not known by this library.
+    Reserved(u8),
+}
+
+impl Op {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Op::Read,
+            1 => Op::ReadResponse,
+            2 => Op::Write,
+            3 => Op::WriteResponse,
+            other => Op::Reserved(other),
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Op::Read => 0,
+            Op::ReadResponse => 1,
+            Op::Write => 2,
+            Op::WriteResponse => 3,
+            Op::Reserved(other) => other,
+        }
+    }
+
+    /// Operation of the response to a request.
+    fn response(self) -> Self {
+        match self {
+            Op::Read => Op::ReadResponse,
+            Op::Write => Op::WriteResponse,
+            other => other,
+        }
+    }
+}
+
+/// Header preceding every SMP message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub op: Op,
+    pub flags: u8,
+    /// Length of the CBOR payload.
+    pub len: u16,
+    pub group: u16,
+    pub sequence: u8,
+    /// Command within the group.
+    pub command: u8,
+}
+
+impl Header {
+    pub const LEN: usize = 8;
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        match *data {
+            [op, flags, l0, l1, g0, g1, sequence, command] => Some(Self {
+                op: Op::from_u8(op),
+                flags,
+                len: u16::from_be_bytes([l0, l1]),
+                group: u16::from_be_bytes([g0, g1]),
+                sequence,
+                command,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; 8] {
+        let [l0, l1] = self.len.to_be_bytes();
+        let [g0, g1] = self.group.to_be_bytes();
+
+        [
+            self.op.to_u8(),
+            self.flags,
+            l0,
+            l1,
+            g0,
+            g1,
+            self.sequence,
+            self.command,
+        ]
+    }
+}
+
+/// A complete SMP message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub header: Header,
+    pub payload: Map,
+}
+
+impl Message {
+    /// Encode the message, setting the header's length to that of the
+    /// payload.
+    pub fn encode(&self) -> Vec<u8> {
+        let payload = serde_cbor::to_vec(&Value::Map(self.payload.clone()))
+            .expect("CBOR maps can always be encoded");
+        let header = Header {
+            len: payload.len() as u16,
+            ..self.header
+        };
+
+        let mut data = header.encode().to_vec();
+        data.extend(payload);
+        data
+    }
+
+    /// Decode a message, which must be exactly as long as its header says.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let header = Header::decode(data.get(..Header::LEN)?)?;
+        let payload = &data[Header::LEN..];
+        if payload.len() != header.len as usize {
+            return None;
+        }
+
+        match serde_cbor::from_slice(payload).ok()? {
+            Value::Map(payload) => Some(Self { header, payload }),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned by a device for an SMP request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SmpError {
+    /// Group of the request which failed.
+    pub group: u16,
+    /// Command of the request which failed.
+    pub command: u8,
+    /// Result code sent by the device.
+    pub rc: i64,
+}
+
+impl SmpError {
+    pub const UNKNOWN: i64 = 1;
+    pub const NO_MEMORY: i64 = 2;
+    pub const INVALID_VALUE: i64 = 3;
+    pub const TIMEOUT: i64 = 4;
+    pub const NO_ENTRY: i64 = 5;
+    pub const BAD_STATE: i64 = 6;
+    pub const MESSAGE_SIZE: i64 = 7;
+    pub const NOT_SUPPORTED: i64 = 8;
+    pub const CORRUPT: i64 = 9;
+    pub const BUSY: i64 = 10;
+}
+
+impl std::fmt::Display for SmpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SMP command {} in group {} failed with result {}",
+            self.command, self.group, self.rc
+        )
+    }
+}
+
+impl std::error::Error for SmpError {}
+
+/// An image slot, as reported by the device.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageSlot {
+    pub image: u64,
+    pub slot: u64,
+    pub version: String,
+    /// SHA-256 hash of the image, used to select it for testing or
+    /// confirmation.
+    pub hash: Vec<u8>,
+    pub bootable: bool,
+    /// Image will be booted next, once if not confirmed.
+    pub pending: bool,
+    pub confirmed: bool,
+    pub active: bool,
+    pub permanent: bool,
+}
+
+impl ImageSlot {
+    fn decode(value: &Value) -> Option<Self> {
+        let map = match value {
+            Value::Map(map) => map,
+            _ => return None,
+        };
+        let flag = |key| matches!(get(map, key), Some(Value::Bool(true)));
+
+        Some(Self {
+            image: get_u64(map, "image").unwrap_or(0),
+            slot: get_u64(map, "slot")?,
+            version: match get(map, "version")? {
+                Value::Text(version) => version.clone(),
+                _ => return None,
+            },
+            hash: get_bytes(map, "hash").cloned().unwrap_or_default(),
+            bootable: flag("bootable"),
+            pending: flag("pending"),
+            confirmed: flag("confirmed"),
+            active: flag("active"),
+            permanent: flag("permanent"),
+        })
+    }
+}
+
+/// Output of a shell command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShellOutput {
+    pub output: String,
+    /// Value returned by the command.
+    pub ret: i64,
+}
+
+/// Client for a device's SMP characteristic.
+pub struct Smp<'a, C: RemoteCharacteristic> {
+    characteristic: &'a C,
+    io: CharacteristicIO<'a, C>,
+    sequence: u8,
+    mtu: usize,
+    timeout: Duration,
+}
+
+impl<'a, C: RemoteCharacteristic> Smp<'a, C> {
+    /// Start receiving responses from the SMP characteristic.
+    pub fn new(characteristic: &'a C) -> Result<Self> {
+        Ok(Self {
+            characteristic,
+            io: CharacteristicIO::new(characteristic)?,
+            sequence: 0,
+            mtu: 247,
+            timeout: Duration::from_secs(5),
+        })
+    }
+
+    /// Set the connection's MTU, which limits the size of each write.
+    ///
+    /// Defaults to 247, which most Zephyr devices negotiate.
+    pub fn with_mtu(mut self, mtu: usize) -> Self {
+        self.mtu = mtu.max(Header::LEN + 4);
+        self
+    }
+
+    /// Set how long to wait for each response.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send a request and wait for its response.
+    ///
+    /// Fails with [Error::Smp] if the device returns an error.
+    pub fn request(&mut self, op: Op, group: u16, command: u8, payload: Map) -> Result<Map> {
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+
+        let request = Message {
+            header: Header {
+                op,
+                flags: 0,
+                len: 0,
+                group,
+                sequence,
+                command,
+            },
+            payload,
+        };
+        log::debug!("Sending SMP request {:?}", request);
+
+        for packet in request.encode().chunks(self.packet_size()) {
+            self.characteristic.write_value(packet)?;
+        }
+
+        let response = self.response(op.response(), group, sequence)?;
+        check_response(&response, command)?;
+
+        Ok(response.payload)
+    }
+
+    fn response(&mut self, op: Op, group: u16, sequence: u8) -> Result<Message> {
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = Vec::new();
+
+        loop {
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(Error::Timeout)?;
+            let packet = self
+                .io
+                .next_notification_timeout(remaining)
+                .ok_or(Error::Timeout)?;
+            buf.extend(packet);
+
+            let header = match buf.get(..Header::LEN).and_then(Header::decode) {
+                Some(header) => header,
+                None => continue,
+            };
+            let len = Header::LEN + header.len as usize;
+            if buf.len() < len {
+                continue;
+            }
+
+            let message = Message::decode(&buf[..len]).ok_or(Error::InvalidValue)?;
+            buf.drain(..len);
+
+            if message.header.op == op
+                && message.header.group == group
+                && message.header.sequence == sequence
+            {
+                return Ok(message);
+            }
+
+            log::debug!("Ignoring unexpected response {:?}", message);
+        }
+    }
+
+    /// Bytes which fit in a single write.
+    fn packet_size(&self) -> usize {
+        self.mtu - 3
+    }
+
+    /// Bytes of data which fit in a single write along with the rest of a
+    /// request.
+    fn chunk_size(&self, mut payload: Map, key: &str) -> usize {
+        payload.insert(text(key), Value::Bytes(Vec::new()));
+        let overhead = Header::LEN
+            + serde_cbor::to_vec(&Value::Map(payload))
+                .expect("CBOR maps can always be encoded")
+                .len();
+
+        // Allow for the longest byte string length needed for a write.
+        self.packet_size().saturating_sub(overhead + 2).max(1)
+    }
+
+    /// Ask the device to send back some text.
+    pub fn echo(&mut self, text: &str) -> Result<String> {
+        let response = self.request(
+            Op::Write,
+            GROUP_OS,
+            0,
+            map(vec![("d", Value::Text(text.to_string()))]),
+        )?;
+
+        match get(&response, "r") {
+            Some(Value::Text(text)) => Ok(text.clone()),
+            _ => Err(Error::InvalidValue),
+        }
+    }
+
+    /// Restart the device.
+    pub fn reset(&mut self) -> Result<()> {
+        self.request(Op::Write, GROUP_OS, 5, Map::new()).map(drop)
+    }
+
+    /// List the images on the device.
+    pub fn image_state(&mut self) -> Result<Vec<ImageSlot>> {
+        let response = self.request(Op::Read, GROUP_IMAGE, 0, Map::new())?;
+
+        decode_images(&response)
+    }
+
+    /// Boot an image once on the next restart, reverting to the current
+    /// image unless it is confirmed.
+    pub fn test_image(&mut self, hash: &[u8]) -> Result<Vec<ImageSlot>> {
+        self.set_image_state(Some(hash), false)
+    }
+
+    /// Keep booting an image, or the running image if no hash is given.
+    pub fn confirm_image(&mut self, hash: Option<&[u8]>) -> Result<Vec<ImageSlot>> {
+        self.set_image_state(hash, true)
+    }
+
+    fn set_image_state(&mut self, hash: Option<&[u8]>, confirm: bool) -> Result<Vec<ImageSlot>> {
+        let mut payload = map(vec![("confirm", Value::Bool(confirm))]);
+        if let Some(hash) = hash {
+            payload.insert(text("hash"), Value::Bytes(hash.to_vec()));
+        }
+
+        let response = self.request(Op::Write, GROUP_IMAGE, 0, payload)?;
+        decode_images(&response)
+    }
+
+    /// Upload an image to the device's secondary slot.
+    ///
+    /// Progress is reported as bytes received by the device and the size of
+    /// the image.
+    pub fn upload(
+        &mut self,
+        image: u32,
+        data: &[u8],
+        progress: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        self.upload_from(image, data, 0, progress)
+    }
+
+    /// Continue an interrupted upload from the last offset the device was
+    /// known to have received.
+    ///
+    /// The device replies with the offset it expects, so uploading continues
+    /// from there even if the given offset was wrong.
+    pub fn upload_from(
+        &mut self,
+        image: u32,
+        data: &[u8],
+        mut offset: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        let mut stalled = 0;
+        while offset < data.len() {
+            let mut payload = map(vec![("off", Value::Integer(offset as i128))]);
+            if offset == 0 {
+                payload.insert(text("image"), Value::Integer(image.into()));
+                payload.insert(text("len"), Value::Integer(data.len() as i128));
+            }
+
+            let end = (offset + self.chunk_size(payload.clone(), "data")).min(data.len());
+            payload.insert(text("data"), Value::Bytes(data[offset..end].to_vec()));
+
+            let response = self.request(Op::Write, GROUP_IMAGE, 1, payload)?;
+            let next = get_u64(&response, "off")
+                .map(|offset| offset as usize)
+                .filter(|&offset| offset <= data.len())
+                .ok_or(Error::InvalidValue)?;
+            check_progress(offset, next, &mut stalled)?;
+            offset = next;
+            progress(offset, data.len());
+        }
+
+        Ok(())
+    }
+
+    /// Run a shell command.
+    pub fn shell(&mut self, argv: &[&str]) -> Result<ShellOutput> {
+        let argv = argv
+            .iter()
+            .map(|arg| Value::Text(arg.to_string()))
+            .collect();
+        let response = self.request(
+            Op::Write,
+            GROUP_SHELL,
+            0,
+            map(vec![("argv", Value::Array(argv))]),
+        )?;
+
+        let output = match get(&response, "o") {
+            Some(Value::Text(output)) => output.clone(),
+            _ => return Err(Error::InvalidValue),
+        };
+        let ret = match get(&response, "ret") {
+            Some(Value::Integer(ret)) => *ret as i64,
+            _ => 0,
+        };
+
+        Ok(ShellOutput { output, ret })
+    }
+
+    /// Read a file from the device.
+    pub fn read_file(&mut self, name: &str) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut len = None;
+
+        loop {
+            let payload = map(vec![
+                ("name", Value::Text(name.to_string())),
+                ("off", Value::Integer(data.len() as i128)),
+            ]);
+            let response = self.request(Op::Read, GROUP_FS, 0, payload)?;
+
+            if get_u64(&response, "off") != Some(data.len() as u64) {
+                return Err(Error::InvalidValue);
+            }
+            if let Some(total) = get_u64(&response, "len") {
+                len = Some(total as usize);
+            }
+            let chunk = get_bytes(&response, "data").ok_or(Error::InvalidValue)?;
+            data.extend_from_slice(chunk);
+
+            match len {
+                Some(len) if data.len() < len && !chunk.is_empty() => continue,
+                Some(_) => return Ok(data),
+                None => return Err(Error::InvalidValue),
+            }
+        }
+    }
+
+    /// Write a file to the device, replacing any existing file.
+    pub fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let mut offset = 0;
+        let mut stalled = 0;
+
+        loop {
+            let mut payload = map(vec![
+                ("name", Value::Text(name.to_string())),
+                ("off", Value::Integer(offset as i128)),
+            ]);
+            if offset == 0 {
+                payload.insert(text("len"), Value::Integer(data.len() as i128));
+            }
+
+            let end = (offset + self.chunk_size(payload.clone(), "data")).min(data.len());
+            payload.insert(text("data"), Value::Bytes(data[offset..end].to_vec()));
+
+            let response = self.request(Op::Write, GROUP_FS, 0, payload)?;
+            let next = get_u64(&response, "off")
+                .map(|offset| offset as usize)
+                .filter(|&offset| offset <= data.len())
+                .ok_or(Error::InvalidValue)?;
+            check_progress(offset, next, &mut stalled)?;
+            offset = next;
+
+            if offset == data.len() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<C: RemoteCharacteristic + std::fmt::Debug> std::fmt::Debug for Smp<'_, C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Smp")
+            .field("characteristic", &self.characteristic)
+            .field("sequence", &self.sequence)
+            .field("mtu", &self.mtu)
+            .finish()
+    }
+}
+
+/// Check the offset a device replied with moved an upload forward, allowing a
+/// few replies which don't, such as when resuming from a stale offset.
+fn check_progress(offset: usize, next: usize, stalled: &mut usize) -> Result<()> {
+    if next > offset {
+        *stalled = 0;
+        return Ok(());
+    }
+
+    *stalled += 1;
+    if *stalled >= MAX_STALLED_REPLIES {
+        log::warn!("Device is not accepting data at offset {}", next);
+        return Err(Error::InvalidValue);
+    }
+
+    Ok(())
+}
+
+/// Check a response for the `rc` field, or the `err` map used by newer
+/// versions of the protocol.
+fn check_response(response: &Message, command: u8) -> Result<()> {
+    let group = response.header.group;
+    let rc = match get(&response.payload, "err") {
+        Some(Value::Map(err)) => get(err, "rc"),
+        _ => get(&response.payload, "rc"),
+    };
+
+    match rc {
+        None | Some(Value::Integer(0)) => Ok(()),
+        Some(Value::Integer(rc)) => Err(SmpError {
+            group,
+            command,
+            rc: *rc as i64,
+        }
+        .into()),
+        Some(_) => Err(Error::InvalidValue),
+    }
+}
+
+fn decode_images(response: &Map) -> Result<Vec<ImageSlot>> {
+    match get(response, "images") {
+        Some(Value::Array(images)) => images
+            .iter()
+            .map(|image| ImageSlot::decode(image).ok_or(Error::InvalidValue))
+            .collect(),
+        _ => Err(Error::InvalidValue),
+    }
+}
+
+fn text(key: &str) -> Value {
+    Value::Text(key.to_string())
+}
+
+fn map(entries: Vec<(&str, Value)>) -> Map {
+    entries
+        .into_iter()
+        .map(|(key, value)| (text(key), value))
+        .collect()
+}
+
+fn get<'a>(map: &'a Map, key: &str) -> Option<&'a Value> {
+    map.get(&text(key))
+}
+
+fn get_u64(map: &Map, key: &str) -> Option<u64> {
+    match get(map, key)? {
+        Value::Integer(value) if *value >= 0 => Some(*value as u64),
+        _ => None,
+    }
+}
+
+fn get_bytes<'a>(map: &'a Map, key: &str) -> Option<&'a Vec<u8>> {
+    match get(map, key)? {
+        Value::Bytes(bytes) => Some(bytes),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        get, get_bytes, get_u64, map, Header, ImageSlot, Map, Message, Op, Smp, SmpError, Value,
+        CHARACTERISTIC, GROUP_FS, GROUP_IMAGE, GROUP_OS, GROUP_SHELL, SERVICE,
+    };
+    use crate::gatt::{GattServer, LocalCharacteristic, LocalService};
+    use crate::sim::Simulator;
+    use crate::{BluetoothAddress, CharacteristicProperties, Error, RemoteService};
+
+    const MTU: usize = 64;
+
+    #[test]
+    fn test_encode_message() {
+        // Echo request for "hi", as sent by the mcumgr command line tool.
+        let data = [
+            0x02, 0x00, 0x00, 0x06, 0x00, 0x00, 0x2A, 0x00, 0xA1, 0x61, 0x64, 0x62, 0x68, 0x69,
+        ];
+
+        let message = Message::decode(&data).unwrap();
+        assert_eq!(
+            message.header,
+            Header {
+                op: Op::Write,
+                flags: 0,
+                len: 6,
+                group: GROUP_OS,
+                sequence: 0x2A,
+                command: 0,
+            }
+        );
+        assert_eq!(
+            get(&message.payload, "d"),
+            Some(&Value::Text("hi".to_string()))
+        );
+        assert_eq!(message.encode(), data.to_vec());
+
+        assert_eq!(Message::decode(&data[..13]), None);
+        assert_eq!(Header::decode(&data[..7]), None);
+        assert_eq!(Op::from_u8(7), Op::Reserved(7));
+    }
+
+    /// State of a simulated Zephyr device.
+    #[derive(Default)]
+    struct Device {
+        request: Vec<u8>,
+        upload: Vec<u8>,
+        files: HashMap<String, Vec<u8>>,
+        /// Image hashes marked pending or confirmed.
+        pending: Option<Vec<u8>>,
+        confirmed: bool,
+        resets: usize,
+        /// Stop accepting uploaded data once it is this long.
+        full_at: Option<usize>,
+    }
+
+    impl Device {
+        fn images(&self) -> Map {
+            let slot = |slot: i128, hash: &[u8], active: bool| {
+                let mut slot = map(vec![
+                    ("slot", Value::Integer(slot)),
+                    ("version", Value::Text(format!("1.{}.0", slot))),
+                    ("hash", Value::Bytes(hash.to_vec())),
+                    ("bootable", Value::Bool(true)),
+                    ("active", Value::Bool(active)),
+                    ("confirmed", Value::Bool(active || self.confirmed)),
+                ]);
+                if self.pending.as_deref() == Some(hash) {
+                    slot.insert(Value::Text("pending".into()), Value::Bool(true));
+                }
+                Value::Map(slot)
+            };
+
+            map(vec![(
+                "images",
+                Value::Array(vec![
+                    slot(0, &[0xA0; 32], true),
+                    slot(1, &[0xB1; 32], false),
+                ]),
+            )])
+        }
+
+        fn handle(&mut self, request: Message) -> Map {
+            let payload = &request.payload;
+            let header = request.header;
+
+            match (header.group, header.command, header.op) {
+                (GROUP_OS, 0, Op::Write) => map(vec![("r", get(payload, "d").unwrap().clone())]),
+                (GROUP_OS, 5, Op::Write) => {
+                    self.resets += 1;
+                    Map::new()
+                }
+                (GROUP_IMAGE, 0, Op::Read) => self.images(),
+                (GROUP_IMAGE, 0, Op::Write) => {
+                    match get_bytes(payload, "hash") {
+                        Some(hash) if hash.as_slice() != [0xB1; 32] => {
+                            return map(vec![("rc", Value::Integer(SmpError::NO_ENTRY.into()))])
+                        }
+                        Some(hash) => self.pending = Some(hash.clone()),
+                        None => {}
+                    }
+                    self.confirmed = get(payload, "confirm") == Some(&Value::Bool(true));
+                    self.images()
+                }
+                (GROUP_IMAGE, 1, Op::Write) => {
+                    let offset = get_u64(payload, "off").unwrap() as usize;
+                    if offset == 0 {
+                        self.upload.clear();
+                    }
+                    if offset == self.upload.len()
+                        && !matches!(self.full_at, Some(full) if offset >= full)
+                    {
+                        self.upload
+                            .extend_from_slice(get_bytes(payload, "data").unwrap());
+                    }
+                    map(vec![("off", Value::Integer(self.upload.len() as i128))])
+                }
+                (GROUP_FS, 0, Op::Read) => {
+                    let name = match get(payload, "name") {
+                        Some(Value::Text(name)) => name,
+                        _ => unreachable!(),
+                    };
+                    let file = match self.files.get(name) {
+                        Some(file) => file,
+                        None => {
+                            return map(vec![("rc", Value::Integer(SmpError::NO_ENTRY.into()))])
+                        }
+                    };
+                    let offset = get_u64(payload, "off").unwrap() as usize;
+                    let end = (offset + 16).min(file.len());
+
+                    let mut response = map(vec![
+                        ("off", Value::Integer(offset as i128)),
+                        ("data", Value::Bytes(file[offset..end].to_vec())),
+                    ]);
+                    if offset == 0 {
+                        response.insert(
+                            Value::Text("len".into()),
+                            Value::Integer(file.len() as i128),
+                        );
+                    }
+                    response
+                }
+                (GROUP_FS, 0, Op::Write) => {
+                    let name = match get(payload, "name") {
+                        Some(Value::Text(name)) => name.clone(),
+                        _ => unreachable!(),
+                    };
+                    let file = self.files.entry(name).or_default();
+                    if get_u64(payload, "off") == Some(0) {
+                        file.clear();
+                    }
+                    if !matches!(self.full_at, Some(full) if file.len() >= full) {
+                        file.extend_from_slice(get_bytes(payload, "data").unwrap());
+                    }
+                    map(vec![("off", Value::Integer(file.len() as i128))])
+                }
+                (GROUP_SHELL, 0, Op::Write) => {
+                    let argv = match get(payload, "argv") {
+                        Some(Value::Array(argv)) => argv.clone(),
+                        _ => unreachable!(),
+                    };
+                    let output = argv
+                        .iter()
+                        .skip(1)
+                        .map(|arg| match arg {
+                            Value::Text(arg) => arg.as_str(),
+                            _ => "",
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    map(vec![("o", Value::Text(output)), ("ret", Value::Integer(0))])
+                }
+                _ => map(vec![("rc", Value::Integer(SmpError::NOT_SUPPORTED.into()))]),
+            }
+        }
+
+        /// Receive part of a request, returning the response once the
+        /// request is complete.
+        fn receive(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+            assert!(packet.len() <= MTU - 3);
+            self.request.extend_from_slice(packet);
+
+            let header = Header::decode(self.request.get(..Header::LEN)?)?;
+            if self.request.len() < Header::LEN + header.len as usize {
+                return None;
+            }
+
+            let request = Message::decode(&self.request).unwrap();
+            self.request.clear();
+
+            let response = Message {
+                header: Header {
+                    op: match header.op {
+                        Op::Read => Op::ReadResponse,
+                        _ => Op::WriteResponse,
+                    },
+                    ..header
+                },
+                payload: self.handle(request),
+            };
+            Some(response.encode())
+        }
+    }
+
+    fn smp_server(device: Arc<Mutex<Device>>) -> GattServer {
+        let server = GattServer::new();
+        server.add_service(
+            LocalService::new(SERVICE).with_characteristic(
+                LocalCharacteristic::new(
+                    CHARACTERISTIC,
+                    CharacteristicProperties::WRITE_WITHOUT_RESPONSE
+                        | CharacteristicProperties::NOTIFY,
+                )
+                .on_write(move |server, packet| {
+                    if let Some(response) = device.lock().unwrap().receive(packet) {
+                        for packet in response.chunks(MTU - 3) {
+                            server.notify(SERVICE, CHARACTERISTIC, packet);
+                        }
+                    }
+                    Ok(())
+                }),
+            ),
+        );
+
+        server
+    }
+
+    #[test]
+    fn test_simulated_device() {
+        let device = Arc::new(Mutex::new(Device::default()));
+        let sim = Simulator::new();
+        let address = BluetoothAddress(0x0102_0304_0506);
+        sim.add_peripheral(address, smp_server(device.clone()));
+
+        let peripheral = sim.connect(address).unwrap();
+        let service = peripheral.service(SERVICE).unwrap().unwrap();
+        let characteristic = service.characteristic(CHARACTERISTIC).unwrap().unwrap();
+        let mut smp = Smp::new(&characteristic).unwrap().with_mtu(MTU);
+
+        let text = "a message long enough to need several notifications";
+        assert_eq!(smp.echo(text).unwrap(), text);
+
+        let image: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let mut offsets = Vec::new();
+        smp.upload(0, &image, |offset, len| offsets.push((offset, len)))
+            .unwrap();
+        assert_eq!(device.lock().unwrap().upload, image);
+        assert!(offsets.len() > 10);
+        assert_eq!(offsets.last(), Some(&(1000, 1000)));
+
+        // Resuming from a stale offset continues from where the device is.
+        device.lock().unwrap().upload.truncate(600);
+        offsets.clear();
+        smp.upload_from(0, &image, 400, |offset, len| offsets.push((offset, len)))
+            .unwrap();
+        assert_eq!(offsets[0], (600, 1000));
+        assert_eq!(device.lock().unwrap().upload, image);
+
+        // A device which stops taking data doesn't keep the upload going.
+        device.lock().unwrap().full_at = Some(300);
+        assert!(matches!(
+            smp.upload(0, &image, |_, _| ()),
+            Err(Error::InvalidValue)
+        ));
+        device.lock().unwrap().full_at = None;
+
+        let slots = smp.image_state().unwrap();
+        assert_eq!(slots.len(), 2);
+        assert_eq!(
+            slots[0],
+            ImageSlot {
+                image: 0,
+                slot: 0,
+                version: "1.0.0".to_string(),
+                hash: vec![0xA0; 32],
+                bootable: true,
+                pending: false,
+                confirmed: true,
+                active: true,
+                permanent: false,
+            }
+        );
+
+        let slots = smp.test_image(&slots[1].hash).unwrap();
+        assert!(slots[1].pending);
+        assert!(!slots[1].confirmed);
+        let slots = smp.confirm_image(None).unwrap();
+        assert!(slots[1].confirmed);
+        assert!(matches!(
+            smp.test_image(&[0; 32]),
+            Err(Error::Smp(SmpError {
+                group: GROUP_IMAGE,
+                command: 0,
+                rc: SmpError::NO_ENTRY,
+            }))
+        ));
+
+        let output = smp.shell(&["echo", "hello", "world"]).unwrap();
+        assert_eq!(output.output, "hello world");
+        assert_eq!(output.ret, 0);
+
+        let file: Vec<u8> = (0..100).collect();
+        smp.write_file("/lfs/data.bin", &file).unwrap();
+        assert_eq!(device.lock().unwrap().files["/lfs/data.bin"], file);
+        assert_eq!(smp.read_file("/lfs/data.bin").unwrap(), file);
+
+        device.lock().unwrap().full_at = Some(0);
+        assert!(matches!(
+            smp.write_file("/lfs/full.bin", &file),
+            Err(Error::InvalidValue)
+        ));
+        device.lock().unwrap().full_at = None;
+        assert!(matches!(
+            smp.read_file("/lfs/missing"),
+            Err(Error::Smp(SmpError {
+                rc: SmpError::NO_ENTRY,
+                ..
+            }))
+        ));
+
+        smp.reset().unwrap();
+        assert_eq!(device.lock().unwrap().resets, 1);
+    }
+}
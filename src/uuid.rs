@@ -0,0 +1,154 @@
+//! Bluetooth UUIDs.
+
+/// The Bluetooth Base UUID, `00000000-0000-1000-8000-00805F9B34FB`, which
+/// 16-bit and 32-bit UUIDs are offsets into.
+const BASE_UUID: u128 = 0x0000_0000_0000_1000_8000_0080_5F9B_34FB;
+
+/// A 128-bit UUID identifying a service, characteristic, or descriptor.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Uuid(pub u128);
+
+impl Uuid {
+    /// Create a UUID from a 16-bit SIG assigned number.
+    pub const fn from_u16(short: u16) -> Self {
+        Self::from_u32(short as u32)
+    }
+
+    /// Create a UUID from a 32-bit SIG assigned number.
+    pub const fn from_u32(short: u32) -> Self {
+        Uuid(BASE_UUID | ((short as u128) << 96))
+    }
+
+    /// Get the 16-bit short form of this UUID, if it has one.
+    pub fn as_u16(&self) -> Option<u16> {
+        self.as_u32()
+            .and_then(|short| std::convert::TryFrom::try_from(short).ok())
+    }
+
+    /// Get the 32-bit short form of this UUID, if it has one.
+    pub fn as_u32(&self) -> Option<u32> {
+        if self.0 & ((1 << 96) - 1) == BASE_UUID {
+            Some((self.0 >> 96) as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Get the bytes of this UUID in the little-endian order used on the air.
+    pub fn to_le_bytes(&self) -> [u8; 16] {
+        self.0.to_le_bytes()
+    }
+
+    /// Create a UUID from bytes in the little-endian order used on the air.
+    pub fn from_le_bytes(bytes: [u8; 16]) -> Self {
+        Uuid(u128::from_le_bytes(bytes))
+    }
+}
+
+impl From<&winrt::Guid> for Uuid {
+    fn from(guid: &winrt::Guid) -> Self {
+        // winrt does not expose the fields of a Guid, but its Debug output is
+        // the hyphenated form.
+        format!("{:?}", guid)
+            .parse()
+            .expect("Guid was not in hyphenated form")
+    }
+}
+
+impl From<Uuid> for winrt::Guid {
+    fn from(uuid: Uuid) -> Self {
+        let bytes = uuid.0.to_be_bytes();
+        let mut data4 = [0u8; 8];
+        data4.copy_from_slice(&bytes[8..]);
+
+        winrt::Guid::from_values(
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            u16::from_be_bytes([bytes[4], bytes[5]]),
+            u16::from_be_bytes([bytes[6], bytes[7]]),
+            data4,
+        )
+    }
+}
+
+impl From<u16> for Uuid {
+    fn from(short: u16) -> Self {
+        Self::from_u16(short)
+    }
+}
+
+/// Error generated when parsing a UUID from a string.
+#[derive(Debug, PartialEq)]
+pub enum UuidParseError {
+    /// UUID was not in the hyphenated 8-4-4-4-12 form.
+    IncorrectFormat,
+    /// UUID contains a value that is not a valid base-16 number.
+    InvalidNumber,
+}
+
+impl std::fmt::Display for UuidParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UuidParseError::IncorrectFormat => write!(f, "UUID was not in 8-4-4-4-12 form"),
+            UuidParseError::InvalidNumber => write!(f, "UUID was not valid base-16 number"),
+        }
+    }
+}
+
+impl std::error::Error for UuidParseError {}
+
+impl std::str::FromStr for Uuid {
+    type Err = UuidParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lengths: Vec<_> = s.split('-').map(str::len).collect();
+        if lengths != [8, 4, 4, 4, 12] {
+            return Err(UuidParseError::IncorrectFormat);
+        }
+
+        let digits: String = s.split('-').collect();
+        u128::from_str_radix(&digits, 16)
+            .map(Uuid)
+            .map_err(|_err| UuidParseError::InvalidNumber)
+    }
+}
+
+impl std::fmt::Display for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex = format!("{:032x}", self.0);
+
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl std::fmt::Debug for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Uuid").field(&self.to_string()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uuid;
+
+    #[test]
+    fn test_short_uuids() {
+        let heart_rate = Uuid::from_u16(0x180D);
+        assert_eq!(
+            heart_rate.to_string(),
+            "0000180d-0000-1000-8000-00805f9b34fb"
+        );
+        assert_eq!(heart_rate.as_u16(), Some(0x180D));
+
+        let parsed: Uuid = "8d53dc1d-1db7-4cd3-868b-8a527460aa84".parse().unwrap();
+        assert_eq!(parsed.as_u32(), None);
+        assert_eq!(Uuid::from_le_bytes(parsed.to_le_bytes()), parsed);
+    }
+}